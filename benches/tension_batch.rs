@@ -0,0 +1,32 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SENSORS: [&str; 4] = ["coherence", "power", "field_strength", "temperature"];
+
+fn loaded_interpreter() -> Interpreter {
+    let path = std::env::temp_dir().join(format!("weavelang_bench_batch_{}.weave", std::process::id()));
+    std::fs::write(&path, "field generalist { coherence_target: 0.5, physics_constant: 1.0 }").unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.load(&path).unwrap();
+    interpreter.set_history_limit(10_000);
+    let _ = std::fs::remove_file(&path);
+    interpreter
+}
+
+fn bench_tension_batch(c: &mut Criterion) {
+    let names: Vec<String> = SENSORS.iter().map(|name| name.to_string()).collect();
+    let agents: Fields = [("generalist".to_string(), Default::default())].into_iter().collect();
+    let mut group = c.benchmark_group("execute_tension_batch");
+    for rows in [100, 1_000, 10_000] {
+        let samples: Vec<Scalar> = (0..rows * SENSORS.len()).map(|i| (i % 13) as Scalar / 13.0).collect();
+        let mut interpreter = loaded_interpreter();
+        group.bench_with_input(BenchmarkId::from_parameter(rows), &samples, |b, samples| {
+            b.iter(|| interpreter.execute_tension_batch(&names, black_box(samples), SENSORS.len(), 100, &agents, 1.0).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_tension_batch);
+criterion_main!(benches);
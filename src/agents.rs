@@ -0,0 +1,39 @@
+use crate::interpreter::{DerivedParam, ParamValue, Params};
+use crate::scalar::Scalar;
+use std::collections::HashMap;
+
+/// `template <name> { ... }`: params a field takes with `extends <name>`
+/// before its own. A template is never a field itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Template {
+    pub params: Params,
+    pub attributes: HashMap<String, ParamValue>,
+    /// Param expressions, each with `field` naming the template.
+    pub derived_params: Vec<DerivedParam>,
+    /// `in [min, max]` ranges, by param.
+    pub bounds: HashMap<String, (Scalar, Scalar)>,
+}
+
+/// An agent made as a copy of a template field, declared with
+/// `agent <name> from <template>;` or spawned by `spawn_agent`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisteredAgent {
+    pub template: String,
+    /// Declared in the weave file rather than spawned at runtime; a reload
+    /// replaces these and keeps the others.
+    pub from_script: bool,
+}
+
+/// Gives `agent` a copy of `template`'s non-numeric params and derived
+/// params; the numeric params are the caller's to copy.
+pub fn copy_template(agent: &str, template: &str, attributes: &mut HashMap<String, HashMap<String, ParamValue>>, derived_params: &mut Vec<DerivedParam>) {
+    if let Some(copied) = attributes.get(template).cloned() {
+        attributes.insert(agent.to_string(), copied);
+    }
+    let copied: Vec<DerivedParam> = derived_params
+        .iter()
+        .filter(|derived| derived.field == template)
+        .map(|derived| DerivedParam { field: agent.to_string(), ..derived.clone() })
+        .collect();
+    derived_params.extend(copied);
+}
@@ -0,0 +1,69 @@
+use crate::scalar::Scalar;
+
+/// A computed tension crossing one of the alarm thresholds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TensionCrossing {
+    /// Tension rose above the high threshold.
+    High(Scalar),
+    /// Tension fell back to the resolved threshold or below, after a `High`.
+    Resolved(Scalar),
+}
+
+/// Watches every computed tension against a high threshold and a lower
+/// resolved one. Keeping the two apart stops a tension hovering around one
+/// value from raising and resolving the alarm on alternate ticks.
+#[derive(Debug, Clone, Default)]
+pub struct TensionAlarm {
+    thresholds: Option<(Scalar, Scalar)>,
+    raised: bool,
+    crossings: Vec<TensionCrossing>,
+}
+
+impl TensionAlarm {
+    /// Arms the alarm; a `resolved` above `high` is lowered to it. The alarm
+    /// starts lowered, so a tension already above `high` raises it on the
+    /// next check.
+    pub fn set_thresholds(&mut self, high: Scalar, resolved: Scalar) {
+        self.thresholds = Some((high, resolved.min(high)));
+        self.raised = false;
+    }
+
+    pub fn clear_thresholds(&mut self) {
+        self.thresholds = None;
+        self.raised = false;
+    }
+
+    /// `(high, resolved)`, or `None` while disarmed.
+    pub fn thresholds(&self) -> Option<(Scalar, Scalar)> {
+        self.thresholds
+    }
+
+    /// Whether the last tension left the alarm raised.
+    pub fn raised(&self) -> bool {
+        self.raised
+    }
+
+    pub fn check(&mut self, tension: Scalar) {
+        let Some((high, resolved)) = self.thresholds else {
+            return;
+        };
+        if !self.raised && tension > high {
+            self.raised = true;
+            self.crossings.push(TensionCrossing::High(tension));
+        } else if self.raised && tension <= resolved {
+            self.raised = false;
+            self.crossings.push(TensionCrossing::Resolved(tension));
+        }
+    }
+
+    /// Lowers the alarm and drops untaken crossings, keeping the thresholds.
+    pub fn reset(&mut self) {
+        self.raised = false;
+        self.crossings.clear();
+    }
+
+    /// Crossings since the last take, oldest first.
+    pub fn take_crossings(&mut self) -> Vec<TensionCrossing> {
+        std::mem::take(&mut self.crossings)
+    }
+}
@@ -0,0 +1,104 @@
+use crate::expr::Expr;
+use crate::rules::RuleCondition;
+use std::fmt;
+use std::str::FromStr;
+
+/// The field name a behavior tree uses for the agent it is ticking.
+pub const SELF_FIELD: &str = "self";
+
+/// What a behavior node reports for one tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BehaviorStatus {
+    Success,
+    Failure,
+    /// Not done yet; the tree is ticked from its root again next tick.
+    Running,
+}
+
+impl BehaviorStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BehaviorStatus::Success => "success",
+            BehaviorStatus::Failure => "failure",
+            BehaviorStatus::Running => "running",
+        }
+    }
+}
+
+impl FromStr for BehaviorStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(BehaviorStatus::Success),
+            "failure" => Ok(BehaviorStatus::Failure),
+            "running" => Ok(BehaviorStatus::Running),
+            _ => Err(format!("unknown behavior status '{}', expected success, failure or running", s)),
+        }
+    }
+}
+
+/// One node of a `behavior` block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BehaviorNode {
+    /// `selector { ... }`: ticks its children in order until one doesn't fail.
+    Selector(Vec<BehaviorNode>),
+    /// `sequence { ... }`: ticks its children in order until one doesn't succeed.
+    Sequence(Vec<BehaviorNode>),
+    /// `check <test>;`: succeeds while the test holds; a test with no value fails.
+    Check(RuleCondition),
+    /// `set <field>.<param> = <expression>;`: succeeds once written, fails when
+    /// the expression can't be computed.
+    Set { field: String, param: String, value: Expr },
+    /// `action <name>;`: `drift` and `resolve` move the agent; any other name
+    /// is a leaf registered by the host, and fails until one is.
+    Action(String),
+}
+
+impl fmt::Display for BehaviorNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (keyword, children) = match self {
+            BehaviorNode::Selector(children) => ("selector", children),
+            BehaviorNode::Sequence(children) => ("sequence", children),
+            BehaviorNode::Check(condition) => return write!(f, "check {};", condition),
+            BehaviorNode::Set { field, param, value } => return write!(f, "set {}.{} = {};", field, param, value),
+            BehaviorNode::Action(name) => return write!(f, "action {};", name),
+        };
+        write!(f, "{} {{", keyword)?;
+        for child in children {
+            write!(f, " {}", child)?;
+        }
+        f.write_str(" }")
+    }
+}
+
+/// `behavior <role> { <node> }`: ticked once per tick for every agent
+/// tagged with the role, with `self` standing for that agent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BehaviorTree {
+    pub role: String,
+    pub root: BehaviorNode,
+}
+
+impl fmt::Display for BehaviorTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "behavior {} {{ {} }}", self.role, self.root)
+    }
+}
+
+/// `condition` with `self` read as `agent`.
+pub fn bind_self(condition: &RuleCondition, agent: &str) -> RuleCondition {
+    let bound = |field: &str| if field == SELF_FIELD { agent.to_string() } else { field.to_string() };
+    match condition {
+        RuleCondition::Sensor { sensor, comparator, threshold } => {
+            let sensor = match sensor.split_once('.') {
+                Some((field, param)) => format!("{}.{}", bound(field), param),
+                None => sensor.clone(),
+            };
+            RuleCondition::Sensor { sensor, comparator: *comparator, threshold: *threshold }
+        }
+        RuleCondition::Attribute { field, param, equal, value } => {
+            RuleCondition::Attribute { field: bound(field), param: param.clone(), equal: *equal, value: value.clone() }
+        }
+    }
+}
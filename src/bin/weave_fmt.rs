@@ -0,0 +1,103 @@
+//! `weave-fmt`: rewrites weave files in the canonical layout, or checks
+//! that they already are.
+//!
+//! Build with `cargo run --no-default-features --features cli --bin weave-fmt -- [options] [files]`.
+
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use weavelang::format::{format, FormatOptions};
+
+const USAGE: &str = "usage: weave-fmt [options] [file.weave ...]
+
+With no files, or -, formats standard input to standard output.
+
+  --write, -w      rewrite each file in place instead of printing it
+  --check          print the files that aren't formatted and exit 1 if any
+  --sort-params    put the params of field and template blocks in name order";
+
+struct Options {
+    files: Vec<PathBuf>,
+    write: bool,
+    check: bool,
+    format: FormatOptions,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options { files: Vec::new(), write: false, check: false, format: FormatOptions::default() };
+    for arg in args {
+        match arg.as_str() {
+            "--write" | "-w" => options.write = true,
+            "--check" => options.check = true,
+            "--sort-params" => options.format.sort_params = true,
+            "-h" | "--help" => return Ok(None),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => options.files.push(PathBuf::from(arg)),
+        }
+    }
+    if options.write && options.check {
+        return Err("--write and --check can't be used together".to_string());
+    }
+    Ok(Some(options))
+}
+
+/// Formats every file; returns whether all of them already were.
+fn run(options: &Options) -> Result<bool, String> {
+    let stdin = options.files.is_empty() || options.files.iter().any(|file| file.as_os_str() == "-");
+    if stdin {
+        if options.files.len() > 1 || options.write {
+            return Err("standard input can't be formatted with other files or with --write".to_string());
+        }
+        let mut code = String::new();
+        io::stdin().read_to_string(&mut code).map_err(|e| format!("can't read standard input: {}", e))?;
+        let formatted = format(&code, options.format).map_err(|e| format!("<stdin>: {}", e))?;
+        if options.check {
+            if formatted != code {
+                println!("<stdin>");
+            }
+        } else {
+            print!("{}", formatted);
+        }
+        return Ok(formatted == code);
+    }
+    let mut tidy = true;
+    for file in &options.files {
+        let code = std::fs::read_to_string(file).map_err(|e| format!("can't read {}: {}", file.display(), e))?;
+        let formatted = format(&code, options.format).map_err(|e| format!("{}: {}", file.display(), e))?;
+        tidy &= formatted == code;
+        if options.check {
+            if formatted != code {
+                println!("{}", file.display());
+            }
+        } else if options.write {
+            if formatted != code {
+                std::fs::write(file, &formatted).map_err(|e| format!("can't write {}: {}", file.display(), e))?;
+            }
+        } else {
+            print!("{}", formatted);
+        }
+    }
+    Ok(tidy)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("weave-fmt: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&options) {
+        Ok(tidy) if options.check && !tidy => ExitCode::FAILURE,
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("weave-fmt: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! `weave-lint`: reports likely mistakes in weave files that still load,
+//! such as a reference to a field no file declares.
+//!
+//! Build with `cargo run --no-default-features --features cli --bin weave-lint -- [files]`.
+
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use weavelang::diagnostic::Diagnostic;
+use weavelang::lint::{lint, LintContext};
+
+const USAGE: &str = "usage: weave-lint [file.weave ...]
+
+With no files, or -, lints standard input. Prints one line per problem,
+file:line:column: severity: message, and exits 1 if there are any.";
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<Vec<PathBuf>>, String> {
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    Ok(Some(files))
+}
+
+fn report(name: &str, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        println!("{}:{}:{}: {}: {}", name, diagnostic.line, diagnostic.column, diagnostic.severity.as_str(), diagnostic.message);
+    }
+}
+
+/// Lints every file; returns whether all of them were clean.
+fn run(files: &[PathBuf]) -> Result<bool, String> {
+    let context = LintContext::default();
+    if files.is_empty() || files.iter().any(|file| file.as_os_str() == "-") {
+        if files.len() > 1 {
+            return Err("standard input can't be linted with other files".to_string());
+        }
+        let mut code = String::new();
+        io::stdin().read_to_string(&mut code).map_err(|e| format!("can't read standard input: {}", e))?;
+        let diagnostics = lint(&code, &context);
+        report("<stdin>", &diagnostics);
+        return Ok(diagnostics.is_empty());
+    }
+    let mut clean = true;
+    for file in files {
+        let code = std::fs::read_to_string(file).map_err(|e| format!("can't read {}: {}", file.display(), e))?;
+        let diagnostics = lint(&code, &context);
+        report(&file.display().to_string(), &diagnostics);
+        clean &= diagnostics.is_empty();
+    }
+    Ok(clean)
+}
+
+fn main() -> ExitCode {
+    let files = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(files)) => files,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("weave-lint: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(&files) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("weave-lint: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
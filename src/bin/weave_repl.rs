@@ -0,0 +1,227 @@
+//! `weave-repl`: reads weave statements one at a time, applies each to a
+//! live interpreter and prints the params it moved.
+//!
+//! Build with `cargo run --no-default-features --features cli --bin weave-repl -- [program] [options]`.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use weavelang::compiled;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const USAGE: &str = "usage: weave-repl [program.weave|.wvc] [options]
+
+  --seed N            seed of the drift noise
+  --sensor NAME=VALUE a sensor value the steps read; may be repeated";
+
+const HELP: &str = "Anything not starting with ':' is run as weave code, as execute_code does:
+field blocks, `field.param = value` assignments, metaweave and safety blocks.
+A line with an open brace continues until the braces close.
+
+  field | field.param     print a field's params, or one param
+  :fields                 print every field
+  :sensor NAME=VALUE      set a sensor the steps read; :sensor NAME removes it
+  :sensors                print the sensors
+  :tension                compute tension now and explain it, term by term
+  :step [N]               step N ticks (default 1) and print what moved
+  :load PATH              load a weave or compiled program, replacing the state
+  :reset                  reload the program from its file
+  :help                   print this
+  :quit                   leave, as end of input does";
+
+struct Repl {
+    interpreter: Interpreter,
+    sensors: HashMap<String, Scalar>,
+}
+
+impl Repl {
+    fn load(&mut self, path: &Path) -> Result<(), WeaveError> {
+        if path.extension().is_some_and(|ext| ext == compiled::EXTENSION) {
+            self.interpreter.load_compiled(path)
+        } else {
+            self.interpreter.load(path)
+        }
+    }
+
+    /// Runs one complete input and returns what to print, or `None` to quit.
+    fn eval(&mut self, input: &str) -> Option<Result<String, String>> {
+        let input = input.trim();
+        let (command, arg) = input.split_once(char::is_whitespace).map_or((input, ""), |(command, arg)| (command, arg.trim()));
+        Some(match command {
+            "" => Ok(String::new()),
+            ":quit" | ":q" => return None,
+            ":help" => Ok(HELP.to_string()),
+            ":fields" => Ok(self.interpreter.fields().keys().map(|field| self.field(field)).collect::<Vec<_>>().join("\n")),
+            ":sensors" => {
+                let mut sensors: Vec<String> = self.sensors.iter().map(|(name, value)| format!("{} = {}", name, value)).collect();
+                sensors.sort();
+                Ok(sensors.join("\n"))
+            }
+            ":sensor" => return Some(self.set_sensor(arg)),
+            ":tension" => self.tension(),
+            ":step" => match arg {
+                "" => self.step(1),
+                ticks => match ticks.parse() {
+                    Ok(ticks) => self.step(ticks),
+                    Err(_) => return Some(Err(":step takes a whole number".to_string())),
+                },
+            },
+            ":load" => self.load(Path::new(arg)).map(|()| format!("loaded {}", arg)),
+            ":reset" => self.interpreter.reset().map(|()| "reset".to_string()),
+            command if command.starts_with(':') => return Some(Err(format!("unknown command {}, see :help", command))),
+            _ => self.lookup(input).map(Ok).unwrap_or_else(|| self.execute(input)),
+        }
+        .map_err(|e| e.to_string()))
+    }
+
+    /// `field` or `field.param` when `input` names one.
+    fn lookup(&self, input: &str) -> Option<String> {
+        match input.split_once('.') {
+            None => self.interpreter.fields().contains_key(input).then(|| self.field(input)),
+            Some((field, param)) => {
+                let value = self.interpreter.fields().get(field).and_then(|params| params.get(param)).map(|value| value.to_string());
+                value.or_else(|| self.interpreter.attribute(field, param).map(|value| value.to_string())).map(|value| format!("{}.{} = {}", field, param, value))
+            }
+        }
+    }
+
+    /// `field { param: value, ... }`, numeric params then the others.
+    fn field(&self, field: &str) -> String {
+        let mut params: Vec<String> = self.interpreter.fields()[field].iter().map(|(param, value)| format!("{}: {}", param, value)).collect();
+        let mut attributes: Vec<String> = self.interpreter.attributes().get(field).into_iter().flatten().map(|(param, value)| format!("{}: {}", param, value)).collect();
+        attributes.sort();
+        params.extend(attributes);
+        format!("{} {{ {} }}", field, params.join(", "))
+    }
+
+    fn set_sensor(&mut self, arg: &str) -> Result<String, String> {
+        let Some((name, value)) = arg.split_once('=') else {
+            return Ok(match self.sensors.remove(arg) {
+                Some(_) => format!("removed {}", arg),
+                None => format!("no sensor {}", arg),
+            });
+        };
+        let value = value.trim().parse().map_err(|_| format!("{} is not a number", value.trim()))?;
+        self.sensors.insert(name.trim().to_string(), value);
+        Ok(format!("{} = {}", name.trim(), value))
+    }
+
+    fn tension(&mut self) -> Result<String, WeaveError> {
+        let tension = self.interpreter.execute_tension(&self.sensors)?;
+        let mut lines = vec![format!("tension = {}", tension)];
+        for term in self.interpreter.tension_terms() {
+            lines.push(format!(
+                "  {} vs {}: |{} - {}| * {} = {}",
+                term.sensor, term.agent, term.sensed, term.target, term.weight, term.contribution
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn step(&mut self, ticks: usize) -> Result<String, WeaveError> {
+        let before = self.interpreter.fields().clone();
+        let agents: Fields = before.keys().map(|name| (name.clone(), Default::default())).collect();
+        let mut tension = 0.0;
+        for _ in 0..ticks {
+            tension = self.interpreter.step(&self.sensors, &agents)?;
+        }
+        let mut lines = vec![format!("tick {}: tension = {}", self.interpreter.tick(), tension)];
+        lines.extend(self.interpreter.take_warnings().into_iter().map(|warning| format!("warning: {}", warning)));
+        for (field, params) in self.interpreter.fields() {
+            for (param, value) in params {
+                let old_value = before.get(field).and_then(|params| params.get(param)).copied();
+                if old_value != Some(*value) {
+                    lines.push(format!("{}.{}: {} -> {}", field, param, old_value.map(|value| value.to_string()).unwrap_or_default(), value));
+                }
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn execute(&mut self, code: &str) -> Result<String, WeaveError> {
+        let outcome = self.interpreter.execute_code(code)?;
+        let mut lines: Vec<String> = outcome.added_fields.iter().map(|field| format!("added {}", self.field(field))).collect();
+        lines.extend(outcome.fired.iter().map(|rule| format!("fired {}", rule)));
+        lines.extend(outcome.changes.iter().map(|change| format!("{}.{}: {} -> {}", change.agent, change.param, change.old_value, change.new_value)));
+        if lines.is_empty() {
+            lines.push("no change".to_string());
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Whether `input` opens more braces or block comments than it closes, so
+/// the statement goes on.
+fn unfinished(input: &str) -> bool {
+    input.matches('{').count() > input.matches('}').count() || input.matches("/*").count() > input.matches("*/").count()
+}
+
+fn run(mut repl: Repl) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut input = String::new();
+    loop {
+        write!(stdout, "{}", if input.is_empty() { "weave> " } else { "  ...> " })?;
+        stdout.flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            writeln!(stdout)?;
+            return Ok(());
+        }
+        input.push_str(&line);
+        if unfinished(&input) {
+            continue;
+        }
+        match repl.eval(&std::mem::take(&mut input)) {
+            None => return Ok(()),
+            Some(Ok(output)) if output.is_empty() => {}
+            Some(Ok(output)) => writeln!(stdout, "{}", output)?,
+            Some(Err(e)) => writeln!(stdout, "error: {}", e)?,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let mut repl = Repl { interpreter: Interpreter::new(), sensors: HashMap::new() };
+    let mut args = std::env::args().skip(1);
+    let (mut program, mut seed) = (None, None);
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--seed" => args.next().and_then(|value| value.parse().ok()).map(|value| seed = Some(value)).ok_or("--seed takes a whole number".to_string()),
+            "--sensor" => args.next().ok_or("--sensor needs a value".to_string()).and_then(|sensor| repl.set_sensor(&sensor).map(|_| ())),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return ExitCode::SUCCESS;
+            }
+            flag if flag.starts_with("--") => Err(format!("unknown option {}", flag)),
+            _ if program.is_none() => {
+                program = Some(arg);
+                Ok(())
+            }
+            _ => Err(format!("unexpected argument {}", arg)),
+        };
+        if let Err(message) = parsed {
+            eprintln!("weave-repl: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    }
+    if let Some(program) = program {
+        if let Err(e) = repl.load(Path::new(&program)) {
+            eprintln!("weave-repl: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Some(seed) = seed {
+        repl.interpreter.set_noise_seed(seed);
+    }
+    match run(repl) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("weave-repl: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
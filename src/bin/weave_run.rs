@@ -0,0 +1,234 @@
+//! `weave-run`: steps a weave program without Godot and writes each
+//! field's trajectory as CSV, one row per tick.
+//!
+//! Build with `cargo run --no-default-features --features cli --bin weave-run -- <program> [options]`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use weavelang::compiled;
+use weavelang::datalog::DEFAULT_FLUSH_TICKS;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter, ParamValue};
+use weavelang::noise::{Noise, DEFAULT_SEED};
+use weavelang::scalar::{to_f64, Scalar};
+
+const USAGE: &str = "usage: weave-run <program.weave|.wvc> [options]
+
+  --ticks N           steps to run (default 100, or one per CSV row)
+  --dt SECONDS        time step of each tick (default 1)
+  --sensors FILE      CSV with a header of sensor names and one row per tick;
+                      an empty cell leaves that sensor out of the tick
+  --sensor NAME=SPEC  a synthetic sensor: a constant such as 0.7,
+                      sin(mean,amplitude,period) with the period in ticks,
+                      or noise(mean,stddev); may be repeated
+  --seed N            seed of the drift noise and of noise() sensors
+  --out FILE          write the trajectories there instead of stdout
+  --log FILE          also log one tick,agent,param,value,tension row per
+                      param and tick to FILE: appended CSV, or Parquet for a
+                      .parquet file in a build with the parquet feature
+  --log-flush N       ticks the log queues between writes (default 60)";
+
+/// A sensor the runner makes up each tick.
+enum Synthetic {
+    Constant(Scalar),
+    Sine { mean: Scalar, amplitude: Scalar, period: Scalar },
+    Noise { mean: Scalar, stddev: Scalar },
+}
+
+impl Synthetic {
+    fn parse(spec: &str) -> Result<Synthetic, String> {
+        let bad = || format!("can't read sensor spec {}", spec);
+        let Some((kind, args)) = spec.strip_suffix(')').and_then(|spec| spec.split_once('(')) else {
+            return spec.trim().parse().map(Synthetic::Constant).map_err(|_| bad());
+        };
+        let args = args.split(',').map(|arg| arg.trim().parse::<Scalar>()).collect::<Result<Vec<_>, _>>().map_err(|_| bad())?;
+        match (kind.trim(), args.as_slice()) {
+            ("sin", &[mean, amplitude, period]) if period > 0.0 => Ok(Synthetic::Sine { mean, amplitude, period }),
+            ("noise", &[mean, stddev]) => Ok(Synthetic::Noise { mean, stddev }),
+            _ => Err(bad()),
+        }
+    }
+
+    fn sample(&self, tick: usize, noise: &mut Noise) -> Scalar {
+        match *self {
+            Synthetic::Constant(value) => value,
+            Synthetic::Sine { mean, amplitude, period } => {
+                let phase = std::f64::consts::TAU * tick as f64 / to_f64(period);
+                mean + amplitude * phase.sin() as Scalar
+            }
+            Synthetic::Noise { mean, stddev } => mean + noise.gaussian(stddev),
+        }
+    }
+}
+
+struct Options {
+    program: PathBuf,
+    ticks: Option<usize>,
+    dt: Scalar,
+    recorded: Vec<HashMap<String, Scalar>>,
+    synthetic: Vec<(String, Synthetic)>,
+    seed: u64,
+    out: Option<PathBuf>,
+    log: Option<PathBuf>,
+    log_flush: usize,
+}
+
+/// The options, or `None` when `--help` asks for the usage.
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut args = args.peekable();
+    let mut options = Options { program: PathBuf::new(), ticks: None, dt: 1.0, recorded: Vec::new(), synthetic: Vec::new(), seed: DEFAULT_SEED, out: None, log: None, log_flush: DEFAULT_FLUSH_TICKS };
+    let mut program = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--ticks" => options.ticks = Some(value("--ticks")?.parse().map_err(|_| "--ticks takes a whole number".to_string())?),
+            "--dt" => options.dt = value("--dt")?.parse().map_err(|_| "--dt takes a number".to_string())?,
+            "--sensors" => options.recorded = read_csv(Path::new(&value("--sensors")?))?,
+            "--sensor" => {
+                let sensor = value("--sensor")?;
+                let (name, spec) = sensor.split_once('=').ok_or_else(|| format!("--sensor takes NAME=SPEC, not {}", sensor))?;
+                options.synthetic.push((name.to_string(), Synthetic::parse(spec)?));
+            }
+            "--seed" => options.seed = value("--seed")?.parse().map_err(|_| "--seed takes a whole number".to_string())?,
+            "--out" => options.out = Some(PathBuf::from(value("--out")?)),
+            "--log" => options.log = Some(PathBuf::from(value("--log")?)),
+            "--log-flush" => options.log_flush = value("--log-flush")?.parse().map_err(|_| "--log-flush takes a whole number".to_string())?,
+            "-h" | "--help" => return Ok(None),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}", flag)),
+            _ if program.is_none() => program = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {}", arg)),
+        }
+    }
+    options.program = program.ok_or_else(|| "no program given".to_string())?;
+    Ok(Some(options))
+}
+
+/// Rows of recorded sensor values, keyed by the header. A `tick` column is
+/// skipped, so a file the runner wrote can be fed back in.
+fn read_csv(path: &Path) -> Result<Vec<HashMap<String, Scalar>>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines.next().map(|line| line.split(',').map(str::trim).collect()).unwrap_or_default();
+    let mut rows = Vec::new();
+    for (row, line) in lines.enumerate() {
+        let mut sensors = HashMap::new();
+        for (name, cell) in header.iter().zip(line.split(',').map(str::trim)) {
+            if *name == "tick" || cell.is_empty() {
+                continue;
+            }
+            let value = cell.parse().map_err(|_| format!("{} row {}: {} is not a number", path.display(), row + 1, cell))?;
+            sensors.insert(name.to_string(), value);
+        }
+        rows.push(sensors);
+    }
+    Ok(rows)
+}
+
+/// `field.param` for every numeric param and `field.attribute.i` for every
+/// vector component, in field order; fixed when the program loads.
+fn columns(interpreter: &Interpreter) -> Vec<(String, String, Option<usize>)> {
+    let mut columns = Vec::new();
+    for (field, params) in interpreter.fields() {
+        columns.extend(params.keys().map(|param| (field.clone(), param.clone(), None)));
+        let mut vectors: Vec<(&String, usize)> = interpreter
+            .attributes()
+            .get(field)
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, value)| match value {
+                ParamValue::Vector(components) => Some((name, components.len())),
+                _ => None,
+            })
+            .collect();
+        vectors.sort();
+        for (name, len) in vectors {
+            columns.extend((0..len).map(|i| (field.clone(), name.clone(), Some(i))));
+        }
+    }
+    columns
+}
+
+fn cell(interpreter: &Interpreter, (field, param, component): &(String, String, Option<usize>)) -> String {
+    let value = match component {
+        None => interpreter.fields().get(field).and_then(|params| params.get(param)).copied(),
+        Some(i) => match interpreter.attribute(field, param) {
+            Some(ParamValue::Vector(components)) => components.get(*i).copied(),
+            _ => None,
+        },
+    };
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn run(options: Options) -> Result<(), WeaveError> {
+    let mut interpreter = Interpreter::new();
+    if options.program.extension().is_some_and(|ext| ext == compiled::EXTENSION) {
+        interpreter.load_compiled(&options.program)?;
+    } else {
+        interpreter.load(&options.program)?;
+    }
+    interpreter.set_noise_seed(options.seed);
+    if let Some(log) = &options.log {
+        interpreter.start_data_log(log, options.log_flush)?;
+    }
+    let mut noise = Noise::default();
+    noise.reseed(options.seed);
+    let ticks = match (options.ticks, options.recorded.len()) {
+        (Some(ticks), 0) => ticks,
+        (Some(ticks), rows) => ticks.min(rows),
+        (None, 0) => 100,
+        (None, rows) => rows,
+    };
+    let columns = columns(&interpreter);
+    let mut out: Box<dyn Write> = match &options.out {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    let header: Vec<String> = columns
+        .iter()
+        .map(|(field, param, component)| match component {
+            Some(i) => format!("{}.{}.{}", field, param, i),
+            None => format!("{}.{}", field, param),
+        })
+        .collect();
+    writeln!(out, "tick,tension,{}", header.join(","))?;
+    for tick in 0..ticks {
+        let mut sensors = options.recorded.get(tick).cloned().unwrap_or_default();
+        for (name, synthetic) in &options.synthetic {
+            sensors.insert(name.clone(), synthetic.sample(tick, &mut noise));
+        }
+        let agents: Fields = interpreter.fields().keys().map(|name| (name.clone(), Default::default())).collect();
+        let tension = interpreter.step_dt(&sensors, &agents, options.dt).map_err(|e| WeaveError::Validation(format!("tick {}: {}", tick, e)))?;
+        for warning in interpreter.take_warnings() {
+            eprintln!("warning: tick {}: {}", tick, warning);
+        }
+        let cells: Vec<String> = columns.iter().map(|column| cell(&interpreter, column)).collect();
+        writeln!(out, "{},{},{}", tick, tension, cells.join(","))?;
+    }
+    out.flush()?;
+    interpreter.stop_data_log()?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("weave-run: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("weave-run: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
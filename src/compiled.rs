@@ -0,0 +1,734 @@
+use crate::behavior::{BehaviorNode, BehaviorTree};
+use crate::agents::Template;
+use crate::constants::Constants;
+use crate::error::WeaveError;
+use crate::events::{EventAction, EventHandler};
+use crate::expr::{Expr, Function, Op, UserFunction};
+use crate::goals::{Comparison, Goal};
+use crate::interpreter::{parse_weave_with, DerivedParam, ParamValue, Params, WeaveProgram};
+use crate::invariant::Invariant;
+use crate::loops::{Loop, LoopKind, LoopStatement};
+use crate::recording::{read_scalar, read_str, read_u32, read_width, write_scalar, write_str, write_u32, SCALAR_WIDTH};
+use crate::rules::{CallRule, Comparator, LabCall, MetaweaveRule, RuleCondition};
+use crate::safety::{SafetyCheck, SafetyRule, SafetySeverity};
+use crate::scalar::Scalar;
+use crate::schedule::ScheduledSet;
+use crate::tension::WeightedTerm;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"WVCP";
+/// Bumped whenever the layout below changes; older files must be recompiled.
+pub const FORMAT_VERSION: u32 = 14;
+/// File extension the bindings load as a compiled program rather than source.
+pub const EXTENSION: &str = "wvc";
+
+const COMPARATORS: [Comparator; 6] = [Comparator::Lt, Comparator::Le, Comparator::Gt, Comparator::Ge, Comparator::Eq, Comparator::Ne];
+const OPS: [Op; 4] = [Op::Add, Op::Sub, Op::Mul, Op::Div];
+
+/// Parses and validates `source` like a load would, then writes the resolved
+/// program to `out` for `load`. Param expressions and constants are already
+/// evaluated, so the compiled program doesn't depend on `constants` later.
+pub fn compile(source: &Path, out: &Path, constants: &Constants) -> Result<WeaveProgram, WeaveError> {
+    let program = parse_weave_with(source, constants)?;
+    save(&program, out)?;
+    Ok(program)
+}
+
+pub fn save(program: &WeaveProgram, path: &Path) -> Result<(), WeaveError> {
+    let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+    out.write_all(MAGIC)?;
+    write_u32(&mut out, FORMAT_VERSION)?;
+    write_u32(&mut out, SCALAR_WIDTH)?;
+    write_u32(&mut out, program.version)?;
+    write_u32(&mut out, program.fields.len() as u32)?;
+    for (name, params) in &program.fields {
+        write_str(&mut out, name)?;
+        write_u32(&mut out, params.len() as u32)?;
+        for (param, value) in params {
+            write_str(&mut out, param)?;
+            write_scalar(&mut out, *value)?;
+        }
+    }
+    let mut attributes: Vec<_> = program.attributes.iter().collect();
+    attributes.sort_by(|a, b| a.0.cmp(b.0));
+    write_u32(&mut out, attributes.len() as u32)?;
+    for (name, values) in attributes {
+        write_str(&mut out, name)?;
+        let mut values: Vec<_> = values.iter().collect();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(&mut out, values.len() as u32)?;
+        for (param, value) in values {
+            write_str(&mut out, param)?;
+            write_value(&mut out, value)?;
+        }
+    }
+    write_u32(&mut out, program.metaweave_rules.len() as u32)?;
+    for rule in &program.metaweave_rules {
+        write_condition(&mut out, &rule.condition)?;
+        write_str(&mut out, &rule.field)?;
+        write_str(&mut out, &rule.param)?;
+        write_scalar(&mut out, rule.value)?;
+    }
+    write_u32(&mut out, program.call_rules.len() as u32)?;
+    for rule in &program.call_rules {
+        write_condition(&mut out, &rule.condition)?;
+        write_str(&mut out, &rule.call.node)?;
+        write_str(&mut out, &rule.call.method)?;
+        write_u32(&mut out, rule.call.args.len() as u32)?;
+        for arg in &rule.call.args {
+            write_scalar(&mut out, *arg)?;
+        }
+    }
+    write_u32(&mut out, program.safety_rules.len() as u32)?;
+    for rule in &program.safety_rules {
+        write_u32(&mut out, rule.checks.len() as u32)?;
+        for check in &rule.checks {
+            write_str(&mut out, &check.sensor)?;
+            write_comparator(&mut out, check.comparator)?;
+            write_scalar(&mut out, check.threshold)?;
+        }
+        write_u8(&mut out, rule.severity as u8)?;
+        write_u32(&mut out, rule.grace_ticks)?;
+    }
+    write_u32(&mut out, program.sensor_bindings.len() as u32)?;
+    for (sensor, source) in &program.sensor_bindings {
+        write_str(&mut out, sensor)?;
+        write_str(&mut out, source)?;
+    }
+    write_u32(&mut out, program.declared_sensors.len() as u32)?;
+    for (sensor, default) in &program.declared_sensors {
+        write_str(&mut out, sensor)?;
+        write_scalar(&mut out, *default)?;
+    }
+    write_u32(&mut out, program.sensor_ranges.len() as u32)?;
+    for (sensor, (min, max)) in &program.sensor_ranges {
+        write_str(&mut out, sensor)?;
+        write_scalar(&mut out, *min)?;
+        write_scalar(&mut out, *max)?;
+    }
+    write_u32(&mut out, program.goals.len() as u32)?;
+    for goal in &program.goals {
+        write_str(&mut out, &goal.name)?;
+        write_comparisons(&mut out, &goal.conditions)?;
+        write_u32(&mut out, goal.hold_ticks)?;
+    }
+    write_u32(&mut out, program.invariants.len() as u32)?;
+    for invariant in &program.invariants {
+        write_str(&mut out, &invariant.name)?;
+        write_comparisons(&mut out, &invariant.conditions)?;
+    }
+    write_u32(&mut out, program.scheduled_sets.len() as u32)?;
+    for set in &program.scheduled_sets {
+        out.write_all(&(set.tick as u64).to_le_bytes())?;
+        write_str(&mut out, &set.field)?;
+        write_str(&mut out, &set.param)?;
+        write_scalar(&mut out, set.value)?;
+    }
+    write_u32(&mut out, program.derived_params.len() as u32)?;
+    for derived in &program.derived_params {
+        write_str(&mut out, &derived.field)?;
+        write_str(&mut out, &derived.param)?;
+        write_expr(&mut out, &derived.expr)?;
+    }
+    write_u32(&mut out, program.functions.len() as u32)?;
+    for (name, function) in &program.functions {
+        write_str(&mut out, name)?;
+        write_u32(&mut out, function.params.len() as u32)?;
+        for param in &function.params {
+            write_str(&mut out, param)?;
+        }
+        write_expr(&mut out, &function.body)?;
+    }
+    write_u32(&mut out, program.loops.len() as u32)?;
+    for looped in &program.loops {
+        write_loop(&mut out, looped)?;
+    }
+    write_u32(&mut out, program.agents.len() as u32)?;
+    for (name, template) in &program.agents {
+        write_str(&mut out, name)?;
+        write_str(&mut out, template)?;
+    }
+    write_u32(&mut out, program.tension.terms.len() as u32)?;
+    for term in &program.tension.terms {
+        write_str(&mut out, &term.sensor)?;
+        write_str(&mut out, &term.field)?;
+        write_str(&mut out, &term.param)?;
+        write_scalar(&mut out, term.weight)?;
+    }
+    write_u32(&mut out, program.handlers.len() as u32)?;
+    for handler in &program.handlers {
+        write_handler(&mut out, handler)?;
+    }
+    write_u32(&mut out, program.behaviors.len() as u32)?;
+    for tree in &program.behaviors {
+        write_str(&mut out, &tree.role)?;
+        write_behavior(&mut out, &tree.root)?;
+    }
+    write_u32(&mut out, program.templates.len() as u32)?;
+    for (name, template) in &program.templates {
+        write_str(&mut out, name)?;
+        write_template(&mut out, template)?;
+    }
+    let mut bounds: Vec<_> = program.param_bounds.iter().collect();
+    bounds.sort_by(|a, b| a.0.cmp(b.0));
+    write_u32(&mut out, bounds.len() as u32)?;
+    for (name, ranges) in bounds {
+        write_str(&mut out, name)?;
+        write_ranges(&mut out, ranges)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads a program written by `save` without running the parser. A file
+/// from another format version fails with `UnsupportedFormat`.
+pub fn load(path: &Path) -> Result<WeaveProgram, WeaveError> {
+    let mut input = io::BufReader::new(std::fs::File::open(path)?);
+    let mut magic = [0; 4];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(WeaveError::Validation(format!("{} is not a compiled weave program", path.display())));
+    }
+    let version = read_u32(&mut input)?;
+    if version != FORMAT_VERSION {
+        return Err(WeaveError::UnsupportedFormat { found: version, expected: FORMAT_VERSION });
+    }
+    read_width(&mut input, "compiled program stores", "fields")?;
+    let input = &mut input;
+    let mut program = WeaveProgram { version: read_u32(input)?, ..WeaveProgram::default() };
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let mut params = Params::new();
+        for _ in 0..read_u32(input)? {
+            let param = read_str(input)?;
+            params.insert(param, read_scalar(input)?);
+        }
+        program.fields.insert(name, params);
+    }
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let mut values = HashMap::new();
+        for _ in 0..read_u32(input)? {
+            let param = read_str(input)?;
+            values.insert(param, read_value(input)?);
+        }
+        program.attributes.insert(name, values);
+    }
+    for _ in 0..read_u32(input)? {
+        let condition = read_condition(input)?;
+        let (field, param) = (read_str(input)?, read_str(input)?);
+        program.metaweave_rules.push(MetaweaveRule { condition, field, param, value: read_scalar(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        let condition = read_condition(input)?;
+        let (node, method) = (read_str(input)?, read_str(input)?);
+        let args = (0..read_u32(input)?).map(|_| read_scalar(input)).collect::<io::Result<Vec<_>>>()?;
+        program.call_rules.push(CallRule { condition, call: LabCall { node, method, args } });
+    }
+    for _ in 0..read_u32(input)? {
+        let mut checks = Vec::new();
+        for _ in 0..read_u32(input)? {
+            let sensor = read_str(input)?;
+            let comparator = read_comparator(input)?;
+            checks.push(SafetyCheck { sensor, comparator, threshold: read_scalar(input)? });
+        }
+        let severity = match read_u8(input)? {
+            0 => SafetySeverity::Warn,
+            1 => SafetySeverity::Halt,
+            tag => return Err(corrupt("safety severity", tag)),
+        };
+        program.safety_rules.push(SafetyRule { checks, severity, grace_ticks: read_u32(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        let sensor = read_str(input)?;
+        program.sensor_bindings.insert(sensor, read_str(input)?);
+    }
+    for _ in 0..read_u32(input)? {
+        let sensor = read_str(input)?;
+        program.declared_sensors.insert(sensor, read_scalar(input)?);
+    }
+    for _ in 0..read_u32(input)? {
+        let sensor = read_str(input)?;
+        let min = read_scalar(input)?;
+        program.sensor_ranges.insert(sensor, (min, read_scalar(input)?));
+    }
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let conditions = read_comparisons(input)?;
+        program.goals.push(Goal { name, conditions, hold_ticks: read_u32(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        program.invariants.push(Invariant { name, conditions: read_comparisons(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        let mut tick = [0; 8];
+        input.read_exact(&mut tick)?;
+        let (field, param) = (read_str(input)?, read_str(input)?);
+        let tick = u64::from_le_bytes(tick) as usize;
+        program.scheduled_sets.push(ScheduledSet { tick, field, param, value: read_scalar(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        let (field, param) = (read_str(input)?, read_str(input)?);
+        program.derived_params.push(DerivedParam { field, param, expr: read_expr(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let params = (0..read_u32(input)?).map(|_| read_str(input)).collect::<io::Result<Vec<_>>>()?;
+        program.functions.insert(name, UserFunction { params, body: read_expr(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        program.loops.push(read_loop(input)?);
+    }
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        program.agents.insert(name, read_str(input)?);
+    }
+    for _ in 0..read_u32(input)? {
+        let (sensor, field, param) = (read_str(input)?, read_str(input)?, read_str(input)?);
+        program.tension.terms.push(WeightedTerm { sensor, field, param, weight: read_scalar(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        program.handlers.push(read_handler(input)?);
+    }
+    for _ in 0..read_u32(input)? {
+        let role = read_str(input)?;
+        program.behaviors.push(BehaviorTree { role, root: read_behavior(input)? });
+    }
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let template = read_template(input, &name)?;
+        program.templates.insert(name, template);
+    }
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        program.param_bounds.insert(name, read_ranges(input)?);
+    }
+    Ok(program)
+}
+
+fn corrupt(what: &str, tag: u8) -> WeaveError {
+    WeaveError::Validation(format!("compiled program is corrupt: unknown {} tag {}", what, tag))
+}
+
+fn write_u8(out: &mut impl Write, value: u8) -> io::Result<()> {
+    out.write_all(&[value])
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn write_value(out: &mut impl Write, value: &ParamValue) -> io::Result<()> {
+    match value {
+        ParamValue::Bool(value) => {
+            write_u8(out, 0)?;
+            write_u8(out, *value as u8)
+        }
+        ParamValue::Text(text) => {
+            write_u8(out, 1)?;
+            write_str(out, text)
+        }
+        ParamValue::Symbol(name) => {
+            write_u8(out, 2)?;
+            write_str(out, name)
+        }
+        ParamValue::List(items) => {
+            write_u8(out, 3)?;
+            write_u32(out, items.len() as u32)?;
+            items.iter().try_for_each(|item| write_value(out, item))
+        }
+        ParamValue::Vector(components) => {
+            write_u8(out, 4)?;
+            write_u32(out, components.len() as u32)?;
+            components.iter().try_for_each(|component| write_scalar(out, *component))
+        }
+    }
+}
+
+fn read_value(input: &mut impl Read) -> Result<ParamValue, WeaveError> {
+    Ok(match read_u8(input)? {
+        0 => ParamValue::Bool(read_u8(input)? != 0),
+        1 => ParamValue::Text(read_str(input)?),
+        2 => ParamValue::Symbol(read_str(input)?),
+        3 => ParamValue::List((0..read_u32(input)?).map(|_| read_value(input)).collect::<Result<_, _>>()?),
+        4 => ParamValue::Vector((0..read_u32(input)?).map(|_| read_scalar(input)).collect::<io::Result<_>>()?),
+        tag => return Err(corrupt("value", tag)),
+    })
+}
+
+fn write_comparator(out: &mut impl Write, comparator: Comparator) -> io::Result<()> {
+    write_u8(out, COMPARATORS.iter().position(|candidate| *candidate == comparator).unwrap_or(0) as u8)
+}
+
+fn read_comparator(input: &mut impl Read) -> Result<Comparator, WeaveError> {
+    let tag = read_u8(input)?;
+    COMPARATORS.get(tag as usize).copied().ok_or_else(|| corrupt("comparator", tag))
+}
+
+fn write_condition(out: &mut impl Write, condition: &RuleCondition) -> io::Result<()> {
+    match condition {
+        RuleCondition::Sensor { sensor, comparator, threshold } => {
+            write_u8(out, 0)?;
+            write_str(out, sensor)?;
+            write_comparator(out, *comparator)?;
+            write_scalar(out, *threshold)
+        }
+        RuleCondition::Attribute { field, param, equal, value } => {
+            write_u8(out, 1)?;
+            write_str(out, field)?;
+            write_str(out, param)?;
+            write_u8(out, *equal as u8)?;
+            write_value(out, value)
+        }
+    }
+}
+
+fn read_condition(input: &mut impl Read) -> Result<RuleCondition, WeaveError> {
+    match read_u8(input)? {
+        0 => {
+            let sensor = read_str(input)?;
+            let comparator = read_comparator(input)?;
+            Ok(RuleCondition::Sensor { sensor, comparator, threshold: read_scalar(input)? })
+        }
+        1 => {
+            let (field, param) = (read_str(input)?, read_str(input)?);
+            let equal = read_u8(input)? != 0;
+            Ok(RuleCondition::Attribute { field, param, equal, value: read_value(input)? })
+        }
+        tag => Err(corrupt("condition", tag)),
+    }
+}
+
+fn write_loop(out: &mut impl Write, looped: &Loop) -> io::Result<()> {
+    match &looped.kind {
+        LoopKind::Repeat(count) => {
+            write_u8(out, 0)?;
+            write_u32(out, *count)?;
+        }
+        LoopKind::While(condition) => {
+            write_u8(out, 1)?;
+            write_condition(out, condition)?;
+        }
+    }
+    write_u32(out, looped.body.len() as u32)?;
+    for statement in &looped.body {
+        match statement {
+            LoopStatement::Drift => write_u8(out, 0)?,
+            LoopStatement::Resolve => write_u8(out, 1)?,
+            LoopStatement::Tension => write_u8(out, 2)?,
+            LoopStatement::Set { field, param, value } => {
+                write_u8(out, 3)?;
+                write_str(out, field)?;
+                write_str(out, param)?;
+                write_expr(out, value)?;
+            }
+            LoopStatement::Send { target, topic, value } => {
+                write_u8(out, 4)?;
+                write_str(out, target)?;
+                write_str(out, topic)?;
+                write_expr(out, value)?;
+            }
+            LoopStatement::Emit { signal, value } => {
+                write_u8(out, 5)?;
+                write_str(out, signal)?;
+                write_expr(out, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_loop(input: &mut impl Read) -> Result<Loop, WeaveError> {
+    let kind = match read_u8(input)? {
+        0 => LoopKind::Repeat(read_u32(input)?),
+        1 => LoopKind::While(read_condition(input)?),
+        tag => return Err(corrupt("loop", tag)),
+    };
+    let body = (0..read_u32(input)?)
+        .map(|_| match read_u8(input)? {
+            0 => Ok(LoopStatement::Drift),
+            1 => Ok(LoopStatement::Resolve),
+            2 => Ok(LoopStatement::Tension),
+            3 => {
+                let (field, param) = (read_str(input)?, read_str(input)?);
+                Ok(LoopStatement::Set { field, param, value: read_expr(input)? })
+            }
+            4 => {
+                let (target, topic) = (read_str(input)?, read_str(input)?);
+                Ok(LoopStatement::Send { target, topic, value: read_expr(input)? })
+            }
+            5 => {
+                let signal = read_str(input)?;
+                Ok(LoopStatement::Emit { signal, value: read_expr(input)? })
+            }
+            tag => Err(corrupt("loop statement", tag)),
+        })
+        .collect::<Result<_, WeaveError>>()?;
+    Ok(Loop { kind, body })
+}
+
+fn write_template(out: &mut impl Write, template: &Template) -> io::Result<()> {
+    write_u32(out, template.params.len() as u32)?;
+    for (param, value) in &template.params {
+        write_str(out, param)?;
+        write_scalar(out, *value)?;
+    }
+    let mut attributes: Vec<_> = template.attributes.iter().collect();
+    attributes.sort_by(|a, b| a.0.cmp(b.0));
+    write_u32(out, attributes.len() as u32)?;
+    for (param, value) in attributes {
+        write_str(out, param)?;
+        write_value(out, value)?;
+    }
+    write_u32(out, template.derived_params.len() as u32)?;
+    for derived in &template.derived_params {
+        write_str(out, &derived.param)?;
+        write_expr(out, &derived.expr)?;
+    }
+    write_ranges(out, &template.bounds)
+}
+
+/// `in [min, max]` ranges by param, sorted so equal programs write equal files.
+fn write_ranges(out: &mut impl Write, ranges: &HashMap<String, (Scalar, Scalar)>) -> io::Result<()> {
+    let mut ranges: Vec<_> = ranges.iter().collect();
+    ranges.sort_by(|a, b| a.0.cmp(b.0));
+    write_u32(out, ranges.len() as u32)?;
+    for (param, (min, max)) in ranges {
+        write_str(out, param)?;
+        write_scalar(out, *min)?;
+        write_scalar(out, *max)?;
+    }
+    Ok(())
+}
+
+fn read_ranges(input: &mut impl Read) -> io::Result<HashMap<String, (Scalar, Scalar)>> {
+    let mut ranges = HashMap::new();
+    for _ in 0..read_u32(input)? {
+        let param = read_str(input)?;
+        let min = read_scalar(input)?;
+        ranges.insert(param, (min, read_scalar(input)?));
+    }
+    Ok(ranges)
+}
+
+/// A template's param expressions are stored without their field, which is `name`.
+fn read_template(input: &mut impl Read, name: &str) -> Result<Template, WeaveError> {
+    let mut template = Template::default();
+    for _ in 0..read_u32(input)? {
+        let param = read_str(input)?;
+        template.params.insert(param, read_scalar(input)?);
+    }
+    for _ in 0..read_u32(input)? {
+        let param = read_str(input)?;
+        template.attributes.insert(param, read_value(input)?);
+    }
+    for _ in 0..read_u32(input)? {
+        let param = read_str(input)?;
+        template.derived_params.push(DerivedParam { field: name.to_string(), param, expr: read_expr(input)? });
+    }
+    template.bounds = read_ranges(input)?;
+    Ok(template)
+}
+
+fn write_handler(out: &mut impl Write, handler: &EventHandler) -> io::Result<()> {
+    write_condition(out, &handler.condition)?;
+    write_u32(out, handler.actions.len() as u32)?;
+    for action in &handler.actions {
+        match action {
+            EventAction::Set { field, param, value } => {
+                write_u8(out, 0)?;
+                write_str(out, field)?;
+                write_str(out, param)?;
+                write_expr(out, value)?;
+            }
+            EventAction::Call(call) => {
+                write_u8(out, 1)?;
+                write_str(out, &call.node)?;
+                write_str(out, &call.method)?;
+                write_u32(out, call.args.len() as u32)?;
+                for arg in &call.args {
+                    write_scalar(out, *arg)?;
+                }
+            }
+            EventAction::Halt => write_u8(out, 2)?,
+            EventAction::Send { target, topic, value } => {
+                write_u8(out, 3)?;
+                write_str(out, target)?;
+                write_str(out, topic)?;
+                write_expr(out, value)?;
+            }
+            EventAction::Emit { signal, value } => {
+                write_u8(out, 4)?;
+                write_str(out, signal)?;
+                write_expr(out, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_handler(input: &mut impl Read) -> Result<EventHandler, WeaveError> {
+    let condition = read_condition(input)?;
+    let actions = (0..read_u32(input)?)
+        .map(|_| match read_u8(input)? {
+            0 => {
+                let (field, param) = (read_str(input)?, read_str(input)?);
+                Ok(EventAction::Set { field, param, value: read_expr(input)? })
+            }
+            1 => {
+                let (node, method) = (read_str(input)?, read_str(input)?);
+                let args = (0..read_u32(input)?).map(|_| read_scalar(input)).collect::<io::Result<_>>()?;
+                Ok(EventAction::Call(LabCall { node, method, args }))
+            }
+            2 => Ok(EventAction::Halt),
+            3 => {
+                let (target, topic) = (read_str(input)?, read_str(input)?);
+                Ok(EventAction::Send { target, topic, value: read_expr(input)? })
+            }
+            4 => {
+                let signal = read_str(input)?;
+                Ok(EventAction::Emit { signal, value: read_expr(input)? })
+            }
+            tag => Err(corrupt("event action", tag)),
+        })
+        .collect::<Result<_, WeaveError>>()?;
+    Ok(EventHandler { condition, actions })
+}
+
+fn write_behavior(out: &mut impl Write, node: &BehaviorNode) -> io::Result<()> {
+    match node {
+        BehaviorNode::Selector(children) | BehaviorNode::Sequence(children) => {
+            write_u8(out, if matches!(node, BehaviorNode::Selector(_)) { 0 } else { 1 })?;
+            write_u32(out, children.len() as u32)?;
+            children.iter().try_for_each(|child| write_behavior(out, child))
+        }
+        BehaviorNode::Check(condition) => {
+            write_u8(out, 2)?;
+            write_condition(out, condition)
+        }
+        BehaviorNode::Set { field, param, value } => {
+            write_u8(out, 3)?;
+            write_str(out, field)?;
+            write_str(out, param)?;
+            write_expr(out, value)
+        }
+        BehaviorNode::Action(name) => {
+            write_u8(out, 4)?;
+            write_str(out, name)
+        }
+    }
+}
+
+fn read_behavior(input: &mut impl Read) -> Result<BehaviorNode, WeaveError> {
+    Ok(match read_u8(input)? {
+        tag @ (0 | 1) => {
+            let children = (0..read_u32(input)?).map(|_| read_behavior(input)).collect::<Result<_, _>>()?;
+            if tag == 0 {
+                BehaviorNode::Selector(children)
+            } else {
+                BehaviorNode::Sequence(children)
+            }
+        }
+        2 => BehaviorNode::Check(read_condition(input)?),
+        3 => {
+            let (field, param) = (read_str(input)?, read_str(input)?);
+            BehaviorNode::Set { field, param, value: read_expr(input)? }
+        }
+        4 => BehaviorNode::Action(read_str(input)?),
+        tag => return Err(corrupt("behavior node", tag)),
+    })
+}
+
+fn write_comparisons(out: &mut impl Write, comparisons: &[Comparison]) -> io::Result<()> {
+    write_u32(out, comparisons.len() as u32)?;
+    for comparison in comparisons {
+        write_expr(out, &comparison.lhs)?;
+        write_comparator(out, comparison.comparator)?;
+        write_expr(out, &comparison.rhs)?;
+    }
+    Ok(())
+}
+
+fn read_comparisons(input: &mut impl Read) -> Result<Vec<Comparison>, WeaveError> {
+    (0..read_u32(input)?)
+        .map(|_| {
+            let lhs = read_expr(input)?;
+            let comparator = read_comparator(input)?;
+            Ok(Comparison { lhs, comparator, rhs: read_expr(input)? })
+        })
+        .collect()
+}
+
+/// Expression numbers are always f64, whatever the field width.
+fn write_expr(out: &mut impl Write, expr: &Expr) -> io::Result<()> {
+    match expr {
+        Expr::Number(value) => {
+            write_u8(out, 0)?;
+            out.write_all(&value.to_le_bytes())
+        }
+        Expr::Name(name) => {
+            write_u8(out, 1)?;
+            write_str(out, name)
+        }
+        Expr::Param(field, param) => {
+            write_u8(out, 2)?;
+            write_str(out, field)?;
+            write_str(out, param)
+        }
+        Expr::Neg(inner) => {
+            write_u8(out, 3)?;
+            write_expr(out, inner)
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            write_u8(out, 4)?;
+            write_u8(out, OPS.iter().position(|candidate| candidate == op).unwrap_or(0) as u8)?;
+            write_expr(out, lhs)?;
+            write_expr(out, rhs)
+        }
+        Expr::Call(function, args) => {
+            write_u8(out, 5)?;
+            write_str(out, function.name())?;
+            write_u32(out, args.len() as u32)?;
+            args.iter().try_for_each(|arg| write_expr(out, arg))
+        }
+        Expr::Receive(topic) => {
+            write_u8(out, 6)?;
+            write_str(out, topic)
+        }
+    }
+}
+
+fn read_expr(input: &mut impl Read) -> Result<Expr, WeaveError> {
+    Ok(match read_u8(input)? {
+        0 => {
+            let mut bytes = [0; 8];
+            input.read_exact(&mut bytes)?;
+            Expr::Number(f64::from_le_bytes(bytes))
+        }
+        1 => Expr::Name(read_str(input)?),
+        2 => {
+            let field = read_str(input)?;
+            Expr::Param(field, read_str(input)?)
+        }
+        3 => Expr::Neg(Box::new(read_expr(input)?)),
+        4 => {
+            let tag = read_u8(input)?;
+            let op = OPS.get(tag as usize).copied().ok_or_else(|| corrupt("operator", tag))?;
+            let lhs = read_expr(input)?;
+            Expr::Binary(op, Box::new(lhs), Box::new(read_expr(input)?))
+        }
+        5 => {
+            let name = read_str(input)?;
+            let function = Function::from_name(&name)
+                .ok_or_else(|| WeaveError::Validation(format!("compiled program calls unknown function {}", name)))?;
+            Expr::Call(function, (0..read_u32(input)?).map(|_| read_expr(input)).collect::<Result<_, _>>()?)
+        }
+        6 => Expr::Receive(read_str(input)?),
+        tag => return Err(corrupt("expression", tag)),
+    })
+}
@@ -0,0 +1,110 @@
+use crate::interpreter::Params;
+use crate::scalar::Scalar;
+use std::str::FromStr;
+
+pub const DEFAULT_DRIFT_RATE: Scalar = 0.01;
+pub const DEFAULT_RESOLVE_RATE: Scalar = 0.005;
+/// Tension samples kept by default before the oldest are dropped.
+pub const DEFAULT_HISTORY_LIMIT: usize = 10_000;
+/// Weight of the newest tension under `DriftStrategy::ExponentialSmoothing`
+/// for fields that declare no `drift_smoothing`.
+pub const DEFAULT_DRIFT_SMOOTHING: Scalar = 0.2;
+
+/// What drift scales each target's step by. Whichever is chosen, the step
+/// is still `drift_rate * dt` times that, so `Linear` is the others with
+/// their tuning left at the defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize), serde(rename_all = "snake_case"))]
+pub enum DriftStrategy {
+    /// The tension itself.
+    #[default]
+    Linear,
+    /// A per-agent PID controller on the tension, with the gains a field
+    /// declares as `drift_kp`, `drift_ki` and `drift_kd` (1, 0 and 0 by
+    /// default). A derivative gain brakes drift while tension is falling.
+    Pid,
+    /// A per-agent moving average of the tension, weighting the newest by
+    /// the field's `drift_smoothing`, so a spike doesn't throw targets.
+    ExponentialSmoothing,
+}
+
+impl DriftStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DriftStrategy::Linear => "linear",
+            DriftStrategy::Pid => "pid",
+            DriftStrategy::ExponentialSmoothing => "exponential_smoothing",
+        }
+    }
+}
+
+impl FromStr for DriftStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(DriftStrategy::Linear),
+            "pid" => Ok(DriftStrategy::Pid),
+            "exponential_smoothing" => Ok(DriftStrategy::ExponentialSmoothing),
+            _ => Err(format!("unknown drift strategy '{}', expected linear, pid or exponential_smoothing", s)),
+        }
+    }
+}
+
+/// The gains and bounds drift and resolve work with. A field overrides the
+/// gains for itself by declaring `drift_rate` or `resolve_rate`, and the
+/// bounds by declaring `target_min` or `target_max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "json", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct InterpreterConfig {
+    /// Drift moves each target by `tension * drift_rate` per tick.
+    pub drift_rate: Scalar,
+    /// Resolve moves each target back by `tension * resolve_rate` per tick,
+    /// and pulls kinematic agents with that stiffness.
+    pub resolve_rate: Scalar,
+    /// Newest tension samples kept; 0 keeps them all.
+    pub history_limit: usize,
+    /// Clamp every target to `target_min..=target_max`, unless its field
+    /// declares its own bound.
+    pub clamp_targets: bool,
+    pub target_min: Scalar,
+    pub target_max: Scalar,
+    pub drift_strategy: DriftStrategy,
+}
+
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        InterpreterConfig {
+            drift_rate: DEFAULT_DRIFT_RATE,
+            resolve_rate: DEFAULT_RESOLVE_RATE,
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            clamp_targets: false,
+            target_min: 0.0,
+            target_max: 1.0,
+            drift_strategy: DriftStrategy::Linear,
+        }
+    }
+}
+
+impl InterpreterConfig {
+    pub fn drift_rate_for(&self, field: &Params) -> Scalar {
+        field.get("drift_rate").copied().unwrap_or(self.drift_rate)
+    }
+
+    /// The field's `drift_smoothing`, kept within `0..=1`.
+    pub fn drift_smoothing_for(&self, field: &Params) -> Scalar {
+        field.get("drift_smoothing").copied().unwrap_or(DEFAULT_DRIFT_SMOOTHING).clamp(0.0, 1.0)
+    }
+
+    pub fn resolve_rate_for(&self, field: &Params) -> Scalar {
+        field.get("resolve_rate").copied().unwrap_or(self.resolve_rate)
+    }
+
+    /// `value` clamped to the field's `target_min` and `target_max`, or to
+    /// the configured bounds where the field declares none.
+    pub fn bounded(&self, field: &Params, value: Scalar) -> Scalar {
+        let global = |bound: Scalar| self.clamp_targets.then_some(bound);
+        let value = field.get("target_min").copied().or_else(|| global(self.target_min)).map_or(value, |min| value.max(min));
+        field.get("target_max").copied().or_else(|| global(self.target_max)).map_or(value, |max| value.min(max))
+    }
+}
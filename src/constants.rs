@@ -0,0 +1,241 @@
+use crate::error::WeaveError;
+use indexmap::IndexMap;
+
+/// Exponents of the SI base dimensions a unit measures:
+/// length, mass, time, temperature, amount, current.
+type Dimensions = [i8; 6];
+
+const LENGTH: Dimensions = [1, 0, 0, 0, 0, 0];
+const MASS: Dimensions = [0, 1, 0, 0, 0, 0];
+const TIME: Dimensions = [0, 0, 1, 0, 0, 0];
+const TEMPERATURE: Dimensions = [0, 0, 0, 1, 0, 0];
+const AMOUNT: Dimensions = [0, 0, 0, 0, 1, 0];
+const CURRENT: Dimensions = [0, 0, 0, 0, 0, 1];
+const FREQUENCY: Dimensions = [0, 0, -1, 0, 0, 0];
+const FORCE: Dimensions = [1, 1, -2, 0, 0, 0];
+const ENERGY: Dimensions = [2, 1, -2, 0, 0, 0];
+const POWER: Dimensions = [2, 1, -3, 0, 0, 0];
+const PRESSURE: Dimensions = [-1, 1, -2, 0, 0, 0];
+
+/// Unit symbols `parse_unit` knows, with their size in SI base units.
+/// Temperatures are absolute only; offset scales like Celsius are not units.
+const UNITS: &[(&str, f64, Dimensions)] = &[
+    ("m", 1.0, LENGTH),
+    ("km", 1e3, LENGTH),
+    ("cm", 1e-2, LENGTH),
+    ("mm", 1e-3, LENGTH),
+    ("um", 1e-6, LENGTH),
+    ("nm", 1e-9, LENGTH),
+    ("in", 0.0254, LENGTH),
+    ("ft", 0.3048, LENGTH),
+    ("mi", 1609.344, LENGTH),
+    ("au", 1.495978707e11, LENGTH),
+    ("ly", 9.4607304725808e15, LENGTH),
+    ("kg", 1.0, MASS),
+    ("g", 1e-3, MASS),
+    ("mg", 1e-6, MASS),
+    ("t", 1e3, MASS),
+    ("lb", 0.45359237, MASS),
+    ("s", 1.0, TIME),
+    ("ms", 1e-3, TIME),
+    ("us", 1e-6, TIME),
+    ("ns", 1e-9, TIME),
+    ("min", 60.0, TIME),
+    ("h", 3600.0, TIME),
+    ("d", 86400.0, TIME),
+    ("K", 1.0, TEMPERATURE),
+    ("mol", 1.0, AMOUNT),
+    ("A", 1.0, CURRENT),
+    ("Hz", 1.0, FREQUENCY),
+    ("N", 1.0, FORCE),
+    ("J", 1.0, ENERGY),
+    ("kJ", 1e3, ENERGY),
+    ("cal", 4.184, ENERGY),
+    ("kcal", 4184.0, ENERGY),
+    ("eV", 1.602176634e-19, ENERGY),
+    ("kWh", 3.6e6, ENERGY),
+    ("W", 1.0, POWER),
+    ("kW", 1e3, POWER),
+    ("Pa", 1.0, PRESSURE),
+    ("kPa", 1e3, PRESSURE),
+    ("bar", 1e5, PRESSURE),
+    ("atm", 101325.0, PRESSURE),
+];
+
+/// A unit as a multiple of SI base units, e.g. `km/h` is 1/3.6 of `m/s`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    pub factor: f64,
+    dimensions: Dimensions,
+}
+
+impl Unit {
+    const ONE: Unit = Unit { factor: 1.0, dimensions: [0; 6] };
+
+    fn times(self, other: Unit, exponent: i8) -> Unit {
+        let mut dimensions = self.dimensions;
+        for (dimension, other) in dimensions.iter_mut().zip(other.dimensions) {
+            *dimension += other * exponent;
+        }
+        Unit { factor: self.factor * other.factor.powi(exponent as i32), dimensions }
+    }
+
+    pub fn same_dimensions(&self, other: &Unit) -> bool {
+        self.dimensions == other.dimensions
+    }
+}
+
+/// Parses unit expressions such as `m/s^2`, `kg*m^2/s^2` or `J/(mol*K)`.
+/// `1` or an empty string is dimensionless.
+pub fn parse_unit(text: &str) -> Result<Unit, WeaveError> {
+    let tokens: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if tokens.is_empty() {
+        return Ok(Unit::ONE);
+    }
+    let mut parser = UnitParser { text, tokens: &tokens, at: 0 };
+    let unit = parser.product()?;
+    if parser.at != tokens.len() {
+        return Err(parser.error());
+    }
+    Ok(unit)
+}
+
+/// `value` measured in `from` expressed in `to`. Fails on an unknown unit or
+/// when the two measure different things (`m` and `s`).
+pub fn convert(value: f64, from: &str, to: &str) -> Result<f64, WeaveError> {
+    let (source, target) = (parse_unit(from)?, parse_unit(to)?);
+    if !source.same_dimensions(&target) {
+        return Err(WeaveError::Validation(format!("cannot convert {} to {}: they measure different quantities", from, to)));
+    }
+    Ok(value * source.factor / target.factor)
+}
+
+struct UnitParser<'a> {
+    text: &'a str,
+    tokens: &'a [char],
+    at: usize,
+}
+
+impl UnitParser<'_> {
+    fn product(&mut self) -> Result<Unit, WeaveError> {
+        let mut unit = self.power()?;
+        while let Some(&op) = self.tokens.get(self.at) {
+            let exponent = match op {
+                '*' | '.' => 1,
+                '/' => -1,
+                _ => break,
+            };
+            self.at += 1;
+            unit = unit.times(self.power()?, exponent);
+        }
+        Ok(unit)
+    }
+
+    fn power(&mut self) -> Result<Unit, WeaveError> {
+        let base = self.atom()?;
+        if self.tokens.get(self.at) != Some(&'^') {
+            return Ok(base);
+        }
+        self.at += 1;
+        let start = self.at;
+        if self.tokens.get(self.at) == Some(&'-') {
+            self.at += 1;
+        }
+        while self.tokens.get(self.at).is_some_and(char::is_ascii_digit) {
+            self.at += 1;
+        }
+        let exponent: String = self.tokens[start..self.at].iter().collect();
+        let exponent = exponent.parse().map_err(|_| self.error())?;
+        Ok(Unit::ONE.times(base, exponent))
+    }
+
+    fn atom(&mut self) -> Result<Unit, WeaveError> {
+        match self.tokens.get(self.at) {
+            Some('(') => {
+                self.at += 1;
+                let unit = self.product()?;
+                if self.tokens.get(self.at) != Some(&')') {
+                    return Err(self.error());
+                }
+                self.at += 1;
+                Ok(unit)
+            }
+            Some('1') => {
+                self.at += 1;
+                Ok(Unit::ONE)
+            }
+            _ => {
+                let start = self.at;
+                while self.tokens.get(self.at).is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.at += 1;
+                }
+                let symbol: String = self.tokens[start..self.at].iter().collect();
+                match UNITS.iter().find(|(name, _, _)| *name == symbol) {
+                    Some(&(_, factor, dimensions)) => Ok(Unit { factor, dimensions }),
+                    None if symbol.is_empty() => Err(self.error()),
+                    None => Err(WeaveError::Validation(format!("unknown unit {} in {}", symbol, self.text))),
+                }
+            }
+        }
+    }
+
+    fn error(&self) -> WeaveError {
+        WeaveError::Validation(format!("malformed unit {}", self.text))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constant {
+    pub value: f64,
+    pub unit: String,
+}
+
+/// Named physical constants weave expressions and GDScript can refer to.
+/// Starts with the standard table; `register` adds or overrides entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constants {
+    table: IndexMap<String, Constant>,
+}
+
+impl Default for Constants {
+    fn default() -> Self {
+        let mut constants = Constants { table: IndexMap::new() };
+        for (name, value, unit) in [
+            ("g", 9.80665, "m/s^2"),
+            ("G", 6.6743e-11, "m^3/(kg*s^2)"),
+            ("C", 299792458.0, "m/s"),
+            ("H", 6.62607015e-34, "J*s"),
+            ("K_B", 1.380649e-23, "J/K"),
+            ("N_A", 6.02214076e23, "1/mol"),
+            ("R", 8.314462618, "J/(mol*K)"),
+            ("P0", 101325.0, "Pa"),
+            ("T0", 273.15, "K"),
+        ] {
+            constants.table.insert(name.to_string(), Constant { value, unit: unit.to_string() });
+        }
+        constants
+    }
+}
+
+impl Constants {
+    /// Adds or replaces `name`. Fails, changing nothing, if `name` is not a
+    /// weave identifier or `unit` does not parse.
+    pub fn register(&mut self, name: &str, value: f64, unit: &str) -> Result<(), WeaveError> {
+        let mut chars = name.chars();
+        let identifier = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !identifier {
+            return Err(WeaveError::Validation(format!("constant name {:?} is not an identifier", name)));
+        }
+        parse_unit(unit)?;
+        self.table.insert(name.to_string(), Constant { value, unit: unit.to_string() });
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Constant> {
+        self.table.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Constant)> {
+        self.table.iter().map(|(name, constant)| (name.as_str(), constant))
+    }
+}
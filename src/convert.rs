@@ -0,0 +1,761 @@
+use gdnative::prelude::*;
+use std::collections::HashMap;
+use crate::behavior::{BehaviorStatus, BehaviorTree};
+use crate::constants::Constants;
+use crate::diagnostic::Diagnostic;
+use crate::error::WeaveError;
+use crate::goals::{Goal, GoalProgress};
+use crate::history::HistoryStats;
+use crate::interpreter::{parse_param_value, CodeOutcome, ExperimentState, Fields, FrameMetrics, ParamValue, Params, StepPreview, TensionTerm};
+use crate::invariant::{Invariant, InvariantStatus, InvariantViolation};
+use crate::logging::LogEntry;
+use crate::messages::Message;
+use crate::metrics::ExperimentMetrics;
+use crate::noise::DEFAULT_SEED;
+use crate::profile::{ProfilePhase, Profiler};
+#[cfg(feature = "godot4")]
+use crate::recording::ReplayOutcome;
+use crate::reload::FieldChanges;
+#[cfg(feature = "godot4")]
+use crate::rules::LabCall;
+use crate::rules::{MetaweaveRule, RuleCondition};
+use crate::safety::{SafetyEvent, SafetyLog};
+use crate::scalar::{to_f32, to_f64, Scalar};
+use crate::schedule::{Schedule, ScheduledAction, ScheduledSet};
+use crate::sensors::SensorSpec;
+use crate::trace::TraceEntry;
+
+pub fn error_dictionary(error: &WeaveError) -> Dictionary {
+    let dict = Dictionary::new();
+    dict.insert("kind", error.kind());
+    dict.insert("code", error_code(error) as i64);
+    dict.insert("message", error.to_string());
+    if let Some((line, column)) = error.line_col() {
+        dict.insert("line", line as i64);
+        dict.insert("column", column as i64);
+    }
+    dict.into_shared()
+}
+
+/// Godot's global error code for `error`, so GDScript can branch on `ERR_*`
+/// constants instead of matching `kind` strings.
+pub fn error_code(error: &WeaveError) -> GodotError {
+    match error {
+        WeaveError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => GodotError::FileNotFound,
+        WeaveError::Io(_) => GodotError::FileCantRead,
+        WeaveError::Parse(_) | WeaveError::UnsupportedVersion { .. } => GodotError::ParseError,
+        WeaveError::UnsupportedFormat { .. } => GodotError::FileUnrecognized,
+        WeaveError::Busy(_) => GodotError::Busy,
+        WeaveError::Validation(_)
+        | WeaveError::MissingField(_)
+        | WeaveError::MissingParam { .. }
+        | WeaveError::MissingSensor(_) => GodotError::InvalidData,
+    }
+}
+
+pub fn changes_dictionary(changes: &FieldChanges) -> Dictionary {
+    let dict = Dictionary::new();
+    dict.insert("added", &changes.added);
+    dict.insert("removed", &changes.removed);
+    dict.insert("updated", &changes.updated);
+    dict.into_shared()
+}
+
+/// Numeric entries of `sensor_data`. Ints and bools (as 0/1) count as numbers;
+/// anything else is skipped with a warning naming the key and its Variant type.
+pub fn sensors_from_dictionary(sensor_data: &Dictionary, warnings: &mut Vec<String>) -> HashMap<String, Scalar> {
+    scalars_from_dictionary(sensor_data, "", warnings).into_iter().collect()
+}
+
+pub fn agents_from_dictionary(agent_data: &Dictionary, warnings: &mut Vec<String>) -> Fields {
+    let mut agents = Fields::new();
+    for (agent_name, props) in agent_data.iter() {
+        if let Ok(name) = agent_name.try_to::<String>() {
+            if let Ok(props_dict) = props.try_to::<Dictionary>() {
+                let params = scalars_from_dictionary(&props_dict, &format!("{}.", name), warnings);
+                agents.insert(name, params);
+            }
+        }
+    }
+    agents
+}
+
+fn scalars_from_dictionary(data: &Dictionary, prefix: &str, warnings: &mut Vec<String>) -> Params {
+    let mut values = Params::new();
+    for (key, value) in data.iter() {
+        if let Ok(key_str) = key.try_to::<String>() {
+            match scalar_from_variant(&value) {
+                Some(val) => {
+                    values.insert(key_str, val);
+                }
+                None => warnings.push(format!("{}{} is a {:?}, not a number; skipped", prefix, key_str, value.get_type())),
+            }
+        }
+    }
+    values
+}
+
+/// What a GDScript behavior action returned: a bool for success or failure,
+/// or `"success"`, `"failure"` or `"running"`. Anything else fails.
+pub fn behavior_status_from_variant(value: &Variant) -> BehaviorStatus {
+    match value.get_type() {
+        VariantType::Bool if value.try_to::<bool>().unwrap_or(false) => BehaviorStatus::Success,
+        VariantType::GodotString => value.try_to::<String>().ok().and_then(|status| status.parse().ok()).unwrap_or(BehaviorStatus::Failure),
+        _ => BehaviorStatus::Failure,
+    }
+}
+
+/// `{ role: tree as source text }`, in file order.
+pub fn behaviors_dictionary(trees: &[BehaviorTree]) -> Dictionary {
+    let dict = Dictionary::new();
+    for tree in trees {
+        dict.insert(tree.role.as_str(), tree.root.to_string());
+    }
+    dict.into_shared()
+}
+
+/// A number, int or bool (as 0/1) as a scalar; `None` for any other type.
+pub fn scalar_from_variant(value: &Variant) -> Option<Scalar> {
+    match value.get_type() {
+        VariantType::F64 => value.try_to::<f64>().ok().map(|v| v as Scalar),
+        VariantType::I64 => value.try_to::<i64>().ok().map(|v| v as Scalar),
+        VariantType::Bool => value.try_to::<bool>().ok().map(|v| if v { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+/// `{ default, min, max }`, each optional; a key that isn't a number is
+/// an error naming it.
+pub fn sensor_spec_from_dictionary(spec: &Dictionary) -> Result<SensorSpec, String> {
+    let entry = |key: &str| match spec.get(key) {
+        None => Ok(None),
+        Some(value) => scalar_from_variant(&value).map(Some).ok_or_else(|| format!("sensor {} must be a number", key)),
+    };
+    Ok(SensorSpec { default: entry("default")?, min: entry("min")?, max: entry("max")? })
+}
+
+/// `{ sensor: { default, min, max } }`, leaving out what a sensor doesn't set.
+pub fn sensor_specs_dictionary<'a>(specs: impl Iterator<Item = (&'a String, &'a SensorSpec)>) -> Dictionary {
+    let dict = Dictionary::new();
+    for (sensor, spec) in specs {
+        let entry = Dictionary::new();
+        for (key, value) in [("default", spec.default), ("min", spec.min), ("max", spec.max)] {
+            if let Some(value) = value {
+                entry.insert(key, value);
+            }
+        }
+        dict.insert(sensor.as_str(), entry.into_shared());
+    }
+    dict.into_shared()
+}
+
+pub fn bindings_dictionary<'a>(bindings: impl Iterator<Item = (&'a str, &'a str)>) -> Dictionary {
+    let dict = Dictionary::new();
+    for (sensor, source) in bindings {
+        dict.insert(sensor, source);
+    }
+    dict.into_shared()
+}
+
+pub fn constants_dictionary(constants: &Constants) -> Dictionary {
+    let dict = Dictionary::new();
+    for (name, constant) in constants.iter() {
+        let entry = Dictionary::new();
+        entry.insert("value", constant.value);
+        entry.insert("unit", &constant.unit);
+        dict.insert(name, entry.into_shared());
+    }
+    dict.into_shared()
+}
+
+/// `{ name: { progress, satisfied_ticks, hold_ticks, completed } }`, with
+/// `progress` the current run as a fraction of `hold_ticks`.
+pub fn goal_progress_dictionary<'a>(goals: impl Iterator<Item = (&'a Goal, GoalProgress)>) -> Dictionary {
+    let dict = Dictionary::new();
+    for (goal, progress) in goals {
+        let entry = Dictionary::new();
+        entry.insert("progress", progress.fraction(goal.hold_ticks));
+        entry.insert("satisfied_ticks", progress.satisfied_ticks as i64);
+        entry.insert("hold_ticks", goal.hold_ticks as i64);
+        entry.insert("completed", progress.completed);
+        dict.insert(&goal.name, entry.into_shared());
+    }
+    dict.into_shared()
+}
+
+/// `{ tension, terms: [{ sensor, agent, target, sensed, weight, contribution }] }`
+/// with the terms largest contribution first; `tension` is their sum.
+pub fn tension_explanation_dictionary(terms: &[TensionTerm]) -> Dictionary {
+    let dict = Dictionary::new();
+    let array = VariantArray::new();
+    for term in terms {
+        let entry = Dictionary::new();
+        entry.insert("sensor", &term.sensor);
+        entry.insert("agent", &term.agent);
+        entry.insert("target", term.target);
+        entry.insert("sensed", term.sensed);
+        entry.insert("weight", term.weight);
+        entry.insert("contribution", term.contribution);
+        array.push(entry.into_shared());
+    }
+    dict.insert("tension", terms.iter().fold(0.0, |sum: Scalar, term| sum + term.contribution));
+    dict.insert("terms", array.into_shared());
+    dict.into_shared()
+}
+
+#[cfg(feature = "godot4")]
+pub fn lab_call_dictionary(call: &LabCall) -> Dictionary {
+    let dict = Dictionary::new();
+    dict.insert("node", &call.node);
+    dict.insert("method", &call.method);
+    dict.insert("args", &call.args);
+    dict.into_shared()
+}
+
+/// Writes `agents` back into the caller's Dictionary, one sub-Dictionary per
+/// agent, adding any agent it lacks.
+pub fn write_agents(agent_data: &Dictionary, agents: &Fields) {
+    // SAFETY: exported methods run on the main thread while the script that
+    // passed `agent_data` waits on the call, so nothing else resizes it.
+    let agent_data = unsafe { agent_data.new_ref().assume_unique() };
+    for (name, props) in agents.iter() {
+        let dict = Dictionary::new();
+        for (prop, val) in props {
+            dict.insert(prop, *val);
+        }
+        agent_data.insert(name, dict);
+    }
+}
+
+/// Numeric entries of a `global` params Dictionary.
+#[cfg(feature = "godot4")]
+pub fn globals_from_dictionary(globals: &Dictionary, warnings: &mut Vec<String>) -> Params {
+    scalars_from_dictionary(globals, "global.", warnings)
+}
+
+#[cfg(feature = "godot3")]
+pub fn params_dictionary(params: &Params) -> Dictionary {
+    let dict = Dictionary::new();
+    for (param, value) in params {
+        dict.insert(param, *value);
+    }
+    dict.into_shared()
+}
+
+/// Every field as a nested Dictionary, in declaration order.
+pub fn fields_dictionary(fields: &Fields) -> Dictionary {
+    let dict = Dictionary::new();
+    for (name, params) in fields {
+        let field = Dictionary::new();
+        for (param, value) in params {
+            field.insert(param, *value);
+        }
+        dict.insert(name, field.into_shared());
+    }
+    dict.into_shared()
+}
+
+pub fn stats_dictionary(stats: Option<HistoryStats>) -> Dictionary {
+    let dict = Dictionary::new();
+    if let Some(stats) = stats {
+        dict.insert("count", stats.count as i64);
+        dict.insert("mean", stats.mean);
+        dict.insert("variance", stats.variance);
+        dict.insert("min", stats.min);
+        dict.insert("max", stats.max);
+        dict.insert("latest", stats.latest);
+        dict.insert("slope", stats.slope);
+    }
+    dict.into_shared()
+}
+
+pub fn curve_array(points: &[(usize, Scalar)]) -> PoolArray<Vector2> {
+    let mut curve = PoolArray::new();
+    for &(tick, tension) in points {
+        curve.push(Vector2::new(tick as f32, to_f32(tension)));
+    }
+    curve
+}
+
+/// One Dictionary per entry, with `trigger_value` null when the trigger has no number.
+pub fn trace_array<'a>(entries: impl Iterator<Item = &'a TraceEntry>) -> VariantArray {
+    let array = VariantArray::new();
+    for entry in entries {
+        let dict = Dictionary::new();
+        dict.insert("tick", entry.tick as i64);
+        dict.insert("phase", entry.phase.as_str());
+        dict.insert("agent", &entry.agent);
+        dict.insert("param", &entry.param);
+        dict.insert("old_value", entry.old_value);
+        dict.insert("new_value", entry.new_value);
+        dict.insert("trigger", &entry.trigger);
+        dict.insert("trigger_value", entry.trigger_value.map(|value| value.to_variant()).unwrap_or_default());
+        array.push(dict.into_shared());
+    }
+    array.into_shared()
+}
+
+/// One `{ id, tick, agent, param, value, from_script }` Dictionary per
+/// pending set, in firing order.
+pub fn scheduled_array(actions: &[ScheduledAction]) -> VariantArray {
+    let array = VariantArray::new();
+    for action in actions {
+        let dict = Dictionary::new();
+        dict.insert("id", action.id as i64);
+        dict.insert("tick", action.set.tick as i64);
+        dict.insert("agent", &action.set.field);
+        dict.insert("param", &action.set.param);
+        dict.insert("value", action.set.value);
+        dict.insert("from_script", action.from_script);
+        array.push(dict.into_shared());
+    }
+    array.into_shared()
+}
+
+/// One `{ severity, message, line, column, end_line, end_column, snippet }`
+/// Dictionary per diagnostic; lines and columns are 1-based, 0 without a place.
+pub fn diagnostics_array(diagnostics: &[Diagnostic]) -> VariantArray {
+    let array = VariantArray::new();
+    for diagnostic in diagnostics {
+        let dict = Dictionary::new();
+        dict.insert("severity", diagnostic.severity.as_str());
+        dict.insert("message", &diagnostic.message);
+        dict.insert("line", diagnostic.line as i64);
+        dict.insert("column", diagnostic.column as i64);
+        dict.insert("end_line", diagnostic.end_line as i64);
+        dict.insert("end_column", diagnostic.end_column as i64);
+        dict.insert("snippet", &diagnostic.snippet);
+        array.push(dict.into_shared());
+    }
+    array.into_shared()
+}
+
+/// One `{ level, message }` Dictionary per entry, oldest first.
+pub fn log_array(entries: &[LogEntry]) -> VariantArray {
+    let array = VariantArray::new();
+    for entry in entries {
+        let dict = Dictionary::new();
+        dict.insert("level", entry.level.as_str());
+        dict.insert("message", &entry.message);
+        array.push(dict.into_shared());
+    }
+    array.into_shared()
+}
+
+pub fn metrics_dictionary(metrics: &FrameMetrics) -> Dictionary {
+    let dict = Dictionary::new();
+    dict.insert("tick", metrics.tick as i64);
+    dict.insert("tension", metrics.tension);
+    dict.insert("smoothed_tension", metrics.smoothed_tension);
+    dict.insert("agents_updated", metrics.agents_updated as i64);
+    let fired = VariantArray::new();
+    for rule in &metrics.fired_rules {
+        fired.push(rule_dictionary(rule));
+    }
+    dict.insert("fired_rules", fired.into_shared());
+    let trips: Vec<i64> = metrics.safety_trips.iter().map(|&index| index as i64).collect();
+    dict.insert("safety_trips", trips);
+    dict.insert("total_drift", metrics.total_drift);
+    dict.insert("load_status", metrics.load_status);
+    dict.insert("execute_micros", metrics.execute_micros as i64);
+    dict.into_shared()
+}
+
+/// `{ ticks, parse_errors, safety_violations, tension, smoothed_tension, coherence, halted, agents: { agent: coherence } }`
+pub fn experiment_metrics_dictionary(metrics: &ExperimentMetrics) -> Dictionary {
+    let dict = Dictionary::new();
+    dict.insert("ticks", metrics.ticks as i64);
+    dict.insert("parse_errors", metrics.parse_errors as i64);
+    dict.insert("safety_violations", metrics.safety_violations as i64);
+    dict.insert("tension", metrics.tension);
+    dict.insert("smoothed_tension", metrics.smoothed_tension);
+    dict.insert("coherence", metrics.coherence);
+    dict.insert("halted", metrics.halted);
+    let agents = Dictionary::new();
+    for (agent, coherence) in &metrics.agent_coherence {
+        agents.insert(agent, *coherence);
+    }
+    dict.insert("agents", agents.into_shared());
+    dict.into_shared()
+}
+
+/// `{ enabled, ticks, phases: { phase: { last_micros, mean_micros, max_micros, total_micros, calls } } }`
+pub fn profile_dictionary(profiler: &Profiler) -> Dictionary {
+    let phases = Dictionary::new();
+    for phase in ProfilePhase::ALL {
+        let timing = profiler.timing(phase);
+        let dict = Dictionary::new();
+        dict.insert("last_micros", timing.last_micros as i64);
+        dict.insert("mean_micros", profiler.mean_micros(phase));
+        dict.insert("max_micros", timing.max_micros as i64);
+        dict.insert("total_micros", timing.total_micros as i64);
+        dict.insert("calls", timing.calls as i64);
+        phases.insert(phase.as_str(), dict.into_shared());
+    }
+    let dict = Dictionary::new();
+    dict.insert("enabled", profiler.enabled());
+    dict.insert("ticks", profiler.ticks() as i64);
+    dict.insert("phases", phases.into_shared());
+    dict.into_shared()
+}
+
+/// Sensor conditions carry `sensor`/`comparator`/`threshold`; attribute
+/// conditions carry `when_field`/`when_param`/`comparator`/`when_value`.
+pub fn rule_dictionary(rule: &MetaweaveRule) -> Dictionary {
+    let dict = Dictionary::new();
+    match &rule.condition {
+        RuleCondition::Sensor { sensor, comparator, threshold } => {
+            dict.insert("sensor", sensor);
+            dict.insert("comparator", comparator.as_str());
+            dict.insert("threshold", *threshold);
+        }
+        RuleCondition::Attribute { field, param, equal, value } => {
+            dict.insert("when_field", field);
+            dict.insert("when_param", param);
+            dict.insert("comparator", if *equal { "==" } else { "!=" });
+            dict.insert("when_value", param_value_variant(value));
+        }
+    }
+    dict.insert("field", &rule.field);
+    dict.insert("param", &rule.param);
+    dict.insert("value", rule.value);
+    dict.into_shared()
+}
+
+/// `{ rule_index, rule, severity, sensor, sensor_value, values, tick }`,
+/// empty without an event; `values` maps each check's sensor to its reading.
+pub fn safety_event_dictionary(event: Option<&SafetyEvent>) -> Dictionary {
+    let dict = Dictionary::new();
+    if let Some(event) = event {
+        dict.insert("rule_index", event.rule_index as i64);
+        dict.insert("rule", &event.rule);
+        dict.insert("severity", event.severity.as_str());
+        dict.insert("sensor", &event.sensor);
+        dict.insert("sensor_value", event.sensor_value);
+        let values = Dictionary::new();
+        for (sensor, value) in &event.values {
+            values.insert(sensor, *value);
+        }
+        dict.insert("values", values.into_shared());
+        dict.insert("tick", event.tick as i64);
+    }
+    dict.into_shared()
+}
+
+/// One `safety_event_dictionary` per logged trip, oldest first.
+pub fn safety_log_array(log: &SafetyLog) -> VariantArray {
+    let array = VariantArray::new();
+    for event in log.events() {
+        array.push(safety_event_dictionary(Some(event)));
+    }
+    array.into_shared()
+}
+
+/// `{ topic, value, tick }` per message, in the order they were sent.
+pub fn message_array<'a>(messages: impl Iterator<Item = &'a Message>) -> VariantArray {
+    let array = VariantArray::new();
+    for message in messages {
+        let dict = Dictionary::new();
+        dict.insert("topic", message.topic.as_str());
+        dict.insert("value", message.value);
+        dict.insert("tick", message.tick as i64);
+        array.push(dict.into_shared());
+    }
+    array.into_shared()
+}
+
+/// `{ name, text, tick, values: { operand: value } }`, empty if no
+/// invariant has been violated; an operand without a value is null.
+pub fn invariant_violation_dictionary(violation: Option<&InvariantViolation>) -> Dictionary {
+    let dict = Dictionary::new();
+    if let Some(violation) = violation {
+        let values = Dictionary::new();
+        for (operand, value) in &violation.values {
+            values.insert(operand, value.map(to_f64).to_variant());
+        }
+        dict.insert("name", &violation.name);
+        dict.insert("text", &violation.text);
+        dict.insert("tick", violation.tick as i64);
+        dict.insert("values", values.into_shared());
+    }
+    dict.into_shared()
+}
+
+/// `{ name: { passing, text, violations, last_violation_tick } }`, with
+/// `last_violation_tick` -1 for an invariant that never failed.
+pub fn invariant_status_dictionary<'a>(invariants: impl Iterator<Item = (&'a Invariant, InvariantStatus)>) -> Dictionary {
+    let dict = Dictionary::new();
+    for (invariant, status) in invariants {
+        let entry = Dictionary::new();
+        entry.insert("passing", status.passing);
+        entry.insert("text", invariant.text());
+        entry.insert("violations", status.violations as i64);
+        entry.insert("last_violation_tick", status.last_violation_tick.map_or(-1, |tick| tick as i64));
+        dict.insert(&invariant.name, entry.into_shared());
+    }
+    dict.into_shared()
+}
+
+#[cfg(feature = "godot4")]
+pub fn replay_dictionary(outcome: &ReplayOutcome) -> Dictionary {
+    let dict = Dictionary::new();
+    match outcome {
+        ReplayOutcome::Matched { tick } => {
+            dict.insert("status", "matched");
+            dict.insert("tick", *tick as i64);
+        }
+        ReplayOutcome::Diverged { tick, first_divergent_tick, expected_tension, actual_tension } => {
+            dict.insert("status", "diverged");
+            dict.insert("tick", *tick as i64);
+            dict.insert("first_divergent_tick", *first_divergent_tick as i64);
+            dict.insert("expected_tension", *expected_tension);
+            dict.insert("actual_tension", *actual_tension);
+        }
+        ReplayOutcome::Finished => {
+            dict.insert("status", "finished");
+        }
+    }
+    dict.into_shared()
+}
+
+/// `execute_code`'s result: `success` plus what changed, or the parse/validation
+/// error under `error` when nothing was applied.
+pub fn code_outcome_dictionary(outcome: &Result<CodeOutcome, WeaveError>) -> Dictionary {
+    let dict = Dictionary::new();
+    match outcome {
+        Ok(outcome) => {
+            dict.insert("success", true);
+            dict.insert("added_fields", &outcome.added_fields);
+            let changes = VariantArray::new();
+            for change in &outcome.changes {
+                let entry = Dictionary::new();
+                entry.insert("agent", &change.agent);
+                entry.insert("param", &change.param);
+                entry.insert("old_value", change.old_value);
+                entry.insert("new_value", change.new_value);
+                changes.push(entry.into_shared());
+            }
+            dict.insert("changes", changes.into_shared());
+            let fired = VariantArray::new();
+            for rule in &outcome.fired {
+                fired.push(rule_dictionary(rule));
+            }
+            dict.insert("fired", fired.into_shared());
+        }
+        Err(e) => {
+            dict.insert("success", false);
+            dict.insert("error", error_dictionary(e));
+        }
+    }
+    dict.into_shared()
+}
+
+/// `preview_step`'s result: `success`, the `tension` the step would compute,
+/// each param it would move with its `delta`, the vectors it would move and
+/// the rules that would fire, or the step's error under `error`.
+pub fn preview_dictionary(preview: &Result<StepPreview, WeaveError>) -> Dictionary {
+    let dict = Dictionary::new();
+    match preview {
+        Ok(preview) => {
+            dict.insert("success", true);
+            dict.insert("tension", preview.tension);
+            let changes = VariantArray::new();
+            for change in &preview.changes {
+                let entry = Dictionary::new();
+                entry.insert("agent", &change.agent);
+                entry.insert("param", &change.param);
+                entry.insert("old_value", change.old_value);
+                entry.insert("new_value", change.new_value);
+                entry.insert("delta", change.new_value - change.old_value);
+                changes.push(entry.into_shared());
+            }
+            dict.insert("changes", changes.into_shared());
+            dict.insert("vectors", vector_model_dictionary(&preview.vectors));
+            let fired = VariantArray::new();
+            for rule in &preview.fired {
+                fired.push(rule_dictionary(rule));
+            }
+            dict.insert("fired", fired.into_shared());
+        }
+        Err(e) => {
+            dict.insert("success", false);
+            dict.insert("error", error_dictionary(e));
+        }
+    }
+    dict.into_shared()
+}
+
+/// Bools stay bools, strings and identifiers become Strings, lists become
+/// Arrays and vectors PoolRealArrays.
+pub fn param_value_variant(value: &ParamValue) -> Variant {
+    match value {
+        ParamValue::Bool(value) => value.to_variant(),
+        ParamValue::Text(text) | ParamValue::Symbol(text) => text.to_variant(),
+        ParamValue::List(items) => {
+            let array = VariantArray::new();
+            for item in items {
+                array.push(param_value_variant(item));
+            }
+            array.into_shared().to_variant()
+        }
+        ParamValue::Vector(components) => vector_array(components).to_variant(),
+    }
+}
+
+pub fn vector_array(components: &[Scalar]) -> PoolArray<f32> {
+    PoolArray::from_vec(components.iter().map(|component| to_f32(*component)).collect())
+}
+
+/// Components from a PoolRealArray, or from an Array whose every item is a
+/// number; `None` for anything else.
+pub fn vector_from_variant(value: &Variant) -> Option<Vec<Scalar>> {
+    if let Ok(components) = value.try_to::<PoolArray<f32>>() {
+        return Some(components.to_vec().into_iter().map(|component| component as Scalar).collect());
+    }
+    let items = value.try_to::<VariantArray>().ok()?;
+    items.iter().map(|item| scalar_from_variant(&item)).collect()
+}
+
+/// `{ "field.param": PoolRealArray }` for every vector.
+pub fn vector_model_dictionary(vectors: &[(String, Vec<Scalar>)]) -> Dictionary {
+    let dict = Dictionary::new();
+    for (key, components) in vectors {
+        dict.insert(key, vector_array(components));
+    }
+    dict.into_shared()
+}
+
+pub fn attributes_dictionary(attributes: Option<&HashMap<String, ParamValue>>) -> Dictionary {
+    let dict = Dictionary::new();
+    for (name, value) in attributes.into_iter().flatten() {
+        dict.insert(name, param_value_variant(value));
+    }
+    dict.into_shared()
+}
+
+/// An exported state as plain Dictionaries, Arrays, numbers and Strings, so
+/// GDScript can keep it in an autoload or write it out with `to_json`.
+/// Attributes are weave source text such as `[1, 0]` or `"label"`, and the
+/// noise seed a String, since JSON numbers can't hold every 64-bit seed.
+pub fn state_dictionary(state: &ExperimentState) -> Dictionary {
+    let attributes = Dictionary::new();
+    for (name, params) in &state.attributes {
+        let field = Dictionary::new();
+        for (param, value) in params {
+            field.insert(param, value.to_string());
+        }
+        attributes.insert(name, field.into_shared());
+    }
+    let history = VariantArray::new();
+    for tension in &state.tension_history {
+        history.push(to_f64(*tension));
+    }
+    let fired = VariantArray::new();
+    for set in state.schedule.fired_script_sets() {
+        fired.push(scheduled_set_dictionary(set));
+    }
+    let schedule = Dictionary::new();
+    schedule.insert("pending", scheduled_array(state.schedule.pending()));
+    schedule.insert("fired", fired.into_shared());
+    schedule.insert("next_id", state.schedule.next_id() as i64);
+    let goals = Dictionary::new();
+    for (name, progress) in &state.goal_progress {
+        let entry = Dictionary::new();
+        entry.insert("satisfied_ticks", progress.satisfied_ticks as i64);
+        entry.insert("completed", progress.completed);
+        goals.insert(name, entry.into_shared());
+    }
+    let locks = Dictionary::new();
+    for (agent, persistent) in &state.locked_agents {
+        locks.insert(agent, *persistent);
+    }
+    let dict = Dictionary::new();
+    dict.insert("fields", fields_dictionary(&state.fields));
+    dict.insert("attributes", attributes.into_shared());
+    dict.insert("tension_history", history.into_shared());
+    dict.insert("dropped_history", state.dropped_history as i64);
+    dict.insert("smoothed_tension", state.smoothed_tension.map(to_f64).to_variant());
+    dict.insert("schedule", schedule.into_shared());
+    dict.insert("goals", goals.into_shared());
+    dict.insert("locked_agents", locks.into_shared());
+    dict.insert("noise_seed", state.noise_seed.to_string());
+    dict.into_shared()
+}
+
+fn scheduled_set_dictionary(set: &ScheduledSet) -> Dictionary {
+    let dict = Dictionary::new();
+    dict.insert("tick", set.tick as i64);
+    dict.insert("agent", &set.field);
+    dict.insert("param", &set.param);
+    dict.insert("value", set.value);
+    dict.into_shared()
+}
+
+/// Reads back a `state_dictionary`, also after a trip through JSON, which
+/// turns its ints into floats. Entries that don't read are skipped with a
+/// warning; a state without `fields` is an error.
+pub fn state_from_dictionary(state: &Dictionary, warnings: &mut Vec<String>) -> Result<ExperimentState, WeaveError> {
+    let dictionary = |key: &str| state.get(key).and_then(|value| value.try_to::<Dictionary>().ok());
+    let array = |key: &str| state.get(key).and_then(|value| value.try_to::<VariantArray>().ok());
+    let count = |value: Option<Variant>| value.as_ref().and_then(scalar_from_variant).map(|value| value.max(0.0) as usize);
+    let fields = dictionary("fields").ok_or_else(|| WeaveError::Validation("the state has no fields Dictionary".to_string()))?;
+    let mut imported = ExperimentState { fields: agents_from_dictionary(&fields, warnings), ..ExperimentState::default() };
+    for (name, params) in dictionary("attributes").iter().flat_map(|attributes| attributes.iter()) {
+        let (Ok(name), Ok(params)) = (name.try_to::<String>(), params.try_to::<Dictionary>()) else { continue };
+        let field = imported.attributes.entry(name.clone()).or_default();
+        for (param, text) in params.iter() {
+            let Ok(param) = param.try_to::<String>() else { continue };
+            match text.try_to::<String>().ok().as_deref().and_then(parse_param_value) {
+                Some(value) => {
+                    field.insert(param, value);
+                }
+                None => warnings.push(format!("state attribute {}.{} is not a weave value; skipped", name, param)),
+            }
+        }
+    }
+    imported.tension_history = array("tension_history").iter().flat_map(|history| history.iter()).filter_map(|tension| scalar_from_variant(&tension)).collect();
+    imported.dropped_history = count(state.get("dropped_history")).unwrap_or(0);
+    imported.smoothed_tension = state.get("smoothed_tension").as_ref().and_then(scalar_from_variant);
+    if let Some(schedule) = dictionary("schedule") {
+        let sets = |key: &str| schedule.get(key).and_then(|value| value.try_to::<VariantArray>().ok()).map(|sets| sets.iter().collect::<Vec<Variant>>()).unwrap_or_default();
+        let set = |entry: &Dictionary| {
+            Some(ScheduledSet {
+                tick: count(entry.get("tick"))?,
+                field: entry.get("agent")?.try_to::<String>().ok()?,
+                param: entry.get("param")?.try_to::<String>().ok()?,
+                value: scalar_from_variant(&entry.get("value")?)?,
+            })
+        };
+        let mut pending = Vec::new();
+        for entry in sets("pending").into_iter().filter_map(|entry| entry.try_to::<Dictionary>().ok()) {
+            let id = count(entry.get("id")).map(|id| id as u64);
+            match (id, set(&entry)) {
+                (Some(id), Some(set)) => {
+                    let from_script = entry.get("from_script").and_then(|value| value.try_to::<bool>().ok()).unwrap_or(false);
+                    pending.push(ScheduledAction { id, set, from_script });
+                }
+                _ => warnings.push("a pending scheduled set in the state is incomplete; skipped".to_string()),
+            }
+        }
+        let fired = sets("fired").into_iter().filter_map(|entry| set(&entry.try_to::<Dictionary>().ok()?)).collect();
+        imported.schedule = Schedule::from_parts(pending, fired, count(schedule.get("next_id")).unwrap_or(0) as u64);
+    }
+    for (name, entry) in dictionary("goals").iter().flat_map(|goals| goals.iter()) {
+        let (Ok(name), Ok(entry)) = (name.try_to::<String>(), entry.try_to::<Dictionary>()) else { continue };
+        let progress = GoalProgress {
+            satisfied_ticks: count(entry.get("satisfied_ticks")).unwrap_or(0) as u32,
+            completed: entry.get("completed").and_then(|value| value.try_to::<bool>().ok()).unwrap_or(false),
+        };
+        imported.goal_progress.insert(name, progress);
+    }
+    for (agent, persistent) in dictionary("locked_agents").iter().flat_map(|locks| locks.iter()) {
+        if let Ok(agent) = agent.try_to::<String>() {
+            imported.locked_agents.insert(agent, persistent.try_to::<bool>().unwrap_or(false));
+        }
+    }
+    imported.noise_seed = match state.get("noise_seed").and_then(|seed| seed.try_to::<String>().ok()) {
+        Some(seed) => seed.parse().map_err(|_| WeaveError::Validation(format!("the state's noise_seed {:?} is not a seed", seed)))?,
+        None => DEFAULT_SEED,
+    };
+    Ok(imported)
+}
@@ -0,0 +1,239 @@
+use crate::error::WeaveError;
+use crate::interpreter::Fields;
+use crate::scalar::Scalar;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[cfg(feature = "parquet")]
+use parquet::data_type::{ByteArray, ByteArrayType, DataType, DoubleType, Int64Type};
+#[cfg(feature = "parquet")]
+use parquet::errors::ParquetError;
+#[cfg(feature = "parquet")]
+use parquet::file::properties::WriterProperties;
+#[cfg(feature = "parquet")]
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+#[cfg(feature = "parquet")]
+use crate::scalar::to_f64;
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+/// Ticks a data log queues before it writes them out, unless told otherwise.
+pub const DEFAULT_FLUSH_TICKS: usize = 60;
+
+/// The columns of every data log, in order.
+pub const COLUMNS: [&str; 5] = ["tick", "agent", "param", "value", "tension"];
+
+#[cfg(feature = "parquet")]
+const PARQUET_SCHEMA: &str = "message trajectory {
+  REQUIRED INT64 tick;
+  REQUIRED BINARY agent (UTF8);
+  REQUIRED BINARY param (UTF8);
+  REQUIRED DOUBLE value;
+  REQUIRED DOUBLE tension;
+}";
+
+/// The file format a data log writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataFormat {
+    /// Appended to: a header is only written to an empty file.
+    #[default]
+    Csv,
+    /// Rewritten from scratch, one row group per flush. Readable only once
+    /// the log is closed; needs the `parquet` cargo feature.
+    Parquet,
+}
+
+impl DataFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DataFormat::Csv => "csv",
+            DataFormat::Parquet => "parquet",
+        }
+    }
+
+    /// Parquet for a `.parquet` path, CSV for any other.
+    pub fn for_path(path: &Path) -> DataFormat {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("parquet")) {
+            DataFormat::Parquet
+        } else {
+            DataFormat::Csv
+        }
+    }
+}
+
+impl FromStr for DataFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "csv" => Ok(DataFormat::Csv),
+            "parquet" => Ok(DataFormat::Parquet),
+            _ => Err(format!("unknown data format '{}', expected csv or parquet", name)),
+        }
+    }
+}
+
+/// One param of one field after a tick, with that tick's tension.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataRow {
+    pub tick: u64,
+    pub agent: String,
+    pub param: String,
+    pub value: Scalar,
+    pub tension: Scalar,
+}
+
+enum Sink {
+    Csv(BufWriter<File>),
+    #[cfg(feature = "parquet")]
+    Parquet(SerializedFileWriter<File>),
+}
+
+/// Writes a field trajectory in long form, one row per numeric param per
+/// tick, for pandas and the like. Rows are queued and written every
+/// `flush_ticks` ticks, and when the log is flushed, closed or dropped.
+pub struct DataLog {
+    path: PathBuf,
+    format: DataFormat,
+    flush_ticks: usize,
+    pending: Vec<DataRow>,
+    pending_ticks: usize,
+    rows_written: u64,
+    /// `None` once closed.
+    sink: Option<Sink>,
+}
+
+impl DataLog {
+    /// Opens `path` for `format`; a `flush_ticks` of 0 counts as 1.
+    pub fn create(path: &Path, format: DataFormat, flush_ticks: usize) -> Result<DataLog, WeaveError> {
+        let sink = match format {
+            DataFormat::Csv => {
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                let empty = file.metadata()?.len() == 0;
+                let mut out = BufWriter::new(file);
+                if empty {
+                    writeln!(out, "{}", COLUMNS.join(","))?;
+                    out.flush()?;
+                }
+                Sink::Csv(out)
+            }
+            #[cfg(feature = "parquet")]
+            DataFormat::Parquet => {
+                let schema = Arc::new(parquet::schema::parser::parse_message_type(PARQUET_SCHEMA).map_err(parquet_error)?);
+                let writer = SerializedFileWriter::new(File::create(path)?, schema, Arc::new(WriterProperties::builder().build())).map_err(parquet_error)?;
+                Sink::Parquet(writer)
+            }
+            #[cfg(not(feature = "parquet"))]
+            DataFormat::Parquet => {
+                return Err(WeaveError::Validation(format!("can't log to {}: this build lacks the parquet feature", path.display())));
+            }
+        };
+        Ok(DataLog { path: path.to_path_buf(), format, flush_ticks: flush_ticks.max(1), pending: Vec::new(), pending_ticks: 0, rows_written: 0, sink: Some(sink) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn format(&self) -> DataFormat {
+        self.format
+    }
+
+    pub fn flush_ticks(&self) -> usize {
+        self.flush_ticks
+    }
+
+    /// Rows on disk so far, not counting those queued.
+    pub fn rows_written(&self) -> u64 {
+        self.rows_written
+    }
+
+    /// Queues a row for every numeric param of every field, writing the
+    /// queue out once it holds `flush_ticks` ticks.
+    pub fn log_tick(&mut self, tick: u64, fields: &Fields, tension: Scalar) -> Result<(), WeaveError> {
+        if self.sink.is_none() {
+            return Err(WeaveError::Validation(format!("data log {} is closed", self.path.display())));
+        }
+        for (agent, params) in fields {
+            self.pending.extend(params.iter().map(|(param, value)| DataRow { tick, agent: agent.clone(), param: param.clone(), value: *value, tension }));
+        }
+        self.pending_ticks += 1;
+        if self.pending_ticks >= self.flush_ticks {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the queued rows out now.
+    pub fn flush(&mut self) -> Result<(), WeaveError> {
+        let rows = std::mem::take(&mut self.pending);
+        self.pending_ticks = 0;
+        match &mut self.sink {
+            None => {}
+            Some(Sink::Csv(out)) => {
+                for row in &rows {
+                    writeln!(out, "{},{},{},{},{}", row.tick, row.agent, row.param, row.value, row.tension)?;
+                }
+                out.flush()?;
+            }
+            #[cfg(feature = "parquet")]
+            Some(Sink::Parquet(writer)) => write_row_group(writer, &rows).map_err(parquet_error)?,
+        }
+        self.rows_written += rows.len() as u64;
+        Ok(())
+    }
+
+    /// Writes the queued rows and finishes the file; later ticks are
+    /// refused. Closing again does nothing.
+    pub fn close(&mut self) -> Result<(), WeaveError> {
+        self.flush()?;
+        match self.sink.take() {
+            None | Some(Sink::Csv(_)) => {}
+            #[cfg(feature = "parquet")]
+            Some(Sink::Parquet(writer)) => {
+                writer.close().map_err(parquet_error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DataLog {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_error(error: ParquetError) -> WeaveError {
+    WeaveError::Io(std::io::Error::other(error.to_string()))
+}
+
+#[cfg(feature = "parquet")]
+fn write_row_group(writer: &mut SerializedFileWriter<File>, rows: &[DataRow]) -> Result<(), ParquetError> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let mut group = writer.next_row_group()?;
+    let ticks: Vec<i64> = rows.iter().map(|row| row.tick as i64).collect();
+    write_column::<Int64Type>(&mut group, &ticks)?;
+    let agents: Vec<ByteArray> = rows.iter().map(|row| ByteArray::from(row.agent.as_str())).collect();
+    write_column::<ByteArrayType>(&mut group, &agents)?;
+    let params: Vec<ByteArray> = rows.iter().map(|row| ByteArray::from(row.param.as_str())).collect();
+    write_column::<ByteArrayType>(&mut group, &params)?;
+    let values: Vec<f64> = rows.iter().map(|row| to_f64(row.value)).collect();
+    write_column::<DoubleType>(&mut group, &values)?;
+    let tensions: Vec<f64> = rows.iter().map(|row| to_f64(row.tension)).collect();
+    write_column::<DoubleType>(&mut group, &tensions)?;
+    group.close()?;
+    Ok(())
+}
+
+#[cfg(feature = "parquet")]
+fn write_column<T: DataType>(group: &mut SerializedRowGroupWriter<'_, File>, values: &[T::T]) -> Result<(), ParquetError> {
+    let mut column = group.next_column()?.ok_or_else(|| ParquetError::General("the data log schema has fewer columns than rows carry".to_string()))?;
+    column.typed::<T>().write_batch(values, None, None)?;
+    column.close()
+}
@@ -0,0 +1,72 @@
+use crate::error::WeaveError;
+use pest::error::LineColLocation;
+use pest::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// One problem with a weave source, placed so an editor can highlight it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// 1-based; 0 when the problem has no place in the source, as for a
+    /// missing file or a validation error.
+    pub line: usize,
+    pub column: usize,
+    /// Where the span ends; the same as the start for a single position.
+    pub end_line: usize,
+    pub end_column: usize,
+    /// The source line the problem starts on, empty without a position.
+    pub snippet: String,
+}
+
+impl Diagnostic {
+    /// A parse error keeps pest's short message, span and line; any other
+    /// error becomes its whole message without a position.
+    pub fn from_error(error: &WeaveError) -> Diagnostic {
+        let WeaveError::Parse(parse) = error else {
+            return Diagnostic::unplaced(Severity::Error, error.to_string());
+        };
+        let ((line, column), (end_line, end_column)) = match parse.line_col {
+            LineColLocation::Pos(pos) => (pos, pos),
+            LineColLocation::Span(start, end) => (start, end),
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            message: parse.variant.message().into_owned(),
+            line,
+            column,
+            end_line,
+            end_column,
+            snippet: parse.line().to_string(),
+        }
+    }
+
+    /// A problem found at `span` of the source.
+    pub fn placed(severity: Severity, message: String, span: Span) -> Diagnostic {
+        let ((line, column), (end_line, end_column)) = (span.start_pos().line_col(), span.end_pos().line_col());
+        let snippet = span.start_pos().line_of().trim_end_matches(['\r', '\n']).to_string();
+        Diagnostic { severity, message, line, column, end_line, end_column, snippet }
+    }
+
+    pub fn warning(message: String) -> Diagnostic {
+        Diagnostic::unplaced(Severity::Warning, message)
+    }
+
+    fn unplaced(severity: Severity, message: String) -> Diagnostic {
+        Diagnostic { severity, message, line: 0, column: 0, end_line: 0, end_column: 0, snippet: String::new() }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::interpreter::{Rule, MIN_LANGUAGE_VERSION};
+use std::fmt;
+
+/// Everything that can go wrong while loading or executing a weave program.
+#[derive(Debug)]
+pub enum WeaveError {
+    /// The weave file could not be read.
+    Io(std::io::Error),
+    /// The source did not match the grammar; the pest error keeps the span.
+    Parse(Box<pest::error::Error<Rule>>),
+    /// The source parsed but describes something the interpreter can't run.
+    Validation(String),
+    /// An execute phase needed a field that the program never declared.
+    MissingField(String),
+    /// A field exists but lacks a param an execute phase depends on.
+    MissingParam { field: String, param: String },
+    /// A sensor was absent and its missing-sensor policy gave no value for it.
+    MissingSensor(String),
+    /// The script's `weave <n>` header asks for a language version outside this build's range.
+    UnsupportedVersion { required: u32, supported: u32 },
+    /// A compiled program was written by a build with another format version.
+    UnsupportedFormat { found: u32, expected: u32 },
+    /// A load was asked for while a background load was still parsing.
+    Busy(String),
+}
+
+impl WeaveError {
+    /// Short machine-readable name of the variant, used as the `kind` key at the Godot boundary.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WeaveError::Io(_) => "io",
+            WeaveError::Parse(_) => "parse",
+            WeaveError::Validation(_) => "validation",
+            WeaveError::MissingField(_) => "missing_field",
+            WeaveError::MissingParam { .. } => "missing_param",
+            WeaveError::MissingSensor(_) => "missing_sensor",
+            WeaveError::UnsupportedVersion { .. } => "unsupported_version",
+            WeaveError::UnsupportedFormat { .. } => "unsupported_format",
+            WeaveError::Busy(_) => "busy",
+        }
+    }
+
+    /// 1-based line and column of a parse error, if this is one.
+    pub fn line_col(&self) -> Option<(usize, usize)> {
+        match self {
+            WeaveError::Parse(e) => Some(match e.line_col {
+                pest::error::LineColLocation::Pos(pos) => pos,
+                pest::error::LineColLocation::Span(start, _) => start,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WeaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeaveError::Io(e) => write!(f, "failed to read weave file: {}", e),
+            WeaveError::Parse(e) => write!(f, "failed to parse weave source:\n{}", e),
+            WeaveError::Validation(msg) => write!(f, "invalid weave program: {}", msg),
+            WeaveError::MissingField(field) => write!(f, "field '{}' is not declared", field),
+            WeaveError::MissingParam { field, param } => write!(f, "field '{}' has no param '{}'", field, param),
+            WeaveError::MissingSensor(sensor) => write!(f, "sensor '{}' is missing and has no fallback value", sensor),
+            WeaveError::UnsupportedVersion { required, supported } => write!(
+                f,
+                "script requires weave language version {}, this build supports {} to {}",
+                required, MIN_LANGUAGE_VERSION, supported
+            ),
+            WeaveError::UnsupportedFormat { found, expected } => write!(
+                f,
+                "compiled program has format version {}, this build reads version {}; compile it again",
+                found, expected
+            ),
+            WeaveError::Busy(what) => write!(f, "refused {}", what),
+        }
+    }
+}
+
+impl std::error::Error for WeaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WeaveError::Io(e) => Some(e),
+            WeaveError::Parse(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for WeaveError {
+    fn from(e: std::io::Error) -> Self {
+        WeaveError::Io(e)
+    }
+}
+
+impl From<pest::error::Error<Rule>> for WeaveError {
+    fn from(e: pest::error::Error<Rule>) -> Self {
+        WeaveError::Parse(Box::new(e))
+    }
+}
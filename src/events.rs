@@ -0,0 +1,70 @@
+use crate::expr::Expr;
+use crate::rules::{LabCall, RuleCondition};
+use crate::scalar::Scalar;
+use std::fmt;
+
+/// One action of an `on` block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventAction {
+    /// `set <field>.<param> = <expression>;`, evaluated when the handler fires.
+    Set { field: String, param: String, value: Expr },
+    /// `call <node>.<method>(<args>);`, dispatched like a call rule.
+    Call(LabCall),
+    /// `send(<target>, <topic>, <expression>);`, evaluated when the handler fires.
+    Send { target: String, topic: String, value: Expr },
+    /// `emit "<signal>" <expression>;`, evaluated when the handler fires.
+    Emit { signal: String, value: Expr },
+    /// `halt;`, stops stepping until `resume`, as a halting invariant does.
+    Halt,
+}
+
+impl fmt::Display for EventAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventAction::Set { field, param, value } => write!(f, "set {}.{} = {};", field, param, value),
+            EventAction::Call(call) => write!(f, "call {};", call),
+            EventAction::Send { target, topic, value } => write!(f, "send({}, {}, {});", target, topic, value),
+            EventAction::Emit { signal, value } => write!(f, "emit \"{}\" {};", signal, value),
+            EventAction::Halt => f.write_str("halt;"),
+        }
+    }
+}
+
+/// `on <test> { ... }`: runs its actions on the step its test becomes true,
+/// and again only after the test has been false on a later step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventHandler {
+    pub condition: RuleCondition,
+    pub actions: Vec<EventAction>,
+}
+
+impl fmt::Display for EventHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "on {} {{", self.condition)?;
+        for action in &self.actions {
+            write!(f, " {}", action)?;
+        }
+        f.write_str(" }")
+    }
+}
+
+/// A handler that fired, for the `weave_event` signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiredEvent {
+    /// The handler's index in file order.
+    pub handler: usize,
+    /// Its test, as weave source.
+    pub condition: String,
+    /// The value the test read; `None` for an attribute test.
+    pub value: Option<Scalar>,
+    pub tick: usize,
+}
+
+/// A value an `emit` statement queued, for the bindings to emit as a Godot
+/// signal after the step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmittedSignal {
+    pub signal: String,
+    pub value: Scalar,
+    pub tick: usize,
+}
@@ -0,0 +1,319 @@
+use crate::error::WeaveError;
+use crate::interpreter::Rule;
+use crate::messages::receive_key;
+use indexmap::IndexMap;
+use pest::iterators::Pair;
+use std::fmt;
+
+/// `fn` declarations by name, in declaration order.
+pub type Functions = IndexMap<String, UserFunction>;
+
+/// `fn <name>(<params>) { <expression> }`. A call is expanded in place when
+/// it is parsed, each param replaced by its argument; other names in the
+/// body are read where the function is called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserFunction {
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+impl UserFunction {
+    /// The body with each param replaced by the matching argument.
+    pub fn expand(&self, args: &[Expr]) -> Expr {
+        self.body.substitute(&|name| self.params.iter().position(|param| param == name).and_then(|index| args.get(index)))
+    }
+}
+
+/// A parsed arithmetic expression, kept so it can be evaluated again later
+/// against different values (goal conditions run every step).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    /// A bare name: a param, sensor, constant or `tension`, depending on where
+    /// the expression is evaluated.
+    Name(String),
+    /// `field.param`
+    Param(String, String),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+    /// `receive(<topic>)`, the latest message on the topic to the agent a
+    /// `set` writes; resolved as the bare name `receive_key` gives.
+    Receive(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    Abs,
+    Min,
+    Max,
+    /// `clamp(x, low, high)`; bounds given the wrong way round are swapped.
+    Clamp,
+    /// `lerp(a, b, t)` is `a + (b - a) * t`, with `t` not clamped.
+    Lerp,
+    Floor,
+    Ceil,
+    Sqrt,
+    /// `sin(x)` and `cos(x)`, in radians.
+    Sin,
+    Cos,
+    /// `noise(x)`: smooth value noise in `-1..=1`, the same for the same `x`,
+    /// so `noise(seed)` is a fixed random number and `noise(t * 0.1)` wanders
+    /// smoothly as `t` rises.
+    Noise,
+}
+
+impl Function {
+    const ALL: [Function; 11] = [
+        Function::Abs,
+        Function::Min,
+        Function::Max,
+        Function::Clamp,
+        Function::Lerp,
+        Function::Floor,
+        Function::Ceil,
+        Function::Sqrt,
+        Function::Sin,
+        Function::Cos,
+        Function::Noise,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Function> {
+        Function::ALL.into_iter().find(|function| function.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Abs => "abs",
+            Function::Min => "min",
+            Function::Max => "max",
+            Function::Clamp => "clamp",
+            Function::Lerp => "lerp",
+            Function::Floor => "floor",
+            Function::Ceil => "ceil",
+            Function::Sqrt => "sqrt",
+            Function::Sin => "sin",
+            Function::Cos => "cos",
+            Function::Noise => "noise",
+        }
+    }
+
+    /// The fewest and most arguments the function takes.
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::Min | Function::Max => (2, usize::MAX),
+            Function::Clamp | Function::Lerp => (3, 3),
+            Function::Abs | Function::Floor | Function::Ceil | Function::Sqrt | Function::Sin | Function::Cos | Function::Noise => (1, 1),
+        }
+    }
+
+    fn arity_text(self) -> String {
+        match self.arity() {
+            (1, 1) => "1 argument".to_string(),
+            (least, usize::MAX) => format!("at least {} arguments", least),
+            (count, _) => format!("{} arguments", count),
+        }
+    }
+}
+
+/// A name an expression could not resolve, or arithmetic it could not do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    Unknown(String),
+    DivisionByZero,
+    /// `sqrt` of this negative value.
+    NegativeSqrt(f64),
+}
+
+impl Expr {
+    /// Builds the tree for an `expression` pair or any of its parts, expanding
+    /// calls to `functions`.
+    pub fn from_pair(pair: Pair<Rule>, functions: &Functions) -> Result<Expr, WeaveError> {
+        match pair.as_rule() {
+            Rule::expression | Rule::term => {
+                let mut parts = pair.into_inner();
+                let mut expr = match parts.next() {
+                    Some(first) => Expr::from_pair(first, functions)?,
+                    None => Expr::Number(0.0),
+                };
+                while let (Some(op), Some(operand)) = (parts.next(), parts.next()) {
+                    let op = match op.as_str() {
+                        "+" => Op::Add,
+                        "-" => Op::Sub,
+                        "*" => Op::Mul,
+                        _ => Op::Div,
+                    };
+                    expr = Expr::Binary(op, Box::new(expr), Box::new(Expr::from_pair(operand, functions)?));
+                }
+                Ok(expr)
+            }
+            Rule::negation => match pair.into_inner().next() {
+                Some(inner) => Ok(Expr::Neg(Box::new(Expr::from_pair(inner, functions)?))),
+                None => Ok(Expr::Number(0.0)),
+            },
+            Rule::function => {
+                let mut parts = pair.into_inner();
+                let name = parts.next().map(|name| name.as_str()).unwrap_or_default();
+                let args = parts.map(|arg| Expr::from_pair(arg, functions)).collect::<Result<Vec<_>, _>>()?;
+                if let Some(function) = functions.get(name) {
+                    if args.len() != function.params.len() {
+                        return Err(WeaveError::Validation(format!("{} takes {} argument(s), got {}", name, function.params.len(), args.len())));
+                    }
+                    return Ok(function.expand(&args));
+                }
+                if name == "receive" {
+                    return match args.as_slice() {
+                        [Expr::Name(topic)] => Ok(Expr::Receive(topic.clone())),
+                        _ => Err(WeaveError::Validation("receive takes 1 argument, a topic name".to_string())),
+                    };
+                }
+                let Some(function) = Function::from_name(name) else {
+                    let names: Vec<&str> =
+                        Function::ALL.iter().map(|function| function.name()).chain(["receive"]).chain(functions.keys().map(String::as_str)).collect();
+                    return Err(WeaveError::Validation(format!("unknown function {}, expected one of {}", name, names.join(", "))));
+                };
+                let (least, most) = function.arity();
+                if args.len() < least || args.len() > most {
+                    return Err(WeaveError::Validation(format!("{} takes {}, got {}", name, function.arity_text(), args.len())));
+                }
+                Ok(Expr::Call(function, args))
+            }
+            Rule::param_ref => {
+                let mut parts = pair.into_inner();
+                let mut next = || parts.next().map(|part| part.as_str().to_owned()).unwrap_or_default();
+                Ok(Expr::Param(next(), next()))
+            }
+            Rule::ident => Ok(Expr::Name(pair.as_str().to_owned())),
+            _ => Ok(Expr::Number(pair.as_str().parse().unwrap_or(0.0))),
+        }
+    }
+
+    /// A copy with every bare name `replacement` maps replaced by its expression.
+    pub fn substitute<'a>(&self, replacement: &dyn Fn(&str) -> Option<&'a Expr>) -> Expr {
+        match self {
+            Expr::Name(name) => replacement(name).cloned().unwrap_or_else(|| self.clone()),
+            Expr::Number(_) | Expr::Param(..) | Expr::Receive(_) => self.clone(),
+            Expr::Neg(inner) => Expr::Neg(Box::new(inner.substitute(replacement))),
+            Expr::Binary(op, lhs, rhs) => Expr::Binary(*op, Box::new(lhs.substitute(replacement)), Box::new(rhs.substitute(replacement))),
+            Expr::Call(function, args) => Expr::Call(*function, args.iter().map(|arg| arg.substitute(replacement)).collect()),
+        }
+    }
+
+    /// Adds every bare name and `field.param` this reads to `operands`,
+    /// skipping ones already there.
+    pub fn collect_operands(&self, operands: &mut Vec<String>) {
+        let operand = match self {
+            Expr::Number(_) => return,
+            Expr::Name(name) => name.clone(),
+            Expr::Param(field, param) => format!("{}.{}", field, param),
+            Expr::Receive(topic) => receive_key(topic),
+            Expr::Neg(inner) => return inner.collect_operands(operands),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.collect_operands(operands);
+                return rhs.collect_operands(operands);
+            }
+            Expr::Call(_, args) => {
+                args.iter().for_each(|arg| arg.collect_operands(operands));
+                return;
+            }
+        };
+        if !operands.contains(&operand) {
+            operands.push(operand);
+        }
+    }
+
+    /// Evaluates with `name` resolving bare names and `param` resolving `field.param`.
+    pub fn eval(&self, name: &dyn Fn(&str) -> Option<f64>, param: &dyn Fn(&str, &str) -> Option<f64>) -> Result<f64, EvalError> {
+        match self {
+            Expr::Number(value) => Ok(*value),
+            Expr::Name(key) => name(key).ok_or_else(|| EvalError::Unknown(key.clone())),
+            Expr::Param(field, key) => param(field, key).ok_or_else(|| EvalError::Unknown(format!("{}.{}", field, key))),
+            Expr::Receive(topic) => {
+                let key = receive_key(topic);
+                name(&key).ok_or(EvalError::Unknown(key))
+            }
+            Expr::Neg(inner) => Ok(-inner.eval(name, param)?),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(name, param)?, rhs.eval(name, param)?);
+                match op {
+                    Op::Add => Ok(lhs + rhs),
+                    Op::Sub => Ok(lhs - rhs),
+                    Op::Mul => Ok(lhs * rhs),
+                    Op::Div if rhs == 0.0 => Err(EvalError::DivisionByZero),
+                    Op::Div => Ok(lhs / rhs),
+                }
+            }
+            Expr::Call(function, args) => {
+                let values = args.iter().map(|arg| arg.eval(name, param)).collect::<Result<Vec<f64>, _>>()?;
+                let arg = |index: usize| values.get(index).copied().unwrap_or(0.0);
+                match function {
+                    Function::Abs => Ok(arg(0).abs()),
+                    Function::Min => Ok(values.iter().copied().fold(f64::INFINITY, f64::min)),
+                    Function::Max => Ok(values.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+                    Function::Clamp => {
+                        let (low, high) = (arg(1).min(arg(2)), arg(1).max(arg(2)));
+                        Ok(arg(0).max(low).min(high))
+                    }
+                    Function::Lerp => Ok(arg(0) + (arg(1) - arg(0)) * arg(2)),
+                    Function::Floor => Ok(arg(0).floor()),
+                    Function::Ceil => Ok(arg(0).ceil()),
+                    Function::Sqrt if arg(0) < 0.0 => Err(EvalError::NegativeSqrt(arg(0))),
+                    Function::Sqrt => Ok(arg(0).sqrt()),
+                    Function::Sin => Ok(arg(0).sin()),
+                    Function::Cos => Ok(arg(0).cos()),
+                    Function::Noise => Ok(value_noise(arg(0))),
+                }
+            }
+        }
+    }
+}
+
+/// Eases between random values in `-1..=1` hashed from the integers either
+/// side of `x`.
+fn value_noise(x: f64) -> f64 {
+    let cell = x.floor();
+    let t = x - cell;
+    let (from, to) = (lattice_value(cell), lattice_value(cell + 1.0));
+    from + (to - from) * t * t * (3.0 - 2.0 * t)
+}
+
+fn lattice_value(cell: f64) -> f64 {
+    let mut hash = (cell as i64 as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^= hash >> 31;
+    (hash >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Number(value) => write!(f, "{}", value),
+            Expr::Name(name) => f.write_str(name),
+            Expr::Param(field, param) => write!(f, "{}.{}", field, param),
+            Expr::Neg(inner) => write!(f, "-{}", inner),
+            Expr::Binary(op, lhs, rhs) => {
+                let op = match op {
+                    Op::Add => "+",
+                    Op::Sub => "-",
+                    Op::Mul => "*",
+                    Op::Div => "/",
+                };
+                write!(f, "({} {} {})", lhs, op, rhs)
+            }
+            Expr::Call(function, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", function.name(), args.join(", "))
+            }
+            Expr::Receive(topic) => f.write_str(&receive_key(topic)),
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::error::WeaveError;
+use crate::interpreter::{parse_rule, Rule};
+use pest::iterators::Pair;
+
+/// Spaces per block level in formatted source.
+pub const INDENT: &str = "  ";
+
+/// How `format` lays a file out beyond the canonical spacing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Puts the params of every field and template block in name order.
+    /// Comments above a param, and after it on its line, move with it.
+    pub sort_params: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    /// An identifier, keyword or number.
+    Word(&'a str),
+    Str(&'a str),
+    Comment(&'a str),
+    Punct(&'a str),
+}
+
+/// One token and what came before it: how many line breaks, and where it
+/// starts in the source.
+#[derive(Debug, Clone, Copy)]
+struct Lexeme<'a> {
+    token: Token<'a>,
+    newlines: usize,
+    start: usize,
+}
+
+/// Parses `code` as a weave file and writes it back out canonically: one
+/// statement, param or rule per line, blocks indented by `INDENT`, single
+/// spaces around operators, no trailing commas and at most one blank line
+/// in a row. Comments are kept. Fails, and formats nothing, if `code`
+/// doesn't parse.
+pub fn format(code: &str, options: FormatOptions) -> Result<String, WeaveError> {
+    let code = code.strip_prefix('\u{feff}').unwrap_or(code);
+    let pairs: Vec<Pair<Rule>> = parse_rule(Rule::file, code)?.filter(|pair| pair.as_rule() != Rule::EOI).collect();
+    if options.sort_params {
+        let sorted = sort_params(code, &pairs);
+        if sorted != code {
+            return format(&sorted, FormatOptions { sort_params: false });
+        }
+    }
+    let statements: Vec<usize> = pairs.iter().map(|pair| pair.as_span().start()).collect();
+    Ok(layout(&lex(code), &statements))
+}
+
+/// Whether `code` is already formatted as `format` would write it.
+pub fn is_formatted(code: &str, options: FormatOptions) -> Result<bool, WeaveError> {
+    Ok(format(code, options)? == code)
+}
+
+fn lex(code: &str) -> Vec<Lexeme<'_>> {
+    let bytes = code.as_bytes();
+    let mut lexemes = Vec::new();
+    let (mut at, mut newlines) = (0, 0);
+    while at < bytes.len() {
+        let start = at;
+        let c = bytes[at];
+        let token = match c {
+            b'\n' => {
+                newlines += 1;
+                at += 1;
+                continue;
+            }
+            c if c.is_ascii_whitespace() => {
+                at += 1;
+                continue;
+            }
+            b'/' if bytes.get(at + 1) == Some(&b'/') => {
+                at = code[at..].find('\n').map_or(bytes.len(), |end| at + end);
+                Token::Comment(code[start..at].trim_end())
+            }
+            b'/' if bytes.get(at + 1) == Some(&b'*') => {
+                at = code[at + 2..].find("*/").map_or(bytes.len(), |end| at + end + 4);
+                Token::Comment(&code[start..at])
+            }
+            b'"' => {
+                at = code[at + 1..].find('"').map_or(bytes.len(), |end| at + end + 2);
+                Token::Str(&code[start..at])
+            }
+            c if c.is_ascii_digit() => {
+                at += bytes[at..].iter().take_while(|b| b.is_ascii_digit()).count();
+                if bytes.get(at) == Some(&b'.') && bytes.get(at + 1).is_some_and(u8::is_ascii_digit) {
+                    at += 1 + bytes[at + 1..].iter().take_while(|b| b.is_ascii_digit()).count();
+                }
+                Token::Word(&code[start..at])
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                at += bytes[at..].iter().take_while(|b| b.is_ascii_alphanumeric() || **b == b'_').count();
+                Token::Word(&code[start..at])
+            }
+            _ => {
+                let pair = code.get(at..at + 2).filter(|pair| ["==", "!=", "<=", ">=", ".."].contains(pair));
+                at += pair.map_or_else(|| code[at..].chars().next().map_or(1, char::len_utf8), str::len);
+                Token::Punct(&code[start..at])
+            }
+        };
+        lexemes.push(Lexeme { token, newlines, start });
+        newlines = 0;
+    }
+    lexemes
+}
+
+/// Writes `lexemes` back out; `statements` are the source offsets at which
+/// top-level statements start, each of which begins a line.
+fn layout(lexemes: &[Lexeme], statements: &[usize]) -> String {
+    let mut out = String::new();
+    // Open brackets, innermost last; only braces indent.
+    let mut open: Vec<&str> = Vec::new();
+    let mut break_line = false;
+    let mut previous: Option<Token> = None;
+    let mut statements = statements.iter().peekable();
+    for (index, lexeme) in lexemes.iter().enumerate() {
+        let token = lexeme.token;
+        let next = lexemes[index + 1..].iter().map(|lexeme| lexeme.token).find(|token| !matches!(token, Token::Comment(_)));
+        if token == Token::Punct(",") && matches!(next, Some(Token::Punct("}" | ")" | "]"))) {
+            continue;
+        }
+        if token == Token::Punct("}") {
+            open.pop();
+            if out.ends_with('{') {
+                out.push('}');
+                previous = Some(token);
+                break_line = !matches!(next, Some(Token::Word("else") | Token::Punct("," | ";" | ")")));
+                continue;
+            }
+            break_line = true;
+        }
+        while statements.next_if(|&&start| start <= lexeme.start).is_some() {
+            break_line = true;
+        }
+        let comment = matches!(token, Token::Comment(_));
+        if !out.is_empty() {
+            if comment && lexeme.newlines == 0 {
+                out.push(' ');
+            } else if break_line || comment {
+                out.push('\n');
+                if lexeme.newlines > 1 && !out.ends_with("{\n") && token != Token::Punct("}") {
+                    out.push('\n');
+                }
+                out.push_str(&INDENT.repeat(open.iter().filter(|bracket| **bracket == "{").count()));
+            } else if spaced(previous, token, previous_unary(&out)) {
+                out.push(' ');
+            }
+        }
+        out.push_str(match token {
+            Token::Word(text) | Token::Str(text) | Token::Comment(text) | Token::Punct(text) => text,
+        });
+        // A block comment with more code after it on its line stays inline,
+        // unless a line break was due where it was written.
+        let block = matches!(token, Token::Comment(text) if text.starts_with("/*"));
+        let line_ends = lexemes.get(index + 1).is_none_or(|next| next.newlines > 0);
+        break_line = match token {
+            Token::Comment(_) if block => line_ends || (break_line && lexeme.newlines == 0),
+            Token::Comment(_) | Token::Punct("{") => true,
+            Token::Punct(";") => !open.iter().any(|bracket| *bracket != "{"),
+            Token::Punct(",") => open.last() == Some(&"{"),
+            Token::Punct("}") => !matches!(next, Some(Token::Word("else") | Token::Punct("," | ";" | ")"))),
+            _ => false,
+        };
+        if let Token::Punct(bracket @ ("{" | "(" | "[")) = token {
+            open.push(bracket);
+        } else if let Token::Punct(")" | "]") = token {
+            open.pop();
+        }
+        if !comment || block {
+            previous = Some(token);
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// Whether the `-` just written is a sign rather than a subtraction.
+fn previous_unary(out: &str) -> bool {
+    let Some(before) = out.strip_suffix('-') else {
+        return false;
+    };
+    let before = before.trim_end();
+    before.is_empty() || before.ends_with(|c: char| "([{,:=<>!+-*/".contains(c)) || before.ends_with(" in")
+}
+
+/// Whether a space goes between `previous` and `token` on one line.
+fn spaced(previous: Option<Token>, token: Token, after_sign: bool) -> bool {
+    let Some(previous) = previous else {
+        return false;
+    };
+    if after_sign {
+        return false;
+    }
+    match (previous, token) {
+        (_, Token::Comment(_)) => true,
+        (Token::Punct("(" | "[" | "." | ".."), _) => false,
+        (_, Token::Punct(")" | "]" | "," | ";" | ":" | "." | "..")) => false,
+        (Token::Word(_), Token::Punct("(")) => false,
+        _ => true,
+    }
+}
+
+/// `code` with the params of each field and template block in name order,
+/// each keeping the comments above it and the one after it on its line.
+fn sort_params(code: &str, pairs: &[Pair<Rule>]) -> String {
+    let mut sorted = code.to_string();
+    for pair in pairs.iter().rev().filter(|pair| matches!(pair.as_rule(), Rule::field | Rule::template_block)) {
+        let params: Vec<(usize, usize)> = pair.clone().into_inner().filter(|part| part.as_rule() == Rule::field_param).map(|param| (param.as_span().start(), param.as_span().end())).collect();
+        let Some(&(first, _)) = params.first() else {
+            continue;
+        };
+        let Some(open) = code[pair.as_span().start()..first].find('{').map(|offset| pair.as_span().start() + offset + 1) else {
+            continue;
+        };
+        let close = pair.as_span().end() - 1;
+        // (name, comments above, param, comment after), in source order.
+        let mut items: Vec<(&str, &str, &str, String)> = Vec::new();
+        // A comment on the brace's line stays there.
+        let (brace_comment, mut leading) = split_gap(&code[open..first]);
+        for (index, &(start, end)) in params.iter().enumerate() {
+            let gap_end = params.get(index + 1).map_or(close, |&(next, _)| next);
+            let (trailing, rest) = split_gap(&code[end..gap_end]);
+            let text = &code[start..end];
+            let name = text.split(|c: char| c == ':' || c.is_whitespace()).next().unwrap_or(text);
+            items.push((name, leading, text, trailing));
+            leading = rest;
+        }
+        items.sort_by(|a, b| a.0.cmp(b.0));
+        let mut body = String::new();
+        if !brace_comment.is_empty() {
+            body.push(' ');
+            body.push_str(&brace_comment);
+        }
+        body.push('\n');
+        for (_, leading, text, trailing) in items {
+            body.push_str(leading);
+            body.push_str(text);
+            body.push(',');
+            body.push_str(if trailing.is_empty() { "\n" } else { " " });
+            if !trailing.is_empty() {
+                body.push_str(&trailing);
+                body.push('\n');
+            }
+        }
+        // The comments left after the last param stay before the brace.
+        body.push_str(leading);
+        sorted.replace_range(open..close, &body);
+    }
+    sorted
+}
+
+/// Splits what lies between a param and the next: the comments on the
+/// param's own line, joined by spaces, and everything after that line. A
+/// block comment that starts on the line counts as on it.
+fn split_gap(gap: &str) -> (String, &str) {
+    let mut comments = Vec::new();
+    let mut at = 0;
+    while let Some(offset) = gap[at..].find(['\n', '/']) {
+        at += offset;
+        let rest = &gap[at..];
+        if rest.starts_with('\n') {
+            return (comments.join(" "), &gap[at + 1..]);
+        }
+        let end = if rest.starts_with("//") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if let Some(block) = rest.strip_prefix("/*") {
+            block.find("*/").map_or(rest.len(), |end| end + 4)
+        } else {
+            1
+        };
+        if end > 1 {
+            comments.push(rest[..end].trim_end());
+        }
+        at += end;
+    }
+    (comments.join(" "), "")
+}
@@ -0,0 +1,48 @@
+use crate::scalar::Scalar;
+
+/// The field whose params a parent shares with its children, which read
+/// them as `global.<param>` and may not write them.
+pub const GLOBAL_FIELD: &str = "global";
+
+/// How a parent combines the latest tensions of its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Reduction {
+    /// The largest tension; weights are ignored.
+    #[default]
+    Max,
+    /// The mean tension, each child counted by its weight.
+    WeightedMean,
+}
+
+impl Reduction {
+    pub fn from_name(name: &str) -> Option<Reduction> {
+        match name {
+            "max" => Some(Reduction::Max),
+            "mean" | "weighted_mean" => Some(Reduction::WeightedMean),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Reduction::Max => "max",
+            Reduction::WeightedMean => "weighted_mean",
+        }
+    }
+
+    /// Combines `(tension, weight)` pairs; `None` when there are none, or
+    /// for a mean whose weights sum to zero.
+    pub fn combine(self, tensions: impl IntoIterator<Item = (Scalar, Scalar)>) -> Option<Scalar> {
+        let mut tensions = tensions.into_iter().peekable();
+        tensions.peek()?;
+        match self {
+            Reduction::Max => tensions.map(|(tension, _)| tension).reduce(Scalar::max),
+            Reduction::WeightedMean => {
+                let (sum, weights) = tensions.fold((0.0, 0.0), |(sum, weights): (Scalar, Scalar), (tension, weight)| {
+                    (sum + tension * weight, weights + weight)
+                });
+                (weights > 0.0).then(|| sum / weights)
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::expr::Expr;
+use crate::rules::Comparator;
+use crate::scalar::Scalar;
+use std::fmt;
+
+/// `goal <name> { when <comparison> [and <comparison> ...] hold_ticks: <n> }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Goal {
+    pub name: String,
+    pub conditions: Vec<Comparison>,
+    /// Consecutive satisfied steps needed to complete the goal.
+    pub hold_ticks: u32,
+}
+
+/// `<expression> <comparator> <expression>` inside a goal condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub lhs: Expr,
+    pub comparator: Comparator,
+    pub rhs: Expr,
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let conditions: Vec<String> = self
+            .conditions
+            .iter()
+            .map(|condition| format!("{} {} {}", condition.lhs, condition.comparator, condition.rhs))
+            .collect();
+        write!(f, "goal {} {{ when {} hold_ticks: {} }}", self.name, conditions.join(" and "), self.hold_ticks)
+    }
+}
+
+/// How far a goal has got: its current run of satisfied steps, and whether
+/// it has completed. Completion sticks until the goal is reset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GoalProgress {
+    pub satisfied_ticks: u32,
+    pub completed: bool,
+}
+
+impl GoalProgress {
+    /// Counts one step; true only on the step the goal completes.
+    pub fn update(&mut self, satisfied: bool, hold_ticks: u32) -> bool {
+        self.satisfied_ticks = if satisfied { self.satisfied_ticks.saturating_add(1) } else { 0 };
+        if self.completed || !satisfied || self.satisfied_ticks < hold_ticks {
+            return false;
+        }
+        self.completed = true;
+        true
+    }
+
+    /// The current run as a fraction of `hold_ticks`, capped at 1; 1 once completed.
+    pub fn fraction(&self, hold_ticks: u32) -> Scalar {
+        if self.completed || hold_ticks == 0 {
+            return if self.completed { 1.0 } else { 0.0 };
+        }
+        (self.satisfied_ticks as Scalar / hold_ticks as Scalar).min(1.0)
+    }
+}
@@ -0,0 +1,189 @@
+use crate::scalar::{to_f64, Scalar};
+
+/// Summary of a window of tension samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistoryStats {
+    pub count: usize,
+    pub mean: Scalar,
+    pub variance: Scalar,
+    pub min: Scalar,
+    pub max: Scalar,
+    pub latest: Scalar,
+    pub slope: Scalar,
+}
+
+/// The newest tension samples, at most `capacity` of them (0 = no limit),
+/// oldest first. A push past the capacity drops the oldest sample. The
+/// samples stay one slice for `stats` and `downsample`: dropped ones are
+/// only skipped until a whole capacity's worth has gone, then compacted
+/// away at once, so pushing stays constant time on average.
+#[derive(Debug, Clone, Default)]
+pub struct TensionHistory {
+    samples: Vec<Scalar>,
+    /// Index of the oldest retained sample in `samples`.
+    start: usize,
+    capacity: usize,
+    /// Samples dropped over the buffer's life, so ticks keep counting.
+    dropped: usize,
+}
+
+impl TensionHistory {
+    pub fn new(capacity: usize) -> Self {
+        TensionHistory { capacity, ..Default::default() }
+    }
+
+    pub fn samples(&self) -> &[Scalar] {
+        &self.samples[self.start..]
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len() - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn last(&self) -> Option<Scalar> {
+        self.samples().last().copied()
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Samples pushed so far, dropped ones included.
+    pub fn total(&self) -> usize {
+        self.dropped + self.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Drops the oldest samples beyond the new capacity.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    pub fn push(&mut self, tension: Scalar) {
+        self.samples.push(tension);
+        self.trim();
+    }
+
+    /// Drops the newest samples until `len` remain, as a rollback of the
+    /// latest ticks does.
+    pub fn truncate(&mut self, len: usize) {
+        self.samples.truncate(self.start + len);
+    }
+
+    pub fn clear(&mut self) {
+        *self = TensionHistory::new(self.capacity);
+    }
+
+    /// Replaces the samples, `dropped` having gone before the first.
+    pub fn restore(&mut self, samples: Vec<Scalar>, dropped: usize) {
+        *self = TensionHistory { samples, start: 0, capacity: self.capacity, dropped };
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        if self.capacity == 0 || self.len() <= self.capacity {
+            return;
+        }
+        let excess = self.len() - self.capacity;
+        self.start += excess;
+        self.dropped += excess;
+        if self.start >= self.capacity {
+            self.samples.drain(..self.start);
+            self.start = 0;
+        }
+    }
+}
+
+/// The last `window` samples, or all of them when `window` is 0 or exceeds the history.
+pub fn window(samples: &[Scalar], window: usize) -> &[Scalar] {
+    if window == 0 || window >= samples.len() {
+        samples
+    } else {
+        &samples[samples.len() - window..]
+    }
+}
+
+/// Population statistics over `samples`; `None` when there are none.
+pub fn stats(samples: &[Scalar]) -> Option<HistoryStats> {
+    let latest = *samples.last()?;
+    let n = samples.len() as f64;
+    let mean = samples.iter().map(|&v| to_f64(v)).sum::<f64>() / n;
+    let variance = samples.iter().map(|&v| (to_f64(v) - mean).powi(2)).sum::<f64>() / n;
+    let (min, max) = samples
+        .iter()
+        .fold((Scalar::INFINITY, Scalar::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)));
+    Some(HistoryStats {
+        count: samples.len(),
+        mean: mean as Scalar,
+        variance: variance as Scalar,
+        min,
+        max,
+        latest,
+        slope: slope(samples),
+    })
+}
+
+/// Least-squares slope of `samples` against their index, in tension per tick.
+/// Zero for fewer than two samples.
+pub fn slope(samples: &[Scalar]) -> Scalar {
+    let n = samples.len();
+    if n < 2 {
+        return 0.0;
+    }
+    let mean_x = (n - 1) as f64 / 2.0;
+    let mean_y = samples.iter().map(|&v| to_f64(v)).sum::<f64>() / n as f64;
+    let mut covariance = 0.0;
+    let mut spread = 0.0;
+    for (i, &y) in samples.iter().enumerate() {
+        let dx = i as f64 - mean_x;
+        covariance += dx * (to_f64(y) - mean_y);
+        spread += dx * dx;
+    }
+    (covariance / spread) as Scalar
+}
+
+/// `(index, value)` points tracing `samples` in at most `max_points` points.
+/// Each bucket keeps its minimum and maximum in index order, so a spike
+/// survives however far the series is reduced. Indices are offset by `start`.
+pub fn downsample(samples: &[Scalar], start: usize, max_points: usize) -> Vec<(usize, Scalar)> {
+    if samples.len() <= max_points {
+        return samples.iter().enumerate().map(|(i, &v)| (start + i, v)).collect();
+    }
+    if max_points == 0 {
+        return Vec::new();
+    }
+    let buckets = (max_points / 2).max(1);
+    let mut points = Vec::with_capacity(max_points);
+    for bucket in 0..buckets {
+        let lo = bucket * samples.len() / buckets;
+        let hi = (bucket + 1) * samples.len() / buckets;
+        let slice = &samples[lo..hi];
+        let (mut min_at, mut max_at) = (0, 0);
+        for (i, &v) in slice.iter().enumerate() {
+            if v < slice[min_at] {
+                min_at = i;
+            }
+            if v > slice[max_at] {
+                max_at = i;
+            }
+        }
+        if max_points == 1 {
+            points.push((start + lo + max_at, slice[max_at]));
+        } else if min_at == max_at {
+            points.push((start + lo + min_at, slice[min_at]));
+        } else {
+            for at in [min_at.min(max_at), min_at.max(max_at)] {
+                points.push((start + lo + at, slice[at]));
+            }
+        }
+    }
+    points
+}
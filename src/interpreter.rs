@@ -1,83 +1,4687 @@
-use gdnative::prelude::*;
-use pest::Parser;
+use indexmap::IndexMap;
+use pest::error::{Error as PestError, ErrorVariant};
+use pest::iterators::{Pair, Pairs};
+use pest::{Parser, Position};
 use pest_derive::Parser;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::compiled;
+use crate::agents::{copy_template, RegisteredAgent, Template};
+use crate::alarm::{TensionAlarm, TensionCrossing};
+use crate::behavior::{bind_self, BehaviorNode, BehaviorStatus, BehaviorTree, SELF_FIELD};
+use crate::config::{DriftStrategy, InterpreterConfig, DEFAULT_HISTORY_LIMIT};
+use crate::constants::Constants;
+use crate::datalog::{DataFormat, DataLog};
+use crate::diagnostic::Diagnostic;
+use crate::error::WeaveError;
+use crate::events::{EmittedSignal, EventAction, EventHandler, FiredEvent};
+use crate::expr::{EvalError, Expr, Function, Functions, UserFunction};
+use crate::goals::{Comparison, Goal, GoalProgress};
+use crate::invariant::{Invariant, InvariantPolicy, InvariantStatus, InvariantViolation};
+#[cfg(feature = "json")]
+use crate::json::{self, JsonState};
+use crate::global::GLOBAL_FIELD;
+use crate::history::{self, HistoryStats, TensionHistory};
+use crate::kinematics::{self, Limits};
+use crate::lint::{self, LintContext};
+use crate::logging::{weave_log, Level, Log};
+use crate::loops::{Loop, LoopKind, LoopStatement, DEFAULT_LOOP_LIMIT};
+use crate::messages::{receive_topic, Message, MessageBus};
+use crate::metrics::{ExperimentMetrics, MetricsServer};
+use crate::noise::Noise;
+use crate::pid::{PidGains, PidState};
+use crate::profile::{ProfilePhase, Profiler};
+use crate::recording::{Recording, ReplayOutcome, TickRecord};
+use crate::reload::{merge_attributes, merge_reload, FieldChanges, ReloadWatch};
+use crate::rules::{CallRule, LabCall, MetaweaveRule, RuleCondition, SensorPolicy};
+use crate::safety::{SafetyCheck, SafetyEvent, SafetyLog, SafetyRule, SafetySeverity};
+use crate::scalar::{to_f64, Scalar};
+use crate::schedule::{Schedule, ScheduledAction, ScheduledSet};
+use crate::sensors::{SensorIssue, SensorSpec};
+use crate::stream::{SensorFrame, SensorReplay, SensorStream, StreamPhase};
+#[cfg(feature = "telemetry")]
+use crate::telemetry::{TelemetryFrame, TelemetryServer};
+use crate::snapshot;
+use crate::sync::{DiffOutcome, SyncState};
+use crate::tension::{CoherenceTension, TensionInput, TensionModel, WeightedTension, WeightedTerm};
+use crate::trace::{Cause, Phase, Trace, TraceEntry};
 
 #[derive(Parser)]
 #[grammar = "weavelang.pest"]
 pub struct WeaveLangParser;
 
-pub fn parse_weave(path: &Path) -> Result<HashMap<String, HashMap<String, f32>>, pest::error::Error<Rule>> {
-    let code = std::fs::read_to_string(path).map_err(|e| pest::error::Error::<Rule>::new_from_span(
-        pest::error::ErrorVariant::CustomError { message: e.to_string() },
-        pest::Span::new("", 0, 0).unwrap(),
-    ))?;
-    let pairs = WeaveLangParser::parse(Rule::file, &code)?;
-    let mut fields = HashMap::new();
-    for pair in pairs {
+/// A field param value that isn't a number. Numbers live in the Scalar field
+/// maps the execute phases work on; everything else is kept here so those
+/// phases never see it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamValue {
+    Bool(bool),
+    Text(String),
+    /// A bare identifier such as `cautious` in `mode: cautious`.
+    Symbol(String),
+    /// `[a, b, ...]`, e.g. `tags: [expert, mobile]`.
+    List(Vec<ParamValue>),
+    /// A non-empty list of numbers such as `position: [1.0, 0.0, 0.0]`, or a
+    /// tuple such as `position: (1.0, 0.0, 0.0)`; these make up the vector model.
+    Vector(Vec<Scalar>),
+}
+
+impl ParamValue {
+    fn from_pair(pair: Pair<Rule>) -> Option<Self> {
         match pair.as_rule() {
-            Rule::field => {
-                let mut field_data = HashMap::new();
-                let mut field_name = String::new();
-                for inner in pair.into_inner() {
-                    match inner.as_rule() {
-                        Rule::ident => field_name = inner.as_str().to_string(),
-                        Rule::field_param => {
-                            let mut param_name = String::new();
-                            let mut param_value = 0.0;
-                            for param in inner.into_inner() {
-                                match param.as_rule() {
-                                    Rule::ident => param_name = param.as_str().to_string(),
-                                    Rule::number => param_value = param.as_str().parse::<f32>().unwrap_or(0.0),
-                                    _ => {}
-                                }
+            Rule::boolean => Some(ParamValue::Bool(pair.as_str() == "true")),
+            Rule::string => Some(ParamValue::Text(pair.into_inner().next().map(|inner| inner.as_str()).unwrap_or_default().to_owned())),
+            Rule::symbol => Some(ParamValue::Symbol(pair.as_str().to_owned())),
+            Rule::list => {
+                let items: Vec<Pair<Rule>> = pair.into_inner().collect();
+                if !items.is_empty() && items.iter().all(|item| item.as_rule() == Rule::number) {
+                    return Some(ParamValue::Vector(items.iter().map(|item| parse_number(item.as_str())).collect()));
+                }
+                Some(ParamValue::List(items.into_iter().filter_map(ParamValue::from_pair).collect()))
+            }
+            Rule::tuple => Some(ParamValue::Vector(pair.into_inner().map(|item| parse_number(item.as_str())).collect())),
+            _ => None,
+        }
+    }
+}
+
+/// Reads back a non-numeric value written by `ParamValue`'s `Display`;
+/// `None` for a number or anything that doesn't parse.
+pub fn parse_param_value(text: &str) -> Option<ParamValue> {
+    let literal = WeaveLangParser::parse(Rule::param_literal, text).ok()?.next()?;
+    ParamValue::from_pair(literal.into_inner().next()?)
+}
+
+impl fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamValue::Bool(value) => write!(f, "{}", value),
+            ParamValue::Text(text) => write!(f, "\"{}\"", text),
+            ParamValue::Symbol(name) => f.write_str(name),
+            ParamValue::List(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            ParamValue::Vector(components) => {
+                let components: Vec<String> = components.iter().map(|component| component.to_string()).collect();
+                write!(f, "[{}]", components.join(", "))
+            }
+        }
+    }
+}
+
+/// One field's numeric params, in declaration order.
+pub type Params = IndexMap<String, Scalar>;
+/// Fields by name, in declaration order; also the shape of agent data.
+pub type Fields = IndexMap<String, Params>;
+
+/// Oldest and newest weave language versions this build runs. Scripts
+/// without a `weave <n>` header are version 1.
+pub const MIN_LANGUAGE_VERSION: u32 = 1;
+pub const LANGUAGE_VERSION: u32 = 2;
+
+/// Fields carrying this tag are never moved by drift or resolve.
+pub const FROZEN_TAG: &str = "frozen";
+
+/// Params drift and resolve steer on a field that doesn't declare `drives`.
+pub const DEFAULT_DRIFT_TARGETS: [&str; 2] = ["coherence_target", "physics_constant"];
+
+/// Everything a weave file declares, before any of it runs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeaveProgram {
+    /// From the `weave <n>` header; 0 in a program that wasn't parsed from a file.
+    pub version: u32,
+    pub fields: Fields,
+    /// Non-numeric params, keyed like `fields`.
+    pub attributes: HashMap<String, HashMap<String, ParamValue>>,
+    pub metaweave_rules: Vec<MetaweaveRule>,
+    pub call_rules: Vec<CallRule>,
+    pub safety_rules: Vec<SafetyRule>,
+    /// `sensor <name> from "<node path>:<property>"` declarations, in file order.
+    pub sensor_bindings: IndexMap<String, String>,
+    /// `sensors { name: default, ... }` entries, in file order.
+    pub declared_sensors: IndexMap<String, Scalar>,
+    /// `in min..max` ranges of `sensors` entries.
+    pub sensor_ranges: IndexMap<String, (Scalar, Scalar)>,
+    pub goals: Vec<Goal>,
+    /// `assert` statements, in file order.
+    pub invariants: Vec<Invariant>,
+    /// `at tick` sets, in file order.
+    pub scheduled_sets: Vec<ScheduledSet>,
+    /// Numeric params whose expression reads a sensor or a param only known
+    /// at runtime, in file order. `fields` holds 0.0 for each until it is
+    /// first computed.
+    pub derived_params: Vec<DerivedParam>,
+    /// `repeat` and `while` blocks, in file order.
+    pub loops: Vec<Loop>,
+    /// `on` blocks, in file order.
+    pub handlers: Vec<EventHandler>,
+    /// `behavior` blocks, in file order, at most one per role.
+    pub behaviors: Vec<BehaviorTree>,
+    /// `agent <name> from <template>;` declarations, each agent mapped to its template.
+    pub agents: IndexMap<String, String>,
+    /// `template` blocks, in file order. Fields that extend one already hold
+    /// its params, so these are only kept for code run later.
+    pub templates: IndexMap<String, Template>,
+    /// `param: value in [min, max]` ranges, by field then param. Drift and
+    /// resolve keep the param within them.
+    pub param_bounds: HashMap<String, HashMap<String, (Scalar, Scalar)>>,
+    /// The `tension` block; without terms, tension is the built-in coherence model.
+    pub tension: WeightedTension,
+    /// `fn` declarations. Calls are already expanded in everything above;
+    /// these are kept for code run later.
+    pub functions: Functions,
+}
+
+/// `<field> { <param>: <expression> }` where the expression reads sensors
+/// or live params, so it is computed again at the start of every step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedParam {
+    pub field: String,
+    pub param: String,
+    pub expr: Expr,
+}
+
+impl fmt::Display for DerivedParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{} = {}", self.field, self.param, self.expr)
+    }
+}
+
+/// A piece of weave code typed at runtime: the declarations a file could
+/// hold plus bare `field.param = value` assignments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snippet {
+    pub program: WeaveProgram,
+    pub assignments: Vec<(String, String, Scalar)>,
+}
+
+/// What `Interpreter::preview_step` found a step would do.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepPreview {
+    pub tension: Scalar,
+    /// Every numeric param the step would move, in field order; a param it
+    /// would add has an `old_value` of 0.0.
+    pub changes: Vec<FieldChange>,
+    /// Every vector the step would move, keyed `field.param`, with its new components.
+    pub vectors: Vec<(String, Vec<Scalar>)>,
+    pub fired: Vec<MetaweaveRule>,
+}
+
+/// The running state of an experiment, from `Interpreter::export_state`,
+/// for `import_state` to pick up where it left off. Rules, sensors and
+/// tuning come from the loaded script and settings, so aren't part of it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExperimentState {
+    pub fields: Fields,
+    pub attributes: HashMap<String, HashMap<String, ParamValue>>,
+    pub tension_history: Vec<Scalar>,
+    pub dropped_history: usize,
+    pub smoothed_tension: Option<Scalar>,
+    pub schedule: Schedule,
+    pub goal_progress: HashMap<String, GoalProgress>,
+    /// Locked agents, each mapped to whether its lock survives a reload.
+    pub locked_agents: HashMap<String, bool>,
+    /// Seed the drift noise continues from.
+    pub noise_seed: u64,
+}
+
+/// What `Interpreter::execute_code` changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CodeOutcome {
+    /// Fields the snippet declared that did not exist before.
+    pub added_fields: Vec<String>,
+    /// Every param the snippet moved, watched or not.
+    pub changes: Vec<FieldChange>,
+    /// Snippet metaweave rules that fired on the last sensor values.
+    pub fired: Vec<MetaweaveRule>,
+}
+
+/// Parses `path` with only the standard constants table.
+pub fn parse_weave(path: &Path) -> Result<WeaveProgram, WeaveError> {
+    parse_weave_with(path, &Constants::default())
+}
+
+/// Parses `path`, resolving constant names in param expressions against `constants`.
+pub fn parse_weave_with(path: &Path, constants: &Constants) -> Result<WeaveProgram, WeaveError> {
+    parse_weave_str_with(&std::fs::read_to_string(path)?, constants)
+}
+
+/// Parses `code`, the text of a whole weave file, with only the standard
+/// constants table; for scripts from a resource, the network or an editor.
+pub fn parse_weave_str(code: &str) -> Result<WeaveProgram, WeaveError> {
+    parse_weave_str_with(code, &Constants::default())
+}
+
+/// `parse_weave_with` for the text of a file rather than its path.
+pub fn parse_weave_str_with(code: &str, constants: &Constants) -> Result<WeaveProgram, WeaveError> {
+    // Checked before the full parse so a script for a newer language gets a
+    // version error rather than a parse error on syntax this build lacks.
+    let version = script_version(code)?;
+    // Generated and exported scripts are often nothing but fields of plain
+    // numbers, which are read several times faster by hand than by pest.
+    if let Some(fields) = plain_fields(code) {
+        return Ok(WeaveProgram { version, fields, ..WeaveProgram::default() });
+    }
+    let pairs = parse_rule(Rule::file, code)?;
+    // Nearly every top-level pair is a field, so the outer map can be
+    // sized up front instead of rehashing as it grows.
+    let mut program = WeaveProgram {
+        version,
+        fields: Fields::with_capacity(pairs.len()),
+        ..WeaveProgram::default()
+    };
+    // Functions go first, so a declaration may call one declared below it.
+    let (functions, declarations): (Vec<_>, Vec<_>) = pairs.partition(|pair| pair.as_rule() == Rule::function_def);
+    for pair in functions.into_iter().chain(declarations) {
+        add_declaration(&mut program, pair, constants)?;
+    }
+    let is_sensor = |name: &str| program.declared_sensors.contains_key(name) || program.sensor_bindings.contains_key(name);
+    check_derived_params(&program.derived_params, &program.fields, constants, is_sensor)?;
+    for term in &program.tension.terms {
+        if !program.fields.get(&term.field).is_some_and(|params| params.contains_key(&term.param)) {
+            return Err(WeaveError::Validation(format!("tension term {} targets {}.{}, which isn't a declared param", term, term.field, term.param)));
+        }
+    }
+    for statement in program.loops.iter().flat_map(|looped| &looped.body) {
+        if let LoopStatement::Set { field, .. } | LoopStatement::Send { target: field, .. } = statement {
+            if !program.fields.contains_key(field) && field != GLOBAL_FIELD {
+                return Err(WeaveError::MissingField(field.clone()));
+            }
+        }
+    }
+    for action in program.handlers.iter().flat_map(|handler| &handler.actions) {
+        if let EventAction::Set { field, .. } | EventAction::Send { target: field, .. } = action {
+            if !program.fields.contains_key(field) && field != GLOBAL_FIELD {
+                return Err(WeaveError::MissingField(field.clone()));
+            }
+        }
+    }
+    for tree in &program.behaviors {
+        check_behavior_sets(&tree.root, &program.fields)?;
+    }
+    Ok(program)
+}
+
+/// Fails on a behavior `set` of a field that is neither declared nor `self`.
+fn check_behavior_sets(node: &BehaviorNode, fields: &Fields) -> Result<(), WeaveError> {
+    match node {
+        BehaviorNode::Selector(children) | BehaviorNode::Sequence(children) => children.iter().try_for_each(|child| check_behavior_sets(child, fields)),
+        BehaviorNode::Set { field, .. } if !fields.contains_key(field) && field != SELF_FIELD && field != GLOBAL_FIELD => Err(WeaveError::MissingField(field.clone())),
+        _ => Ok(()),
+    }
+}
+
+/// The version declared by the optional `weave <n>` header at the top of
+/// `code`, or 1 without one. Fails if this build can't run that version.
+pub fn script_version(code: &str) -> Result<u32, WeaveError> {
+    let header = parse_rule(Rule::header, code)?.find(|pair| pair.as_rule() == Rule::version_header);
+    let version = match header.and_then(|pair| pair.into_inner().next()) {
+        Some(number) => number
+            .as_str()
+            .parse()
+            .map_err(|_| WeaveError::Validation(format!("weave version {} is out of range", number.as_str())))?,
+        None => MIN_LANGUAGE_VERSION,
+    };
+    if !(MIN_LANGUAGE_VERSION..=LANGUAGE_VERSION).contains(&version) {
+        return Err(WeaveError::UnsupportedVersion { required: version, supported: LANGUAGE_VERSION });
+    }
+    Ok(version)
+}
+
+/// Runs the parser on `code`. If it fails and `code` has a non-ASCII
+/// character outside strings and comments, the error points at that instead:
+/// identifiers are ASCII only, so the script can't parse while it is there,
+/// and pest's own error for it is usually far from the character.
+pub(crate) fn parse_rule(rule: Rule, code: &str) -> Result<Pairs<'_, Rule>, WeaveError> {
+    WeaveLangParser::parse(rule, code).map_err(|error| {
+        let non_ascii = first_non_ascii_code(code).and_then(|offset| Some((Position::new(code, offset)?, code[offset..].chars().next()?)));
+        match non_ascii {
+            Some((position, c)) => {
+                let message = format!("{:?} is not allowed here: identifiers are ASCII letters, digits and _, and other text belongs in strings or comments", c);
+                PestError::new_from_pos(ErrorVariant::CustomError { message }, position).into()
+            }
+            None => error.into(),
+        }
+    })
+}
+
+/// Byte offset of the first non-ASCII character outside string literals and
+/// `//` comments, ignoring a leading byte order mark.
+fn first_non_ascii_code(code: &str) -> Option<usize> {
+    let start = if code.starts_with('\u{feff}') { '\u{feff}'.len_utf8() } else { 0 };
+    let (mut in_string, mut in_comment, mut in_block) = (false, false, false);
+    let mut chars = code[start..].char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        match c {
+            '*' if in_block && next == Some('/') => {
+                chars.next();
+                in_block = false;
+            }
+            _ if in_block => {}
+            '\n' | '\r' => in_comment = false,
+            _ if in_comment => {}
+            '"' => in_string = !in_string,
+            '/' if !in_string && next == Some('/') => in_comment = true,
+            '/' if !in_string && next == Some('*') => {
+                chars.next();
+                in_block = true;
+            }
+            c if !c.is_ascii() && !in_string => return Some(start + offset),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The fields of `code` when it is an optional header followed by fields
+/// whose params are all plain numbers, read the way the grammar would read
+/// them. `None` for anything else, including every script that doesn't
+/// parse, so those go through pest and get its errors.
+fn plain_fields(code: &str) -> Option<Fields> {
+    let mut scan = PlainScan { code, at: 0 };
+    scan.skip();
+    scan.eat("\u{feff}");
+    scan.skip();
+    if scan.eat("weave") {
+        scan.skip();
+        scan.digits()?;
+    }
+    let mut fields = Fields::new();
+    loop {
+        scan.skip();
+        if scan.at == code.len() {
+            return Some(fields);
+        }
+        scan.expect("field")?;
+        scan.skip();
+        let name = scan.ident()?;
+        scan.skip();
+        scan.expect("{")?;
+        scan.skip();
+        let mut params = Params::new();
+        while !scan.eat("}") {
+            let param = scan.ident()?;
+            scan.skip();
+            scan.expect(":")?;
+            scan.skip();
+            params.insert(param.to_owned(), scan.number()?);
+            scan.skip();
+            if !scan.eat(",") {
+                scan.expect("}")?;
+                break;
+            }
+            scan.skip();
+        }
+        fields.insert(name.to_owned(), params);
+    }
+}
+
+/// A cursor over a script for `plain_fields`. Every method stops on ASCII,
+/// so `at` is always a char boundary.
+struct PlainScan<'a> {
+    code: &'a str,
+    at: usize,
+}
+
+impl<'a> PlainScan<'a> {
+    fn rest(&self) -> &'a str {
+        &self.code[self.at..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.at += token.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &str) -> Option<()> {
+        self.eat(token).then_some(())
+    }
+
+    /// Moves past the bytes at the cursor that `accept` takes; `None` if
+    /// there are none.
+    fn take(&mut self, accept: impl Fn(u8) -> bool) -> Option<&'a str> {
+        let start = self.at;
+        let len = self.rest().bytes().position(|byte| !accept(byte)).unwrap_or(self.code.len() - start);
+        self.at += len;
+        (len > 0).then(|| &self.code[start..self.at])
+    }
+
+    fn digits(&mut self) -> Option<&'a str> {
+        self.take(|byte| byte.is_ascii_digit())
+    }
+
+    /// `WHITESPACE` and `COMMENT`. An unclosed block comment is left for
+    /// pest to report.
+    fn skip(&mut self) {
+        loop {
+            if self.take(|byte| matches!(byte, b' ' | b'\t' | b'\n' | b'\r')).is_some() {
+                continue;
+            }
+            if self.eat("//") {
+                self.take(|byte| !matches!(byte, b'\n' | b'\r'));
+                continue;
+            }
+            if self.rest().starts_with("/*") {
+                if let Some(end) = self.rest()[2..].find("*/") {
+                    self.at += end + 4;
+                    continue;
+                }
+            }
+            return;
+        }
+    }
+
+    fn ident(&mut self) -> Option<&'a str> {
+        let start = self.at;
+        self.rest().bytes().next().filter(|byte| byte.is_ascii_alphabetic() || *byte == b'_')?;
+        self.take(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+        Some(&self.code[start..self.at])
+    }
+
+    /// A `number` that the expression it starts goes no further than, so it
+    /// must be followed by the `,` or `}` that ends its param.
+    fn number(&mut self) -> Option<Scalar> {
+        let start = self.at;
+        self.eat("-");
+        self.digits()?;
+        if self.rest().starts_with('.') && self.rest().as_bytes().get(1).is_some_and(u8::is_ascii_digit) {
+            self.at += 1;
+            self.digits();
+        }
+        let number = &self.code[start..self.at];
+        self.skip();
+        self.rest().starts_with([',', '}']).then_some(())?;
+        number.parse().ok()
+    }
+}
+
+/// Parses runtime code, which may be any mix of top-level blocks and bare
+/// assignments without the rest of a file around them. It may call
+/// `functions` as well as any it declares itself, and extend `templates`.
+pub fn parse_snippet(code: &str, constants: &Constants, functions: &Functions, templates: &IndexMap<String, Template>) -> Result<Snippet, WeaveError> {
+    let mut snippet = Snippet::default();
+    snippet.program.functions = functions.clone();
+    snippet.program.templates = templates.clone();
+    let (defined, rest): (Vec<_>, Vec<_>) = parse_rule(Rule::snippet, code)?.partition(|pair| pair.as_rule() == Rule::function_def);
+    for pair in defined.into_iter().chain(rest) {
+        if pair.as_rule() == Rule::assignment {
+            let mut parts = pair.into_inner();
+            let mut next = || parts.next().map(|part| part.as_str()).unwrap_or_default();
+            let field = next().to_owned();
+            let param = next().to_owned();
+            let value = parse_number(next());
+            snippet.assignments.push((field, param, value));
+        } else {
+            add_declaration(&mut snippet.program, pair, constants)?;
+        }
+    }
+    Ok(snippet)
+}
+
+/// What `extends parent` gives `child`: a template's params, or those of an
+/// earlier field, with its param expressions moved over to `child`.
+fn inherited(program: &WeaveProgram, parent: &str, child: &str) -> Result<Template, WeaveError> {
+    let mut body = match program.templates.get(parent) {
+        Some(template) => template.clone(),
+        None => Template {
+            params: program.fields.get(parent).cloned().ok_or_else(|| WeaveError::MissingField(parent.to_string()))?,
+            attributes: program.attributes.get(parent).cloned().unwrap_or_default(),
+            bounds: program.param_bounds.get(parent).cloned().unwrap_or_default(),
+            derived_params: program.derived_params.iter().filter(|derived| derived.field == parent).cloned().collect(),
+        },
+    };
+    for derived in &mut body.derived_params {
+        derived.field = child.to_string();
+    }
+    Ok(body)
+}
+
+/// Declares the field `name` with everything `body` holds.
+fn add_field(program: &mut WeaveProgram, name: String, body: Template) {
+    for derived in body.derived_params {
+        program.derived_params.retain(|existing| existing.field != derived.field || existing.param != derived.param);
+        program.derived_params.push(derived);
+    }
+    if !body.attributes.is_empty() {
+        program.attributes.insert(name.clone(), body.attributes);
+    }
+    if !body.bounds.is_empty() {
+        program.param_bounds.insert(name.clone(), body.bounds);
+    }
+    program.fields.insert(name, body.params);
+}
+
+fn add_declaration(program: &mut WeaveProgram, pair: Pair<Rule>, constants: &Constants) -> Result<(), WeaveError> {
+    match pair.as_rule() {
+        Rule::function_def => {
+            let mut parts: Vec<Pair<Rule>> = pair.into_inner().collect();
+            let (Some(body), false) = (parts.pop(), parts.is_empty()) else {
+                return Ok(());
+            };
+            let name = parts.remove(0).as_str().to_owned();
+            if Function::from_name(&name).is_some() {
+                return Err(WeaveError::Validation(format!("fn {} has the name of a built-in function", name)));
+            }
+            if program.functions.contains_key(&name) {
+                return Err(WeaveError::Validation(format!("fn {} is declared twice", name)));
+            }
+            let params: Vec<String> = parts.into_iter().map(|param| param.as_str().to_owned()).collect();
+            if let Some(param) = params.iter().enumerate().find_map(|(index, param)| params[..index].contains(param).then_some(param)) {
+                return Err(WeaveError::Validation(format!("fn {} names param {} twice", name, param)));
+            }
+            // Expanded against the functions declared so far, so a function
+            // can't call itself or one declared after it.
+            let body = Expr::from_pair(body, &program.functions)?;
+            program.functions.insert(name, UserFunction { params, body });
+        }
+        Rule::field | Rule::template_block => {
+            let is_template = pair.as_rule() == Rule::template_block;
+            let mut inner = pair.into_inner().peekable();
+            let field_name = match inner.next() {
+                Some(ident) => ident.as_str().to_owned(),
+                None => return Ok(()),
+            };
+            let mut body = match inner.next_if(|part| part.as_rule() == Rule::field_parent) {
+                Some(parent) => inherited(program, parent.into_inner().as_str(), &field_name)?,
+                None => Template::default(),
+            };
+            for param in inner {
+                let mut parts = param.into_inner();
+                if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                    let name = name.as_str().to_owned();
+                    if let Some(range) = parts.next() {
+                        let mut bounds = range.into_inner().map(|bound| parse_number(bound.as_str()));
+                        let (min, max) = (bounds.next().unwrap_or_default(), bounds.next().unwrap_or_default());
+                        if min > max {
+                            return Err(WeaveError::Validation(format!("{}.{} has the empty range [{}, {}]", field_name, name, min, max)));
+                        }
+                        body.bounds.insert(name.clone(), (min, max));
+                    }
+                    body.derived_params.retain(|derived| derived.param != name);
+                    if value.as_rule() == Rule::expression {
+                        let context = format!("{}.{}", field_name, name);
+                        match evaluate_param(value, &body.params, program, constants, &context)? {
+                            Evaluated::Number(number) => {
+                                body.attributes.remove(&name);
+                                body.params.insert(name, number);
+                            }
+                            Evaluated::Symbol(symbol) => {
+                                body.params.shift_remove(&name);
+                                body.attributes.insert(name, ParamValue::Symbol(symbol));
+                            }
+                            Evaluated::Live(expr, _) => {
+                                body.attributes.remove(&name);
+                                body.derived_params.push(DerivedParam { field: field_name.clone(), param: name.clone(), expr });
+                                body.params.insert(name, 0.0);
                             }
-                            field_data.insert(param_name, param_value);
                         }
-                        _ => {}
+                    } else if let Some(value) = ParamValue::from_pair(value) {
+                        body.params.shift_remove(&name);
+                        body.attributes.insert(name, value);
                     }
                 }
-                fields.insert(field_name, field_data);
             }
-            _ => {}
+            for (param, &(min, max)) in &body.bounds {
+                if body.attributes.contains_key(param) {
+                    return Err(WeaveError::Validation(format!("{}.{} takes a range but isn't a number", field_name, param)));
+                }
+                let live = body.derived_params.iter().any(|derived| &derived.param == param);
+                if let Some(value) = body.params.get(param).filter(|value| !live && !(min..=max).contains(*value)) {
+                    return Err(WeaveError::Validation(format!("{}.{} = {} is outside its range [{}, {}]", field_name, param, value, min, max)));
+                }
+            }
+            if is_template {
+                if program.templates.contains_key(&field_name) {
+                    return Err(WeaveError::Validation(format!("template {} is declared twice", field_name)));
+                }
+                program.templates.insert(field_name, body);
+                return Ok(());
+            }
+            add_field(program, field_name, body);
+        }
+        Rule::tension_block => {
+            if !program.tension.terms.is_empty() {
+                return Err(WeaveError::Validation("tension is declared twice".to_string()));
+            }
+            for term in pair.into_inner() {
+                let mut parts = term.into_inner();
+                let (Some(sensor), Some(target)) = (parts.next(), parts.next()) else {
+                    continue;
+                };
+                let (field, param) = target.as_str().split_once('.').unwrap_or_default();
+                let weight = parts.next().map_or(1.0, |weight| parse_number(weight.as_str()));
+                program.tension.terms.push(WeightedTerm { sensor: sensor.as_str().to_owned(), field: field.to_owned(), param: param.to_owned(), weight });
+            }
+        }
+        Rule::agent_decl => {
+            let mut parts = pair.into_inner();
+            let (Some(name), Some(template)) = (parts.next(), parts.next()) else {
+                return Ok(());
+            };
+            let (name, template) = (name.as_str().to_owned(), template.as_str().to_owned());
+            if program.fields.contains_key(&name) || name == GLOBAL_FIELD {
+                return Err(WeaveError::Validation(format!("agent {} is already declared", name)));
+            }
+            let body = inherited(program, &template, &name)?;
+            add_field(program, name.clone(), body);
+            program.agents.insert(name, template);
+        }
+        Rule::metaweave_block => {
+            for rule in pair.into_inner() {
+                match rule.as_rule() {
+                    Rule::call_rule => program.call_rules.push(call_rule(rule)?),
+                    Rule::if_block => add_if_block(program, rule, constants)?,
+                    _ => {
+                        let attribute_rule = rule.as_rule() == Rule::attribute_rule;
+                        let mut parts = rule.into_inner();
+                        let condition = if attribute_rule { attribute_condition(&mut parts)? } else { sensor_condition(&mut parts)? };
+                        let rule = set_rule(program, condition, parts, constants)?;
+                        program.metaweave_rules.push(rule);
+                    }
+                }
+            }
+        }
+        Rule::if_block => add_if_block(program, pair, constants)?,
+        Rule::loop_block => {
+            let mut parts = pair.into_inner();
+            let Some(head) = parts.next() else {
+                return Ok(());
+            };
+            let kind = if head.as_rule() == Rule::repeat_head {
+                let count = head.into_inner().next().map(|count| count.as_str()).unwrap_or_default();
+                LoopKind::Repeat(count.parse().map_err(|_| WeaveError::Validation(format!("repeat {} must be a whole, non-negative count", count)))?)
+            } else {
+                match head.into_inner().next() {
+                    Some(test) => LoopKind::While(test_condition(test)?),
+                    None => return Ok(()),
+                }
+            };
+            let body = parts.map(|statement| loop_statement(statement, &program.functions)).collect::<Result<_, _>>()?;
+            program.loops.push(Loop { kind, body });
+        }
+        Rule::on_block => {
+            let mut parts = pair.into_inner();
+            let Some(test) = parts.next() else {
+                return Ok(());
+            };
+            let condition = test_condition(test)?;
+            let actions = parts.map(|action| event_action(action, &program.functions)).collect::<Result<_, _>>()?;
+            program.handlers.push(EventHandler { condition, actions });
+        }
+        Rule::behavior_block => {
+            let mut parts = pair.into_inner();
+            let role = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+            if program.behaviors.iter().any(|tree| tree.role == role) {
+                return Err(WeaveError::Validation(format!("role {} has more than one behavior tree", role)));
+            }
+            let root = match parts.next() {
+                Some(node) => behavior_node(node, &program.functions)?,
+                None => BehaviorNode::Sequence(Vec::new()),
+            };
+            program.behaviors.push(BehaviorTree { role, root });
+        }
+        Rule::safety_block => {
+            for rule in pair.into_inner() {
+                let mut checks = Vec::new();
+                let mut severity = SafetySeverity::Halt;
+                let mut grace_ticks = 0;
+                for part in rule.into_inner() {
+                    match part.as_rule() {
+                        Rule::safety_check => {
+                            let mut parts = part.into_inner().map(|part| part.as_str());
+                            let sensor = parts.next().unwrap_or_default().to_owned();
+                            let comparator = parts.next().unwrap_or_default().parse().map_err(WeaveError::Validation)?;
+                            checks.push(SafetyCheck { sensor, comparator, threshold: parse_number(parts.next().unwrap_or_default()) });
+                        }
+                        Rule::safety_severity => severity = part.as_str().parse().map_err(WeaveError::Validation)?,
+                        _ => {
+                            let ticks = part.as_str();
+                            grace_ticks = ticks
+                                .parse()
+                                .map_err(|_| WeaveError::Validation(format!("grace_ticks {} must be a whole, non-negative count", ticks)))?;
+                        }
+                    }
+                }
+                program.safety_rules.push(SafetyRule { checks, severity, grace_ticks });
+            }
+        }
+        Rule::sensors_block => {
+            for entry in pair.into_inner() {
+                let mut parts = entry.into_inner();
+                let mut next = || parts.next().map(|part| part.as_str()).unwrap_or_default();
+                let sensor = next().to_owned();
+                let default = parse_number(next());
+                if program.declared_sensors.insert(sensor.clone(), default).is_some() {
+                    return Err(WeaveError::Validation(format!("sensor {} is declared twice", sensor)));
+                }
+                let (min, max) = (next(), next());
+                if !min.is_empty() {
+                    let range = (parse_number(min), parse_number(max));
+                    let spec = SensorSpec { default: Some(default), min: Some(range.0), max: Some(range.1) };
+                    if let Some(problem) = spec.problem() {
+                        return Err(WeaveError::Validation(format!("sensor {}: {}", sensor, problem)));
+                    }
+                    program.sensor_ranges.insert(sensor, range);
+                }
+            }
+        }
+        Rule::sensor_binding => {
+            let mut parts = pair.into_inner();
+            let sensor = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+            let source = parts.next().and_then(|string| string.into_inner().next()).map(|inner| inner.as_str()).unwrap_or_default();
+            if binding_target(source).is_none() {
+                return Err(WeaveError::Validation(format!("sensor {} must be bound to \"<node path>:<property>\", got \"{}\"", sensor, source)));
+            }
+            program.sensor_bindings.insert(sensor, source.to_owned());
+        }
+        Rule::goal_block => {
+            let mut parts = pair.into_inner();
+            let name = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+            let conditions = parts.next().map(|condition| comparisons(condition, &program.functions)).transpose()?.unwrap_or_default();
+            let ticks = parts.next().map(|part| part.as_str()).unwrap_or_default();
+            let hold_ticks = ticks
+                .parse()
+                .map_err(|_| WeaveError::Validation(format!("hold_ticks {} must be a whole, non-negative count", ticks)))?;
+            if program.goals.iter().any(|goal| goal.name == name) {
+                return Err(WeaveError::Validation(format!("goal {} is declared twice", name)));
+            }
+            program.goals.push(Goal { name, conditions, hold_ticks });
+        }
+        Rule::invariant => {
+            let mut parts = pair.into_inner().peekable();
+            let name = parts.next_if(|part| part.as_rule() == Rule::ident).map(|part| part.as_str().to_owned());
+            let conditions = parts.next().map(|condition| comparisons(condition, &program.functions)).transpose()?.unwrap_or_default();
+            let mut invariant = Invariant { name: name.unwrap_or_default(), conditions };
+            if invariant.name.is_empty() {
+                invariant.name = invariant.text();
+            }
+            if program.invariants.iter().any(|other| other.name == invariant.name) {
+                return Err(WeaveError::Validation(format!("invariant {} is declared twice", invariant.name)));
+            }
+            program.invariants.push(invariant);
         }
+        Rule::scheduled_set => {
+            let mut parts = pair.into_inner();
+            let mut next = || parts.next().map(|part| part.as_str()).unwrap_or_default();
+            let tick = next();
+            let tick = tick
+                .parse::<usize>()
+                .map_err(|_| WeaveError::Validation(format!("at tick {} must name a whole, non-negative tick", tick)))?;
+            let field = next().to_owned();
+            let param = next().to_owned();
+            let value = parse_number(next());
+            program.scheduled_sets.push(ScheduledSet { tick, field, param, value });
+        }
+        // The header was already read by `script_version`.
+        Rule::version_header | Rule::EOI => {}
+        rule => return Err(WeaveError::Validation(format!("unexpected {:?} at the top level", rule))),
+    }
+    Ok(())
+}
+
+/// The comparisons of a `goal_condition`, as goals and invariants hold them.
+fn comparisons(condition: Pair<Rule>, functions: &Functions) -> Result<Vec<Comparison>, WeaveError> {
+    let mut conditions = Vec::new();
+    for comparison in condition.into_inner() {
+        let mut sides = comparison.into_inner();
+        let (Some(lhs), Some(comparator), Some(rhs)) = (sides.next(), sides.next(), sides.next()) else {
+            continue;
+        };
+        conditions.push(Comparison {
+            lhs: Expr::from_pair(lhs, functions)?,
+            comparator: comparator.as_str().parse().map_err(WeaveError::Validation)?,
+            rhs: Expr::from_pair(rhs, functions)?,
+        });
     }
-    godot_print!("Executing WeaveLang code: {}", path.display());
-    Ok(fields)
+    Ok(conditions)
 }
 
-pub fn execute_tension(fields: &mut HashMap<String, HashMap<String, f32>>, sensors: &HashMap<String, f32>) -> f32 {
-    let coherence = sensors.get("coherence").unwrap_or(&0.0);
-    let generalist_coherence = fields.get("generalist").unwrap().get("coherence_target").unwrap_or(&0.5);
-    let tension = (coherence - generalist_coherence).abs();
-    godot_print!("Tension calculated: {}", tension);
-    tension
+fn call_rule(rule: Pair<Rule>) -> Result<CallRule, WeaveError> {
+    let mut parts = rule.into_inner();
+    let condition = sensor_condition(&mut parts)?;
+    Ok(CallRule { condition, call: lab_call(parts) })
 }
 
-pub fn execute_drift(fields: &mut HashMap<String, HashMap<String, f32>>, agents: &HashMap<String, HashMap<String, f32>>, history: &[f32], tension: f32) {
-    for (agent_name, props) in agents {
-        if let Some(field) = fields.get_mut(agent_name) {
-            if let Some(target) = field.get_mut("coherence_target").or_else(|| field.get_mut("physics_constant")) {
-                *target += tension * 0.01;
+/// `<sensor> <comparator> <threshold>`, the next three of `parts`.
+fn sensor_condition(parts: &mut Pairs<Rule>) -> Result<RuleCondition, WeaveError> {
+    let mut next = || parts.next().map(|part| part.as_str()).unwrap_or_default();
+    let sensor = next().to_owned();
+    let comparator = next().parse().map_err(WeaveError::Validation)?;
+    let threshold = parse_number(next());
+    Ok(RuleCondition::Sensor { sensor, comparator, threshold })
+}
+
+/// `<field>.<param> == <value>` or `!=`, the next four of `parts`.
+fn attribute_condition(parts: &mut Pairs<Rule>) -> Result<RuleCondition, WeaveError> {
+    let field = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let param = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let equal = parts.next().map(|part| part.as_str()) == Some("==");
+    let value = match parts.next().and_then(ParamValue::from_pair) {
+        Some(value) => value,
+        None => return Err(WeaveError::Validation(format!("{}.{} can only be compared with a bool, string or identifier", field, param))),
+    };
+    Ok(RuleCondition::Attribute { field, param, equal, value })
+}
+
+/// The `<field>.<param> = <value>` rest of a set rule or statement, taken
+/// under `condition`. The value must evaluate to a number when parsed.
+fn set_rule(program: &WeaveProgram, condition: RuleCondition, mut parts: Pairs<Rule>, constants: &Constants) -> Result<MetaweaveRule, WeaveError> {
+    let field = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let param = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let value = match parts.next() {
+        Some(expression) => {
+            let context = format!("the metaweave value for {}.{}", field, param);
+            match evaluate_param(expression, &Params::new(), program, constants, &context)? {
+                Evaluated::Number(value) => value,
+                Evaluated::Symbol(name) => {
+                    return Err(WeaveError::Validation(format!("{} uses {}, which is not a constant", context, name)));
+                }
+                Evaluated::Live(_, name) => return Err(unknown_name(&context, &name)),
             }
         }
+        None => 0.0,
+    };
+    Ok(MetaweaveRule { condition, field, param, value })
+}
+
+/// The `<node>.<method>(<args>)` rest of a call rule or statement.
+fn lab_call(mut parts: Pairs<Rule>) -> LabCall {
+    let node = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let method = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let args = parts.map(|arg| parse_number(arg.as_str())).collect();
+    LabCall { node, method, args }
+}
+
+fn script_agents(agents: IndexMap<String, String>) -> IndexMap<String, RegisteredAgent> {
+    agents.into_iter().map(|(name, template)| (name, RegisteredAgent { template, from_script: true })).collect()
+}
+
+/// The condition of a `sensor_test` or `attribute_test`.
+fn test_condition(test: Pair<Rule>) -> Result<RuleCondition, WeaveError> {
+    match test.as_rule() {
+        Rule::attribute_test => attribute_condition(&mut test.into_inner()),
+        _ => sensor_condition(&mut test.into_inner()),
+    }
+}
+
+fn loop_statement(statement: Pair<Rule>, functions: &Functions) -> Result<LoopStatement, WeaveError> {
+    if statement.as_rule() == Rule::loop_pass {
+        return Ok(match statement.into_inner().next().map(|kind| kind.as_str()) {
+            Some("drift") => LoopStatement::Drift,
+            Some("resolve") => LoopStatement::Resolve,
+            _ => LoopStatement::Tension,
+        });
+    }
+    if statement.as_rule() == Rule::send_statement {
+        let (target, topic, value) = send_statement(statement, functions)?;
+        return Ok(LoopStatement::Send { target, topic, value });
+    }
+    if statement.as_rule() == Rule::emit_statement {
+        let (signal, value) = emit_statement(statement, functions)?;
+        return Ok(LoopStatement::Emit { signal, value });
+    }
+    let mut parts = statement.into_inner();
+    let field = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let param = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let value = match parts.next() {
+        Some(expression) => Expr::from_pair(expression, functions)?,
+        None => Expr::Number(0.0),
+    };
+    Ok(LoopStatement::Set { field, param, value })
+}
+
+fn event_action(action: Pair<Rule>, functions: &Functions) -> Result<EventAction, WeaveError> {
+    match action.as_rule() {
+        Rule::halt_statement => Ok(EventAction::Halt),
+        Rule::call_statement => Ok(EventAction::Call(lab_call(action.into_inner()))),
+        Rule::send_statement => {
+            let (target, topic, value) = send_statement(action, functions)?;
+            Ok(EventAction::Send { target, topic, value })
+        }
+        Rule::emit_statement => {
+            let (signal, value) = emit_statement(action, functions)?;
+            Ok(EventAction::Emit { signal, value })
+        }
+        _ => {
+            let mut parts = action.into_inner();
+            let field = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+            let param = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+            let value = match parts.next() {
+                Some(expression) => Expr::from_pair(expression, functions)?,
+                None => Expr::Number(0.0),
+            };
+            Ok(EventAction::Set { field, param, value })
+        }
+    }
+}
+
+fn behavior_node(node: Pair<Rule>, functions: &Functions) -> Result<BehaviorNode, WeaveError> {
+    match node.as_rule() {
+        Rule::behavior_selector => Ok(BehaviorNode::Selector(node.into_inner().map(|child| behavior_node(child, functions)).collect::<Result<_, _>>()?)),
+        Rule::behavior_sequence => Ok(BehaviorNode::Sequence(node.into_inner().map(|child| behavior_node(child, functions)).collect::<Result<_, _>>()?)),
+        Rule::behavior_check => match node.into_inner().next() {
+            Some(test) => Ok(BehaviorNode::Check(test_condition(test)?)),
+            None => Err(WeaveError::Validation("check needs a test".to_string())),
+        },
+        Rule::behavior_action => Ok(BehaviorNode::Action(node.into_inner().next().map(|name| name.as_str()).unwrap_or_default().to_owned())),
+        _ => {
+            let mut parts = node.into_inner();
+            let field = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+            let param = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+            let value = match parts.next() {
+                Some(expression) => Expr::from_pair(expression, functions)?,
+                None => Expr::Number(0.0),
+            };
+            Ok(BehaviorNode::Set { field, param, value })
+        }
+    }
+}
+
+/// The target, topic and value of a `send(...)` statement.
+fn send_statement(statement: Pair<Rule>, functions: &Functions) -> Result<(String, String, Expr), WeaveError> {
+    let mut parts = statement.into_inner();
+    let target = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let topic = parts.next().map(|part| part.as_str()).unwrap_or_default().to_owned();
+    let value = match parts.next() {
+        Some(expression) => Expr::from_pair(expression, functions)?,
+        None => Expr::Number(0.0),
+    };
+    Ok((target, topic, value))
+}
+
+/// The signal and value of an `emit` statement. The signal name must be an
+/// identifier, as Godot signal names are.
+fn emit_statement(statement: Pair<Rule>, functions: &Functions) -> Result<(String, Expr), WeaveError> {
+    let mut parts = statement.into_inner();
+    let signal = parts.next().and_then(|string| string.into_inner().next()).map(|inner| inner.as_str()).unwrap_or_default().to_owned();
+    let identifier = signal.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && signal.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !identifier {
+        return Err(WeaveError::Validation(format!("emit needs a signal name such as \"door_opened\", got \"{}\"", signal)));
     }
+    let value = match parts.next() {
+        Some(expression) => Expr::from_pair(expression, functions)?,
+        None => Expr::Number(0.0),
+    };
+    Ok((signal, value))
 }
 
-pub fn execute_resolve(fields: &mut HashMap<String, HashMap<String, f32>>, agents: &HashMap<String, HashMap<String, f32>>, tension: f32) {
-    for (agent_name, props) in agents {
-        if let Some(field) = fields.get_mut(agent_name) {
-            if let Some(target) = field.get_mut("coherence_target").or_else(|| field.get_mut("physics_constant")) {
-                *target -= tension * 0.005;
+/// Turns an `if` block into the metaweave and call rules it stands for: the
+/// statements of the first branch run under its test, those of the `else`
+/// branch under the negated test. A test on a missing sensor runs neither.
+fn add_if_block(program: &mut WeaveProgram, pair: Pair<Rule>, constants: &Constants) -> Result<(), WeaveError> {
+    let mut parts = pair.into_inner();
+    let Some(test) = parts.next() else {
+        return Ok(());
+    };
+    let condition = test_condition(test)?;
+    let negated = condition.negated();
+    for (branch, condition) in parts.zip([condition, negated]) {
+        for statement in branch.into_inner() {
+            if statement.as_rule() == Rule::call_statement {
+                program.call_rules.push(CallRule { condition: condition.clone(), call: lab_call(statement.into_inner()) });
+            } else {
+                let rule = set_rule(program, condition.clone(), statement.into_inner(), constants)?;
+                program.metaweave_rules.push(rule);
             }
         }
     }
+    Ok(())
+}
+
+/// Splits a binding source like `/root/Lab/Accelerator:field_strength` into
+/// its node path and property, which may itself be indexed (`position:x`).
+pub fn binding_target(source: &str) -> Option<(&str, &str)> {
+    source.split_once(':').filter(|(path, property)| !path.is_empty() && !property.is_empty())
+}
+
+enum Evaluated {
+    Number(Scalar),
+    Symbol(String),
+    /// Reads the named value, which isn't known yet; see [`check_derived_params`].
+    Live(Expr, String),
+}
+
+/// A field param expression as a number, or a symbol for a lone identifier
+/// that names neither an earlier param of the field nor a constant. Any other
+/// unknown name is a validation error rather than a silent zero. `field.param`
+/// reads a field declared earlier in the same file.
+/// Evaluates against `params` of the field being parsed and the fields and
+/// functions `program` declared so far.
+fn evaluate_param(expression: Pair<Rule>, params: &Params, program: &WeaveProgram, constants: &Constants, context: &str) -> Result<Evaluated, WeaveError> {
+    let fields = &program.fields;
+    // Plain numbers are parsed straight to a Scalar so they keep their exact bits.
+    if let Ok(number) = expression.as_str().trim().parse::<Scalar>() {
+        return Ok(Evaluated::Number(number));
+    }
+    let lookup = |name: &str| params.get(name).map(|value| to_f64(*value)).or_else(|| constants.get(name).map(|constant| constant.value));
+    let field_param = |field: &str, param: &str| fields.get(field).and_then(|params| params.get(param)).map(|value| to_f64(*value));
+    let expr = Expr::from_pair(expression, &program.functions)?;
+    match &expr {
+        Expr::Name(name) if lookup(name).is_none() => return Ok(Evaluated::Symbol(name.clone())),
+        _ => {}
+    }
+    match expr.eval(&lookup, &field_param) {
+        Ok(value) => Ok(Evaluated::Number(value as Scalar)),
+        Err(EvalError::DivisionByZero) => Err(WeaveError::Validation(format!("{} divides by zero", context))),
+        Err(EvalError::NegativeSqrt(value)) => Err(WeaveError::Validation(format!("{} takes the square root of {}, which is negative", context, value))),
+        Err(EvalError::Unknown(name)) => Ok(Evaluated::Live(expr, name)),
+    }
+}
+
+fn unknown_name(context: &str, name: &str) -> WeaveError {
+    WeaveError::Validation(format!("{} uses {}, which is neither an earlier param of the field nor a constant", context, name))
+}
+
+/// Fails unless every name a derived param reads is a param of its own
+/// field, a constant, `tension`, a sensor `is_sensor` accepts or a
+/// `field.param` of `fields`.
+fn check_derived_params(derived_params: &[DerivedParam], fields: &Fields, constants: &Constants, is_sensor: impl Fn(&str) -> bool) -> Result<(), WeaveError> {
+    for derived in derived_params {
+        let own = &fields[&derived.field];
+        let mut operands = Vec::new();
+        derived.expr.collect_operands(&mut operands);
+        let unknown = operands.into_iter().find(|operand| match operand.split_once('.') {
+            Some((field, param)) => field != GLOBAL_FIELD && !fields.get(field).is_some_and(|params| params.contains_key(param)),
+            None => !own.contains_key(operand) && constants.get(operand).is_none() && operand != "tension" && !is_sensor(operand),
+        });
+        if let Some(name) = unknown {
+            return Err(WeaveError::Validation(format!(
+                "{}.{} uses {}, which is neither a param, a constant nor a declared sensor",
+                derived.field, derived.param, name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn parse_number(text: &str) -> Scalar {
+    text.parse::<Scalar>().unwrap_or(0.0)
+}
+
+/// A watched param modified by an execute phase. `old_value` is 0.0 when
+/// the phase created the param.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub agent: String,
+    pub param: String,
+    pub old_value: Scalar,
+    pub new_value: Scalar,
+}
+
+/// What the last `step` did, for debug overlays and performance tracking.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameMetrics {
+    pub tick: usize,
+    pub tension: Scalar,
+    pub smoothed_tension: Scalar,
+    /// Agents with at least one param moved by the step.
+    pub agents_updated: usize,
+    pub fired_rules: Vec<MetaweaveRule>,
+    /// Indices of the safety rules the step tripped.
+    pub safety_trips: Vec<usize>,
+    /// Sum of the absolute param changes made by drift.
+    pub total_drift: Scalar,
+    pub load_status: &'static str,
+    /// Wall time of the whole step.
+    pub execute_micros: u64,
+}
+
+/// A drift, resolve or noise write to `field.param` that its declared
+/// `in [min, max]` range clamped; `value` is what it would have written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutOfBounds {
+    pub field: String,
+    pub param: String,
+    pub value: Scalar,
+    pub min: Scalar,
+    pub max: Scalar,
+    pub tick: usize,
+}
+
+/// One term of the last tension: how far `sensor` sat from `agent`'s target,
+/// scaled by `weight`. Tension is the sum of the contributions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensionTerm {
+    pub sensor: String,
+    pub agent: String,
+    pub target: Scalar,
+    pub sensed: Scalar,
+    pub weight: Scalar,
+    /// `weight * |sensed - target|`
+    pub contribution: Scalar,
+}
+
+/// How `execute_resolve` moves targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveMode {
+    /// Subtract `tension * resolve_rate` from each target.
+    Fixed,
+    /// Run a per-field PID controller on `sensor − target`.
+    Pid,
+}
+
+/// Runtime state of one weave program: the live fields, what the script
+/// declared, the tension history and the tuning knobs of each phase.
+///
+/// Both Godot bindings are thin adapters over this, so every stateful
+/// feature lives here once.
+#[derive(Clone)]
+pub struct Interpreter {
+    fields: Fields,
+    declared_fields: Fields,
+    attributes: HashMap<String, HashMap<String, ParamValue>>,
+    /// The attributes as the script declares them, which a reload compares against.
+    declared_attributes: HashMap<String, HashMap<String, ParamValue>>,
+    tension_history: TensionHistory,
+    /// Steps run since the last load or reset; see `tick`.
+    steps: usize,
+    config: InterpreterConfig,
+    tension_reference: String,
+    tension_threshold: Scalar,
+    smoothing_alpha: Scalar,
+    smoothed_tension: Option<Scalar>,
+    tension_alarm: TensionAlarm,
+    safety_metric: Scalar,
+    source_path: Option<PathBuf>,
+    /// The source is a compiled program, so reloads skip the parser.
+    source_compiled: bool,
+    /// The program last loaded or reloaded, which `reset` starts over from.
+    loaded_program: Option<WeaveProgram>,
+    reload_watch: Option<ReloadWatch>,
+    /// Shared so a cloned interpreter doesn't stop the server; a preview
+    /// drops its copy.
+    #[cfg(feature = "telemetry")]
+    telemetry: Option<Arc<TelemetryServer>>,
+    reload_interval: f64,
+    tick_changes: Fields,
+    last_tick_delta: Scalar,
+    convergence_epsilon: Scalar,
+    converged: bool,
+    last_sensors: HashMap<String, Scalar>,
+    seen_sensors: HashMap<String, Scalar>,
+    sensor_policy: SensorPolicy,
+    sensor_policies: HashMap<String, SensorPolicy>,
+    sensor_defaults: HashMap<String, Scalar>,
+    declared_sensors: IndexMap<String, Scalar>,
+    sensor_ranges: IndexMap<String, (Scalar, Scalar)>,
+    /// Sensors added at runtime with `add_sensor`, kept across reloads.
+    added_sensors: IndexMap<String, SensorSpec>,
+    sensor_issues: HashSet<(String, SensorIssue)>,
+    /// Declared sensors currently running on their default, warned about once.
+    defaulted_sensors: HashSet<String>,
+    missing_sensors: Vec<String>,
+    param_bounds: HashMap<String, HashMap<String, (Scalar, Scalar)>>,
+    /// Params a declared range is holding back, so each is queued once.
+    pinned_params: HashSet<(String, String)>,
+    out_of_bounds: Vec<OutOfBounds>,
+    warnings: Vec<String>,
+    resolve_mode: ResolveMode,
+    signed_correction: bool,
+    /// Agents no execute phase but `execute_code` may write, each mapped to
+    /// whether its lock survives a reload.
+    locked_agents: HashMap<String, bool>,
+    /// Points kinematic agents steer to instead of their `target_position`,
+    /// such as the next corner of a navigation path.
+    waypoints: HashMap<String, Vec<Scalar>>,
+    drift_targets: Vec<String>,
+    /// Agents drift and resolve skipped for having no drift target, warned about once.
+    undriven_agents: HashSet<String>,
+    pid_gains: HashMap<String, PidGains>,
+    pid_sensors: HashMap<String, String>,
+    pid_states: HashMap<String, PidState>,
+    pid_integral_limit: Scalar,
+    /// Per-agent drift state of the PID and smoothing strategies.
+    drift_pid_states: HashMap<String, PidState>,
+    drift_smoothed: HashMap<String, Scalar>,
+    watches: HashMap<String, HashSet<String>>,
+    watch_epsilon: Scalar,
+    field_changes: Vec<FieldChange>,
+    script_rules: Vec<MetaweaveRule>,
+    runtime_rules: Vec<MetaweaveRule>,
+    call_rules: Vec<CallRule>,
+    lab_calls: Vec<LabCall>,
+    safety_rules: Vec<SafetyRule>,
+    safety_streaks: Vec<u32>,
+    safety_events: Vec<SafetyEvent>,
+    last_safety_event: Option<SafetyEvent>,
+    /// Every trip, kept until cleared, unlike `safety_events`, which the
+    /// bindings drain each step.
+    safety_log: SafetyLog,
+    recording: Option<Recording>,
+    frame_metrics: FrameMetrics,
+    metrics: ExperimentMetrics,
+    /// Shared like `telemetry`; a preview drops its copy.
+    metrics_server: Option<Arc<MetricsServer>>,
+    /// Shared like `telemetry`; a preview drops its copy.
+    data_log: Option<Arc<Mutex<DataLog>>>,
+    profiler: Profiler,
+    load_status: &'static str,
+    script_version: u32,
+    replay: Option<Replay>,
+    sensor_recording: Option<SensorStream>,
+    sensor_replay: Option<SensorReplay>,
+    time_slice: TimeSlice,
+    script_bindings: IndexMap<String, String>,
+    derived_params: Vec<DerivedParam>,
+    functions: Functions,
+    templates: IndexMap<String, Template>,
+    loops: Vec<Loop>,
+    loop_limit: u32,
+    /// Loops that hit `loop_limit` since the last load, warned about once.
+    capped_loops: HashSet<usize>,
+    handlers: Vec<EventHandler>,
+    /// Handlers whose test held on the last step, so they don't fire again
+    /// until it has been false.
+    armed_handlers: HashSet<usize>,
+    fired_events: Vec<FiredEvent>,
+    emitted_signals: Vec<EmittedSignal>,
+    behaviors: Vec<BehaviorTree>,
+    /// What each agent's tree reported on its last tick.
+    behavior_status: IndexMap<String, BehaviorStatus>,
+    /// Host actions a tree asked for that weren't registered, warned about once.
+    missing_actions: HashSet<String>,
+    messages: MessageBus,
+    agents: IndexMap<String, RegisteredAgent>,
+    script_tension: WeightedTension,
+    /// Installed from Rust; wins over `script_tension`.
+    tension_model: Option<Arc<dyn TensionModel>>,
+    runtime_bindings: IndexMap<String, String>,
+    sync: SyncState,
+    constants: Constants,
+    goals: Vec<Goal>,
+    goal_progress: HashMap<String, GoalProgress>,
+    completed_goals: Vec<String>,
+    tension_terms: Vec<TensionTerm>,
+    invariants: Vec<Invariant>,
+    /// Kept in step with `invariants`.
+    invariant_status: Vec<InvariantStatus>,
+    invariant_policy: InvariantPolicy,
+    invariant_violations: Vec<InvariantViolation>,
+    last_invariant_violation: Option<InvariantViolation>,
+    /// The invariant that stopped stepping under `InvariantPolicy::Halt`.
+    halted_by: Option<String>,
+    /// Set by `emergency_stop`; kept across loads until `resume`.
+    emergency_stopped: bool,
+    /// The state the current step started from, under `InvariantPolicy::Rollback`.
+    checkpoint: Option<Checkpoint>,
+    schedule: Schedule,
+    /// The `global` params of a parent, while this program runs as its child.
+    globals: Option<Params>,
+    /// Global params a write was rejected for, warned about once.
+    rejected_globals: HashSet<String>,
+    noise: Noise,
+    trace: Trace,
+    log: Log,
+}
+
+/// Round-robin cursor for time-sliced `step`s. Each agent remembers the
+/// step that last moved it, so a batch can cover the ticks it sat out; an
+/// agent not seen yet counts from the start of the current sweep.
+#[derive(Debug, Clone, Default)]
+struct TimeSlice {
+    agents_per_tick: usize,
+    budget_micros: u64,
+    cursor: usize,
+    roster_len: usize,
+    tick: usize,
+    origin: usize,
+    last_stepped: HashMap<String, usize>,
+}
+
+impl TimeSlice {
+    fn enabled(&self) -> bool {
+        self.agents_per_tick > 0 || self.budget_micros > 0
+    }
+
+    /// Starts a fresh sweep in which every agent counts as up to date.
+    fn restart(&mut self) {
+        self.cursor = 0;
+        self.origin = self.tick;
+        self.last_stepped.clear();
+    }
+}
+
+/// What a rollback restores. The tension history is cut back to `tensions`
+/// samples rather than copied on every step, and the notifications the step queued
+/// are dropped by cutting their queues back to the lengths they had.
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    state: ExperimentState,
+    tick: usize,
+    tensions: usize,
+    field_changes: usize,
+    lab_calls: usize,
+    emitted_signals: usize,
+    messages: usize,
+}
+
+#[derive(Clone)]
+struct Replay {
+    recording: Recording,
+    cursor: usize,
+    first_divergent_tick: Option<usize>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter {
+            fields: Fields::new(),
+            declared_fields: Fields::new(),
+            attributes: HashMap::new(),
+            declared_attributes: HashMap::new(),
+            tension_history: TensionHistory::new(DEFAULT_HISTORY_LIMIT),
+            steps: 0,
+            config: InterpreterConfig::default(),
+            tension_reference: "generalist".to_string(),
+            tension_threshold: 0.0,
+            smoothing_alpha: 1.0,
+            smoothed_tension: None,
+            tension_alarm: TensionAlarm::default(),
+            safety_metric: 1.0,
+            source_path: None,
+            source_compiled: false,
+            loaded_program: None,
+            reload_watch: None,
+            #[cfg(feature = "telemetry")]
+            telemetry: None,
+            reload_interval: 1.0,
+            tick_changes: Fields::new(),
+            last_tick_delta: 0.0,
+            convergence_epsilon: 1e-4,
+            converged: false,
+            last_sensors: HashMap::new(),
+            seen_sensors: HashMap::new(),
+            sensor_policy: SensorPolicy::Default,
+            sensor_policies: HashMap::new(),
+            sensor_defaults: HashMap::new(),
+            declared_sensors: IndexMap::new(),
+            sensor_ranges: IndexMap::new(),
+            added_sensors: IndexMap::new(),
+            sensor_issues: HashSet::new(),
+            defaulted_sensors: HashSet::new(),
+            missing_sensors: Vec::new(),
+            param_bounds: HashMap::new(),
+            pinned_params: HashSet::new(),
+            out_of_bounds: Vec::new(),
+            warnings: Vec::new(),
+            resolve_mode: ResolveMode::Fixed,
+            signed_correction: true,
+            locked_agents: HashMap::new(),
+            waypoints: HashMap::new(),
+            drift_targets: DEFAULT_DRIFT_TARGETS.iter().map(|param| param.to_string()).collect(),
+            undriven_agents: HashSet::new(),
+            pid_gains: HashMap::new(),
+            pid_sensors: HashMap::new(),
+            pid_states: HashMap::new(),
+            pid_integral_limit: 10.0,
+            drift_pid_states: HashMap::new(),
+            drift_smoothed: HashMap::new(),
+            watches: HashMap::new(),
+            watch_epsilon: 1e-6,
+            field_changes: Vec::new(),
+            script_rules: Vec::new(),
+            runtime_rules: Vec::new(),
+            call_rules: Vec::new(),
+            lab_calls: Vec::new(),
+            safety_rules: Vec::new(),
+            safety_streaks: Vec::new(),
+            safety_events: Vec::new(),
+            last_safety_event: None,
+            safety_log: SafetyLog::default(),
+            recording: None,
+            frame_metrics: FrameMetrics::default(),
+            metrics: ExperimentMetrics::default(),
+            metrics_server: None,
+            data_log: None,
+            profiler: Profiler::default(),
+            load_status: "unloaded",
+            script_version: 0,
+            replay: None,
+            sensor_recording: None,
+            sensor_replay: None,
+            time_slice: TimeSlice::default(),
+            script_bindings: IndexMap::new(),
+            derived_params: Vec::new(),
+            functions: Functions::new(),
+            templates: IndexMap::new(),
+            loops: Vec::new(),
+            loop_limit: DEFAULT_LOOP_LIMIT,
+            capped_loops: HashSet::new(),
+            handlers: Vec::new(),
+            armed_handlers: HashSet::new(),
+            fired_events: Vec::new(),
+            emitted_signals: Vec::new(),
+            behaviors: Vec::new(),
+            behavior_status: IndexMap::new(),
+            missing_actions: HashSet::new(),
+            messages: MessageBus::default(),
+            agents: IndexMap::new(),
+            script_tension: WeightedTension::default(),
+            tension_model: None,
+            runtime_bindings: IndexMap::new(),
+            sync: SyncState::default(),
+            constants: Constants::default(),
+            goals: Vec::new(),
+            goal_progress: HashMap::new(),
+            completed_goals: Vec::new(),
+            tension_terms: Vec::new(),
+            invariants: Vec::new(),
+            invariant_status: Vec::new(),
+            invariant_policy: InvariantPolicy::Warn,
+            invariant_violations: Vec::new(),
+            last_invariant_violation: None,
+            halted_by: None,
+            emergency_stopped: false,
+            checkpoint: None,
+            schedule: Schedule::default(),
+            globals: None,
+            rejected_globals: HashSet::new(),
+            noise: Noise::default(),
+            log: Log::default(),
+            trace: Trace::default(),
+        }
+    }
 }
 
-pub fn execute_metaweave(fields: &mut HashMap<String, HashMap<String, f32>>, sensors: &HashMap<String, f32>) {
-    if sensors.get("gravity_sensor").unwrap_or(&0.0) > 0.0 {
-        fields.get_mut("quantum_expert").unwrap().insert("gravity".to_string(), 9.81);
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<(), WeaveError> {
+        let started = self.profiler.start();
+        let program = parse_weave_with(path, &self.constants);
+        self.profiler.stop(ProfilePhase::Parse, started);
+        self.count_parse(&program);
+        let program = program.inspect_err(|_| self.load_status = "load_failed")?;
+        self.apply_parsed(path, program);
+        Ok(())
+    }
+
+    /// Loads a program written by `compiled::compile`, without the parser.
+    /// Hot reload reads the compiled file again.
+    pub fn load_compiled(&mut self, path: &Path) -> Result<(), WeaveError> {
+        let program = compiled::load(path).inspect_err(|_| self.load_status = "load_failed")?;
+        self.apply_parsed(path, program);
+        self.source_compiled = true;
+        Ok(())
+    }
+
+    /// Reads the loaded source again, compiled or not.
+    fn read_source(&mut self, path: &Path) -> Result<WeaveProgram, WeaveError> {
+        if self.source_compiled {
+            return compiled::load(path);
+        }
+        let started = self.profiler.start();
+        let program = parse_weave_with(path, &self.constants);
+        self.profiler.stop(ProfilePhase::Parse, started);
+        self.count_parse(&program);
+        program
+    }
+
+    /// Counts a failed parse towards `metrics`.
+    fn count_parse<T>(&mut self, parsed: &Result<T, WeaveError>) {
+        if parsed.is_err() {
+            self.metrics.parse_errors += 1;
+            self.publish_metrics();
+        }
+    }
+
+    /// Parses `code` as a whole weave file and loads it, as `load` does a
+    /// file. With no file behind it, hot reload has nothing to read until the
+    /// next `load`; `reset` still starts over from `code`.
+    pub fn load_str(&mut self, code: &str) -> Result<(), WeaveError> {
+        let started = self.profiler.start();
+        let program = parse_weave_str_with(code, &self.constants);
+        self.profiler.stop(ProfilePhase::Parse, started);
+        self.count_parse(&program);
+        let program = program.inspect_err(|_| self.load_status = "load_failed")?;
+        self.reload_watch = None;
+        self.source_path = None;
+        self.source_compiled = false;
+        self.install(program);
+        Ok(())
     }
-    godot_print!("Metaweave executed");
-}
\ No newline at end of file
+
+    /// Installs a program parsed from `path`, e.g. by a background loader.
+    pub fn apply_parsed(&mut self, path: &Path, program: WeaveProgram) {
+        if self.reload_watch.as_ref().map(|watch| watch.path() != path).unwrap_or(false) {
+            self.reload_watch = Some(ReloadWatch::new(path, self.reload_interval));
+        }
+        if let Some(watch) = self.reload_watch.as_mut() {
+            watch.mark_loaded();
+        }
+        self.source_path = Some(path.to_path_buf());
+        self.source_compiled = false;
+        self.install(program);
+    }
+
+    fn install(&mut self, program: WeaveProgram) {
+        self.loaded_program = Some(program.clone());
+        self.steps = 0;
+        self.script_version = program.version;
+        self.declared_fields = program.fields.clone();
+        self.fields = program.fields;
+        self.declared_attributes = program.attributes.clone();
+        self.attributes = program.attributes;
+        self.script_rules = program.metaweave_rules;
+        self.call_rules = program.call_rules;
+        self.script_bindings = program.sensor_bindings;
+        self.declared_sensors = program.declared_sensors;
+        self.sensor_ranges = program.sensor_ranges;
+        self.sensor_issues.clear();
+        self.defaulted_sensors.clear();
+        self.undriven_agents.clear();
+        self.set_goals(program.goals, false);
+        self.invariant_status = vec![InvariantStatus::default(); program.invariants.len()];
+        self.invariants = program.invariants;
+        self.halted_by = None;
+        self.set_safety_rules(program.safety_rules);
+        self.pid_states.clear();
+        self.clear_drift_states();
+        self.locked_agents.retain(|_, persistent| *persistent);
+        self.waypoints.clear();
+        self.load_status = "loaded";
+        self.schedule.replace_script_sets(program.scheduled_sets, false);
+        self.derived_params = program.derived_params;
+        self.functions = program.functions;
+        self.templates = program.templates;
+        self.param_bounds = program.param_bounds;
+        self.pinned_params.clear();
+        self.loops = program.loops;
+        self.capped_loops.clear();
+        self.handlers = program.handlers;
+        self.armed_handlers.clear();
+        self.fired_events.clear();
+        self.messages.clear();
+        self.behaviors = program.behaviors;
+        self.behavior_status.clear();
+        self.missing_actions.clear();
+        self.agents = script_agents(program.agents);
+        self.script_tension = program.tension;
+        self.derive_params(&self.last_sensors.clone());
+        self.run_schedule();
+    }
+
+    /// Starts the experiment over from the program last loaded or reloaded,
+    /// not the file as it is now: fields, attributes, tension history, every
+    /// scheduled set, goals, the trace and every lock, persistent or not, go
+    /// back to how a first load leaves them, and the drift noise restarts from
+    /// its seed. Settings, runtime rules and runtime bindings stay.
+    pub fn reset(&mut self) -> Result<(), WeaveError> {
+        let program = self.loaded_program.clone().ok_or_else(|| WeaveError::Validation("no weave program is loaded to reset".to_string()))?;
+        self.tension_history.clear();
+        self.smoothed_tension = None;
+        self.tension_alarm.reset();
+        self.safety_metric = 1.0;
+        self.tick_changes.clear();
+        self.last_tick_delta = 0.0;
+        self.converged = false;
+        self.last_sensors.clear();
+        self.seen_sensors.clear();
+        self.missing_sensors.clear();
+        self.field_changes.clear();
+        self.lab_calls.clear();
+        self.emitted_signals.clear();
+        self.safety_events.clear();
+        self.last_safety_event = None;
+        self.safety_log.clear();
+        self.replay = None;
+        self.time_slice = TimeSlice { agents_per_tick: self.time_slice.agents_per_tick, budget_micros: self.time_slice.budget_micros, ..TimeSlice::default() };
+        self.frame_metrics = FrameMetrics::default();
+        self.rejected_globals.clear();
+        self.schedule = Schedule::default();
+        self.locked_agents.clear();
+        self.waypoints.clear();
+        self.invariant_violations.clear();
+        self.last_invariant_violation = None;
+        self.trace.clear();
+        let seed = self.noise.seed();
+        self.noise.reseed(seed);
+        self.install(program);
+        Ok(())
+    }
+
+    /// Merges a re-parsed program into the live fields and attributes, see
+    /// [`merge_reload`] and [`merge_attributes`]; the tension history and tick
+    /// carry on. Script metaweave rules are replaced; rules added at runtime are kept.
+    pub fn apply_reload(&mut self, reloaded: WeaveProgram) -> FieldChanges {
+        self.loaded_program = Some(reloaded.clone());
+        self.script_version = reloaded.version;
+        // Spawned agents aren't in the script, so they keep their state
+        // unless the script now declares a field of the same name.
+        let mut spawned = std::mem::take(&mut self.agents);
+        spawned.retain(|name, agent| !agent.from_script && !reloaded.fields.contains_key(name));
+        let spawned_attributes: Vec<_> = spawned.keys().filter_map(|name| self.attributes.remove(name).map(|attributes| (name.clone(), attributes))).collect();
+        let spawned_derived: Vec<DerivedParam> = self.derived_params.drain(..).filter(|derived| spawned.contains_key(&derived.field)).collect();
+        let mut changes = merge_reload(&mut self.fields, &self.declared_fields, &reloaded.fields);
+        changes.merge(merge_attributes(&mut self.attributes, &self.declared_attributes, &reloaded.attributes));
+        self.declared_fields = reloaded.fields;
+        self.declared_attributes = reloaded.attributes;
+        self.attributes.extend(spawned_attributes);
+        self.script_rules = reloaded.metaweave_rules;
+        self.call_rules = reloaded.call_rules;
+        self.script_bindings = reloaded.sensor_bindings;
+        self.declared_sensors = reloaded.declared_sensors;
+        self.sensor_ranges = reloaded.sensor_ranges;
+        self.sensor_issues.clear();
+        self.defaulted_sensors.retain(|sensor| self.declared_sensors.contains_key(sensor));
+        self.undriven_agents.clear();
+        self.set_goals(reloaded.goals, true);
+        self.set_invariants(reloaded.invariants);
+        if reloaded.safety_rules != self.safety_rules {
+            self.set_safety_rules(reloaded.safety_rules);
+        }
+        self.pid_states.clear();
+        self.clear_drift_states();
+        self.locked_agents.retain(|_, persistent| *persistent);
+        self.waypoints.retain(|agent, _| self.fields.contains_key(agent));
+        self.schedule.replace_script_sets(reloaded.scheduled_sets, true);
+        self.derived_params = reloaded.derived_params;
+        self.functions = reloaded.functions;
+        self.templates = reloaded.templates;
+        self.param_bounds = reloaded.param_bounds;
+        self.derived_params.extend(spawned_derived);
+        self.loops = reloaded.loops;
+        self.capped_loops.clear();
+        if reloaded.handlers != self.handlers {
+            self.handlers = reloaded.handlers;
+            self.armed_handlers.clear();
+        }
+        self.agents = script_agents(reloaded.agents);
+        self.agents.extend(spawned);
+        self.script_tension = reloaded.tension;
+        self.derive_params(&self.last_sensors.clone());
+        self.run_schedule();
+        changes
+    }
+
+    /// Language version of the loaded script; 0 before anything is loaded.
+    pub fn script_version(&self) -> u32 {
+        self.script_version
+    }
+
+    pub fn source_path(&self) -> Option<&Path> {
+        self.source_path.as_deref()
+    }
+
+    /// Returns false if watching was requested before anything was loaded.
+    pub fn set_auto_reload(&mut self, enabled: bool) -> bool {
+        self.reload_watch = match (enabled, &self.source_path) {
+            (true, Some(path)) => Some(ReloadWatch::new(path, self.reload_interval)),
+            (true, None) => return false,
+            (false, _) => None,
+        };
+        true
+    }
+
+    pub fn auto_reload(&self) -> bool {
+        self.reload_watch.is_some()
+    }
+
+    pub fn set_reload_interval(&mut self, seconds: f64) {
+        self.reload_interval = seconds.max(0.0);
+        if let Some(watch) = self.reload_watch.as_mut() {
+            watch.set_interval(self.reload_interval);
+        }
+    }
+
+    /// Advances the auto-reload timer. Returns `None` when nothing was saved,
+    /// otherwise the outcome of re-parsing; on error the previous state is kept.
+    pub fn poll_reload(&mut self, delta: f64) -> Option<Result<FieldChanges, WeaveError>> {
+        let watch = self.reload_watch.as_mut()?;
+        if !watch.poll(delta) {
+            return None;
+        }
+        Some(self.reload())
+    }
+
+    /// Hot-reloads the loaded script now, as a detected save would: the
+    /// program is read and parsed once more and steps keep running the parsed
+    /// form. On a parse error the previous program stays.
+    pub fn reload(&mut self) -> Result<FieldChanges, WeaveError> {
+        let path = self.source_path.clone().ok_or_else(|| WeaveError::Validation("no weave file is loaded to reload".to_string()))?;
+        let result = self.read_source(&path).map(|reloaded| self.apply_reload(reloaded));
+        self.load_status = if result.is_ok() { "reloaded" } else { "reload_failed" };
+        result
+    }
+
+    /// `unloaded`, `loaded`, `load_failed`, `reloaded` or `reload_failed`,
+    /// after the most recent load or hot reload.
+    pub fn load_status(&self) -> &'static str {
+        self.load_status
+    }
+
+    pub fn fields(&self) -> &Fields {
+        &self.fields
+    }
+
+    pub fn attributes(&self) -> &HashMap<String, HashMap<String, ParamValue>> {
+        &self.attributes
+    }
+
+    pub fn attribute(&self, field: &str, param: &str) -> Option<&ParamValue> {
+        self.attributes.get(field)?.get(param)
+    }
+
+    /// Every number-list param as `(field.param, components)`, sorted by key.
+    /// The components are copies.
+    pub fn vector_model(&self) -> Vec<(String, Vec<Scalar>)> {
+        let mut vectors: Vec<(String, Vec<Scalar>)> = self
+            .attributes
+            .iter()
+            .flat_map(|(field, params)| {
+                params.iter().filter_map(move |(param, value)| match value {
+                    ParamValue::Vector(components) => Some((format!("{}.{}", field, param), components.clone())),
+                    _ => None,
+                })
+            })
+            .collect();
+        vectors.sort_by(|a, b| a.0.cmp(&b.0));
+        vectors
+    }
+
+    /// Sets the vector `key`, written `field.param`, replacing any earlier
+    /// components. The field must be declared, and the param must not already
+    /// hold a number or a non-vector value.
+    pub fn set_vector(&mut self, key: &str, components: Vec<Scalar>) -> Result<(), WeaveError> {
+        let (field, param) = key
+            .split_once('.')
+            .filter(|(field, param)| !field.is_empty() && !param.is_empty())
+            .ok_or_else(|| WeaveError::Validation(format!("vector key {:?} must be written field.param", key)))?;
+        if !self.fields.contains_key(field) {
+            return Err(WeaveError::MissingField(field.to_string()));
+        }
+        if self.fields[field].contains_key(param) {
+            return Err(WeaveError::Validation(format!("{} is a number, not a vector", key)));
+        }
+        if components.is_empty() {
+            return Err(WeaveError::Validation(format!("vector {} needs at least one component", key)));
+        }
+        let attributes = self.attributes.entry(field.to_string()).or_default();
+        if let Some(value) = attributes.get(param).filter(|value| !matches!(value, ParamValue::Vector(_))) {
+            return Err(WeaveError::Validation(format!("{} holds {}, not a vector", key, value)));
+        }
+        attributes.insert(param.to_string(), ParamValue::Vector(components));
+        Ok(())
+    }
+
+    /// The identifiers and strings in `field`'s `tags: [...]` list.
+    pub fn tags(&self, field: &str) -> impl Iterator<Item = &str> {
+        let tags = match self.attribute(field, "tags") {
+            Some(ParamValue::List(tags)) => tags.as_slice(),
+            _ => &[],
+        };
+        tags.iter().filter_map(|tag| match tag {
+            ParamValue::Symbol(tag) | ParamValue::Text(tag) => Some(tag.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn has_tag(&self, field: &str, tag: &str) -> bool {
+        self.tags(field).any(|candidate| candidate == tag)
+    }
+
+    /// Declared fields tagged `tag`, sorted by name.
+    pub fn agents_with_tag(&self, tag: &str) -> Vec<String> {
+        let mut agents: Vec<String> = self.fields.keys().filter(|field| self.has_tag(field, tag)).cloned().collect();
+        agents.sort();
+        agents
+    }
+
+    /// `(field, scene path)` for every field declaring `spawn: true` and a
+    /// `scene` string, sorted by field name.
+    pub fn spawn_requests(&self) -> Vec<(String, String)> {
+        let mut requests: Vec<(String, String)> = self
+            .attributes
+            .iter()
+            .filter(|(_, params)| params.get("spawn") == Some(&ParamValue::Bool(true)))
+            .filter_map(|(field, params)| match params.get("scene") {
+                Some(ParamValue::Text(scene)) => Some((field.clone(), scene.clone())),
+                _ => None,
+            })
+            .collect();
+        requests.sort();
+        requests
+    }
+
+    /// The retained tension samples, oldest first; see `set_history_limit`.
+    pub fn tension_history(&self) -> &[Scalar] {
+        self.tension_history.samples()
+    }
+
+    /// Steps run since the program was loaded or reset, counted once the
+    /// step's tension is in; a halted step or a bare `execute_tension`
+    /// doesn't count. Scheduled sets fire against it.
+    pub fn tick(&self) -> usize {
+        self.steps
+    }
+
+    /// Keeps only the newest `limit` tension samples, 10 000 by default; 0
+    /// keeps them all. Ticks keep counting from the first sample either way.
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.config.history_limit = limit;
+        self.tension_history.set_capacity(limit);
+    }
+
+    pub fn history_limit(&self) -> usize {
+        self.config.history_limit
+    }
+
+    /// Statistics over the last `window` tension samples (0 = all).
+    pub fn history_stats(&self, window: usize) -> Option<HistoryStats> {
+        history::stats(history::window(self.tension_history.samples(), window))
+    }
+
+    /// `(tick, tension)` points for ticks `from..to`, reduced to at most
+    /// `max_points` by min/max bucketing.
+    /// Ticks dropped by the history limit are skipped.
+    pub fn history_curve(&self, from: usize, to: usize, max_points: usize) -> Vec<(usize, Scalar)> {
+        let dropped = self.tension_history.dropped();
+        let to = to.saturating_sub(dropped).min(self.tension_history.len());
+        let from = from.saturating_sub(dropped).min(to);
+        history::downsample(&self.tension_history.samples()[from..to], dropped + from, max_points)
+    }
+
+    /// Largest per-tick param change still considered settled by `is_converged`.
+    pub fn set_convergence_epsilon(&mut self, epsilon: Scalar) {
+        self.convergence_epsilon = epsilon.abs();
+    }
+
+    /// True once the last `window` tensions (0 = all) all sit below `threshold`
+    /// and no field param moved more than the convergence epsilon in a tick.
+    pub fn is_converged(&self, threshold: Scalar, window: usize) -> bool {
+        if window > self.tension_history.len() || self.tension_history.is_empty() {
+            return false;
+        }
+        let settled = history::window(self.tension_history.samples(), window)
+            .iter()
+            .all(|tension| tension.abs() < threshold);
+        settled && self.last_tick_delta.max(max_change(&self.tick_changes)) < self.convergence_epsilon
+    }
+
+    /// Like `is_converged`, but also remembers the result and returns true
+    /// only on the check where it flips from not converged to converged.
+    pub fn update_convergence(&mut self, threshold: Scalar, window: usize) -> bool {
+        let converged = self.is_converged(threshold, window);
+        let became_converged = converged && !self.converged;
+        self.converged = converged;
+        became_converged
+    }
+
+    /// The field whose `coherence_target` tension measures the `coherence`
+    /// sensor against; `generalist` by default.
+    pub fn set_tension_reference(&mut self, field: &str) {
+        self.tension_reference = field.to_string();
+    }
+
+    pub fn tension_reference(&self) -> &str {
+        &self.tension_reference
+    }
+
+    /// Tension below which drift and resolve leave every target alone, so
+    /// sensor noise around a settled swarm doesn't keep nudging it; 0 by default.
+    pub fn set_tension_threshold(&mut self, threshold: Scalar) {
+        self.tension_threshold = threshold.max(0.0);
+    }
+
+    pub fn tension_threshold(&self) -> Scalar {
+        self.tension_threshold
+    }
+
+    pub fn config(&self) -> &InterpreterConfig {
+        &self.config
+    }
+
+    /// Replaces the gains, history limit and target bounds at once; a
+    /// shorter history limit drops the oldest samples straight away.
+    pub fn set_config(&mut self, config: InterpreterConfig) {
+        if config.drift_strategy != self.config.drift_strategy {
+            self.clear_drift_states();
+        }
+        self.config = config;
+        self.tension_history.set_capacity(config.history_limit);
+    }
+
+    pub fn drift_strategy(&self) -> DriftStrategy {
+        self.config.drift_strategy
+    }
+
+    /// Switching strategy starts every agent's controller or average afresh.
+    pub fn set_drift_strategy(&mut self, strategy: DriftStrategy) {
+        self.set_config(InterpreterConfig { drift_strategy: strategy, ..self.config });
+    }
+
+    fn clear_drift_states(&mut self) {
+        self.drift_pid_states.clear();
+        self.drift_smoothed.clear();
+    }
+
+    pub fn drift_rate(&self) -> Scalar {
+        self.config.drift_rate
+    }
+
+    pub fn set_drift_rate(&mut self, rate: Scalar) {
+        self.config.drift_rate = rate;
+    }
+
+    pub fn resolve_rate(&self) -> Scalar {
+        self.config.resolve_rate
+    }
+
+    pub fn set_resolve_rate(&mut self, rate: Scalar) {
+        self.config.resolve_rate = rate;
+    }
+
+    /// Clamps the targets of fields without their own `target_min` and
+    /// `target_max` to `min..=max`; the bounds are swapped if reversed.
+    pub fn set_target_bounds(&mut self, min: Scalar, max: Scalar) {
+        self.config.clamp_targets = true;
+        self.config.target_min = min.min(max);
+        self.config.target_max = min.max(max);
+    }
+
+    /// Leaves targets unbounded again, except where a field declares bounds.
+    pub fn clear_target_bounds(&mut self) {
+        self.config.clamp_targets = false;
+    }
+
+    pub fn target_bounds(&self) -> Option<(Scalar, Scalar)> {
+        self.config.clamp_targets.then_some((self.config.target_min, self.config.target_max))
+    }
+
+    /// Weight of the newest sample in the smoothed tension; 1.0 disables smoothing.
+    pub fn set_smoothing_alpha(&mut self, alpha: Scalar) {
+        self.smoothing_alpha = alpha.clamp(0.0, 1.0);
+    }
+
+    pub fn smoothing_alpha(&self) -> Scalar {
+        self.smoothing_alpha
+    }
+
+    /// Reports a `TensionCrossing::High` once a computed tension rises above
+    /// `high`, then a `Resolved` once one falls to `resolved` or below.
+    pub fn set_tension_thresholds(&mut self, high: Scalar, resolved: Scalar) {
+        self.tension_alarm.set_thresholds(high, resolved);
+    }
+
+    pub fn clear_tension_thresholds(&mut self) {
+        self.tension_alarm.clear_thresholds();
+    }
+
+    /// `(high, resolved)`, or `None` when no thresholds are set.
+    pub fn tension_thresholds(&self) -> Option<(Scalar, Scalar)> {
+        self.tension_alarm.thresholds()
+    }
+
+    /// Whether tension crossed the high threshold and hasn't resolved since.
+    pub fn is_tension_high(&self) -> bool {
+        self.tension_alarm.raised()
+    }
+
+    /// Threshold crossings since the last take, oldest first.
+    pub fn take_tension_crossings(&mut self) -> Vec<TensionCrossing> {
+        self.tension_alarm.take_crossings()
+    }
+
+    pub fn smoothed_tension(&self) -> Scalar {
+        self.smoothed_tension.unwrap_or(0.0)
+    }
+
+    pub fn set_resolve_mode(&mut self, mode: ResolveMode) {
+        if mode != self.resolve_mode {
+            self.pid_states.clear();
+        }
+        self.resolve_mode = mode;
+    }
+
+    pub fn resolve_mode(&self) -> ResolveMode {
+        self.resolve_mode
+    }
+
+    /// Overrides any `kp`/`ki`/`kd` params the script declares for `agent`.
+    pub fn set_pid_gains(&mut self, agent: &str, gains: PidGains) {
+        self.pid_gains.insert(agent.to_string(), gains);
+    }
+
+    /// With signed correction on (the default), fixed-rate drift and resolve
+    /// move each target towards its sensor's value, see `adjust_targets`.
+    /// Off restores the old behaviour: drift always raises targets and
+    /// resolve always lowers them, whichever side of the sensor they are on.
+    pub fn set_signed_correction(&mut self, enabled: bool) {
+        self.signed_correction = enabled;
+    }
+
+    pub fn signed_correction(&self) -> bool {
+        self.signed_correction
+    }
+
+    /// Locks or unlocks `agent`. Drift, resolve, metaweave rules, derived
+    /// params, loops, `on` handlers and behavior trees leave a locked agent's
+    /// params alone; `execute_code` and scheduled sets still write them. Locks
+    /// are dropped on load and reload unless `persistent`.
+    pub fn set_agent_locked(&mut self, agent: &str, locked: bool, persistent: bool) {
+        if locked {
+            self.locked_agents.insert(agent.to_string(), persistent);
+        } else {
+            self.locked_agents.remove(agent);
+        }
+    }
+
+    pub fn is_agent_locked(&self, agent: &str) -> bool {
+        self.locked_agents.contains_key(agent)
+    }
+
+    /// Steers kinematic `agent` to `point` rather than its `target_position`
+    /// until the waypoint is cleared, so a path finder can lead it around
+    /// obstacles one corner at a time. A point of another length than the
+    /// agent's `position` is ignored. Load and reset clear every waypoint.
+    pub fn set_waypoint(&mut self, agent: &str, point: Vec<Scalar>) {
+        self.waypoints.insert(agent.to_string(), point);
+    }
+
+    /// Steers `agent` to its `target_position` again; false if it had no
+    /// waypoint.
+    pub fn clear_waypoint(&mut self, agent: &str) -> bool {
+        self.waypoints.remove(agent).is_some()
+    }
+
+    pub fn waypoint(&self, agent: &str) -> Option<&[Scalar]> {
+        self.waypoints.get(agent).map(Vec::as_slice)
+    }
+
+    /// Currently locked agents, sorted by name.
+    pub fn locked_agents(&self) -> Vec<&str> {
+        let mut agents: Vec<&str> = self.locked_agents.keys().map(String::as_str).collect();
+        agents.sort_unstable();
+        agents
+    }
+
+    /// Runs this program as a child reading a parent's `global` params, or
+    /// as a standalone program again with `None`. A child reads `global.*`
+    /// from `globals` instead of its own fields, and every write to them is
+    /// rejected with a warning.
+    pub fn set_globals(&mut self, globals: Option<Params>) {
+        if globals.is_none() {
+            self.rejected_globals.clear();
+        }
+        self.globals = globals;
+    }
+
+    pub fn globals(&self) -> Option<&Params> {
+        self.globals.as_ref()
+    }
+
+    /// The params this program shares as a parent: its own `global` field.
+    pub fn global_params(&self) -> Params {
+        self.fields.get(GLOBAL_FIELD).cloned().unwrap_or_default()
+    }
+
+    /// Whether `field` is a parent's `global` field this child may only read.
+    fn is_parent_global(&self, field: &str) -> bool {
+        field == GLOBAL_FIELD && self.globals.is_some()
+    }
+
+    /// A live param, with `global.*` read from the parent in a child.
+    fn param_value(&self, field: &str, param: &str) -> Option<Scalar> {
+        match &self.globals {
+            Some(globals) if field == GLOBAL_FIELD => globals.get(param).copied(),
+            _ => self.fields.get(field).and_then(|params| params.get(param)).copied(),
+        }
+    }
+
+    /// Sensor whose value `agent`'s target tracks in PID mode and under
+    /// signed correction; `coherence` by default.
+    pub fn set_pid_sensor(&mut self, agent: &str, sensor: &str) {
+        self.pid_sensors.insert(agent.to_string(), sensor.to_string());
+    }
+
+    /// Bound on each controller's integral; NaN and infinite limits are refused.
+    pub fn set_pid_integral_limit(&mut self, limit: Scalar) -> Result<(), WeaveError> {
+        if !limit.is_finite() {
+            return Err(WeaveError::Validation(format!("PID integral limit must be finite, got {}", limit)));
+        }
+        self.pid_integral_limit = limit.abs();
+        Ok(())
+    }
+
+    /// Policy for every sensor without a per-sensor override.
+    pub fn set_sensor_policy(&mut self, policy: SensorPolicy) {
+        self.sensor_policy = policy;
+    }
+
+    pub fn set_sensor_policy_for(&mut self, sensor: &str, policy: SensorPolicy) {
+        self.sensor_policies.insert(sensor.to_string(), policy);
+    }
+
+    /// Value used for `sensor` under the `Default` policy.
+    pub fn set_sensor_default(&mut self, sensor: &str, value: Scalar) {
+        self.sensor_defaults.insert(sensor.to_string(), value);
+    }
+
+    fn policy_for(&self, sensor: &str) -> SensorPolicy {
+        self.sensor_policies.get(sensor).copied().unwrap_or(self.sensor_policy)
+    }
+
+    /// Reads `sensor` from `sensors`, falling back to the default it was
+    /// added with, then to its default from the script's `sensors` block,
+    /// then to its policy.
+    fn resolve_sensor(&self, sensors: &HashMap<String, Scalar>, sensor: &str) -> Option<Scalar> {
+        let added = || self.added_sensors.get(sensor).and_then(|spec| spec.default);
+        if let Some(value) = sensors.get(sensor).copied().or_else(added).or_else(|| self.declared_sensors.get(sensor).copied()) {
+            return Some(value);
+        }
+        match self.policy_for(sensor) {
+            SensorPolicy::Strict => None,
+            SensorPolicy::Hold => self.seen_sensors.get(sensor).copied(),
+            SensorPolicy::Default => Some(self.sensor_defaults.get(sensor).copied().unwrap_or(0.0)),
+        }
+    }
+
+    /// Sensors the script's `sensors` block declares, with their defaults.
+    pub fn declared_sensors(&self) -> &IndexMap<String, Scalar> {
+        &self.declared_sensors
+    }
+
+    /// Registers `name` with a default and a range, as a `sensors` entry
+    /// does, over any declaration of it. It stays across reloads. Fails on
+    /// an empty range or a default outside it.
+    pub fn add_sensor(&mut self, name: &str, spec: SensorSpec) -> Result<(), WeaveError> {
+        if let Some(problem) = spec.problem() {
+            return Err(WeaveError::Validation(format!("sensor {}: {}", name, problem)));
+        }
+        self.added_sensors.insert(name.to_string(), spec);
+        self.sensor_issues.retain(|(sensor, _)| sensor != name);
+        Ok(())
+    }
+
+    pub fn remove_sensor(&mut self, name: &str) -> bool {
+        self.added_sensors.shift_remove(name).is_some()
+    }
+
+    /// What is known of `sensor`: what `add_sensor` gave, then what the
+    /// script declares. `None` for a sensor neither knows.
+    pub fn sensor_spec(&self, sensor: &str) -> Option<SensorSpec> {
+        let declared = self.declared_sensors.get(sensor).map(|default| {
+            let range = self.sensor_ranges.get(sensor);
+            SensorSpec { default: Some(*default), min: range.map(|range| range.0), max: range.map(|range| range.1) }
+        });
+        match (self.added_sensors.get(sensor), declared) {
+            (Some(added), declared) => Some(added.or(declared.unwrap_or_default())),
+            (None, declared) => declared,
+        }
+    }
+
+    /// Every declared or added sensor with its spec, declared ones first.
+    pub fn sensor_specs(&self) -> IndexMap<String, SensorSpec> {
+        self.declared_sensors.keys().chain(self.added_sensors.keys()).filter_map(|sensor| Some((sensor.clone(), self.sensor_spec(sensor)?))).collect()
+    }
+
+    /// Whether `sensor` is declared, added or bound to a node property.
+    fn knows_sensor(&self, sensor: &str) -> bool {
+        self.declared_sensors.contains_key(sensor) || self.added_sensors.contains_key(sensor) || self.script_bindings.contains_key(sensor) || self.runtime_bindings.contains_key(sensor)
+    }
+
+    /// Warns once for each supplied sensor that is read outside its range,
+    /// and, once any sensor is declared or added, for each one that is
+    /// neither declared, added nor bound. A warning repeats only after the
+    /// sensor has been fine again.
+    fn check_sensors(&mut self, sensors: &HashMap<String, Scalar>) {
+        let registry = !self.declared_sensors.is_empty() || !self.added_sensors.is_empty();
+        let mut warnings = Vec::new();
+        for (sensor, value) in sensors {
+            let spec = self.sensor_spec(sensor);
+            let unknown = registry && !self.knows_sensor(sensor);
+            let out_of_range = spec.filter(|spec| !spec.in_range(*value));
+            self.sensor_issues.remove(&(sensor.clone(), SensorIssue::Zeroed));
+            if self.flag_sensor(sensor, SensorIssue::Unknown, unknown) {
+                warnings.push(format!("sensor {} is not declared or added, check its name", sensor));
+            }
+            if self.flag_sensor(sensor, SensorIssue::OutOfRange, out_of_range.is_some()) {
+                warnings.push(format!("sensor {} read {}, outside its range {}", sensor, value, out_of_range.map(|spec| spec.range()).unwrap_or_default()));
+            }
+        }
+        for warning in warnings {
+            self.warn(warning);
+        }
+    }
+
+    /// Records whether `sensor` has `issue`; true when it has just started to.
+    fn flag_sensor(&mut self, sensor: &str, issue: SensorIssue, flagged: bool) -> bool {
+        let key = (sensor.to_string(), issue);
+        if flagged {
+            self.sensor_issues.insert(key)
+        } else {
+            self.sensor_issues.remove(&key);
+            false
+        }
+    }
+
+    /// Warns once for each of `read` that had no value and no default, so
+    /// the `Default` policy read it as 0.
+    fn note_zeroed_sensors(&mut self, sensors: &HashMap<String, Scalar>, read: &[String]) {
+        let mut warnings = Vec::new();
+        for sensor in read {
+            let zeroed = !sensors.contains_key(sensor)
+                && self.sensor_spec(sensor).is_none_or(|spec| spec.default.is_none())
+                && !self.sensor_defaults.contains_key(sensor)
+                && self.policy_for(sensor) == SensorPolicy::Default;
+            if zeroed && self.flag_sensor(sensor, SensorIssue::Zeroed, true) {
+                warnings.push(format!("sensor {} was not supplied and has no default, so it reads 0", sensor));
+            }
+        }
+        for warning in warnings {
+            self.warn(warning);
+        }
+    }
+
+    /// Warns once for each declared sensor that `sensors` lacks, until it is
+    /// supplied again.
+    fn note_defaulted_sensors(&mut self, sensors: &HashMap<String, Scalar>) {
+        let mut defaulted = Vec::new();
+        for (sensor, default) in &self.declared_sensors {
+            if sensors.contains_key(sensor) {
+                self.defaulted_sensors.remove(sensor);
+            } else if self.defaulted_sensors.insert(sensor.clone()) {
+                defaulted.push(format!("sensor {} was not supplied, using its declared default {}", sensor, default));
+            }
+        }
+        for warning in defaulted {
+            self.warn(warning);
+        }
+    }
+
+    fn note_missing_sensor(&mut self, sensor: &str) {
+        if !self.missing_sensors.iter().any(|missing| missing == sensor) {
+            self.missing_sensors.push(sensor.to_string());
+        }
+    }
+
+    /// Sensors that had no value under their policy since the last call.
+    pub fn take_missing_sensors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.missing_sensors)
+    }
+
+    pub fn log(&self) -> &Log {
+        &self.log
+    }
+
+    /// Writes to `log` from now on, e.g. to share one log between the program
+    /// slots of a binding.
+    pub fn set_log(&mut self, log: Log) {
+        self.log = log;
+    }
+
+    /// Records a recoverable problem for `take_warnings`, e.g. input that had to be skipped.
+    pub fn warn(&mut self, message: String) {
+        weave_log!(self.log, Level::Warn, "{}", message);
+        self.warnings.push(message);
+    }
+
+    /// Warnings recorded since the last call, oldest first.
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Everything wrong with `code` as a whole weave file, without loading
+    /// it: the parse error, or the warnings a load would give. Empty when it
+    /// loads cleanly.
+    pub fn check_str(&self, code: &str) -> Vec<Diagnostic> {
+        let mut scratch = Interpreter::new();
+        *scratch.constants_mut() = self.constants.clone();
+        match scratch.load_str(code) {
+            Ok(()) => scratch.take_warnings().into_iter().map(Diagnostic::warning).collect(),
+            Err(e) => vec![Diagnostic::from_error(&e)],
+        }
+    }
+
+    /// `lint::lint` for `code`, counting the sensors this interpreter was
+    /// given or fed and the agents it spawned as provided.
+    pub fn lint_str(&self, code: &str) -> Vec<Diagnostic> {
+        let sensors = self.added_sensors.keys().chain(self.runtime_bindings.keys()).chain(self.seen_sensors.keys()).cloned().collect();
+        let fields = self.agents.iter().filter(|(_, agent)| !agent.from_script).map(|(name, _)| name.clone()).collect();
+        lint::lint(code, &LintContext { constants: self.constants.clone(), sensors, fields })
+    }
+
+    /// `lint_str` for the file last loaded from disk; empty when the
+    /// program came from a string.
+    pub fn lint_source(&self) -> Vec<Diagnostic> {
+        let Some(path) = self.source_path() else {
+            return Vec::new();
+        };
+        match std::fs::read_to_string(path) {
+            Ok(code) => self.lint_str(&code),
+            Err(e) => vec![Diagnostic::from_error(&WeaveError::Io(e))],
+        }
+    }
+
+    /// Constants that param expressions in later loads and snippets can use.
+    pub fn constants(&self) -> &Constants {
+        &self.constants
+    }
+
+    pub fn constants_mut(&mut self) -> &mut Constants {
+        &mut self.constants
+    }
+
+    /// Sensor bindings in script order, each as `(sensor, source)`. A runtime
+    /// rebind overrides the script's source; sensors bound only at runtime
+    /// come last.
+    pub fn sensor_bindings(&self) -> impl Iterator<Item = (&str, &str)> {
+        let rebound = self.script_bindings.iter().map(|(sensor, source)| (sensor, self.runtime_bindings.get(sensor).unwrap_or(source)));
+        let added = self.runtime_bindings.iter().filter(|(sensor, _)| !self.script_bindings.contains_key(*sensor));
+        rebound.chain(added).map(|(sensor, source)| (sensor.as_str(), source.as_str()))
+    }
+
+    /// Binds `sensor` to `source` (`"<node path>:<property>"`), surviving
+    /// reloads. Returns false and changes nothing if `source` is malformed.
+    pub fn rebind_sensor(&mut self, sensor: &str, source: &str) -> bool {
+        if binding_target(source).is_none() {
+            return false;
+        }
+        self.runtime_bindings.insert(sensor.to_string(), source.to_string());
+        true
+    }
+
+    pub fn set_safety_rules(&mut self, rules: Vec<SafetyRule>) {
+        self.safety_streaks = vec![0; rules.len()];
+        self.safety_rules = rules;
+    }
+
+    pub fn safety_rules(&self) -> &[SafetyRule] {
+        &self.safety_rules
+    }
+
+    pub fn last_safety_event(&self) -> Option<&SafetyEvent> {
+        self.last_safety_event.as_ref()
+    }
+
+    /// Rules that tripped since the last call, in rule order per tick.
+    pub fn take_safety_events(&mut self) -> Vec<SafetyEvent> {
+        std::mem::take(&mut self.safety_events)
+    }
+
+    /// The newest trips, oldest first, up to `SAFETY_LOG_CAPACITY`.
+    pub fn safety_log(&self) -> &SafetyLog {
+        &self.safety_log
+    }
+
+    pub fn clear_safety_log(&mut self) {
+        self.safety_log.clear();
+    }
+
+    /// Evaluates every safety rule against `sensors`. A rule is violated on a
+    /// tick when all its checks are, and trips once it has been violated for
+    /// more than its `grace_ticks` consecutive checks; the violation counter
+    /// then starts over, and a `halt` rule drops the safety metric to 0.
+    /// Sensors without a value under their policy count as not violating.
+    pub fn check_safety(&mut self, sensors: &HashMap<String, Scalar>) -> usize {
+        let tick = self.tick() as u64;
+        let mut tripped = 0;
+        for index in 0..self.safety_rules.len() {
+            let rule = &self.safety_rules[index];
+            let values: Option<Vec<(String, Scalar)>> = rule
+                .checks
+                .iter()
+                .map(|check| self.resolve_sensor(sensors, &check.sensor).filter(|&value| check.violated(value)).map(|value| (check.sensor.clone(), value)))
+                .collect();
+            let Some(values) = values.filter(|values| !values.is_empty()) else {
+                self.safety_streaks[index] = 0;
+                continue;
+            };
+            self.safety_streaks[index] += 1;
+            if self.safety_streaks[index] <= rule.grace_ticks {
+                continue;
+            }
+            let (sensor, sensor_value) = values[0].clone();
+            let event = SafetyEvent { rule_index: index, rule: rule.to_string(), severity: rule.severity, sensor, sensor_value, values, tick };
+            self.safety_streaks[index] = 0;
+            if rule.severity == SafetySeverity::Halt {
+                self.safety_metric = 0.0;
+            }
+            self.last_safety_event = Some(event.clone());
+            self.safety_log.push(event.clone());
+            self.safety_events.push(event);
+            self.metrics.safety_violations += 1;
+            tripped += 1;
+        }
+        tripped
+    }
+
+    pub fn set_safety_metric(&mut self, value: Scalar) {
+        self.safety_metric = value;
+    }
+
+    pub fn safety_metric(&self) -> Scalar {
+        self.safety_metric
+    }
+
+    /// 1.0 when the latest smoothed tension is zero, falling towards 0.0 as it grows.
+    pub fn coherence(&self) -> Scalar {
+        1.0 / (1.0 + self.smoothed_tension())
+    }
+
+    pub fn execute_tension(&mut self, sensors: &HashMap<String, Scalar>) -> Result<Scalar, WeaveError> {
+        let started = self.profiler.start();
+        let tension = self.compute_tension(sensors);
+        self.profiler.stop(ProfilePhase::Tension, started);
+        tension
+    }
+
+    fn compute_tension(&mut self, sensors: &HashMap<String, Scalar>) -> Result<Scalar, WeaveError> {
+        let replayed = self.stream_sensors(StreamPhase::Tension, sensors);
+        let sensors = replayed.as_ref().unwrap_or(sensors);
+        self.note_defaulted_sensors(sensors);
+        self.check_sensors(sensors);
+        self.last_sensors.clone_from(sensors);
+        for (sensor, value) in sensors {
+            match self.seen_sensors.get_mut(sensor) {
+                Some(seen) => *seen = *value,
+                None => {
+                    self.seen_sensors.insert(sensor.clone(), *value);
+                }
+            }
+        }
+        self.check_safety(sensors);
+        let mut terms = match self.model_terms(sensors) {
+            Ok(terms) => terms,
+            Err(e) => {
+                if let WeaveError::MissingSensor(sensor) = &e {
+                    self.note_missing_sensor(sensor);
+                }
+                return Err(e);
+            }
+        };
+        let read: Vec<String> = terms.iter().map(|term| term.sensor.clone()).collect();
+        self.note_zeroed_sensors(sensors, &read);
+        terms.sort_by(|a, b| b.contribution.total_cmp(&a.contribution));
+        let tension = terms.iter().fold(0.0, |sum: Scalar, term| sum + term.contribution);
+        self.tension_terms = terms;
+        weave_log!(self.log, Level::Trace, "Tension calculated: {}", tension);
+        self.last_tick_delta = max_change(&self.tick_changes);
+        self.tick_changes.clear();
+        self.tension_history.push(tension);
+        self.smoothed_tension = Some(match self.smoothed_tension {
+            Some(previous) => previous + self.smoothing_alpha * (tension - previous),
+            None => tension,
+        });
+        self.tension_alarm.check(tension);
+        Ok(tension)
+    }
+
+    /// Runs `execute_tension` on each `stride`-long row of `samples`, where
+    /// column `i` is the sensor `names[i]` and any further columns are
+    /// ignored, and returns the row tensions. With a nonzero `drift_every`,
+    /// drift and resolve move `agents` by `dt` after every `drift_every` rows,
+    /// and after a shorter last chunk, using the mean tension of the chunk.
+    /// Stops at the first row whose tension fails; earlier rows stay in the
+    /// history.
+    pub fn execute_tension_batch(
+        &mut self,
+        names: &[String],
+        samples: &[Scalar],
+        stride: usize,
+        drift_every: usize,
+        agents: &Fields,
+        dt: Scalar,
+    ) -> Result<Vec<Scalar>, WeaveError> {
+        if stride == 0 || stride < names.len() {
+            return Err(WeaveError::Validation(format!("stride {} is shorter than the {} sensor names", stride, names.len())));
+        }
+        if !samples.len().is_multiple_of(stride) {
+            return Err(WeaveError::Validation(format!("{} samples are not whole rows of {}", samples.len(), stride)));
+        }
+        let mut tensions = Vec::with_capacity(samples.len() / stride);
+        let mut sensors: HashMap<String, Scalar> = names.iter().map(|name| (name.clone(), 0.0)).collect();
+        for (index, row) in samples.chunks_exact(stride).enumerate() {
+            for (name, value) in names.iter().zip(row) {
+                if let Some(sensor) = sensors.get_mut(name) {
+                    *sensor = *value;
+                }
+            }
+            tensions.push(self.execute_tension(&sensors)?);
+            if drift_every > 0 && ((index + 1).is_multiple_of(drift_every) || tensions.len() * stride == samples.len()) {
+                let chunk = &tensions[index - index % drift_every..];
+                let mean = chunk.iter().sum::<Scalar>() / chunk.len() as Scalar;
+                self.execute_drift_dt(agents, mean, dt);
+                self.execute_resolve_dt(agents, mean, dt);
+            }
+        }
+        Ok(tensions)
+    }
+
+    /// The terms the last successful `execute_tension` summed, largest
+    /// contribution first. Empty before the first one.
+    pub fn tension_terms(&self) -> &[TensionTerm] {
+        &self.tension_terms
+    }
+
+    /// Drift for one tick; same as `execute_drift_dt` with `dt = 1.0`.
+    pub fn execute_drift(&mut self, agents: &Fields, tension: Scalar) {
+        self.execute_drift_dt(agents, tension, 1.0);
+    }
+
+    /// Drift over `dt` seconds, treating the drift rate as per second.
+    pub fn execute_drift_dt(&mut self, agents: &Fields, tension: Scalar, dt: Scalar) {
+        self.drift(&uniform(agents, dt), tension, None);
+    }
+
+    /// Drift over `dt` for only the agents tagged `tag`.
+    pub fn execute_drift_group(&mut self, tag: &str, agents: &Fields, tension: Scalar, dt: Scalar) {
+        self.drift(&uniform(agents, dt), tension, Some(tag));
+    }
+
+    /// Drifts each `(agent, dt)` in `batch` and returns the summed absolute step.
+    /// Noise is added first, whatever the tension.
+    fn drift(&mut self, batch: &[(&str, Scalar)], tension: Scalar, tag: Option<&str>) -> Scalar {
+        if self.emergency_stopped {
+            return 0.0;
+        }
+        let started = self.profiler.start();
+        self.perturb(batch, tag);
+        let total_drift = if tension < self.tension_threshold {
+            0.0
+        } else {
+            let cause = Cause::new(Phase::Drift, "tension", Some(tension));
+            self.adjust_targets(batch, tension, InterpreterConfig::drift_rate_for, self.config.drift_strategy, tag, cause)
+        };
+        self.profiler.stop(ProfilePhase::Drift, started);
+        total_drift
+    }
+
+    /// Resolve for one tick; same as `execute_resolve_dt` with `dt = 1.0`.
+    pub fn execute_resolve(&mut self, agents: &Fields, tension: Scalar) {
+        self.execute_resolve_dt(agents, tension, 1.0);
+    }
+
+    /// Resolve over `dt` seconds: the fixed rate is per second, and PID
+    /// integrates and differentiates over `dt`.
+    pub fn execute_resolve_dt(&mut self, agents: &Fields, tension: Scalar, dt: Scalar) {
+        self.resolve(&uniform(agents, dt), tension, None);
+    }
+
+    /// Resolve over `dt` for only the agents tagged `tag`.
+    pub fn execute_resolve_group(&mut self, tag: &str, agents: &Fields, tension: Scalar, dt: Scalar) {
+        self.resolve(&uniform(agents, dt), tension, Some(tag));
+    }
+
+    fn resolve(&mut self, batch: &[(&str, Scalar)], tension: Scalar, tag: Option<&str>) {
+        if self.emergency_stopped || tension < self.tension_threshold {
+            return;
+        }
+        let started = self.profiler.start();
+        self.steer(batch, tension, tag);
+        match self.resolve_mode {
+            ResolveMode::Fixed => {
+                let cause = Cause::new(Phase::Resolve, "tension", Some(tension));
+                self.adjust_targets(batch, -tension, InterpreterConfig::resolve_rate_for, DriftStrategy::Linear, tag, cause);
+            }
+            ResolveMode::Pid => self.resolve_pid(batch, tag),
+        }
+        self.profiler.stop(ProfilePhase::Resolve, started);
+    }
+
+    /// Whether drift and resolve may move `agent`: never when it is tagged
+    /// `frozen` or locked, and only when it carries `tag` if one is given.
+    fn movable(&self, agent: &str, tag: Option<&str>) -> bool {
+        !self.has_tag(agent, FROZEN_TAG) && !self.is_agent_locked(agent) && tag.is_none_or(|tag| self.has_tag(agent, tag))
+    }
+
+    /// Moves each agent's target by its PID correction against the sensor values
+    /// seen by the last `execute_tension`. Agents whose sensor has no value under
+    /// its policy are left alone.
+    fn resolve_pid(&mut self, batch: &[(&str, Scalar)], tag: Option<&str>) {
+        for &(agent_name, dt) in batch {
+            let field = match self.fields.get(agent_name) {
+                Some(field) if self.movable(agent_name, tag) => field,
+                _ => continue,
+            };
+            let sensor = self.tracked_sensor(agent_name);
+            let sensed = match self.resolve_sensor(&self.last_sensors, sensor) {
+                Some(sensed) => sensed,
+                None => {
+                    let sensor = sensor.to_string();
+                    self.note_missing_sensor(&sensor);
+                    continue;
+                }
+            };
+            let gains = self
+                .pid_gains
+                .get(agent_name)
+                .copied()
+                .or_else(|| PidGains::from_params(field))
+                .unwrap_or_default();
+            let trigger = if self.trace.enabled() { sensor.to_string() } else { String::new() };
+            for (key, target) in self.drive_targets(agent_name) {
+                if !self.fields.contains_key(agent_name) {
+                    break;
+                }
+                let state = self.pid_states.entry(format!("{}.{}", agent_name, key)).or_default();
+                let correction = state.update(gains, sensed - target, dt, self.pid_integral_limit);
+                let value = self.bounded(agent_name, &key, target + correction);
+                let cause = Cause::new(Phase::Resolve, &trigger, Some(sensed));
+                self.write_param(agent_name, &key, value, cause);
+            }
+        }
+    }
+
+    /// Accelerates each kinematic agent in `batch` that declares a
+    /// `target_position` towards it, or towards its waypoint, pulling with `tension` times its resolve
+    /// rate per unit of distance.
+    fn steer(&mut self, batch: &[(&str, Scalar)], tension: Scalar, tag: Option<&str>) {
+        for &(agent_name, dt) in batch {
+            if !self.movable(agent_name, tag) {
+                continue;
+            }
+            let (Some(params), Some(attributes)) = (self.fields.get(agent_name), self.attributes.get_mut(agent_name)) else {
+                continue;
+            };
+            let (Some(ParamValue::Vector(target)), Some(ParamValue::Vector(position))) = (attributes.get("target_position"), attributes.get("position")) else {
+                continue;
+            };
+            let target = self.waypoints.get(agent_name).filter(|point| point.len() == position.len()).unwrap_or(target).clone();
+            let position = position.clone();
+            let Some(ParamValue::Vector(velocity)) = attributes.get_mut("velocity") else {
+                continue;
+            };
+            if velocity.len() == position.len() && target.len() == position.len() {
+                let gain = (tension * self.config.resolve_rate_for(params)).abs();
+                kinematics::steer(velocity, &position, &target, gain, Limits::from_params(params), dt);
+            }
+        }
+    }
+
+    /// Moves every unlocked agent declaring both a `position` and a `velocity`
+    /// of the same length by its velocity over `dt`, clamped to `max_speed`.
+    fn integrate(&mut self, dt: Scalar) {
+        for (agent_name, attributes) in self.attributes.iter_mut() {
+            if self.locked_agents.contains_key(agent_name) {
+                continue;
+            }
+            let limits = self.fields.get(agent_name).map(Limits::from_params);
+            let Some(ParamValue::Vector(mut velocity)) = attributes.remove("velocity") else {
+                continue;
+            };
+            if let (Some(limits), Some(ParamValue::Vector(position))) = (limits, attributes.get_mut("position")) {
+                if position.len() == velocity.len() {
+                    kinematics::advance(position, &mut velocity, limits, dt);
+                }
+            }
+            attributes.insert("velocity".to_string(), ParamValue::Vector(velocity));
+        }
+    }
+
+    /// Pushes apart every two agents whose `position` vectors have the
+    /// same length and sit closer than the larger of their `min_distance`
+    /// params, in name order, so robots don't overlap when their positions
+    /// are applied. Each moves half the overlap; a locked agent stays put
+    /// and the other moves all of it.
+    fn separate(&mut self) {
+        let mut agents: Vec<(String, Vec<Scalar>, Scalar, bool)> = self
+            .attributes
+            .iter()
+            .filter_map(|(name, attributes)| match attributes.get("position") {
+                Some(ParamValue::Vector(position)) => {
+                    let min_distance = self.fields.get(name).and_then(|params| params.get("min_distance")).copied().unwrap_or(0.0);
+                    Some((name.clone(), position.clone(), min_distance, self.locked_agents.contains_key(name)))
+                }
+                _ => None,
+            })
+            .collect();
+        if agents.iter().all(|(_, _, min_distance, _)| *min_distance <= 0.0) {
+            return;
+        }
+        agents.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut moved = vec![false; agents.len()];
+        for first in 0..agents.len() {
+            let (head, tail) = agents.split_at_mut(first + 1);
+            let a = &mut head[first];
+            for (offset, b) in tail.iter_mut().enumerate() {
+                let min_distance = a.2.max(b.2);
+                if min_distance <= 0.0 || a.1.len() != b.1.len() || (a.3 && b.3) {
+                    continue;
+                }
+                let share = if a.3 { 0.0 } else if b.3 { 1.0 } else { 0.5 };
+                if kinematics::separate(&mut a.1, &mut b.1, min_distance, share) {
+                    moved[first] |= !a.3;
+                    moved[first + 1 + offset] |= !b.3;
+                }
+            }
+        }
+        for ((name, position, _, _), moved) in agents.into_iter().zip(moved) {
+            if let Some(attributes) = self.attributes.get_mut(&name).filter(|_| moved) {
+                attributes.insert("position".to_string(), ParamValue::Vector(position));
+            }
+        }
+    }
+
+    fn tracked_sensor(&self, agent: &str) -> &str {
+        self.pid_sensors.get(agent).map(String::as_str).unwrap_or("coherence")
+    }
+
+    /// Moves each of an agent's drift targets by `scale * rate * dt`, with the
+    /// agent's rate from `rate_for` and `scale` shaped per agent by
+    /// `strategy`; returns the summed absolute step.
+    ///
+    /// Under signed correction only the size of `rate` counts: each target
+    /// moves towards the value of its tracked sensor, and never past it, so
+    /// the error shrinks whichever side the target starts on. Agents whose
+    /// sensor has no value under its policy are left alone.
+    fn adjust_targets(&mut self, batch: &[(&str, Scalar)], scale: Scalar, rate_for: RateFor, strategy: DriftStrategy, tag: Option<&str>, cause: Cause) -> Scalar {
+        let mut total = 0.0;
+        for &(agent_name, dt) in batch {
+            if !self.movable(agent_name, tag) {
+                continue;
+            }
+            let targets = self.drive_targets(agent_name);
+            let Some(field) = self.fields.get(agent_name).filter(|_| !targets.is_empty()) else {
+                continue;
+            };
+            let rate = rate_for(&self.config, field);
+            let sensed = if self.signed_correction {
+                let sensor = self.tracked_sensor(agent_name);
+                match self.resolve_sensor(&self.last_sensors, sensor) {
+                    Some(sensed) => Some(sensed),
+                    None => {
+                        let sensor = sensor.to_string();
+                        self.note_missing_sensor(&sensor);
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            let rate = self.shape_drift(strategy, agent_name, scale, dt) * rate;
+            for (key, target) in targets {
+                let step = match sensed {
+                    Some(sensed) => {
+                        let error = sensed - target;
+                        error.signum() * (rate * dt).abs().min(error.abs())
+                    }
+                    None => rate * dt,
+                };
+                let bounded = self.bounded(agent_name, &key, target + step);
+                self.write_param(agent_name, &key, bounded, cause);
+                total += step.abs();
+            }
+        }
+        total
+    }
+
+    /// `value` for `agent.param` within the field's `target_min` and
+    /// `target_max`, or the configured bounds, and then within the param's
+    /// declared range. The first write the range holds back is queued as
+    /// an `OutOfBounds`; the next comes once a write has landed inside.
+    fn bounded(&mut self, agent: &str, param: &str, value: Scalar) -> Scalar {
+        let value = self.fields.get(agent).map_or(value, |field| self.config.bounded(field, value));
+        let Some((min, max)) = self.param_range(agent, param) else {
+            return value;
+        };
+        let clamped = value.clamp(min, max);
+        let key = (agent.to_string(), param.to_string());
+        if clamped == value {
+            self.pinned_params.remove(&key);
+        } else if self.pinned_params.insert(key) {
+            let (field, param) = (agent.to_string(), param.to_string());
+            self.out_of_bounds.push(OutOfBounds { field, param, value, min, max, tick: self.tick() });
+        }
+        clamped
+    }
+
+    /// The `in [min, max]` range `agent` declares for `param`, or else the
+    /// one its template declares.
+    pub fn param_range(&self, agent: &str, param: &str) -> Option<(Scalar, Scalar)> {
+        let declared = |field: &str| self.param_bounds.get(field).and_then(|bounds| bounds.get(param)).copied();
+        declared(agent).or_else(|| self.agents.get(agent).and_then(|registered| declared(&registered.template)))
+    }
+
+    /// Writes that a declared range held back since the last call, oldest first.
+    pub fn take_out_of_bounds(&mut self) -> Vec<OutOfBounds> {
+        std::mem::take(&mut self.out_of_bounds)
+    }
+
+    /// `scale` as `strategy` shapes it for `agent` this tick, see `DriftStrategy`.
+    fn shape_drift(&mut self, strategy: DriftStrategy, agent: &str, scale: Scalar, dt: Scalar) -> Scalar {
+        let Some(field) = self.fields.get(agent) else {
+            return scale;
+        };
+        match strategy {
+            DriftStrategy::Linear => scale,
+            DriftStrategy::Pid => {
+                let state = self.drift_pid_states.entry(agent.to_string()).or_default();
+                state.update(PidGains::drift_from_params(field), scale, dt, self.pid_integral_limit)
+            }
+            DriftStrategy::ExponentialSmoothing => {
+                let alpha = self.config.drift_smoothing_for(field);
+                let smoothed = self.drift_smoothed.entry(agent.to_string()).or_insert(scale);
+                *smoothed += alpha * (scale - *smoothed);
+                *smoothed
+            }
+        }
+    }
+
+    /// The params drift and resolve steer on fields that don't name their
+    /// own with `drives: [...]`. Each one a field declares moves; a field
+    /// with none of them is skipped with a warning.
+    pub fn set_drift_targets(&mut self, params: Vec<String>) {
+        self.drift_targets = params;
+        self.undriven_agents.clear();
+    }
+
+    pub fn drift_targets(&self) -> &[String] {
+        &self.drift_targets
+    }
+
+    /// The params drift and resolve move on `agent`, with their current
+    /// values: those of its `drives` list, or else of the drift targets,
+    /// that the field declares, in list order.
+    pub fn driven_params(&self, agent: &str) -> Vec<(String, Scalar)> {
+        let Some(field) = self.fields.get(agent) else {
+            return Vec::new();
+        };
+        let names: Vec<&str> = match self.attribute(agent, "drives") {
+            Some(ParamValue::List(items)) => items
+                .iter()
+                .filter_map(|item| match item {
+                    ParamValue::Symbol(name) | ParamValue::Text(name) => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect(),
+            Some(ParamValue::Symbol(name) | ParamValue::Text(name)) => vec![name.as_str()],
+            _ => self.drift_targets.iter().map(String::as_str).collect(),
+        };
+        names.into_iter().filter_map(|name| field.get(name).map(|value| (name.to_string(), *value))).collect()
+    }
+
+    /// `driven_params` of `agent`, warning once, with the params it does
+    /// have, when a declared field has none.
+    fn drive_targets(&mut self, agent: &str) -> Vec<(String, Scalar)> {
+        let targets = self.driven_params(agent);
+        if targets.is_empty() && !self.undriven_agents.contains(agent) {
+            if let Some(field) = self.fields.get(agent) {
+                let wanted = match self.attribute(agent, "drives") {
+                    Some(drives) => format!("its drives {}", drives),
+                    None => self.drift_targets.join(", "),
+                };
+                let available: Vec<&str> = field.keys().map(String::as_str).collect();
+                let available = if available.is_empty() { "none".to_string() } else { available.join(", ") };
+                let message = format!("drift and resolve skip {}: it has none of {}; its numeric params are {}", agent, wanted, available);
+                self.undriven_agents.insert(agent.to_string());
+                self.warn(message);
+            }
+        }
+        targets
+    }
+
+    /// Adds Gaussian noise to every drift target and `position` component of
+    /// each agent in `batch` that declares `noise_stddev`. The stddev is per unit of time, so an agent
+    /// that sat out `dt` ticks gets `stddev * sqrt(dt)`, the spread of `dt`
+    /// separate draws.
+    fn perturb(&mut self, batch: &[(&str, Scalar)], tag: Option<&str>) {
+        if self.noise.scale() == 0.0 {
+            return;
+        }
+        for &(agent_name, dt) in batch {
+            if !self.movable(agent_name, tag) {
+                continue;
+            }
+            let Some(field) = self.fields.get(agent_name) else {
+                continue;
+            };
+            let Some(stddev) = field.get("noise_stddev").copied().filter(|stddev| *stddev > 0.0) else {
+                continue;
+            };
+            for (key, target) in self.drive_targets(agent_name) {
+                let noisy = target + self.noise.gaussian(stddev * dt.max(0.0).sqrt());
+                let noisy = self.bounded(agent_name, &key, noisy);
+                self.write_param(agent_name, &key, noisy, Cause::new(Phase::Drift, "noise", None));
+            }
+            if let Some(ParamValue::Vector(position)) = self.attributes.get_mut(agent_name).and_then(|attributes| attributes.get_mut("position")) {
+                for component in position.iter_mut() {
+                    *component += self.noise.gaussian(stddev * dt.max(0.0).sqrt());
+                }
+            }
+        }
+    }
+
+    /// Multiplies every declared `noise_stddev`; 0 disables noise.
+    pub fn set_noise_scale(&mut self, scale: Scalar) {
+        self.noise.set_scale(scale);
+    }
+
+    pub fn noise_scale(&self) -> Scalar {
+        self.noise.scale()
+    }
+
+    /// Restarts the noise stream; the same seed draws the same noise.
+    pub fn set_noise_seed(&mut self, seed: u64) {
+        self.noise.reseed(seed);
+    }
+
+    pub fn noise_seed(&self) -> u64 {
+        self.noise.seed()
+    }
+
+    /// Metaweave rules from the script followed by those added at runtime,
+    /// in evaluation order.
+    pub fn metaweave_rules(&self) -> impl Iterator<Item = &MetaweaveRule> {
+        self.script_rules.iter().chain(self.runtime_rules.iter())
+    }
+
+    pub fn add_metaweave_rule(&mut self, rule: MetaweaveRule) {
+        self.runtime_rules.push(rule);
+    }
+
+    /// Drops the rules added at runtime; script rules stay until the next reload.
+    pub fn clear_metaweave_rules(&mut self) {
+        self.runtime_rules.clear();
+    }
+
+    /// Applies every metaweave rule whose condition holds and returns the fired
+    /// rules. Rules whose sensor has no value under its policy are skipped.
+    /// Fails without changing anything if a fired rule targets an undeclared field.
+    pub fn execute_metaweave(&mut self, sensors: &HashMap<String, Scalar>) -> Result<Vec<MetaweaveRule>, WeaveError> {
+        let started = self.profiler.start();
+        let fired = self.apply_metaweave(sensors);
+        self.profiler.stop(ProfilePhase::Metaweave, started);
+        fired
+    }
+
+    fn apply_metaweave(&mut self, sensors: &HashMap<String, Scalar>) -> Result<Vec<MetaweaveRule>, WeaveError> {
+        let replayed = self.stream_sensors(StreamPhase::Metaweave, sensors);
+        let sensors = replayed.as_ref().unwrap_or(sensors);
+        self.note_defaulted_sensors(sensors);
+        self.check_sensors(sensors);
+        for (sensor, value) in sensors {
+            self.seen_sensors.insert(sensor.clone(), *value);
+        }
+        let mut fired = Vec::new();
+        let mut missing = Vec::new();
+        for rule in self.metaweave_rules() {
+            match self.rule_fires(rule, sensors) {
+                Some(true) => fired.push(rule.clone()),
+                Some(false) => {}
+                None => missing.extend(rule.sensor().filter(|sensor| !sensor.contains('.')).map(str::to_string)),
+            }
+        }
+        for rule in &self.call_rules {
+            match self.condition_holds(&rule.condition, sensors) {
+                Some(true) => self.lab_calls.push(rule.call.clone()),
+                Some(false) => {}
+                None => missing.extend(rule.condition.sensor().filter(|sensor| !sensor.contains('.')).map(str::to_string)),
+            }
+        }
+        for sensor in &missing {
+            self.note_missing_sensor(sensor);
+        }
+        let conditions = self.metaweave_rules().map(|rule| &rule.condition).chain(self.call_rules.iter().map(|rule| &rule.condition));
+        let read: Vec<String> = conditions.filter_map(RuleCondition::sensor).filter(|sensor| !sensor.contains('.')).map(str::to_string).collect();
+        self.note_zeroed_sensors(sensors, &read);
+        if let Some(rule) = fired.iter().find(|rule| !self.fields.contains_key(&rule.field) && !self.is_parent_global(&rule.field)) {
+            return Err(WeaveError::MissingField(rule.field.clone()));
+        }
+        for rule in &fired {
+            let cause = self.rule_cause(rule, sensors);
+            self.write_param(&rule.field, &rule.param, rule.value, cause);
+        }
+        weave_log!(self.log, Level::Debug, "Metaweave executed, {} rule(s) fired", fired.len());
+        Ok(fired)
+    }
+
+    /// What to trace as the trigger of a fired `rule`: its sensor and the
+    /// value it read, or the attribute it compared.
+    fn rule_cause<'a>(&self, rule: &'a MetaweaveRule, sensors: &HashMap<String, Scalar>) -> Cause<'a> {
+        match &rule.condition {
+            RuleCondition::Sensor { sensor, .. } => {
+                let value = if self.trace.enabled() { self.sensed(sensors, sensor) } else { None };
+                Cause::new(Phase::Metaweave, sensor, value)
+            }
+            RuleCondition::Attribute { field, param, .. } => Cause { trigger_param: Some(param), ..Cause::new(Phase::Metaweave, field, None) },
+        }
+    }
+
+    /// Whether `rule`'s condition holds; `None` when its sensor has no value under its policy.
+    fn rule_fires(&self, rule: &MetaweaveRule, sensors: &HashMap<String, Scalar>) -> Option<bool> {
+        self.condition_holds(&rule.condition, sensors)
+    }
+
+    /// Whether `condition` holds, or `None` when its sensor has no value. A
+    /// sensor named `tension` that the input lacks reads the latest tension.
+    fn condition_holds(&self, condition: &RuleCondition, sensors: &HashMap<String, Scalar>) -> Option<bool> {
+        match condition {
+            RuleCondition::Sensor { sensor, .. } => self.sensed(sensors, sensor).map(|sensed| condition.holds(sensed)),
+            RuleCondition::Attribute { field, param, .. } => Some(condition.holds_on(self.attribute(field, param))),
+        }
+    }
+
+    /// The value a rule condition reads for `sensor`: the input, the latest
+    /// tension for a `tension` the input lacks, then the sensor's policy. A
+    /// `field.param` name reads that live param instead.
+    fn sensed(&self, sensors: &HashMap<String, Scalar>, sensor: &str) -> Option<Scalar> {
+        if let Some((field, param)) = sensor.split_once('.') {
+            return self.param_value(field, param);
+        }
+        let latest_tension = self.tension_history.last().filter(|_| sensor == "tension" && !sensors.contains_key(sensor));
+        latest_tension.or_else(|| self.resolve_sensor(sensors, sensor))
+    }
+
+    /// Script call rules, in file order.
+    pub fn call_rules(&self) -> &[CallRule] {
+        &self.call_rules
+    }
+
+    /// Lab calls fired by metaweave since the last take, oldest first, for the
+    /// Godot side to dispatch.
+    pub fn take_lab_calls(&mut self) -> Vec<LabCall> {
+        std::mem::take(&mut self.lab_calls)
+    }
+
+    /// Parses `code` as a snippet and applies it: field blocks merge into the
+    /// current fields, assignments set params, snippet metaweave rules run once
+    /// against the last sensor values and snippet safety rules join the active
+    /// ones. Nothing changes unless the whole snippet parses and every field it
+    /// writes is declared here or in the snippet itself.
+    pub fn execute_code(&mut self, code: &str) -> Result<CodeOutcome, WeaveError> {
+        let started = self.profiler.start();
+        let snippet = parse_snippet(code, &self.constants, &self.functions, &self.templates);
+        self.profiler.stop(ProfilePhase::Parse, started);
+        self.count_parse(&snippet);
+        let snippet = snippet?;
+        let declared = |field: &str| self.fields.contains_key(field) || snippet.program.fields.contains_key(field) || self.is_parent_global(field);
+        if let Some((field, _, _)) = snippet.assignments.iter().find(|(field, _, _)| !declared(field)) {
+            return Err(WeaveError::MissingField(field.clone()));
+        }
+        let fired: Vec<MetaweaveRule> = snippet
+            .program
+            .metaweave_rules
+            .iter()
+            .filter(|rule| self.rule_fires(rule, &self.last_sensors) == Some(true))
+            .cloned()
+            .collect();
+        if let Some(rule) = fired.iter().find(|rule| !declared(&rule.field)) {
+            return Err(WeaveError::MissingField(rule.field.clone()));
+        }
+        if !snippet.program.derived_params.is_empty() {
+            let mut fields = self.fields.clone();
+            for (name, params) in &snippet.program.fields {
+                fields.entry(name.clone()).or_default().extend(params.iter().map(|(param, value)| (param.clone(), *value)));
+            }
+            let is_sensor = |name: &str| {
+                self.declared_sensors.contains_key(name) || self.script_bindings.contains_key(name) || self.runtime_bindings.contains_key(name)
+            };
+            check_derived_params(&snippet.program.derived_params, &fields, &self.constants, is_sensor)?;
+        }
+
+        let Snippet { program, assignments } = snippet;
+        let derived = |field: &str, param: &str| program.derived_params.iter().any(|derived| derived.field == field && derived.param == param);
+        let declares = |field: &str, param: &str| program.fields.get(field).is_some_and(|params| params.contains_key(param));
+        self.derived_params.retain(|existing| !declares(&existing.field, &existing.param));
+        let mut outcome = CodeOutcome::default();
+        let mut writes = Vec::new();
+        for (name, params) in &program.fields {
+            if !self.fields.contains_key(name) && !self.is_parent_global(name) {
+                self.fields.insert(name.clone(), Params::new());
+                outcome.added_fields.push(name.clone());
+            }
+            writes.extend(params.iter().filter(|(param, _)| !derived(name, param)).map(|(param, value)| (name.clone(), param.clone(), *value)));
+        }
+        writes.extend(assignments);
+        let code = Cause::new(Phase::Code, "code", None);
+        for (agent, param, value) in writes {
+            if let Some(old_value) = self.write_param(&agent, &param, value, code) {
+                outcome.changes.push(FieldChange { agent, param, old_value, new_value: value });
+            }
+        }
+        for rule in &fired {
+            let cause = self.rule_cause(rule, &self.last_sensors);
+            if let Some(old_value) = self.write_param(&rule.field, &rule.param, rule.value, cause) {
+                outcome.changes.push(FieldChange { agent: rule.field.clone(), param: rule.param.clone(), old_value, new_value: rule.value });
+            }
+        }
+        outcome.fired = fired;
+        for rule in &program.call_rules {
+            if self.condition_holds(&rule.condition, &self.last_sensors) == Some(true) {
+                self.lab_calls.push(rule.call.clone());
+            }
+        }
+        for (name, attributes) in program.attributes {
+            self.attributes.entry(name).or_default().extend(attributes);
+        }
+        if !program.safety_rules.is_empty() {
+            self.safety_streaks.extend(program.safety_rules.iter().map(|_| 0));
+            self.safety_rules.extend(program.safety_rules);
+        }
+        if !program.derived_params.is_empty() {
+            self.derived_params.extend(program.derived_params);
+            self.derive_params(&self.last_sensors.clone());
+        }
+        self.functions = program.functions;
+        self.templates = program.templates;
+        for (name, bounds) in program.param_bounds {
+            self.param_bounds.entry(name).or_default().extend(bounds);
+        }
+        Ok(outcome)
+    }
+
+    /// One full tick; same as `step_dt` with `dt = 1.0`.
+    pub fn step(&mut self, sensors: &HashMap<String, Scalar>, agents: &Fields) -> Result<Scalar, WeaveError> {
+        self.step_dt(sensors, agents, 1.0)
+    }
+
+    /// One full tick lasting `dt` seconds: tension on `sensors`, then drift and
+    /// resolve for `agents`, then metaweave, then kinematic agents move by
+    /// their velocity and crowded ones are pushed apart, then invariants are
+    /// checked. Recorded when recording is on. With time slicing on, tension
+    /// and metaweave still run every tick but only a batch of agents is
+    /// moved; see `set_agents_per_tick`.
+    /// While an invariant or an emergency stop has halted stepping, returns
+    /// the latest tension and does nothing else.
+    pub fn step_dt(&mut self, sensors: &HashMap<String, Scalar>, agents: &Fields, dt: Scalar) -> Result<Scalar, WeaveError> {
+        if self.is_halted() {
+            return Ok(self.tension_history.last().unwrap_or(0.0));
+        }
+        self.messages.deliver();
+        // A replayed tick reads its recorded tension sensors throughout.
+        let replayed = self.sensor_replay.as_ref().and_then(|replay| replay.peek(StreamPhase::Tension)).map(SensorFrame::to_map);
+        let sensors = replayed.as_ref().unwrap_or(sensors);
+        if self.invariant_policy == InvariantPolicy::Rollback && !self.invariants.is_empty() {
+            self.checkpoint = Some(self.checkpoint());
+        }
+        let started = Instant::now();
+        let queued_events = self.safety_events.len();
+        self.derive_params(sensors);
+        let tension = self.execute_tension(sensors)?;
+        self.steps += 1;
+        self.time_slice.tick += 1;
+        let total_drift = if self.time_slice.enabled() {
+            self.step_slice(agents, tension, dt, started)
+        } else {
+            let batch = uniform(agents, dt);
+            let total_drift = self.drift(&batch, tension, None);
+            self.resolve(&batch, tension, None);
+            total_drift
+        };
+        let total_drift = total_drift + self.run_loops(sensors, agents, dt, tension);
+        let metaweave = self.execute_metaweave(sensors);
+        self.run_handlers(sensors);
+        self.integrate(dt);
+        self.separate();
+        self.run_schedule();
+        self.evaluate_goals(sensors);
+        let rolled_back = self.check_invariants(sensors);
+        if self.recording.is_some() && !rolled_back {
+            let record = self.tick_record(sensors, agents, tension);
+            if let Some(recording) = self.recording.as_mut() {
+                recording.ticks.push(record);
+            }
+        }
+        self.frame_metrics = FrameMetrics {
+            tick: self.tick(),
+            tension,
+            smoothed_tension: self.smoothed_tension(),
+            agents_updated: self.tick_changes.values().filter(|params| params.values().any(|delta| *delta != 0.0)).count(),
+            fired_rules: metaweave.as_ref().ok().cloned().unwrap_or_default(),
+            safety_trips: self.safety_events[queued_events.min(self.safety_events.len())..].iter().map(|event| event.rule_index).collect(),
+            total_drift,
+            load_status: self.load_status,
+            execute_micros: started.elapsed().as_micros() as u64,
+        };
+        self.update_metrics(agents, tension);
+        self.log_data(tension);
+        self.profiler.end_tick();
+        #[cfg(feature = "telemetry")]
+        self.publish_telemetry(tension, queued_events);
+        metaweave.map(|_| tension)
+    }
+
+    fn update_metrics(&mut self, agents: &Fields, tension: Scalar) {
+        self.metrics.ticks += 1;
+        self.metrics.tension = tension;
+        self.metrics.smoothed_tension = self.smoothed_tension();
+        self.metrics.coherence = self.coherence();
+        self.metrics.halted = self.is_halted();
+        self.metrics.agent_coherence = agents.iter().filter_map(|(agent, params)| params.get(self.tracked_sensor(agent)).map(|value| (agent.clone(), *value))).collect();
+        self.publish_metrics();
+    }
+
+    /// Counters and gauges for long-running monitoring, see
+    /// [`ExperimentMetrics`].
+    pub fn metrics(&self) -> &ExperimentMetrics {
+        &self.metrics
+    }
+
+    /// `metrics` in the Prometheus text format.
+    pub fn metrics_text(&self) -> String {
+        self.metrics.to_prometheus()
+    }
+
+    fn publish_metrics(&self) {
+        if let Some(server) = &self.metrics_server {
+            server.publish(self.metrics_text());
+        }
+    }
+
+    /// Serves `metrics_text`, as of the last step or failed parse, over HTTP
+    /// on `address` for a Prometheus scraper, replacing any metrics server
+    /// already running. Returns the address listened on.
+    pub fn start_metrics_server(&mut self, address: &str) -> Result<SocketAddr, WeaveError> {
+        // Stopped first, so a restart may bind the same port.
+        self.metrics_server = None;
+        let server = MetricsServer::start(address, self.metrics_text())?;
+        let bound = server.address();
+        self.metrics_server = Some(Arc::new(server));
+        Ok(bound)
+    }
+
+    /// False if no metrics server was running.
+    pub fn stop_metrics_server(&mut self) -> bool {
+        self.metrics_server.take().is_some()
+    }
+
+    pub fn metrics_address(&self) -> Option<SocketAddr> {
+        self.metrics_server.as_ref().map(|server| server.address())
+    }
+
+    /// Queues the tick just stepped in the data log. A log that can't be
+    /// written is stopped with a warning rather than failing the step.
+    fn log_data(&mut self, tension: Scalar) {
+        let Some(log) = &self.data_log else {
+            return;
+        };
+        let logged = log.lock().map_err(|_| WeaveError::Validation("the data log was poisoned".to_string())).and_then(|mut log| log.log_tick(self.tick() as u64, &self.fields, tension));
+        if let Err(e) = logged {
+            self.data_log = None;
+            self.warn(format!("stopped the data log: {}", e));
+        }
+    }
+
+    /// After every step from now on, logs each numeric param of every field
+    /// as a `tick, agent, param, value, tension` row to `path`: Parquet for a
+    /// `.parquet` path, CSV for any other. Rows are written out every
+    /// `flush_ticks` ticks. Replaces any data log already running.
+    pub fn start_data_log(&mut self, path: &Path, flush_ticks: usize) -> Result<(), WeaveError> {
+        self.stop_data_log()?;
+        let log = DataLog::create(path, DataFormat::for_path(path), flush_ticks)?;
+        self.data_log = Some(Arc::new(Mutex::new(log)));
+        Ok(())
+    }
+
+    /// Writes the rows the data log has queued, if one is running.
+    pub fn flush_data_log(&mut self) -> Result<(), WeaveError> {
+        match self.data_log.as_ref().map(|log| log.lock()) {
+            Some(Ok(mut log)) => log.flush(),
+            Some(Err(_)) => Err(WeaveError::Validation("the data log was poisoned".to_string())),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes out and closes the data log; false if none was running.
+    pub fn stop_data_log(&mut self) -> Result<bool, WeaveError> {
+        let Some(log) = self.data_log.take() else {
+            return Ok(false);
+        };
+        let closed = match log.lock() {
+            Ok(mut log) => log.close(),
+            Err(_) => Err(WeaveError::Validation("the data log was poisoned".to_string())),
+        };
+        closed.map(|_| true)
+    }
+
+    /// The file the data log writes, while one runs.
+    pub fn data_log_path(&self) -> Option<PathBuf> {
+        self.data_log.as_ref().and_then(|log| log.lock().ok().map(|log| log.path().to_path_buf()))
+    }
+
+    /// Sends the tick just stepped to the telemetry clients, with the safety
+    /// events queued since `queued_events`.
+    #[cfg(feature = "telemetry")]
+    fn publish_telemetry(&self, tension: Scalar, queued_events: usize) {
+        let Some(server) = &self.telemetry else {
+            return;
+        };
+        server.publish(&TelemetryFrame {
+            tick: self.tick(),
+            tension,
+            smoothed_tension: self.smoothed_tension(),
+            halted: self.is_halted(),
+            fields: &self.fields,
+            safety_events: self.safety_events[queued_events.min(self.safety_events.len())..].iter().map(Into::into).collect(),
+        });
+    }
+
+    /// Streams every tick from now on, as a JSON frame of its tension, field
+    /// values and safety events, to the WebSocket clients of a server on
+    /// `address`, replacing any server already running. Returns the address
+    /// listened on. Fails if it can't be bound, or without the `telemetry`
+    /// feature.
+    pub fn start_telemetry(&mut self, address: &str) -> Result<SocketAddr, WeaveError> {
+        #[cfg(feature = "telemetry")]
+        {
+            // Stopped first, so a restart may bind the same port.
+            self.telemetry = None;
+            let server = TelemetryServer::start(address)?;
+            let bound = server.address();
+            self.telemetry = Some(Arc::new(server));
+            Ok(bound)
+        }
+        #[cfg(not(feature = "telemetry"))]
+        {
+            Err(WeaveError::Validation(format!("can't serve telemetry on {}: this build lacks the telemetry feature", address)))
+        }
+    }
+
+    /// Stops the telemetry server, disconnecting its clients; false if none
+    /// was running.
+    pub fn stop_telemetry(&mut self) -> bool {
+        #[cfg(feature = "telemetry")]
+        {
+            self.telemetry.take().is_some()
+        }
+        #[cfg(not(feature = "telemetry"))]
+        {
+            false
+        }
+    }
+
+    /// Where the telemetry server listens, while one runs.
+    pub fn telemetry_address(&self) -> Option<SocketAddr> {
+        #[cfg(feature = "telemetry")]
+        {
+            self.telemetry.as_ref().map(|server| server.address())
+        }
+        #[cfg(not(feature = "telemetry"))]
+        {
+            None
+        }
+    }
+
+    /// Telemetry clients connected now.
+    pub fn telemetry_clients(&self) -> usize {
+        #[cfg(feature = "telemetry")]
+        {
+            self.telemetry.as_ref().map_or(0, |server| server.clients())
+        }
+        #[cfg(not(feature = "telemetry"))]
+        {
+            0
+        }
+    }
+
+    /// Runs `step_dt` on a copy of the interpreter, with its rates, smoothing,
+    /// rules and history, and reports what the step would change. This
+    /// interpreter is left exactly as it was. A real step on the same inputs
+    /// then makes the same changes, unless a step budget makes time slicing
+    /// depend on wall time.
+    pub fn preview_step(&self, sensors: &HashMap<String, Scalar>, agents: &Fields, dt: Scalar) -> Result<StepPreview, WeaveError> {
+        let mut preview = self.clone();
+        preview.reload_watch = None;
+        preview.metrics_server = None;
+        preview.data_log = None;
+        #[cfg(feature = "telemetry")]
+        {
+            preview.telemetry = None;
+        }
+        preview.recording = None;
+        preview.replay = None;
+        preview.trace = Trace::default();
+        preview.log = Log::default();
+        preview.log.set_level(Level::Error);
+        let tension = preview.step_dt(sensors, agents, dt)?;
+        let mut changes = Vec::new();
+        for (agent, params) in &preview.fields {
+            for (param, &new_value) in params {
+                let old_value = self.fields.get(agent).and_then(|params| params.get(param)).copied();
+                if old_value != Some(new_value) {
+                    let old_value = old_value.unwrap_or(0.0);
+                    changes.push(FieldChange { agent: agent.clone(), param: param.clone(), old_value, new_value });
+                }
+            }
+        }
+        let before = self.vector_model();
+        let vectors = preview.vector_model().into_iter().filter(|vector| !before.contains(vector)).collect();
+        Ok(StepPreview { tension, changes, vectors, fired: preview.frame_metrics.fired_rules })
+    }
+
+    /// Drifts and resolves the next round-robin batch of `agents`, each over
+    /// the number of steps since it last moved, so every agent advances at the
+    /// same average rate as an unsliced step. At least one agent moves per
+    /// tick even when the budget is already spent.
+    fn step_slice(&mut self, agents: &Fields, tension: Scalar, dt: Scalar, started: Instant) -> Scalar {
+        let roster: Vec<&String> = agents.keys().collect();
+        if roster.len() != self.time_slice.roster_len {
+            self.time_slice.roster_len = roster.len();
+            self.time_slice.cursor %= roster.len().max(1);
+        }
+        let limit = match self.time_slice.agents_per_tick {
+            0 => roster.len(),
+            k => k.min(roster.len()),
+        };
+        let mut total_drift = 0.0;
+        for moved in 0..limit {
+            let budget = self.time_slice.budget_micros;
+            if moved > 0 && budget > 0 && started.elapsed().as_micros() as u64 >= budget {
+                break;
+            }
+            let agent = roster[self.time_slice.cursor];
+            let tick = self.time_slice.tick;
+            let last = self.time_slice.last_stepped.insert(agent.clone(), tick).unwrap_or(self.time_slice.origin);
+            let batch = [(agent.as_str(), (tick - last) as Scalar * dt)];
+            total_drift += self.drift(&batch, tension, None);
+            self.resolve(&batch, tension, None);
+            self.time_slice.cursor += 1;
+            if self.time_slice.cursor == roster.len() {
+                self.time_slice.cursor = 0;
+                self.time_slice.origin = tick;
+                self.time_slice.last_stepped.retain(|name, _| agents.contains_key(name));
+            }
+        }
+        total_drift
+    }
+
+    /// Moves at most `count` agents per `step`, round-robin; 0 moves them all.
+    pub fn set_agents_per_tick(&mut self, count: usize) {
+        let was_enabled = self.time_slice.enabled();
+        self.time_slice.agents_per_tick = count;
+        if !was_enabled {
+            self.time_slice.restart();
+        }
+    }
+
+    /// Stops moving agents once `step` has run for `micros`; 0 means no budget.
+    /// Sweeps then depend on wall time, so replays of a budgeted run may diverge.
+    pub fn set_step_budget_micros(&mut self, micros: u64) {
+        let was_enabled = self.time_slice.enabled();
+        self.time_slice.budget_micros = micros;
+        if !was_enabled {
+            self.time_slice.restart();
+        }
+    }
+
+    /// How far the current sweep has got through the roster, from 0 up to 1;
+    /// always 0 while time slicing is off.
+    pub fn step_progress(&self) -> Scalar {
+        if !self.time_slice.enabled() || self.time_slice.roster_len == 0 {
+            return 0.0;
+        }
+        self.time_slice.cursor as Scalar / self.time_slice.roster_len as Scalar
+    }
+
+    /// Schedules `field.param = value` for `tick`, returning the id
+    /// `cancel_scheduled` takes. A tick that has already come fires at once.
+    /// The field must be declared.
+    pub fn schedule_set(&mut self, tick: usize, field: &str, param: &str, value: Scalar) -> Result<u64, WeaveError> {
+        if !self.fields.contains_key(field) {
+            return Err(WeaveError::MissingField(field.to_string()));
+        }
+        let set = ScheduledSet { tick, field: field.to_string(), param: param.to_string(), value };
+        let id = self.schedule.add(set, false);
+        self.run_schedule();
+        Ok(id)
+    }
+
+    /// Sets still waiting for their tick, in the order they will fire.
+    pub fn scheduled(&self) -> &[ScheduledAction] {
+        self.schedule.pending()
+    }
+
+    /// Drops a pending set; false if it already fired or was never scheduled.
+    pub fn cancel_scheduled(&mut self, id: u64) -> bool {
+        self.schedule.cancel(id)
+    }
+
+    /// Fires every scheduled set due by the current tick. Sets on the same
+    /// tick fire in the order they were scheduled, so the last write wins.
+    fn run_schedule(&mut self) {
+        for action in self.schedule.take_due(self.tick()) {
+            let set = action.set;
+            let cause = Cause::new(Phase::Schedule, "tick", Some(set.tick as Scalar));
+            if self.write_param(&set.field, &set.param, set.value, cause).is_none() {
+                self.warn(format!("skipped `{}`: field {} is not declared", set, set.field));
+            }
+        }
+    }
+
+    /// Replaces the script goals. With `keep_unchanged`, a goal whose
+    /// definition is identical to the old one keeps its progress.
+    fn set_goals(&mut self, goals: Vec<Goal>, keep_unchanged: bool) {
+        let kept: HashMap<String, GoalProgress> = if keep_unchanged {
+            goals
+                .iter()
+                .filter(|goal| self.goals.contains(goal))
+                .filter_map(|goal| self.goal_progress.get(&goal.name).map(|progress| (goal.name.clone(), *progress)))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        self.completed_goals.retain(|name| kept.contains_key(name));
+        self.goal_progress = kept;
+        self.goals = goals;
+    }
+
+    /// Advances every goal by one step. A condition that cannot be evaluated,
+    /// e.g. because a sensor it reads has no value, counts as not satisfied.
+    fn evaluate_goals(&mut self, sensors: &HashMap<String, Scalar>) {
+        let mut completed = Vec::new();
+        let mut missing = Vec::new();
+        for goal in &self.goals {
+            let satisfied = goal.conditions.iter().all(|condition| match self.compare(condition, sensors) {
+                Ok(holds) => holds,
+                Err(EvalError::Unknown(name)) => {
+                    if !name.contains('.') {
+                        missing.push(name);
+                    }
+                    false
+                }
+                Err(EvalError::DivisionByZero | EvalError::NegativeSqrt(_)) => false,
+            });
+            if self.goal_progress.entry(goal.name.clone()).or_default().update(satisfied, goal.hold_ticks) {
+                completed.push(goal.name.clone());
+            }
+        }
+        for sensor in &missing {
+            self.note_missing_sensor(sensor);
+        }
+        self.completed_goals.extend(completed);
+    }
+
+    /// Evaluates one goal comparison. Bare names read `sensors`, then the latest
+    /// tension for `tension`, then constants, then the sensor's policy;
+    /// `field.param` reads the live fields, and `global.*` a parent's in a child.
+    fn compare(&self, condition: &Comparison, sensors: &HashMap<String, Scalar>) -> Result<bool, EvalError> {
+        let name = |name: &str| self.named_value(name, sensors);
+        let param = |field: &str, param: &str| self.param_value(field, param).map(to_f64);
+        let lhs = condition.lhs.eval(&name, &param)?;
+        let rhs = condition.rhs.eval(&name, &param)?;
+        Ok(condition.comparator.holds(lhs as Scalar, rhs as Scalar))
+    }
+
+    /// A bare name in a goal or invariant condition, see `compare`.
+    fn named_value(&self, name: &str, sensors: &HashMap<String, Scalar>) -> Option<f64> {
+        if receive_topic(name).is_some() {
+            // Only a `set` has an agent to receive on; see `received`.
+            return None;
+        }
+        let latest_tension = self.tension_history.last().filter(|_| name == "tension" && !sensors.contains_key(name));
+        sensors
+            .get(name)
+            .copied()
+            .or(latest_tension)
+            .map(to_f64)
+            .or_else(|| self.constants.get(name).map(|constant| constant.value))
+            .or_else(|| self.resolve_sensor(sensors, name).map(to_f64))
+    }
+
+    /// Computes every derived param again from `sensors` and the live
+    /// params, in file order, so each sees the ones before it. A param whose
+    /// expression can't be computed, for a missing sensor or a division by
+    /// zero, keeps its value.
+    fn derive_params(&mut self, sensors: &HashMap<String, Scalar>) {
+        if self.derived_params.is_empty() {
+            return;
+        }
+        let derived_params = std::mem::take(&mut self.derived_params);
+        for derived in &derived_params {
+            let value = {
+                let own = self.fields.get(&derived.field);
+                let name = |name: &str| own.and_then(|params| params.get(name)).map(|value| to_f64(*value)).or_else(|| self.named_value(name, sensors));
+                let param = |field: &str, param: &str| self.param_value(field, param).map(to_f64);
+                derived.expr.eval(&name, &param)
+            };
+            match value {
+                Ok(value) => {
+                    self.write_param(&derived.field, &derived.param, value as Scalar, Cause::new(Phase::Derive, "expression", None));
+                }
+                Err(EvalError::Unknown(name)) if !name.contains('.') => self.note_missing_sensor(&name),
+                Err(_) => {}
+            }
+        }
+        self.derived_params = derived_params;
+    }
+
+    /// Runs every script loop once, in file order, and returns the drift its
+    /// `drift` passes made. Each loop starts from the step's `tension`, and a
+    /// `tension` statement, or the test of a `while` on tension after the
+    /// first, updates it for the rest of that loop. A loop
+    /// stops after `loop_limit` iterations, warning the first time.
+    fn run_loops(&mut self, sensors: &HashMap<String, Scalar>, agents: &Fields, dt: Scalar, tension: Scalar) -> Scalar {
+        if self.loops.is_empty() {
+            return 0.0;
+        }
+        let loops = std::mem::take(&mut self.loops);
+        let batch = uniform(agents, dt);
+        let mut total_drift = 0.0;
+        for (index, looped) in loops.iter().enumerate() {
+            let mut tension = tension;
+            let mut iterations = 0;
+            loop {
+                let more = match &looped.kind {
+                    LoopKind::Repeat(count) => iterations < *count,
+                    LoopKind::While(condition) if condition.sensor() == Some("tension") => {
+                        // The body's passes move the fields, so each later test
+                        // sees the tension they leave.
+                        if iterations > 0 {
+                            if let Some(current) = self.reference_tension(sensors) {
+                                tension = current;
+                            }
+                        }
+                        condition.holds(tension)
+                    }
+                    LoopKind::While(condition) => self.condition_holds(condition, sensors) == Some(true),
+                };
+                if !more {
+                    break;
+                }
+                if iterations == self.loop_limit {
+                    if self.capped_loops.insert(index) {
+                        self.warn(format!("{} stopped after {} iterations, the loop limit", looped, self.loop_limit));
+                    }
+                    break;
+                }
+                iterations += 1;
+                for statement in &looped.body {
+                    match statement {
+                        LoopStatement::Drift => total_drift += self.drift(&batch, tension, None),
+                        LoopStatement::Resolve => self.resolve(&batch, tension, None),
+                        LoopStatement::Tension => {
+                            if let Some(current) = self.reference_tension(sensors) {
+                                tension = current;
+                            }
+                        }
+                        LoopStatement::Set { field, param, value } => {
+                            let name = |name: &str| match name {
+                                "tension" => Some(to_f64(tension)),
+                                _ => self.received(field, name).or_else(|| self.named_value(name, sensors)),
+                            };
+                            let param_value = |field: &str, param: &str| self.param_value(field, param).map(to_f64);
+                            if let Ok(value) = value.eval(&name, &param_value) {
+                                self.write_param(field, param, value as Scalar, Cause::new(Phase::Loop, "loop", Some(tension)));
+                            }
+                        }
+                        LoopStatement::Send { target, topic, value } => {
+                            let name = |name: &str| if name == "tension" { Some(to_f64(tension)) } else { self.named_value(name, sensors) };
+                            let param_value = |field: &str, param: &str| self.param_value(field, param).map(to_f64);
+                            if let Ok(value) = value.eval(&name, &param_value) {
+                                self.send_message(target, topic, value as Scalar);
+                            }
+                        }
+                        LoopStatement::Emit { signal, value } => {
+                            let name = |name: &str| if name == "tension" { Some(to_f64(tension)) } else { self.named_value(name, sensors) };
+                            let param_value = |field: &str, param: &str| self.param_value(field, param).map(to_f64);
+                            if let Ok(value) = value.eval(&name, &param_value) {
+                                self.emitted_signals.push(EmittedSignal { signal: signal.clone(), value: value as Scalar, tick: self.tick() });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.loops = loops;
+        total_drift
+    }
+
+    /// Runs the actions of every `on` handler whose test holds now but
+    /// didn't on the last step. A test with no value counts as false.
+    fn run_handlers(&mut self, sensors: &HashMap<String, Scalar>) {
+        if self.handlers.is_empty() {
+            return;
+        }
+        let handlers = std::mem::take(&mut self.handlers);
+        for (index, handler) in handlers.iter().enumerate() {
+            if self.condition_holds(&handler.condition, sensors) != Some(true) {
+                self.armed_handlers.remove(&index);
+                continue;
+            }
+            if !self.armed_handlers.insert(index) {
+                continue;
+            }
+            let value = handler.condition.sensor().and_then(|sensor| self.sensed(sensors, sensor));
+            let trigger = handler.condition.sensor().unwrap_or("on").to_string();
+            for action in &handler.actions {
+                match action {
+                    EventAction::Set { field, param, value: expr } => {
+                        let name = |name: &str| self.received(field, name).or_else(|| self.named_value(name, sensors));
+                        let param_value = |field: &str, param: &str| self.param_value(field, param).map(to_f64);
+                        if let Ok(result) = expr.eval(&name, &param_value) {
+                            self.write_param(field, param, result as Scalar, Cause::new(Phase::Event, &trigger, value));
+                        }
+                    }
+                    EventAction::Send { target, topic, value: expr } => {
+                        let name = |name: &str| self.named_value(name, sensors);
+                        let param_value = |field: &str, param: &str| self.param_value(field, param).map(to_f64);
+                        if let Ok(result) = expr.eval(&name, &param_value) {
+                            self.send_message(target, topic, result as Scalar);
+                        }
+                    }
+                    EventAction::Emit { signal, value: expr } => {
+                        let name = |name: &str| self.named_value(name, sensors);
+                        let param_value = |field: &str, param: &str| self.param_value(field, param).map(to_f64);
+                        if let Ok(result) = expr.eval(&name, &param_value) {
+                            self.emitted_signals.push(EmittedSignal { signal: signal.clone(), value: result as Scalar, tick: self.tick() });
+                        }
+                    }
+                    EventAction::Call(call) => self.lab_calls.push(call.clone()),
+                    EventAction::Halt => self.halted_by = Some(format!("on {}", handler.condition)),
+                }
+            }
+            self.fired_events.push(FiredEvent { handler: index, condition: handler.condition.to_string(), value, tick: self.tick() });
+        }
+        self.handlers = handlers;
+    }
+
+    /// What `name` reads in a `set` on `field`, when it is `receive(topic)`:
+    /// the latest message on the topic delivered to `field` this tick.
+    fn received(&self, field: &str, name: &str) -> Option<f64> {
+        let topic = receive_topic(name)?;
+        self.messages.receive(field, topic).map(to_f64)
+    }
+
+    /// Queues `value` for `target` on `topic`, to be read on the next tick.
+    /// Script `send` statements go through here too.
+    pub fn send_message(&mut self, target: &str, topic: &str, value: Scalar) {
+        self.messages.send(Message { target: target.to_string(), topic: topic.to_string(), value, tick: self.tick() });
+    }
+
+    /// Messages delivered to `target` this tick, in the order they were sent.
+    pub fn inbox<'a>(&'a self, target: &'a str) -> impl Iterator<Item = &'a Message> {
+        self.messages.inbox(target)
+    }
+
+    /// Messages sent this tick, which the next step delivers.
+    pub fn pending_messages(&self) -> &[Message] {
+        self.messages.pending()
+    }
+
+    /// Script `on` handlers, in file order.
+    pub fn handlers(&self) -> &[EventHandler] {
+        &self.handlers
+    }
+
+    /// Script behavior trees, in file order.
+    pub fn behaviors(&self) -> &[BehaviorTree] {
+        &self.behaviors
+    }
+
+    /// What `agent`'s behavior tree reported on its last tick; `None` if no
+    /// tree has ticked it since the last load.
+    pub fn behavior_status(&self, agent: &str) -> Option<BehaviorStatus> {
+        self.behavior_status.get(agent).copied()
+    }
+
+    /// Ticks every behavior tree once for each agent tagged with its role, in
+    /// file then name order, and returns how many agents it ticked. `actions`
+    /// runs the leaves the host registered, given the action and the agent,
+    /// and returns `None` for one it doesn't know, which fails with a warning
+    /// the first time. The `drift` and `resolve` actions move just the agent
+    /// over `dt` against the latest tension. Nothing ticks while stepping is
+    /// halted.
+    pub fn tick_behaviors(&mut self, sensors: &HashMap<String, Scalar>, dt: Scalar, actions: &mut dyn FnMut(&str, &str) -> Option<BehaviorStatus>) -> usize {
+        if self.behaviors.is_empty() || self.is_halted() {
+            return 0;
+        }
+        let behaviors = std::mem::take(&mut self.behaviors);
+        let mut ticked = 0;
+        for tree in &behaviors {
+            for agent in self.agents_with_tag(&tree.role) {
+                let status = self.tick_behavior(&tree.root, &agent, sensors, dt, actions);
+                self.behavior_status.insert(agent, status);
+                ticked += 1;
+            }
+        }
+        self.behaviors = behaviors;
+        ticked
+    }
+
+    fn tick_behavior(
+        &mut self,
+        node: &BehaviorNode,
+        agent: &str,
+        sensors: &HashMap<String, Scalar>,
+        dt: Scalar,
+        actions: &mut dyn FnMut(&str, &str) -> Option<BehaviorStatus>,
+    ) -> BehaviorStatus {
+        match node {
+            BehaviorNode::Selector(children) => {
+                for child in children {
+                    match self.tick_behavior(child, agent, sensors, dt, actions) {
+                        BehaviorStatus::Failure => continue,
+                        status => return status,
+                    }
+                }
+                BehaviorStatus::Failure
+            }
+            BehaviorNode::Sequence(children) => {
+                for child in children {
+                    match self.tick_behavior(child, agent, sensors, dt, actions) {
+                        BehaviorStatus::Success => continue,
+                        status => return status,
+                    }
+                }
+                BehaviorStatus::Success
+            }
+            BehaviorNode::Check(condition) => match self.condition_holds(&bind_self(condition, agent), sensors) {
+                Some(true) => BehaviorStatus::Success,
+                _ => BehaviorStatus::Failure,
+            },
+            BehaviorNode::Set { field, param, value } => {
+                let bound = |field: &'_ str| -> String { if field == SELF_FIELD { agent.to_string() } else { field.to_string() } };
+                let name = |name: &str| self.named_value(name, sensors);
+                let param_value = |field: &str, param: &str| self.param_value(&bound(field), param).map(to_f64);
+                match value.eval(&name, &param_value) {
+                    Ok(value) => {
+                        self.write_param(&bound(field), param, value as Scalar, Cause::new(Phase::Behavior, "behavior", None));
+                        BehaviorStatus::Success
+                    }
+                    Err(_) => BehaviorStatus::Failure,
+                }
+            }
+            BehaviorNode::Action(name) => {
+                let tension = self.tension_history.last().unwrap_or(0.0);
+                match name.as_str() {
+                    "drift" => {
+                        self.drift(&[(agent, dt)], tension, None);
+                        BehaviorStatus::Success
+                    }
+                    "resolve" => {
+                        self.resolve(&[(agent, dt)], tension, None);
+                        BehaviorStatus::Success
+                    }
+                    _ => actions(name, agent).unwrap_or_else(|| {
+                        if self.missing_actions.insert(name.clone()) {
+                            self.warn(format!("behavior action {} isn't registered; it fails until it is", name));
+                        }
+                        BehaviorStatus::Failure
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Handlers that fired since the last call, oldest first.
+    pub fn take_fired_events(&mut self) -> Vec<FiredEvent> {
+        std::mem::take(&mut self.fired_events)
+    }
+
+    /// Signals `emit` statements queued since the last call, oldest first.
+    pub fn take_emitted_signals(&mut self) -> Vec<EmittedSignal> {
+        std::mem::take(&mut self.emitted_signals)
+    }
+
+    /// The tension `sensors` give right now, without recording it; `None`
+    /// when the tension model can't compute one.
+    fn reference_tension(&self, sensors: &HashMap<String, Scalar>) -> Option<Scalar> {
+        let terms = self.model_terms(sensors).ok()?;
+        Some(terms.iter().fold(0.0, |sum: Scalar, term| sum + term.contribution))
+    }
+
+    /// The terms of the tension `sensors` give under the installed model,
+    /// else the script's `tension` block, else the coherence default.
+    fn model_terms(&self, sensors: &HashMap<String, Scalar>) -> Result<Vec<TensionTerm>, WeaveError> {
+        let sensor = |name: &str| self.resolve_sensor(sensors, name);
+        let input = TensionInput::new(&self.fields, &self.tension_reference, &sensor);
+        match &self.tension_model {
+            Some(model) => model.terms(&input),
+            None if !self.script_tension.terms.is_empty() => self.script_tension.terms(&input),
+            None => CoherenceTension.terms(&input),
+        }
+    }
+
+    /// Computes tension with `model` from now on, whatever the script's
+    /// `tension` block says, until `clear_tension_model`.
+    pub fn set_tension_model(&mut self, model: Arc<dyn TensionModel>) {
+        self.tension_model = Some(model);
+    }
+
+    /// Goes back to the script's `tension` block, or the coherence default.
+    pub fn clear_tension_model(&mut self) {
+        self.tension_model = None;
+    }
+
+    /// The terms of the script's `tension` block; empty for the default model.
+    pub fn tension_block(&self) -> &[WeightedTerm] {
+        &self.script_tension.terms
+    }
+
+    /// Adds the agent `name` as a copy of the field `template`, starting from
+    /// the values the script declared for it, or its current values for a
+    /// field the script doesn't declare; failing a field, of the `template`
+    /// block of that name. A reload keeps spawned agents.
+    pub fn spawn_agent(&mut self, name: &str, template: &str) -> Result<(), WeaveError> {
+        if self.fields.contains_key(name) || name == GLOBAL_FIELD || self.is_parent_global(name) {
+            return Err(WeaveError::Validation(format!("agent {} already exists", name)));
+        }
+        let params = match self.declared_fields.get(template).or_else(|| self.fields.get(template)) {
+            Some(params) => {
+                let params = params.clone();
+                copy_template(name, template, &mut self.attributes, &mut self.derived_params);
+                params
+            }
+            None => {
+                let body = self.templates.get(template).cloned().ok_or_else(|| WeaveError::MissingField(template.to_string()))?;
+                if !body.attributes.is_empty() {
+                    self.attributes.insert(name.to_string(), body.attributes);
+                }
+                if !body.bounds.is_empty() {
+                    self.param_bounds.insert(name.to_string(), body.bounds);
+                }
+                self.derived_params.extend(body.derived_params.into_iter().map(|derived| DerivedParam { field: name.to_string(), ..derived }));
+                body.params
+            }
+        };
+        self.fields.insert(name.to_string(), Params::with_capacity(params.len()));
+        for (param, value) in params {
+            self.write_param(name, &param, value, Cause::new(Phase::Code, "spawn", None));
+        }
+        self.agents.insert(name.to_string(), RegisteredAgent { template: template.to_string(), from_script: false });
+        self.derive_params(&self.last_sensors.clone());
+        Ok(())
+    }
+
+    /// Removes the field `name` and everything kept about it. A field the
+    /// script declares comes back on the next reload. The tension reference
+    /// can't be despawned.
+    pub fn despawn_agent(&mut self, name: &str) -> Result<(), WeaveError> {
+        if name == self.tension_reference {
+            return Err(WeaveError::Validation(format!("{} is the tension reference and can't be despawned", name)));
+        }
+        if self.fields.shift_remove(name).is_none() {
+            return Err(WeaveError::MissingField(name.to_string()));
+        }
+        let prefix = format!("{}.", name);
+        self.attributes.remove(name);
+        self.param_bounds.remove(name);
+        self.agents.shift_remove(name);
+        self.derived_params.retain(|derived| derived.field != name);
+        self.locked_agents.remove(name);
+        self.undriven_agents.remove(name);
+        self.pid_gains.remove(name);
+        self.pid_sensors.remove(name);
+        self.pid_states.retain(|key, _| !key.starts_with(&prefix));
+        self.drift_pid_states.remove(name);
+        self.drift_smoothed.remove(name);
+        self.watches.remove(name);
+        self.tick_changes.shift_remove(name);
+        self.time_slice.last_stepped.remove(name);
+        Ok(())
+    }
+
+    /// Agents declared with `agent ... from` or spawned, in the order they were added.
+    pub fn agents(&self) -> &IndexMap<String, RegisteredAgent> {
+        &self.agents
+    }
+
+    /// Caps the iterations each loop runs per step; at least 1.
+    pub fn set_loop_limit(&mut self, limit: u32) {
+        self.loop_limit = limit.max(1);
+    }
+
+    pub fn loop_limit(&self) -> u32 {
+        self.loop_limit
+    }
+
+    /// Script `repeat` and `while` blocks, in file order.
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    /// The script's `fn` declarations and those `execute_code` added.
+    pub fn functions(&self) -> &Functions {
+        &self.functions
+    }
+
+    /// The script's `template` blocks and those `execute_code` added.
+    pub fn templates(&self) -> &IndexMap<String, Template> {
+        &self.templates
+    }
+
+    /// Params computed from an expression every step, in file order.
+    pub fn derived_params(&self) -> &[DerivedParam] {
+        &self.derived_params
+    }
+
+    /// Script goals, in file order.
+    pub fn goals(&self) -> &[Goal] {
+        &self.goals
+    }
+
+    /// Progress of every script goal, in file order.
+    pub fn goal_progress(&self) -> impl Iterator<Item = (&Goal, GoalProgress)> {
+        self.goals.iter().map(|goal| (goal, self.goal_progress.get(&goal.name).copied().unwrap_or_default()))
+    }
+
+    /// Goals that completed since the last take, in completion order.
+    pub fn take_completed_goals(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.completed_goals)
+    }
+
+    /// Starts `name` over, so it can complete again. False if there is no such goal.
+    pub fn reset_goal(&mut self, name: &str) -> bool {
+        if !self.goals.iter().any(|goal| goal.name == name) {
+            return false;
+        }
+        self.goal_progress.remove(name);
+        self.completed_goals.retain(|completed| completed != name);
+        true
+    }
+
+    /// Replaces the script invariants on reload; one with an unchanged
+    /// definition keeps its status.
+    fn set_invariants(&mut self, invariants: Vec<Invariant>) {
+        self.invariant_status = invariants
+            .iter()
+            .map(|invariant| {
+                let old = self.invariants.iter().position(|old| old == invariant);
+                old.map(|index| self.invariant_status[index]).unwrap_or_default()
+            })
+            .collect();
+        self.invariants = invariants;
+    }
+
+    /// Checks every invariant after a step. One that held before and fails
+    /// now, or can't be evaluated, is a violation: it is recorded, warned
+    /// about and handled by the policy. True if the step was rolled back.
+    fn check_invariants(&mut self, sensors: &HashMap<String, Scalar>) -> bool {
+        let checkpoint = self.checkpoint.take();
+        let holds: Vec<bool> = self
+            .invariants
+            .iter()
+            .map(|invariant| invariant.conditions.iter().all(|condition| self.compare(condition, sensors).unwrap_or(false)))
+            .collect();
+        let tick = self.tick();
+        let mut violations = Vec::new();
+        for (index, holds) in holds.into_iter().enumerate() {
+            let status = &mut self.invariant_status[index];
+            let newly_violated = status.passing && !holds;
+            status.passing = holds;
+            if newly_violated {
+                status.violations += 1;
+                status.last_violation_tick = Some(tick);
+                violations.push(index);
+            }
+        }
+        if violations.is_empty() {
+            return false;
+        }
+        let violations: Vec<InvariantViolation> = violations
+            .into_iter()
+            .map(|index| {
+                let invariant = &self.invariants[index];
+                let values = invariant
+                    .operands()
+                    .into_iter()
+                    .map(|operand| {
+                        let value = match operand.split_once('.') {
+                            Some((field, param)) => self.param_value(field, param),
+                            None => self.named_value(&operand, sensors).map(|value| value as Scalar),
+                        };
+                        (operand, value)
+                    })
+                    .collect();
+                InvariantViolation { name: invariant.name.clone(), text: invariant.text(), tick, values }
+            })
+            .collect();
+        let rolled_back = match (self.invariant_policy, checkpoint) {
+            (InvariantPolicy::Rollback, Some(checkpoint)) => {
+                self.roll_back(checkpoint);
+                for violation in &violations {
+                    if let Some(index) = self.invariants.iter().position(|invariant| invariant.name == violation.name) {
+                        self.invariant_status[index].passing = true;
+                    }
+                }
+                true
+            }
+            _ => false,
+        };
+        let outcome = match self.invariant_policy {
+            InvariantPolicy::Warn => "",
+            InvariantPolicy::Halt => "; stepping is halted until resume",
+            InvariantPolicy::Rollback if rolled_back => "; the step was rolled back",
+            InvariantPolicy::Rollback => "",
+        };
+        for violation in &violations {
+            self.warn(format!("{}{}", violation, outcome));
+        }
+        if self.invariant_policy == InvariantPolicy::Halt {
+            self.halted_by = violations.first().map(|violation| violation.name.clone());
+        }
+        self.last_invariant_violation = violations.last().cloned();
+        self.invariant_violations.extend(violations);
+        rolled_back
+    }
+
+    /// The running state for a rollback; see [`Checkpoint`].
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            state: ExperimentState {
+                fields: self.fields.clone(),
+                attributes: self.attributes.clone(),
+                tension_history: Vec::new(),
+                dropped_history: self.tension_history.dropped(),
+                smoothed_tension: self.smoothed_tension,
+                schedule: self.schedule.clone(),
+                goal_progress: self.goal_progress.clone(),
+                locked_agents: self.locked_agents.clone(),
+                noise_seed: self.noise.seed(),
+            },
+            tick: self.tick(),
+            tensions: self.tension_history.total(),
+            field_changes: self.field_changes.len(),
+            lab_calls: self.lab_calls.len(),
+            emitted_signals: self.emitted_signals.len(),
+            messages: self.messages.pending().len(),
+        }
+    }
+
+    /// Undoes everything since `checkpoint` was taken, including the ticks:
+    /// the tensions computed since are dropped from the history. The drift
+    /// noise carries on rather than repeating its draws.
+    fn roll_back(&mut self, checkpoint: Checkpoint) {
+        let undone = self.tension_history.total().saturating_sub(checkpoint.tensions);
+        self.tension_history.truncate(self.tension_history.len().saturating_sub(undone));
+        self.steps = checkpoint.tick;
+        let state = checkpoint.state;
+        self.fields = state.fields;
+        self.attributes = state.attributes;
+        self.smoothed_tension = state.smoothed_tension;
+        self.schedule = state.schedule;
+        self.goal_progress = state.goal_progress;
+        self.completed_goals.retain(|name| self.goal_progress.get(name).is_some_and(|progress| progress.completed));
+        self.locked_agents = state.locked_agents;
+        self.field_changes.truncate(checkpoint.field_changes);
+        self.lab_calls.truncate(checkpoint.lab_calls);
+        self.emitted_signals.truncate(checkpoint.emitted_signals);
+        self.messages.truncate_pending(checkpoint.messages);
+        self.tick_changes.clear();
+    }
+
+    /// Script invariants, in file order.
+    pub fn invariants(&self) -> &[Invariant] {
+        &self.invariants
+    }
+
+    /// Status of every script invariant, in file order.
+    pub fn invariant_status(&self) -> impl Iterator<Item = (&Invariant, InvariantStatus)> {
+        self.invariants.iter().zip(self.invariant_status.iter().copied())
+    }
+
+    pub fn set_invariant_policy(&mut self, policy: InvariantPolicy) {
+        self.invariant_policy = policy;
+    }
+
+    pub fn invariant_policy(&self) -> InvariantPolicy {
+        self.invariant_policy
+    }
+
+    /// Violations since the last call, oldest first.
+    pub fn take_invariant_violations(&mut self) -> Vec<InvariantViolation> {
+        std::mem::take(&mut self.invariant_violations)
+    }
+
+    pub fn last_invariant_violation(&self) -> Option<&InvariantViolation> {
+        self.last_invariant_violation.as_ref()
+    }
+
+    /// The invariant, or `on <test>` for a handler's `halt;`, that halted
+    /// stepping, if one has.
+    pub fn halted_by(&self) -> Option<&str> {
+        self.halted_by.as_deref()
+    }
+
+    /// Whether stepping is halted, by an invariant or an emergency stop.
+    pub fn is_halted(&self) -> bool {
+        self.halted_by.is_some() || self.emergency_stopped
+    }
+
+    /// Halts stepping and freezes drift and resolve, including calls made
+    /// outside `step`, until `resume`, and zeros every agent's `velocity`
+    /// so nothing coasts once stepping resumes. Loading a script keeps the
+    /// stop. False if the experiment was already stopped this way.
+    pub fn emergency_stop(&mut self) -> bool {
+        for attributes in self.attributes.values_mut() {
+            if let Some(ParamValue::Vector(velocity)) = attributes.get_mut("velocity") {
+                velocity.iter_mut().for_each(|component| *component = 0.0);
+            }
+        }
+        self.pid_states.clear();
+        self.clear_drift_states();
+        !std::mem::replace(&mut self.emergency_stopped, true)
+    }
+
+    pub fn is_emergency_stopped(&self) -> bool {
+        self.emergency_stopped
+    }
+
+    /// Lets a halted or emergency-stopped experiment step again; false if it
+    /// wasn't halted. An invariant that still fails halts it again only after
+    /// it has held once.
+    pub fn resume(&mut self) -> bool {
+        let stopped = std::mem::take(&mut self.emergency_stopped);
+        self.halted_by.take().is_some() || stopped
+    }
+
+    /// Metrics of the last `step`; default until the first one.
+    pub fn frame_metrics(&self) -> &FrameMetrics {
+        &self.frame_metrics
+    }
+
+    /// Per-phase timings; see [`Profiler`].
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    pub fn profiler_mut(&mut self) -> &mut Profiler {
+        &mut self.profiler
+    }
+
+    fn tick_record(&self, sensors: &HashMap<String, Scalar>, agents: &Fields, tension: Scalar) -> TickRecord {
+        let mut sensors: Vec<(String, Scalar)> = sensors.iter().map(|(name, value)| (name.clone(), *value)).collect();
+        sensors.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut agents: Vec<String> = agents.keys().cloned().collect();
+        agents.sort();
+        let mut deltas: Vec<(String, String, Scalar)> = self
+            .tick_changes
+            .iter()
+            .flat_map(|(agent, params)| params.iter().map(move |(param, delta)| (agent.clone(), param.clone(), *delta)))
+            .collect();
+        deltas.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        TickRecord { sensors, agents, tension, deltas }
+    }
+
+    /// Field params changed since `since_revision`, encoded for
+    /// `apply_state_diff` on another interpreter. Commits any pending changes
+    /// first, so the diff's revision is the one `state_revision` reports after.
+    pub fn state_diff(&mut self, since_revision: u64) -> Vec<u8> {
+        self.sync.commit(&self.fields);
+        self.sync.encode(since_revision)
+    }
+
+    /// Revision of the newest committed field state; 0 before the first diff.
+    pub fn state_revision(&self) -> u64 {
+        self.sync.revision()
+    }
+
+    /// Applies a diff from another interpreter's `state_diff`. Diffs that are
+    /// stale or leave a gap after the last applied one change nothing.
+    pub fn apply_state_diff(&mut self, bytes: &[u8]) -> Result<DiffOutcome, WeaveError> {
+        self.sync.apply(bytes, &mut self.fields)
+    }
+
+    /// Revision of the newest diff `apply_state_diff` accepted, for acking to the sender.
+    pub fn applied_revision(&self) -> u64 {
+        self.sync.applied_revision()
+    }
+
+    /// The running state, for `import_state` here or in another interpreter
+    /// running the same script. The drift noise forks so both carry on with
+    /// the same draws.
+    pub fn export_state(&mut self) -> ExperimentState {
+        ExperimentState {
+            fields: self.fields.clone(),
+            attributes: self.attributes.clone(),
+            tension_history: self.tension_history.samples().to_vec(),
+            dropped_history: self.tension_history.dropped(),
+            smoothed_tension: self.smoothed_tension,
+            schedule: self.schedule.clone(),
+            goal_progress: self.goal_progress.clone(),
+            locked_agents: self.locked_agents.clone(),
+            noise_seed: self.noise.fork(),
+        }
+    }
+
+    /// Fields, attributes, the tension history and the config as JSON, for
+    /// tools outside Godot to inspect.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> String {
+        JsonState {
+            fields: Some(self.fields.clone()),
+            attributes: Some(json::attributes_to_json(&self.attributes)),
+            tension_history: Some(self.tension_history.samples().to_vec()),
+            dropped_history: Some(self.tension_history.dropped()),
+            config: Some(self.config),
+        }
+        .to_json()
+    }
+
+    /// Injects the sections `text` has, in `to_json`'s shape, replacing the
+    /// current ones whole; sections it leaves out stay. Malformed JSON, an
+    /// unknown key or an unreadable attribute changes nothing.
+    #[cfg(feature = "json")]
+    pub fn from_json(&mut self, text: &str) -> Result<(), WeaveError> {
+        let state = JsonState::from_json(text)?;
+        let attributes = state.attributes.as_ref().map(json::attributes_from_json).transpose()?;
+        if let Some(fields) = state.fields {
+            self.fields = fields;
+        }
+        if let Some(attributes) = attributes {
+            self.attributes = attributes;
+        }
+        if state.tension_history.is_some() || state.dropped_history.is_some() {
+            let samples = state.tension_history.unwrap_or_else(|| self.tension_history.samples().to_vec());
+            self.tension_history.restore(samples, state.dropped_history.unwrap_or(self.tension_history.dropped()));
+        }
+        if let Some(config) = state.config {
+            self.set_config(config);
+        }
+        Ok(())
+    }
+
+    /// Continues from an exported state, usually after loading the same
+    /// script. PID controllers start over, as after a reload.
+    pub fn import_state(&mut self, state: ExperimentState) {
+        self.fields = state.fields;
+        self.attributes = state.attributes;
+        self.tension_history.restore(state.tension_history, state.dropped_history);
+        // A state carries no tick; each step of its run left one tension.
+        self.steps = self.tension_history.total();
+        self.smoothed_tension = state.smoothed_tension;
+        self.schedule = state.schedule;
+        self.goal_progress = state.goal_progress;
+        self.completed_goals.clear();
+        self.locked_agents = state.locked_agents;
+        self.noise.reseed(state.noise_seed);
+        self.pid_states.clear();
+        self.clear_drift_states();
+        self.tick_changes.clear();
+        self.time_slice.restart();
+    }
+
+    /// `export_state` as compact bytes, e.g. for a save file or a rewind buffer.
+    pub fn snapshot(&mut self) -> Vec<u8> {
+        snapshot::encode(&self.export_state())
+    }
+
+    /// Continues from a `snapshot`, as `import_state` does. On bytes that
+    /// aren't a snapshot nothing changes.
+    pub fn restore(&mut self, bytes: &[u8]) -> Result<(), WeaveError> {
+        let state = snapshot::decode(bytes)?;
+        self.import_state(state);
+        Ok(())
+    }
+
+    /// Starts capturing every `step` from the current field state, discarding
+    /// any previous unsaved recording.
+    pub fn start_recording(&mut self) {
+        let noise_seed = self.noise.fork();
+        self.recording = Some(Recording::new(self.fields.clone(), noise_seed));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn save_recording(&self, path: &Path) -> Result<(), WeaveError> {
+        match &self.recording {
+            Some(recording) => recording.save(path),
+            None => Err(WeaveError::Validation("nothing is being recorded".to_string())),
+        }
+    }
+
+    /// Loads a recording and rewinds the fields to the state it started from,
+    /// ready for `replay_step`.
+    pub fn load_recording(&mut self, path: &Path) -> Result<usize, WeaveError> {
+        let recording = Recording::load(path)?;
+        let ticks = recording.ticks.len();
+        self.fields = recording.initial_fields.clone();
+        self.noise.reseed(recording.noise_seed);
+        self.pid_states.clear();
+        self.clear_drift_states();
+        self.smoothed_tension = None;
+        self.tick_changes.clear();
+        self.replay = Some(Replay { recording, cursor: 0, first_divergent_tick: None });
+        Ok(ticks)
+    }
+
+    /// Feeds the next recorded tick through `step` and compares the tension
+    /// and param deltas with what was recorded.
+    pub fn replay_step(&mut self) -> Result<ReplayOutcome, WeaveError> {
+        let (tick, record) = match &self.replay {
+            Some(replay) => match replay.recording.ticks.get(replay.cursor) {
+                Some(record) => (replay.cursor, record.clone()),
+                None => return Ok(ReplayOutcome::Finished),
+            },
+            None => return Err(WeaveError::Validation("no recording is loaded for replay".to_string())),
+        };
+        let sensors: HashMap<String, Scalar> = record.sensors.iter().cloned().collect();
+        let agents: Fields = record.agents.iter().map(|agent| (agent.clone(), Params::new())).collect();
+        let actual_tension = self.step(&sensors, &agents)?;
+        let actual = self.tick_record(&sensors, &agents, actual_tension);
+        let Some(replay) = self.replay.as_mut() else {
+            return Ok(ReplayOutcome::Finished);
+        };
+        replay.cursor += 1;
+        if same_bits(&actual, &record) {
+            return Ok(ReplayOutcome::Matched { tick });
+        }
+        let first_divergent_tick = *replay.first_divergent_tick.get_or_insert(tick);
+        Ok(ReplayOutcome::Diverged { tick, first_divergent_tick, expected_tension: record.tension, actual_tension })
+    }
+
+    /// Starts capturing every sensor map `execute_tension` and
+    /// `execute_metaweave` receive, `step`'s included, discarding any
+    /// unsaved capture.
+    pub fn start_sensor_recording(&mut self) {
+        self.sensor_recording = Some(SensorStream::default());
+    }
+
+    pub fn stop_sensor_recording(&mut self) -> Option<SensorStream> {
+        self.sensor_recording.take()
+    }
+
+    pub fn is_recording_sensors(&self) -> bool {
+        self.sensor_recording.is_some()
+    }
+
+    pub fn save_sensor_recording(&self, path: &Path) -> Result<(), WeaveError> {
+        match &self.sensor_recording {
+            Some(stream) => stream.save(path),
+            None => Err(WeaveError::Validation("no sensors are being recorded".to_string())),
+        }
+    }
+
+    /// Feeds a saved sensor recording back: from now on each call of
+    /// `execute_tension` and `execute_metaweave` reads the next frame
+    /// recorded for its phase instead of the sensors it is given, until the
+    /// frames run out. Returns how many frames there are.
+    pub fn start_sensor_replay(&mut self, path: &Path) -> Result<usize, WeaveError> {
+        let stream = SensorStream::load(path)?;
+        let frames = stream.frames.len();
+        self.sensor_replay = Some(SensorReplay::new(stream));
+        Ok(frames)
+    }
+
+    pub fn stop_sensor_replay(&mut self) {
+        self.sensor_replay = None;
+    }
+
+    pub fn is_replaying_sensors(&self) -> bool {
+        self.sensor_replay.is_some()
+    }
+
+    /// Frames of the sensor replay not read yet, 0 without one.
+    pub fn sensor_replay_remaining(&self) -> usize {
+        self.sensor_replay.as_ref().map_or(0, SensorReplay::remaining)
+    }
+
+    /// The recorded frame `phase` reads instead of `sensors` while replaying,
+    /// recording whichever map the phase ends up reading. A replay whose
+    /// frames for `phase` have run out ends, with a warning.
+    fn stream_sensors(&mut self, phase: StreamPhase, sensors: &HashMap<String, Scalar>) -> Option<HashMap<String, Scalar>> {
+        let tick = self.tick();
+        let mut warning = None;
+        let replayed = match self.sensor_replay.as_mut() {
+            Some(replay) => {
+                let in_step = !replay.out_of_step();
+                match replay.next(phase, tick) {
+                    Some(frame) => {
+                        if in_step && replay.out_of_step() {
+                            warning = Some(format!("sensor replay fed tick {} a frame recorded on another tick; the run may not reproduce", tick));
+                        }
+                        Some(frame)
+                    }
+                    None => {
+                        self.sensor_replay = None;
+                        warning = Some(format!("sensor replay has no {} frames left, reading live sensors again", phase.as_str()));
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        if let Some(warning) = warning {
+            self.warn(warning);
+        }
+        if let Some(stream) = self.sensor_recording.as_mut() {
+            stream.push(tick, phase, replayed.as_ref().unwrap_or(sensors));
+        }
+        replayed
+    }
+
+    /// The single write path for every execute phase: stores `value`, accumulates
+    /// the tick's net change, queues a [`FieldChange`] if the param is watched
+    /// and traces the write with its `cause` while tracing is on.
+    /// Does nothing if `agent` has no field, is a parent's `global` field, or
+    /// is locked and the write comes from anything but `execute_code` or a
+    /// scheduled set, see `set_agent_locked`.
+    /// Returns the previous value (0.0 for a new param), or `None` if nothing was written.
+    fn write_param(&mut self, agent: &str, param: &str, value: Scalar, cause: Cause) -> Option<Scalar> {
+        if self.is_parent_global(agent) {
+            if self.rejected_globals.insert(param.to_string()) {
+                self.warn(format!("{}.{} belongs to the parent program; a child can read it but not write it", agent, param));
+            }
+            return None;
+        }
+        if matches!(cause.phase, Phase::Drift | Phase::Resolve | Phase::Metaweave | Phase::Derive | Phase::Loop | Phase::Event | Phase::Behavior) && self.is_agent_locked(agent) {
+            return None;
+        }
+        let field = self.fields.get_mut(agent)?;
+        let old_value = match field.get_mut(param) {
+            Some(current) => std::mem::replace(current, value),
+            None => {
+                field.insert(param.to_string(), value);
+                0.0
+            }
+        };
+        record_change(&mut self.tick_changes, agent, param, value - old_value);
+        if (value - old_value).abs() > self.watch_epsilon && self.is_watched(agent, param) {
+            self.field_changes.push(FieldChange {
+                agent: agent.to_string(),
+                param: param.to_string(),
+                old_value,
+                new_value: value,
+            });
+        }
+        if self.trace.enabled() {
+            self.trace.push(TraceEntry::new(self.tick(), agent, param, old_value, value, cause));
+        }
+        Some(old_value)
+    }
+
+    /// Starts (with an empty buffer) or stops tracing every field write.
+    /// Stopping keeps the entries for `trace`.
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.trace.set_enabled(enabled);
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.trace.enabled()
+    }
+
+    /// Keeps at most `capacity` trace entries, dropping the oldest first.
+    pub fn set_trace_capacity(&mut self, capacity: usize) {
+        self.trace.set_capacity(capacity);
+    }
+
+    /// The newest `max_entries` traced writes, oldest first.
+    pub fn trace(&self, max_entries: usize) -> impl Iterator<Item = &TraceEntry> {
+        self.trace.latest(max_entries)
+    }
+
+    /// Writes the whole trace buffer to `path` as JSON lines.
+    pub fn dump_trace(&self, path: &Path) -> Result<(), WeaveError> {
+        self.trace.dump(path)
+    }
+
+    /// Registers interest in `agent.param`; `"*"` watches every param of the agent.
+    pub fn watch_field(&mut self, agent: &str, param: &str) {
+        self.watches.entry(agent.to_string()).or_default().insert(param.to_string());
+    }
+
+    pub fn unwatch_field(&mut self, agent: &str, param: &str) {
+        if let Some(params) = self.watches.get_mut(agent) {
+            params.remove(param);
+            if params.is_empty() {
+                self.watches.remove(agent);
+            }
+        }
+    }
+
+    pub fn is_watched(&self, agent: &str, param: &str) -> bool {
+        self.watches
+            .get(agent)
+            .map(|params| params.contains(param) || params.contains("*"))
+            .unwrap_or(false)
+    }
+
+    /// Smallest absolute change of a watched param that is reported.
+    pub fn set_watch_epsilon(&mut self, epsilon: Scalar) {
+        self.watch_epsilon = epsilon.abs();
+    }
+
+    /// Changes to watched params since the last call, in the order they happened.
+    pub fn take_field_changes(&mut self) -> Vec<FieldChange> {
+        std::mem::take(&mut self.field_changes)
+    }
+}
+
+fn same_bits(a: &TickRecord, b: &TickRecord) -> bool {
+    a.tension.to_bits() == b.tension.to_bits()
+        && a.deltas.len() == b.deltas.len()
+        && a.deltas.iter().zip(&b.deltas).all(|(x, y)| x.0 == y.0 && x.1 == y.1 && x.2.to_bits() == y.2.to_bits())
+}
+
+/// Picks an agent's drift or resolve rate from the config and its field.
+type RateFor = fn(&InterpreterConfig, &Params) -> Scalar;
+
+/// Every agent in `agents` paired with the same `dt`.
+fn uniform(agents: &Fields, dt: Scalar) -> Vec<(&str, Scalar)> {
+    agents.keys().map(|agent| (agent.as_str(), dt)).collect()
+}
+
+/// Accumulates the net change applied to `agent.param` during the current tick.
+fn record_change(changes: &mut Fields, agent: &str, param: &str, delta: Scalar) {
+    let params = match changes.get_mut(agent) {
+        Some(params) => params,
+        None => changes.entry(agent.to_string()).or_default(),
+    };
+    match params.get_mut(param) {
+        Some(total) => *total += delta,
+        None => {
+            params.insert(param.to_string(), delta);
+        }
+    }
+}
+
+fn max_change(changes: &Fields) -> Scalar {
+    changes
+        .values()
+        .flat_map(|params| params.values())
+        .fold(0.0, |max, delta| max.max(delta.abs()))
+}
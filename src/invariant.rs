@@ -0,0 +1,111 @@
+use crate::goals::Comparison;
+use crate::scalar::Scalar;
+use std::fmt;
+
+/// `assert [<name>:] <comparison> [and <comparison> ...]`, checked after
+/// every step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invariant {
+    /// The declared name, or the condition text for an unnamed assert.
+    pub name: String,
+    pub conditions: Vec<Comparison>,
+}
+
+impl Invariant {
+    /// The conditions as written back from the parse, joined by `and`.
+    pub fn text(&self) -> String {
+        let conditions: Vec<String> = self
+            .conditions
+            .iter()
+            .map(|condition| format!("{} {} {}", condition.lhs, condition.comparator, condition.rhs))
+            .collect();
+        conditions.join(" and ")
+    }
+
+    /// Every name and `field.param` the conditions read, first use first.
+    pub fn operands(&self) -> Vec<String> {
+        let mut operands = Vec::new();
+        for condition in &self.conditions {
+            condition.lhs.collect_operands(&mut operands);
+            condition.rhs.collect_operands(&mut operands);
+        }
+        operands
+    }
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "assert {}: {}", self.name, self.text())
+    }
+}
+
+/// What a step does when an invariant stops holding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvariantPolicy {
+    /// Warn and carry on.
+    #[default]
+    Warn,
+    /// Warn and stop stepping until `resume`.
+    Halt,
+    /// Warn and undo the step, back to the state it started from.
+    Rollback,
+}
+
+impl InvariantPolicy {
+    pub fn from_name(name: &str) -> Option<InvariantPolicy> {
+        match name {
+            "warn" => Some(InvariantPolicy::Warn),
+            "halt" => Some(InvariantPolicy::Halt),
+            "rollback" => Some(InvariantPolicy::Rollback),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InvariantPolicy::Warn => "warn",
+            InvariantPolicy::Halt => "halt",
+            InvariantPolicy::Rollback => "rollback",
+        }
+    }
+}
+
+/// An invariant that stopped holding, with the values its operands had.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvariantViolation {
+    pub name: String,
+    pub text: String,
+    pub tick: usize,
+    /// Each operand with its value, `None` where it had none.
+    pub values: Vec<(String, Option<Scalar>)>,
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invariant {} ({}) violated at tick {}", self.name, self.text, self.tick)?;
+        for (i, (operand, value)) in self.values.iter().enumerate() {
+            let separator = if i == 0 { " with " } else { ", " };
+            match value {
+                Some(value) => write!(f, "{}{} = {}", separator, operand, value)?,
+                None => write!(f, "{}{} unset", separator, operand)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How an invariant has fared so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvariantStatus {
+    /// Whether it held on the last check; true before the first.
+    pub passing: bool,
+    /// Times it went from holding to violated.
+    pub violations: u32,
+    pub last_violation_tick: Option<usize>,
+}
+
+impl Default for InvariantStatus {
+    fn default() -> Self {
+        InvariantStatus { passing: true, violations: 0, last_violation_tick: None }
+    }
+}
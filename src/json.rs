@@ -0,0 +1,62 @@
+use crate::config::InterpreterConfig;
+use crate::error::WeaveError;
+use crate::interpreter::{parse_param_value, Fields, ParamValue};
+use crate::scalar::Scalar;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Non-numeric params as weave source text, as in the `export_state`
+/// Dictionary, sorted so the JSON is stable.
+pub type JsonAttributes = BTreeMap<String, BTreeMap<String, String>>;
+
+/// What `Interpreter::to_json` writes and `from_json` reads. Every section
+/// is optional on the way in, so a tool can inject just the fields, say,
+/// and leave the rest running as it was.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Fields>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<JsonAttributes>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tension_history: Option<Vec<Scalar>>,
+    /// Ticks before the first history sample, which the history limit dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dropped_history: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<InterpreterConfig>,
+}
+
+impl JsonState {
+    pub fn to_json(&self) -> String {
+        // Maps with String keys and plain numbers always serialize.
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn from_json(text: &str) -> Result<JsonState, WeaveError> {
+        serde_json::from_str(text).map_err(|e| WeaveError::Validation(format!("invalid state JSON: {}", e)))
+    }
+}
+
+pub fn attributes_to_json(attributes: &HashMap<String, HashMap<String, ParamValue>>) -> JsonAttributes {
+    attributes
+        .iter()
+        .map(|(field, params)| (field.clone(), params.iter().map(|(param, value)| (param.clone(), value.to_string())).collect()))
+        .collect()
+}
+
+/// The attributes `attributes` holds as text; a value that isn't a weave
+/// literal is an error.
+pub fn attributes_from_json(attributes: &JsonAttributes) -> Result<HashMap<String, HashMap<String, ParamValue>>, WeaveError> {
+    let mut parsed = HashMap::new();
+    for (field, params) in attributes {
+        let mut values = HashMap::new();
+        for (param, text) in params {
+            let value = parse_param_value(text).ok_or_else(|| WeaveError::Validation(format!("state JSON has an unreadable value {} for {}.{}", text, field, param)))?;
+            values.insert(param.clone(), value);
+        }
+        parsed.insert(field.clone(), values);
+    }
+    Ok(parsed)
+}
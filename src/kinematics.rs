@@ -0,0 +1,133 @@
+use crate::interpreter::Params;
+use crate::scalar::Scalar;
+use std::str::FromStr;
+
+/// Speed and acceleration limits declared as `max_speed`/`max_accel` params;
+/// an undeclared limit is unbounded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub max_speed: Scalar,
+    pub max_accel: Scalar,
+}
+
+impl Limits {
+    pub fn from_params(params: &Params) -> Self {
+        let limit = |key| params.get(key).map(|value: &Scalar| value.max(0.0)).unwrap_or(Scalar::INFINITY);
+        Limits { max_speed: limit("max_speed"), max_accel: limit("max_accel") }
+    }
+}
+
+/// How the Godot 3 bindings move an agent's node to its `position` each
+/// physics frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DriveMode {
+    /// `set_translation` straight to the position, which physics never sees.
+    #[default]
+    Teleport,
+    /// A RigidBody is given the linear velocity that carries it towards the
+    /// position, and a KinematicBody moves by it with `move_and_slide`.
+    Velocity,
+    /// As `Velocity`, except that a RigidBody gets the impulse that changes
+    /// its velocity to that one, so collisions and other forces still act.
+    Impulse,
+}
+
+impl DriveMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DriveMode::Teleport => "teleport",
+            DriveMode::Velocity => "velocity",
+            DriveMode::Impulse => "impulse",
+        }
+    }
+}
+
+impl FromStr for DriveMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "teleport" => Ok(DriveMode::Teleport),
+            "velocity" => Ok(DriveMode::Velocity),
+            "impulse" => Ok(DriveMode::Impulse),
+            _ => Err(format!("unknown drive mode '{}', expected teleport, velocity or impulse", s)),
+        }
+    }
+}
+
+fn length(vector: &[Scalar]) -> Scalar {
+    vector.iter().map(|component| component * component).sum::<Scalar>().sqrt()
+}
+
+/// Scales `vector` down, keeping its direction, so its length is at most `max`.
+fn clamp_length(vector: &mut [Scalar], max: Scalar) {
+    let length = length(vector);
+    if length > max {
+        let scale = if length > 0.0 { max / length } else { 0.0 };
+        vector.iter_mut().for_each(|component| *component *= scale);
+    }
+}
+
+/// Accelerates `velocity` over `dt` as a critically damped spring of
+/// stiffness `gain` pulling `position` to `target`, so the agent settles
+/// without overshooting. The acceleration is clamped to `max_accel`; a zero
+/// gain leaves the velocity alone.
+pub fn steer(velocity: &mut [Scalar], position: &[Scalar], target: &[Scalar], gain: Scalar, limits: Limits, dt: Scalar) {
+    let damping = 2.0 * gain.sqrt();
+    let mut accel: Vec<Scalar> = target
+        .iter()
+        .zip(position)
+        .zip(velocity.iter())
+        .map(|((target, position), velocity)| (target - position) * gain - velocity * damping)
+        .collect();
+    clamp_length(&mut accel, limits.max_accel);
+    for (component, accel) in velocity.iter_mut().zip(accel) {
+        *component += accel * dt;
+    }
+    clamp_length(velocity, limits.max_speed);
+}
+
+/// Clamps `velocity` to `max_speed`, then moves `position` by it over `dt`.
+pub fn advance(position: &mut [Scalar], velocity: &mut [Scalar], limits: Limits, dt: Scalar) {
+    clamp_length(velocity, limits.max_speed);
+    for (component, speed) in position.iter_mut().zip(velocity.iter()) {
+        *component += speed * dt;
+    }
+}
+
+/// The velocity a body at `current` moving at `velocity` should take to
+/// reach `target` in `dt`: no faster than `max_speed`, and changed from
+/// `velocity` by no more than `max_accel * dt`, so a driven body speeds up
+/// and brakes rather than jumping. Missing components read 0; a `dt` of 0
+/// keeps `velocity`.
+pub fn drive_velocity(current: &[Scalar], target: &[Scalar], velocity: &[Scalar], limits: Limits, dt: Scalar) -> Vec<Scalar> {
+    let component = |vector: &[Scalar], index: usize| vector.get(index).copied().unwrap_or(0.0);
+    let dimensions = current.len().max(target.len()).max(velocity.len());
+    if dt <= 0.0 {
+        return (0..dimensions).map(|index| component(velocity, index)).collect();
+    }
+    let mut wanted: Vec<Scalar> = (0..dimensions).map(|index| (component(target, index) - component(current, index)) / dt).collect();
+    clamp_length(&mut wanted, limits.max_speed);
+    let mut change: Vec<Scalar> = wanted.iter().enumerate().map(|(index, wanted)| wanted - component(velocity, index)).collect();
+    clamp_length(&mut change, limits.max_accel * dt);
+    (0..dimensions).map(|index| component(velocity, index) + change[index]).collect()
+}
+
+/// Pushes `a` and `b` apart along the line between them until they are
+/// `min_distance` apart, `a` taking `share_a` of the push and `b` the rest.
+/// Two agents on the same spot are split along the first axis. Returns
+/// whether they were too close.
+pub fn separate(a: &mut [Scalar], b: &mut [Scalar], min_distance: Scalar, share_a: Scalar) -> bool {
+    let offset: Vec<Scalar> = a.iter().zip(b.iter()).map(|(a, b)| a - b).collect();
+    let distance = length(&offset);
+    if distance >= min_distance || offset.is_empty() {
+        return false;
+    }
+    let overlap = min_distance - distance;
+    for (index, (a, b)) in a.iter_mut().zip(b.iter_mut()).enumerate() {
+        let direction = if distance > 0.0 { offset[index] / distance } else if index == 0 { 1.0 } else { 0.0 };
+        *a += direction * overlap * share_a;
+        *b -= direction * overlap * (1.0 - share_a);
+    }
+    true
+}
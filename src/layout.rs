@@ -0,0 +1,61 @@
+use crate::error::WeaveError;
+use crate::interpreter::{Fields, Params};
+use crate::scalar::Scalar;
+use indexmap::IndexSet;
+use std::collections::HashMap;
+
+/// Agents and sensors a binding registers once, so that each frame can pass
+/// a plain buffer of sensor values instead of Dictionaries to be converted
+/// and written back. The sensor map is kept between frames, so a frame
+/// allocates nothing unless a sensor comes back after a missing value.
+#[derive(Debug, Clone, Default)]
+pub struct FrameLayout {
+    agents: Fields,
+    sensors: IndexSet<String>,
+    values: HashMap<String, Scalar>,
+}
+
+impl FrameLayout {
+    /// Replaces the registered agents; a name given twice counts once.
+    pub fn set_agents(&mut self, names: impl IntoIterator<Item = String>) {
+        self.agents = names.into_iter().map(|name| (name, Params::new())).collect();
+    }
+
+    /// The registered agents, with no params: drift and resolve only need
+    /// their names.
+    pub fn agents(&self) -> &Fields {
+        &self.agents
+    }
+
+    /// Replaces the sensors a buffer holds, in buffer order.
+    pub fn set_sensors(&mut self, names: impl IntoIterator<Item = String>) {
+        self.sensors = names.into_iter().collect();
+        self.values.clear();
+    }
+
+    pub fn sensors(&self) -> &IndexSet<String> {
+        &self.sensors
+    }
+
+    /// Takes one value per registered sensor, in order; a NaN leaves that
+    /// sensor out of the frame, as a missing Dictionary key does. Values a
+    /// caller added to the map last frame are dropped. Returns the sensor map
+    /// for the caller to add to, and the registered agents.
+    pub fn read(&mut self, values: impl ExactSizeIterator<Item = Scalar>) -> Result<(&mut HashMap<String, Scalar>, &Fields), WeaveError> {
+        if values.len() != self.sensors.len() {
+            return Err(WeaveError::Validation(format!("sensor buffer holds {} values for {} registered sensors", values.len(), self.sensors.len())));
+        }
+        let sensors = &self.sensors;
+        self.values.retain(|name, _| sensors.contains(name));
+        for (name, value) in self.sensors.iter().zip(values) {
+            if value.is_nan() {
+                self.values.remove(name);
+            } else if let Some(slot) = self.values.get_mut(name) {
+                *slot = value;
+            } else {
+                self.values.insert(name.clone(), value);
+            }
+        }
+        Ok((&mut self.values, &self.agents))
+    }
+}
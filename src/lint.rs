@@ -0,0 +1,437 @@
+use crate::behavior::SELF_FIELD;
+use crate::constants::Constants;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error::WeaveError;
+use crate::global::GLOBAL_FIELD;
+use crate::interpreter::{parse_rule, parse_weave_str_with, Rule};
+use crate::scalar::Scalar;
+use pest::iterators::Pair;
+use pest::Span;
+use std::collections::{HashMap, HashSet};
+
+/// What `lint` knows about the world outside the file.
+#[derive(Debug, Clone, Default)]
+pub struct LintContext {
+    pub constants: Constants,
+    /// Sensors provided without the file declaring them: added, bound at
+    /// runtime or already fed.
+    pub sensors: HashSet<String>,
+    /// Fields that exist without the file declaring them, such as spawned
+    /// agents.
+    pub fields: HashSet<String>,
+}
+
+/// A field, agent or template block: its own params and what it extends.
+#[derive(Debug, Default)]
+struct Block<'a> {
+    params: Vec<&'a str>,
+    ranges: HashMap<&'a str, (Scalar, Scalar)>,
+    parent: Option<&'a str>,
+}
+
+/// The names a file declares, gathered before any of them is checked so a
+/// reference may come before its declaration.
+#[derive(Debug, Default)]
+struct Declarations<'a> {
+    blocks: HashMap<&'a str, Block<'a>>,
+    /// Fields and agents, not templates.
+    fields: HashSet<&'a str>,
+    sensors: HashSet<&'a str>,
+    sensor_ranges: HashMap<&'a str, (Scalar, Scalar)>,
+}
+
+impl<'a> Declarations<'a> {
+    fn gather(pairs: &[Pair<'a, Rule>]) -> Declarations<'a> {
+        let mut declarations = Declarations::default();
+        for pair in pairs {
+            let mut inner = pair.clone().into_inner();
+            match pair.as_rule() {
+                Rule::field | Rule::template_block => {
+                    let Some(name) = inner.next() else {
+                        continue;
+                    };
+                    let block = declarations.blocks.entry(name.as_str()).or_default();
+                    for part in inner {
+                        match part.as_rule() {
+                            Rule::field_parent => block.parent = Some(part.into_inner().as_str()),
+                            Rule::field_param => {
+                                let mut parts = part.into_inner();
+                                let Some(param) = parts.next().map(|param| param.as_str()) else {
+                                    continue;
+                                };
+                                block.params.push(param);
+                                if let Some(range) = parts.find(|part| part.as_rule() == Rule::param_range) {
+                                    let mut bounds = range.into_inner().map(|bound| bound.as_str().parse::<Scalar>().unwrap_or_default());
+                                    block.ranges.insert(param, (bounds.next().unwrap_or_default(), bounds.next().unwrap_or_default()));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    if pair.as_rule() == Rule::field {
+                        declarations.fields.insert(name.as_str());
+                    }
+                }
+                Rule::agent_decl => {
+                    if let (Some(name), Some(template)) = (inner.next(), inner.next()) {
+                        declarations.blocks.entry(name.as_str()).or_default().parent = Some(template.as_str());
+                        declarations.fields.insert(name.as_str());
+                    }
+                }
+                Rule::sensors_block => {
+                    for default in inner {
+                        let mut parts = default.into_inner();
+                        let Some(sensor) = parts.next().map(|sensor| sensor.as_str()) else {
+                            continue;
+                        };
+                        declarations.sensors.insert(sensor);
+                        let mut bounds = parts.skip(1).map(|bound| bound.as_str().parse::<Scalar>().unwrap_or_default());
+                        if let (Some(min), Some(max)) = (bounds.next(), bounds.next()) {
+                            declarations.sensor_ranges.insert(sensor, (min, max));
+                        }
+                    }
+                }
+                Rule::sensor_binding => {
+                    if let Some(sensor) = inner.next() {
+                        declarations.sensors.insert(sensor.as_str());
+                    }
+                }
+                _ => {}
+            }
+        }
+        declarations
+    }
+
+    /// The blocks `name` is built from: itself, then what it extends.
+    fn lineage(&self, name: &'a str) -> impl Iterator<Item = &Block<'a>> + '_ {
+        let mut next = Some(name);
+        // Bounded so a block that extends itself can't loop forever.
+        std::iter::from_fn(move || {
+            let block = self.blocks.get(next?)?;
+            next = block.parent;
+            Some(block)
+        })
+        .take(self.blocks.len())
+    }
+
+    fn has_param(&self, block: &'a str, param: &str) -> bool {
+        self.lineage(block).any(|block| block.params.contains(&param))
+    }
+
+    fn param_range(&self, block: &'a str, param: &str) -> Option<(Scalar, Scalar)> {
+        self.lineage(block).find_map(|block| block.ranges.get(param).copied())
+    }
+}
+
+struct Linter<'a, 'c> {
+    declarations: Declarations<'a>,
+    context: &'c LintContext,
+    /// Whether any sensor is declared, bound or provided; without one every
+    /// otherwise unknown name is taken to be a sensor the host feeds.
+    registry: bool,
+    /// Set while checking a behavior tree, where `self` is a field.
+    in_behavior: bool,
+    diagnostics: Vec<Diagnostic>,
+    /// Fields referenced but never declared, with their diagnostics' indices.
+    undeclared: Vec<(&'a str, usize)>,
+}
+
+/// Checks `code` as a whole weave file for mistakes that load without
+/// complaint: references to fields or agents it never declares, sensors it
+/// reads but nothing provides, `on` handlers whose test can't become true
+/// and fields or params declared twice. Also reports the error, if any,
+/// that stops the file loading. Empty for a clean file.
+pub fn lint(code: &str, context: &LintContext) -> Vec<Diagnostic> {
+    let loaded = parse_weave_str_with(code, &context.constants);
+    let pairs: Vec<Pair<Rule>> = match parse_rule(Rule::file, code) {
+        Ok(pairs) => pairs.collect(),
+        Err(e) => return vec![Diagnostic::from_error(&loaded.err().unwrap_or(e))],
+    };
+    let declarations = Declarations::gather(&pairs);
+    let registry = !declarations.sensors.is_empty() || !context.sensors.is_empty();
+    let mut linter = Linter { declarations, context, registry, in_behavior: false, diagnostics: Vec::new(), undeclared: Vec::new() };
+    linter.duplicates(&pairs);
+    for pair in pairs {
+        linter.statement(pair);
+    }
+    match loaded {
+        // A load fails on some undeclared fields outright; the warning placed
+        // at the reference then stands for that error.
+        Err(WeaveError::MissingField(field)) if linter.undeclared.iter().any(|(name, _)| *name == field) => {
+            for (_, index) in linter.undeclared.iter().filter(|(name, _)| *name == field) {
+                linter.diagnostics[*index].severity = Severity::Error;
+            }
+        }
+        Err(e) => linter.diagnostics.insert(0, Diagnostic::from_error(&e)),
+        Ok(_) => {}
+    }
+    linter.diagnostics
+}
+
+/// Whether `comparator threshold` can't hold for any value in `range`.
+fn never_holds(comparator: &str, threshold: Scalar, (min, max): (Scalar, Scalar)) -> bool {
+    match comparator {
+        ">" => threshold >= max,
+        ">=" => threshold > max,
+        "<" => threshold <= min,
+        "<=" => threshold < min,
+        "==" => threshold < min || threshold > max,
+        _ => false,
+    }
+}
+
+impl<'a> Linter<'a, '_> {
+    fn warn(&mut self, span: Span, message: String) {
+        self.diagnostics.push(Diagnostic::placed(Severity::Warning, message, span));
+    }
+
+    /// Fields and agents declared more than once, and params declared twice
+    /// in one block. A later field declaration replaces the earlier's params.
+    fn duplicates(&mut self, pairs: &[Pair<'a, Rule>]) {
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for pair in pairs {
+            if !matches!(pair.as_rule(), Rule::field | Rule::template_block | Rule::agent_decl) {
+                continue;
+            }
+            let mut inner = pair.clone().into_inner();
+            let Some(name) = inner.next() else {
+                continue;
+            };
+            let line = name.as_span().start_pos().line_col().0;
+            if pair.as_rule() != Rule::template_block {
+                // Declaring an agent twice already fails the load.
+                if let Some(first) = seen.insert(name.as_str(), line).filter(|_| pair.as_rule() == Rule::field) {
+                    self.warn(name.as_span(), format!("field {} is already declared on line {}; this declaration replaces its params", name.as_str(), first));
+                }
+            }
+            let mut params: HashSet<&str> = HashSet::new();
+            for param in inner.filter(|part| part.as_rule() == Rule::field_param) {
+                let Some(param) = param.into_inner().next() else {
+                    continue;
+                };
+                if !params.insert(param.as_str()) {
+                    self.warn(param.as_span(), format!("{}.{} is declared twice; the later value wins", name.as_str(), param.as_str()));
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self, pair: Pair<'a, Rule>) {
+        match pair.as_rule() {
+            Rule::field | Rule::template_block => {
+                let mut inner = pair.into_inner();
+                let Some(name) = inner.next().map(|name| name.as_str()) else {
+                    return;
+                };
+                for param in inner.filter(|part| part.as_rule() == Rule::field_param) {
+                    // A lone unknown name, as in `mode: cautious`, is a symbol.
+                    let symbol = |value: &Pair<Rule>| value.as_str().trim().chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                    for value in param.into_inner().skip(1).filter(|part| part.as_rule() == Rule::expression && !symbol(part)) {
+                        self.expression(value, Some(name));
+                    }
+                }
+            }
+            Rule::agent_decl => {
+                if let Some(template) = pair.into_inner().nth(1) {
+                    if !self.declarations.blocks.contains_key(template.as_str()) {
+                        self.undeclared.push((template.as_str(), self.diagnostics.len()));
+                        self.warn(template.as_span(), format!("agent template {} is not a declared field", template.as_str()));
+                    }
+                }
+            }
+            Rule::tension_block => {
+                for term in pair.into_inner() {
+                    let mut parts = term.into_inner();
+                    if let (Some(sensor), Some(target)) = (parts.next(), parts.next()) {
+                        self.sensor_read(sensor.as_span(), sensor.as_str());
+                        self.param_ref(target);
+                    }
+                }
+            }
+            Rule::metaweave_block => {
+                for rule in pair.into_inner() {
+                    self.rule(rule);
+                }
+            }
+            Rule::if_block | Rule::loop_block | Rule::goal_block | Rule::invariant | Rule::safety_block => {
+                for part in pair.into_inner() {
+                    self.rule(part);
+                }
+            }
+            Rule::on_block => {
+                for part in pair.into_inner() {
+                    if part.as_rule() == Rule::sensor_test {
+                        self.reachable(part.clone());
+                    }
+                    self.rule(part);
+                }
+            }
+            Rule::behavior_block => {
+                self.in_behavior = true;
+                for node in pair.into_inner().skip(1) {
+                    self.rule(node);
+                }
+                self.in_behavior = false;
+            }
+            Rule::scheduled_set => self.set_target(pair.into_inner().skip(1)),
+            _ => {}
+        }
+    }
+
+    /// Anything nested in a statement that can read or write a name.
+    fn rule(&mut self, pair: Pair<'a, Rule>) {
+        match pair.as_rule() {
+            // A `call` targets a lab object rather than a field.
+            Rule::sensor_test | Rule::call_rule => {
+                if let Some(operand) = pair.into_inner().next() {
+                    self.operand(operand);
+                }
+            }
+            Rule::metaweave_rule => {
+                let mut inner = pair.into_inner();
+                if let Some(operand) = inner.next() {
+                    self.operand(operand);
+                }
+                self.set_target(inner.skip(2));
+            }
+            Rule::attribute_rule => {
+                let mut inner = pair.into_inner();
+                if let Some(field) = inner.next() {
+                    self.field_ref(field.as_span(), field.as_str());
+                }
+                self.set_target(inner.skip(3));
+            }
+            Rule::attribute_test => {
+                if let Some(field) = pair.into_inner().next() {
+                    self.field_ref(field.as_span(), field.as_str());
+                }
+            }
+            Rule::set_statement => self.set_target(pair.into_inner()),
+            Rule::send_statement => {
+                let mut inner = pair.into_inner();
+                if let Some(target) = inner.next() {
+                    self.field_ref(target.as_span(), target.as_str());
+                }
+                for expression in inner.skip(1) {
+                    self.expression(expression, None);
+                }
+            }
+            Rule::safety_check => {
+                if let Some(sensor) = pair.into_inner().next() {
+                    self.sensor_read(sensor.as_span(), sensor.as_str());
+                }
+            }
+            Rule::expression => self.expression(pair, None),
+            Rule::if_block
+            | Rule::branch
+            | Rule::while_head
+            | Rule::emit_statement
+            | Rule::behavior_selector
+            | Rule::behavior_sequence
+            | Rule::behavior_check
+            | Rule::safety_rule
+            | Rule::goal_condition
+            | Rule::comparison => {
+                for part in pair.into_inner() {
+                    self.rule(part);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `<field> . <param> = <expression>`, as `set` and `at tick` write it.
+    fn set_target(&mut self, mut parts: impl Iterator<Item = Pair<'a, Rule>>) {
+        if let Some(field) = parts.next() {
+            self.field_ref(field.as_span(), field.as_str());
+        }
+        for value in parts.filter(|part| part.as_rule() == Rule::expression) {
+            self.expression(value, None);
+        }
+    }
+
+    /// The left side of a test: a field's param or a sensor.
+    fn operand(&mut self, operand: Pair<'a, Rule>) {
+        match operand.as_rule() {
+            Rule::param_ref => self.param_ref(operand),
+            _ => self.sensor_read(operand.as_span(), operand.as_str()),
+        }
+    }
+
+    /// Walks an expression; `own` is the block whose params bare names may
+    /// read.
+    fn expression(&mut self, pair: Pair<'a, Rule>, own: Option<&'a str>) {
+        for part in pair.into_inner() {
+            match part.as_rule() {
+                Rule::param_ref => self.param_ref(part),
+                Rule::ident => {
+                    let name = part.as_str();
+                    if !own.is_some_and(|block| self.declarations.has_param(block, name)) {
+                        self.sensor_read(part.as_span(), name);
+                    }
+                }
+                // The first ident is the function's name.
+                Rule::function => {
+                    for argument in part.into_inner().skip(1) {
+                        self.expression(argument, own);
+                    }
+                }
+                _ => self.expression(part, own),
+            }
+        }
+    }
+
+    fn param_ref(&mut self, pair: Pair<'a, Rule>) {
+        if let Some(field) = pair.into_inner().next() {
+            self.field_ref(field.as_span(), field.as_str());
+        }
+    }
+
+    fn field_ref(&mut self, span: Span<'a>, field: &'a str) {
+        let known = self.declarations.fields.contains(field)
+            || field == GLOBAL_FIELD
+            || (self.in_behavior && field == SELF_FIELD)
+            || self.context.fields.contains(field);
+        if known {
+            return;
+        }
+        let message = if self.declarations.blocks.contains_key(field) {
+            format!("{} is a template, not a field; declare an agent from it to use it", field)
+        } else {
+            format!("{} is not a declared field or agent", field)
+        };
+        self.undeclared.push((field, self.diagnostics.len()));
+        self.warn(span, message);
+    }
+
+    fn sensor_read(&mut self, span: Span<'a>, name: &str) {
+        let provided = name == "tension"
+            || self.context.constants.get(name).is_some()
+            || self.declarations.sensors.contains(name)
+            || self.context.sensors.contains(name);
+        if self.registry && !provided {
+            self.warn(span, format!("sensor {} is read, but no sensor of that name is declared, bound or added", name));
+        }
+    }
+
+    /// Warns when an `on` test compares against a threshold its sensor or
+    /// param can never reach inside its declared range.
+    fn reachable(&mut self, test: Pair<'a, Rule>) {
+        let span = test.as_span();
+        let mut inner = test.into_inner();
+        let (Some(operand), Some(comparator), Some(threshold)) = (inner.next(), inner.next(), inner.next()) else {
+            return;
+        };
+        let range = match operand.as_rule() {
+            Rule::param_ref => {
+                let (field, param) = operand.as_str().split_once('.').unwrap_or_default();
+                self.declarations.param_range(field, param)
+            }
+            _ => self.declarations.sensor_ranges.get(operand.as_str()).copied(),
+        };
+        let threshold = threshold.as_str().parse::<Scalar>().unwrap_or_default();
+        if let Some((min, max)) = range.filter(|&range| never_holds(comparator.as_str(), threshold, range)) {
+            self.warn(span, format!("this handler never runs: {} stays within [{}, {}]", operand.as_str(), min, max));
+        }
+    }
+}
@@ -0,0 +1,134 @@
+#[cfg(any(feature = "godot3", feature = "godot4"))]
+use gdnative::prelude::*;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Entries `recent` can return; older ones are dropped.
+pub const RECENT_LOG_CAPACITY: usize = 256;
+
+/// Severity of a log message, most severe first. A log at one level shows
+/// that level and every level above it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    /// Once-per-step summaries, such as how many metaweave rules fired.
+    Debug,
+    /// Per-step values, such as every computed tension.
+    Trace,
+}
+
+impl Level {
+    /// 0 is `Error` through 4 for `Trace`; out-of-range values clamp.
+    pub fn from_index(index: i64) -> Level {
+        match index {
+            i64::MIN..=0 => Level::Error,
+            1 => Level::Warn,
+            2 => Level::Info,
+            3 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub level: Level,
+    pub message: String,
+}
+
+#[derive(Debug)]
+struct Shared {
+    level: AtomicU8,
+    recent: Mutex<VecDeque<LogEntry>>,
+}
+
+/// Level-filtered messages, printed by `print_entry` and kept for
+/// `recent`. Clones share one log, so every program slot of a binding
+/// writes to the same place. Log through `weave_log!`, which skips
+/// formatting a message the level filters out.
+#[derive(Debug, Clone)]
+pub struct Log {
+    shared: Arc<Shared>,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Log {
+            shared: Arc::new(Shared { level: AtomicU8::new(Level::Warn as u8), recent: Mutex::new(VecDeque::new()) }),
+        }
+    }
+}
+
+impl Log {
+    pub fn level(&self) -> Level {
+        Level::from_index(self.shared.level.load(Ordering::Relaxed) as i64)
+    }
+
+    pub fn set_level(&self, level: Level) {
+        self.shared.level.store(level as u8, Ordering::Relaxed);
+    }
+
+    pub fn enabled(&self, level: Level) -> bool {
+        level <= self.level()
+    }
+
+    /// Prints `message` and keeps it, whatever the level; callers check
+    /// `enabled` first.
+    pub fn push(&self, level: Level, message: String) {
+        print_entry(level, &message);
+        let mut recent = self.shared.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        if recent.len() == RECENT_LOG_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(LogEntry { level, message });
+    }
+
+    /// The newest `max_entries` entries, oldest first.
+    pub fn recent(&self, max_entries: usize) -> Vec<LogEntry> {
+        let recent = self.shared.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        recent.iter().skip(recent.len().saturating_sub(max_entries)).cloned().collect()
+    }
+}
+
+/// To the Godot output, or to stderr in a build without bindings so stdout
+/// stays free for a tool's own output.
+#[cfg(any(feature = "godot3", feature = "godot4"))]
+fn print_entry(level: Level, message: &str) {
+    match level {
+        Level::Error => godot_error!("{}", message),
+        Level::Warn => godot_warn!("{}", message),
+        _ => godot_print!("{}", message),
+    }
+}
+
+#[cfg(not(any(feature = "godot3", feature = "godot4")))]
+fn print_entry(level: Level, message: &str) {
+    eprintln!("{}: {}", level.as_str(), message);
+}
+
+/// `weave_log!(log, level, "format", args...)` formats and pushes the
+/// message only when `log` shows `level`.
+macro_rules! weave_log {
+    ($log:expr, $level:expr, $($arg:tt)+) => {{
+        let log: &$crate::logging::Log = &$log;
+        let level = $level;
+        if log.enabled(level) {
+            log.push(level, format!($($arg)+));
+        }
+    }};
+}
+
+pub(crate) use weave_log;
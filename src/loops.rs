@@ -0,0 +1,68 @@
+use crate::expr::Expr;
+use crate::rules::RuleCondition;
+use std::fmt;
+
+/// Iterations a loop runs per step at most unless `set_loop_limit` says
+/// otherwise; the cap applies to `repeat` counts too.
+pub const DEFAULT_LOOP_LIMIT: u32 = 100;
+
+/// How many times a loop body runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoopKind {
+    /// `repeat <n>`
+    Repeat(u32),
+    /// `while <test>`, checked before every iteration.
+    While(RuleCondition),
+}
+
+/// One statement of a loop body.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoopStatement {
+    /// `drift;`, another drift pass over the step's agents.
+    Drift,
+    /// `resolve;`, another resolve pass over the step's agents.
+    Resolve,
+    /// `tension;`, computes the tension again from the step's sensors for the
+    /// rest of the loop, without adding it to the history.
+    Tension,
+    /// `set <field>.<param> = <expression>;`, evaluated every iteration.
+    Set { field: String, param: String, value: Expr },
+    /// `send(<target>, <topic>, <expression>);`, evaluated every iteration.
+    Send { target: String, topic: String, value: Expr },
+    /// `emit "<signal>" <expression>;`, evaluated every iteration.
+    Emit { signal: String, value: Expr },
+}
+
+impl fmt::Display for LoopStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoopStatement::Drift => f.write_str("drift;"),
+            LoopStatement::Resolve => f.write_str("resolve;"),
+            LoopStatement::Tension => f.write_str("tension;"),
+            LoopStatement::Set { field, param, value } => write!(f, "set {}.{} = {};", field, param, value),
+            LoopStatement::Send { target, topic, value } => write!(f, "send({}, {}, {});", target, topic, value),
+            LoopStatement::Emit { signal, value } => write!(f, "emit \"{}\" {};", signal, value),
+        }
+    }
+}
+
+/// `repeat <n> { ... }` or `while <test> { ... }`, run once per step after
+/// resolve and before metaweave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    pub kind: LoopKind,
+    pub body: Vec<LoopStatement>,
+}
+
+impl fmt::Display for Loop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            LoopKind::Repeat(count) => write!(f, "repeat {} {{", count)?,
+            LoopKind::While(condition) => write!(f, "while {} {{", condition)?,
+        }
+        for statement in &self.body {
+            write!(f, " {}", statement)?;
+        }
+        f.write_str(" }")
+    }
+}
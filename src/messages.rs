@@ -0,0 +1,68 @@
+use crate::scalar::Scalar;
+
+/// `send(<target>, <topic>, <value>);`, a value for another agent's script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub target: String,
+    pub topic: String,
+    pub value: Scalar,
+    /// The tick it was sent on; it is read on the next.
+    pub tick: usize,
+}
+
+/// The name an expression resolver is asked for when `receive(topic)` is
+/// evaluated. No bare name has parentheses, so it can't shadow one.
+pub fn receive_key(topic: &str) -> String {
+    format!("receive({})", topic)
+}
+
+/// The topic of a name `receive_key` made.
+pub fn receive_topic(name: &str) -> Option<&str> {
+    name.strip_prefix("receive(")?.strip_suffix(')')
+}
+
+/// Messages sent this tick, and those delivered at its start. Whatever is
+/// sent during a tick arrives when the next one starts, in the order it
+/// was sent, and can be read for that tick only.
+#[derive(Debug, Clone, Default)]
+pub struct MessageBus {
+    outbox: Vec<Message>,
+    inbox: Vec<Message>,
+}
+
+impl MessageBus {
+    pub fn send(&mut self, message: Message) {
+        self.outbox.push(message);
+    }
+
+    /// Starts a tick: last tick's messages become the inbox, replacing the
+    /// ones read during it.
+    pub fn deliver(&mut self) {
+        self.inbox = std::mem::take(&mut self.outbox);
+    }
+
+    /// The value of the latest message to `target` on `topic` this tick.
+    pub fn receive(&self, target: &str, topic: &str) -> Option<Scalar> {
+        self.inbox.iter().rev().find(|message| message.target == target && message.topic == topic).map(|message| message.value)
+    }
+
+    /// Messages delivered to `target` this tick, in the order they were sent.
+    pub fn inbox<'a>(&'a self, target: &'a str) -> impl Iterator<Item = &'a Message> {
+        self.inbox.iter().filter(move |message| message.target == target)
+    }
+
+    /// Messages sent this tick, waiting for the next.
+    pub fn pending(&self) -> &[Message] {
+        &self.outbox
+    }
+
+    /// Drops the messages sent since the `len`th, as a rollback does.
+    pub fn truncate_pending(&mut self, len: usize) {
+        self.outbox.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.outbox.clear();
+        self.inbox.clear();
+    }
+}
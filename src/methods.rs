@@ -0,0 +1,1047 @@
+/// Declares on `$class`, a binding inheriting `$base`, every `#[method]` that
+/// `WeaveLangNative` and `WeaveLang` export the same way, as the
+/// `SharedMethods` mix-in its `register` adds. Written once here, the two
+/// bindings can't drift apart. The bodies use the names both binding modules
+/// import: the gdnative prelude, the convert helpers, `weave_log!` and the
+/// core types.
+macro_rules! shared_methods {
+    ($class:ident, $base:ty) => {
+        #[methods(mixin = "SharedMethods")]
+        impl $class {
+            /// Emits `field_changed` whenever an execute phase moves `agent.param`;
+            /// `param` may be `"*"` to watch every param of the agent.
+            #[method]
+            fn watch_field(&mut self, agent: String, param: String) {
+                self.interpreter.watch_field(&agent, &param);
+            }
+
+            #[method]
+            fn unwatch_field(&mut self, agent: String, param: String) {
+                self.interpreter.unwatch_field(&agent, &param);
+            }
+
+            #[method]
+            fn set_watch_epsilon(&mut self, epsilon: Scalar) {
+                self.interpreter.set_watch_epsilon(epsilon);
+            }
+
+            /// Adds `when sensor <comparator> threshold set field.param = value`.
+            /// Returns false if `comparator` isn't one of `< <= > >= == !=`.
+            #[method]
+            fn add_metaweave_rule(&mut self, sensor: String, comparator: String, threshold: Scalar, field: String, param: String, value: Scalar) -> bool {
+                match comparator.parse() {
+                    Ok(comparator) => {
+                        self.interpreter.add_metaweave_rule(MetaweaveRule {
+                            condition: RuleCondition::Sensor { sensor, comparator, threshold },
+                            field,
+                            param,
+                            value,
+                        });
+                        true
+                    }
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Warn, "Rejected metaweave rule: {}", e);
+                        false
+                    }
+                }
+            }
+
+            #[method]
+            fn clear_metaweave_rules(&mut self) {
+                self.interpreter.clear_metaweave_rules();
+            }
+
+            /// Names of the fields whose `tags` list contains `tag`, sorted.
+            #[method]
+            fn get_agents_with_tag(&self, tag: String) -> VariantArray {
+                let agents = VariantArray::new();
+                for agent in self.interpreter.agents_with_tag(&tag) {
+                    agents.push(agent);
+                }
+                agents.into_shared()
+            }
+
+            /// Warnings recorded since the last call, such as skipped non-numeric inputs.
+            #[method]
+            fn take_warnings(&mut self) -> VariantArray {
+                let warnings = VariantArray::new();
+                for warning in self.interpreter.take_warnings() {
+                    warnings.push(warning);
+                }
+                warnings.into_shared()
+            }
+
+            /// Numeric params of every field, in the order the weave file declares them.
+            #[method]
+            fn get_fields(&self) -> Dictionary {
+                fields_dictionary(self.interpreter.fields())
+            }
+
+            /// The non-numeric params of `agent`: bools as bools, strings and identifiers as Strings.
+            #[method]
+            fn get_field_attributes(&self, agent: String) -> Dictionary {
+                attributes_dictionary(self.interpreter.attributes().get(&agent))
+            }
+
+            /// Sensors the script's `sensors` block declares, name to default, so
+            /// glue code can check it supplies all of them before a run.
+            #[method]
+            fn get_declared_sensors(&self) -> Dictionary {
+                let sensors = Dictionary::new();
+                for (sensor, default) in self.interpreter.declared_sensors() {
+                    sensors.insert(sensor, *default);
+                }
+                sensors.into_shared()
+            }
+
+            /// Registers a sensor with `spec`, a Dictionary of optional `default`,
+            /// `min` and `max` numbers, over any `sensors` entry for it. It stays
+            /// across reloads. False for a value that isn't a number, an empty range
+            /// or a default outside it.
+            #[method]
+            fn add_sensor(&mut self, name: String, spec: Dictionary) -> bool {
+                let added = sensor_spec_from_dictionary(&spec).map_err(WeaveError::Validation).and_then(|spec| self.interpreter.add_sensor(&name, spec));
+                match added {
+                    Ok(()) => true,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Could not add sensor {}: {}", name, e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            #[method]
+            fn remove_sensor(&mut self, name: String) -> bool {
+                self.interpreter.remove_sensor(&name)
+            }
+
+            /// Every declared or added sensor to `{ default, min, max }`, with the
+            /// keys it doesn't set left out.
+            #[method]
+            fn get_sensors(&self) -> Dictionary {
+                sensor_specs_dictionary(self.interpreter.sensor_specs().iter())
+            }
+
+            /// Sensor name to `"<node path>:<property>"` for every bound sensor. Only
+            /// `WeaveLangNative`, which lives in the scene tree, samples them.
+            #[method]
+            fn get_sensor_bindings(&self) -> Dictionary {
+                bindings_dictionary(self.interpreter.sensor_bindings())
+            }
+
+            /// Field params changed since `since_revision` as a compact binary diff for
+            /// `apply_state_diff` on a peer. Pass the revision the peer last acked
+            /// (its `get_applied_revision`), or 0 for a full snapshot.
+            #[method]
+            fn get_state_diff(&mut self, since_revision: i64) -> PoolArray<u8> {
+                PoolArray::from_vec(self.interpreter.state_diff(since_revision.max(0) as u64))
+            }
+
+            /// Revision the next `get_state_diff` is cut at, counting committed changes.
+            #[method]
+            fn get_state_revision(&self) -> i64 {
+                self.interpreter.state_revision() as i64
+            }
+
+            /// Revision of the newest diff this side accepted.
+            #[method]
+            fn get_applied_revision(&self) -> i64 {
+                self.interpreter.applied_revision() as i64
+            }
+
+            /// Adds or replaces a named constant that param expressions in later
+            /// loads and snippets can use, e.g. `energy: mass * C * C`. False if the
+            /// name isn't an identifier or the unit is unknown.
+            #[method]
+            fn register_constant(&mut self, name: String, value: f64, unit: String) -> bool {
+                match self.interpreter.constants_mut().register(&name, value, &unit) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "register_constant failed: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            /// Every script goal as `{ name: { progress, satisfied_ticks, hold_ticks, completed } }`.
+            #[method]
+            fn get_goal_progress(&self) -> Dictionary {
+                goal_progress_dictionary(self.interpreter.goal_progress())
+            }
+
+            /// Every script `assert` as `{ name: { passing, text, violations, last_violation_tick } }`.
+            #[method]
+            fn get_invariant_status(&self) -> Dictionary {
+                invariant_status_dictionary(self.interpreter.invariant_status())
+            }
+
+            /// `{ name, text, tick, values }` of the last violated invariant, with
+            /// the value each name in it had; empty if none has been violated.
+            #[method]
+            fn get_last_invariant_violation(&self) -> Dictionary {
+                invariant_violation_dictionary(self.interpreter.last_invariant_violation())
+            }
+
+            /// What a step does when an invariant fails: `warn` (the default),
+            /// `halt` stepping until `resume`, or `rollback` the step.
+            #[method]
+            fn set_invariant_policy(&mut self, policy: String) -> bool {
+                match InvariantPolicy::from_name(&policy) {
+                    Some(policy) => {
+                        self.interpreter.set_invariant_policy(policy);
+                        true
+                    }
+                    None => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Unknown invariant policy {}, expected warn, halt or rollback", policy);
+                        false
+                    }
+                }
+            }
+
+            #[method]
+            fn get_invariant_policy(&self) -> String {
+                self.interpreter.invariant_policy().as_str().to_string()
+            }
+
+            /// Whether an invariant under the `halt` policy, or `emergency_stop`,
+            /// has stopped stepping.
+            #[method]
+            fn is_halted(&self) -> bool {
+                self.interpreter.is_halted()
+            }
+
+            /// Freezes drift and resolve, zeros every agent's velocity and emits
+            /// `halted`; nothing moves again until `resume`. False if already
+            /// stopped, though `halted` is emitted again.
+            #[method]
+            fn emergency_stop(&mut self, #[base] owner: &$base) -> bool {
+                let stopped = self.interpreter.emergency_stop();
+                weave_log!(self.interpreter.log(), Level::Warn, "Emergency stop: drift and resolve are frozen until resume");
+                owner.emit_signal("halted", &[]);
+                stopped
+            }
+
+            #[method]
+            fn is_emergency_stopped(&self) -> bool {
+                self.interpreter.is_emergency_stopped()
+            }
+
+            /// Lets a halted or emergency-stopped experiment step again; false if
+            /// it wasn't halted.
+            #[method]
+            fn resume(&mut self) -> bool {
+                self.interpreter.resume()
+            }
+
+            /// Clears `name`'s progress so it can complete, and signal, again.
+            /// False if the script declares no such goal.
+            #[method]
+            fn reset_goal(&mut self, name: String) -> bool {
+                self.interpreter.reset_goal(&name)
+            }
+
+            /// Fields, attributes, the tension history and the tuning as a JSON
+            /// String, for external tools to inspect.
+            #[method]
+            fn to_json(&self) -> String {
+                self.interpreter.to_json()
+            }
+
+            /// The running state as a Dictionary to keep in an autoload, or write
+            /// out with `to_json`, and hand to `import_state` later.
+            #[method]
+            fn export_state(&mut self) -> Dictionary {
+                state_dictionary(&self.interpreter.export_state())
+            }
+
+            /// The running state as bytes for `restore`: what `export_state`
+            /// returns, fields, tension history and agent vectors included, in a
+            /// compact form for save files or a rewind buffer.
+            #[method]
+            fn snapshot(&mut self) -> PoolArray<u8> {
+                PoolArray::from_vec(self.interpreter.snapshot())
+            }
+
+            /// Steps counted so far; `at tick` sets and `schedule_set` fire against it.
+            #[method]
+            fn get_tick(&self) -> i64 {
+                self.interpreter.tick() as i64
+            }
+
+            /// Sets `agent.param` to `value` once `tick` comes, or at once if it
+            /// already has. Returns the id `cancel_scheduled` takes, or -1 and logs
+            /// why if `agent` isn't declared or `tick` is negative.
+            #[method]
+            fn schedule_set(&mut self, #[base] owner: &$base, tick: i64, agent: String, param: String, value: f64) -> i64 {
+                let result = match usize::try_from(tick) {
+                    Ok(tick) => self.interpreter.schedule_set(tick, &agent, &param, value as Scalar),
+                    Err(_) => Err(WeaveError::Validation(format!("cannot schedule for tick {}", tick))),
+                };
+                self.emit_field_changes(owner);
+                match result {
+                    Ok(id) => id as i64,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "schedule_set failed: {}", e);
+                        self.record_error(&e);
+                        -1
+                    }
+                }
+            }
+
+            /// Pending scheduled sets in firing order, as Dictionaries with `id`,
+            /// `tick`, `agent`, `param`, `value` and `from_script`.
+            #[method]
+            fn get_scheduled(&self) -> VariantArray {
+                scheduled_array(self.interpreter.scheduled())
+            }
+
+            /// Drops a pending set; false if it already fired or never existed.
+            #[method]
+            fn cancel_scheduled(&mut self, id: i64) -> bool {
+                u64::try_from(id).is_ok_and(|id| self.interpreter.cancel_scheduled(id))
+            }
+
+            /// Every constant as `{ name: { value, unit } }`.
+            #[method]
+            fn get_constants(&self) -> Dictionary {
+                constants_dictionary(self.interpreter.constants())
+            }
+
+            /// `value` in unit `from` expressed in unit `to`, e.g. `convert(36, "km/h", "m/s")`.
+            /// Null if either unit is unknown or they measure different quantities.
+            #[method]
+            fn convert(&mut self, value: f64, from: String, to: String) -> Variant {
+                match constants::convert(value, &from, &to) {
+                    Ok(converted) => converted.to_variant(),
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "convert failed: {}", e);
+                        self.record_error(&e);
+                        Variant::nil()
+                    }
+                }
+            }
+
+            /// Language version the loaded script declared (1 without a header, 0 before any load).
+            #[method]
+            fn get_script_version(&self) -> i64 {
+                self.interpreter.script_version() as i64
+            }
+
+            /// Newest weave language version this build can load.
+            #[method]
+            fn get_supported_version(&self) -> i64 {
+                LANGUAGE_VERSION as i64
+            }
+
+            #[method]
+            fn get_metaweave_rules(&self) -> VariantArray {
+                let rules = VariantArray::new();
+                for rule in self.interpreter.metaweave_rules() {
+                    rules.push(rule_dictionary(rule));
+                }
+                rules.into_shared()
+            }
+
+            /// `"strict"`, `"hold"` or `"default"` for every sensor without its own policy.
+            #[method]
+            fn set_sensor_policy(&mut self, policy: String) {
+                match policy.parse() {
+                    Ok(policy) => self.interpreter.set_sensor_policy(policy),
+                    Err(e) => weave_log!(self.interpreter.log(), Level::Warn, "{}", e),
+                }
+            }
+
+            #[method]
+            fn set_sensor_policy_for(&mut self, sensor: String, policy: String) {
+                match policy.parse() {
+                    Ok(policy) => self.interpreter.set_sensor_policy_for(&sensor, policy),
+                    Err(e) => weave_log!(self.interpreter.log(), Level::Warn, "{}", e),
+                }
+            }
+
+            #[method]
+            fn set_sensor_default(&mut self, sensor: String, value: Scalar) {
+                self.interpreter.set_sensor_default(&sensor, value);
+            }
+
+            /// Details of the most recent failure as `{kind, message, line?, column?}`,
+            /// empty after a success.
+            #[method]
+            fn get_last_error(&self) -> Dictionary {
+                self.last_error.duplicate().into_shared()
+            }
+
+            /// The most recent failure as an array of `{ severity, message, line,
+            /// column, end_line, end_column, snippet }` Dictionaries, for a UI that
+            /// highlights the offending line; empty after a successful load.
+            #[method]
+            fn get_diagnostics(&self) -> VariantArray {
+                diagnostics_array(&self.last_diagnostics)
+            }
+
+            /// Diagnostics for `code` as a whole weave file, without loading it:
+            /// its parse error, or the warnings loading it would give.
+            #[method]
+            fn check_weave(&self, code: String) -> VariantArray {
+                diagnostics_array(&self.interpreter.check_str(&code))
+            }
+
+            /// Lint warnings for `code`, or for the loaded weave file when `code` is
+            /// empty, in the same Dictionaries as `get_diagnostics`: fields or agents
+            /// referenced but never declared, sensors read that nothing provides,
+            /// `on` handlers that can never run and fields declared twice.
+            #[method]
+            fn validate(&self, #[opt] code: String) -> VariantArray {
+                let diagnostics = if code.is_empty() { self.interpreter.lint_source() } else { self.interpreter.lint_str(&code) };
+                diagnostics_array(&diagnostics)
+            }
+
+            /// `{rule_index, sensor, sensor_value, tick}` of the last tripped safety rule, empty if none has.
+            #[method]
+            fn get_last_safety_event(&self) -> Dictionary {
+                safety_event_dictionary(self.interpreter.last_safety_event())
+            }
+
+            /// Every trip kept in the safety log, oldest first, as
+            /// `get_last_safety_event` Dictionaries.
+            #[method]
+            fn get_safety_log(&self) -> VariantArray {
+                safety_log_array(self.interpreter.safety_log())
+            }
+
+            #[method]
+            fn clear_safety_log(&mut self) {
+                self.interpreter.clear_safety_log();
+            }
+
+            /// Streams each step from now on, as a JSON frame of tension, field
+            /// values and safety events, to WebSocket clients on `address`, such as
+            /// `127.0.0.1:9001`. False if it can't be bound or this build lacks the
+            /// `telemetry` feature.
+            #[method]
+            fn start_telemetry(&mut self, address: String) -> bool {
+                match self.interpreter.start_telemetry(&address) {
+                    Ok(bound) => {
+                        weave_log!(self.interpreter.log(), Level::Info, "Serving telemetry on ws://{}", bound);
+                        true
+                    }
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "start_telemetry failed: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            #[method]
+            fn stop_telemetry(&mut self) -> bool {
+                self.interpreter.stop_telemetry()
+            }
+
+            /// The address the telemetry server listens on, or "" while none runs.
+            #[method]
+            fn get_telemetry_address(&self) -> String {
+                self.interpreter.telemetry_address().map(|address| address.to_string()).unwrap_or_default()
+            }
+
+            #[method]
+            fn get_telemetry_clients(&self) -> i64 {
+                self.interpreter.telemetry_clients() as i64
+            }
+
+            /// Counters and gauges for monitoring a long experiment: `{ ticks,
+            /// parse_errors, safety_violations, tension, smoothed_tension, coherence,
+            /// halted, agents }`, with `agents` holding each agent's coherence.
+            #[method]
+            fn get_experiment_metrics(&self) -> Dictionary {
+                experiment_metrics_dictionary(self.interpreter.metrics())
+            }
+
+            /// `get_experiment_metrics` in the Prometheus text format.
+            #[method]
+            fn get_metrics_text(&self) -> String {
+                self.interpreter.metrics_text()
+            }
+
+            /// Serves `get_metrics_text` over HTTP on `address`, such as
+            /// `127.0.0.1:9100`, for a Prometheus scraper. False if it can't be bound.
+            #[method]
+            fn start_metrics_server(&mut self, address: String) -> bool {
+                match self.interpreter.start_metrics_server(&address) {
+                    Ok(bound) => {
+                        weave_log!(self.interpreter.log(), Level::Info, "Serving metrics on http://{}/metrics", bound);
+                        true
+                    }
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "start_metrics_server failed: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            #[method]
+            fn stop_metrics_server(&mut self) -> bool {
+                self.interpreter.stop_metrics_server()
+            }
+
+            /// The address the metrics server listens on, or "" while none runs.
+            #[method]
+            fn get_metrics_address(&self) -> String {
+                self.interpreter.metrics_address().map(|address| address.to_string()).unwrap_or_default()
+            }
+
+            /// Logs every field's numeric params after each step as `tick, agent,
+            /// param, value, tension` rows to `path`, Parquet for a `.parquet` path
+            /// and CSV otherwise, writing them out every `flush_ticks` ticks (60 when
+            /// left out). False if the file can't be opened.
+            #[method]
+            fn start_data_log(&mut self, path: String, #[opt] flush_ticks: i64) -> bool {
+                let flush_ticks = if flush_ticks > 0 { flush_ticks as usize } else { DEFAULT_FLUSH_TICKS };
+                match self.interpreter.start_data_log(std::path::Path::new(&path), flush_ticks) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Failed to start data log: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            #[method]
+            fn flush_data_log(&mut self) -> bool {
+                match self.interpreter.flush_data_log() {
+                    Ok(()) => true,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Failed to flush data log: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            /// Writes out and closes the data log; false if none was running or the
+            /// last rows couldn't be written.
+            #[method]
+            fn stop_data_log(&mut self) -> bool {
+                match self.interpreter.stop_data_log() {
+                    Ok(stopped) => stopped,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Failed to close data log: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            /// The file the data log writes, or "" while none runs.
+            #[method]
+            fn get_data_log_path(&self) -> String {
+                self.interpreter.data_log_path().map(|path| path.display().to_string()).unwrap_or_default()
+            }
+
+            /// Calls `target.method(event)` whenever a `halt` safety rule trips, with
+            /// the event as `get_last_safety_event` returns it. False if `target`
+            /// has no such method.
+            #[method]
+            fn register_halt_callback(&mut self, target: Ref<Object>, method: String) -> bool {
+                let has_method = unsafe { target.assume_safe_if_sane() }.is_some_and(|object| object.has_method(method.as_str()));
+                if !has_method {
+                    weave_log!(self.interpreter.log(), Level::Warn, "register_halt_callback: the target has no method {}", method);
+                    return false;
+                }
+                self.halt_callbacks.push((target, method));
+                true
+            }
+
+            #[method]
+            fn clear_halt_callbacks(&mut self) {
+                self.halt_callbacks.clear();
+            }
+
+            /// Makes `action <name>;` in a behavior tree call `target.method(agent)`,
+            /// replacing any earlier leaf of that name. The method returns true or
+            /// false, or `"success"`, `"failure"` or `"running"`. False if `target`
+            /// has no such method.
+            #[method]
+            fn register_behavior_action(&mut self, name: String, target: Ref<Object>, method: String) -> bool {
+                let has_method = unsafe { target.assume_safe_if_sane() }.is_some_and(|object| object.has_method(method.as_str()));
+                if !has_method {
+                    weave_log!(self.interpreter.log(), Level::Warn, "register_behavior_action: the target has no method {}", method);
+                    return false;
+                }
+                self.behavior_actions.insert(name, (target, method));
+                true
+            }
+
+            /// False if no action of that name was registered.
+            #[method]
+            fn unregister_behavior_action(&mut self, name: String) -> bool {
+                self.behavior_actions.remove(&name).is_some()
+            }
+
+            /// Each role's behavior tree as source text.
+            #[method]
+            fn get_behavior_trees(&self) -> Dictionary {
+                behaviors_dictionary(self.interpreter.behaviors())
+            }
+
+            /// What `agent`'s behavior tree reported on the last step: `"success"`,
+            /// `"failure"` or `"running"`, or empty if no tree has ticked it.
+            #[method]
+            fn get_behavior_status(&self, agent: String) -> String {
+                self.interpreter.behavior_status(&agent).map(|status| status.as_str().to_string()).unwrap_or_default()
+            }
+
+            /// Queues `value` for the agent `target` on `topic`, as a script `send`
+            /// does; it can be read with `receive(topic)` on the next tick.
+            #[method]
+            fn send_message(&mut self, target: String, topic: String, value: Scalar) {
+                self.interpreter.send_message(&target, &topic, value);
+            }
+
+            /// Messages delivered to `agent` this tick as `{topic, value, tick}`, in
+            /// the order they were sent.
+            #[method]
+            fn get_inbox(&self, agent: String) -> VariantArray {
+                message_array(self.interpreter.inbox(&agent))
+            }
+
+            /// Moves at most `count` agents per `step`, round-robin, scaling each
+            /// agent's update by the ticks it sat out. 0 moves every agent every step.
+            #[method]
+            fn set_agents_per_tick(&mut self, count: i64) {
+                self.interpreter.set_agents_per_tick(count.max(0) as usize);
+            }
+
+            /// Stops moving agents once `step` has used `usec` microseconds; the rest
+            /// wait for later steps. 0 removes the budget.
+            #[method]
+            fn set_step_budget_usec(&mut self, usec: i64) {
+                self.interpreter.set_step_budget_micros(usec.max(0) as u64);
+            }
+
+            /// Fraction of the roster the current time-sliced sweep has covered.
+            #[method]
+            fn get_step_progress(&self) -> Scalar {
+                self.interpreter.step_progress()
+            }
+
+            /// Declares the agents `step_buffer`, `execute_drift_registered` and
+            /// `execute_resolve_registered` move, once rather than as a Dictionary
+            /// every frame. Returns how many are registered.
+            #[method]
+            fn register_agents(&mut self, names: PoolArray<GodotString>) -> i64 {
+                self.frame.set_agents(names.read().iter().map(|name| name.to_string()));
+                self.frame.agents().len() as i64
+            }
+
+            /// Declares the sensors a `step_buffer` buffer holds, in buffer order.
+            /// Returns how many are registered.
+            #[method]
+            fn register_sensors(&mut self, names: PoolArray<GodotString>) -> i64 {
+                self.frame.set_sensors(names.read().iter().map(|name| name.to_string()));
+                self.frame.sensors().len() as i64
+            }
+
+            /// Hot-reloads the loaded weave file now, as auto-reload does on a save:
+            /// current values and runtime state stay, and `weave_reloaded` or
+            /// `weave_reload_failed` is emitted. Returns a Godot error code, `OK` or
+            /// an `ERR_*` constant.
+            #[method]
+            fn reload(&mut self, #[base] owner: &$base) -> i64 {
+                let result = self.interpreter.reload();
+                self.finish_reload(owner, &result);
+                match result {
+                    Ok(_) => 0,
+                    Err(e) => error_code(&e) as i64,
+                }
+            }
+
+            /// Parses and validates the weave file `src_path` and writes it to
+            /// `out_path` as a compiled program, which a `.wvc` `weave_path` or
+            /// `load_compiled` then loads without the parser. Returns a Godot error
+            /// code, `OK` or an `ERR_*` constant.
+            #[method]
+            fn compile_weave(&mut self, src_path: String, out_path: String) -> i64 {
+                match compiled::compile(std::path::Path::new(&src_path), std::path::Path::new(&out_path), self.interpreter.constants()) {
+                    Ok(_) => 0,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Failed to compile {}: {}", src_path, e);
+                        self.record_error(&e);
+                        error_code(&e) as i64
+                    }
+                }
+            }
+
+            /// Emits `converged` the first time this returns true after returning false.
+            #[method]
+            fn is_converged(&mut self, #[base] owner: &$base, threshold: Scalar, window: i64) -> bool {
+                if self.interpreter.update_convergence(threshold, window.max(0) as usize) {
+                    owner.emit_signal("converged", &[]);
+                }
+                self.interpreter.is_converged(threshold, window.max(0) as usize)
+            }
+
+            /// `"fixed"` for the tension-proportional resolve step, `"pid"` for per-field PID control.
+            #[method]
+            fn set_resolve_mode(&mut self, mode: String) {
+                match mode.as_str() {
+                    "fixed" => self.interpreter.set_resolve_mode(ResolveMode::Fixed),
+                    "pid" => self.interpreter.set_resolve_mode(ResolveMode::Pid),
+                    _ => weave_log!(self.interpreter.log(), Level::Warn, "Unknown resolve mode '{}', expected 'fixed' or 'pid'", mode),
+                }
+            }
+
+            /// `"linear"`, `"pid"` or `"exponential_smoothing"`: what drift scales
+            /// each step by, see `DriftStrategy`. False for an unknown name.
+            #[method]
+            fn set_drift_strategy(&mut self, strategy: String) -> bool {
+                match strategy.parse::<DriftStrategy>() {
+                    Ok(strategy) => {
+                        self.interpreter.set_drift_strategy(strategy);
+                        true
+                    }
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Warn, "{}", e);
+                        false
+                    }
+                }
+            }
+
+            #[method]
+            fn get_drift_strategy(&self) -> String {
+                self.interpreter.drift_strategy().as_str().to_string()
+            }
+
+            #[method]
+            fn set_pid_gains(&mut self, agent: String, kp: Scalar, ki: Scalar, kd: Scalar) {
+                self.interpreter.set_pid_gains(&agent, PidGains { kp, ki, kd });
+            }
+
+            /// On (the default): drift and resolve move each target towards its
+            /// sensor value. Off: the old unsigned steps, drift up and resolve down.
+            #[method]
+            fn set_signed_correction(&mut self, enabled: bool) {
+                self.interpreter.set_signed_correction(enabled);
+            }
+
+            /// The params drift and resolve move on fields without a `drives` list,
+            /// `coherence_target` and `physics_constant` by default. A field that
+            /// declares none of them is skipped, with one warning naming its params.
+            #[method]
+            fn set_drift_targets(&mut self, params: PoolArray<GodotString>) {
+                self.interpreter.set_drift_targets(params.read().iter().map(|param| param.to_string()).collect());
+            }
+
+            #[method]
+            fn get_drift_targets(&self) -> PoolArray<GodotString> {
+                PoolArray::from_vec(self.interpreter.drift_targets().iter().map(GodotString::from).collect())
+            }
+
+            /// Multiplies the `noise_stddev` every field declares; 0 turns drift noise off.
+            #[method]
+            fn set_noise_scale(&mut self, scale: f64) {
+                self.interpreter.set_noise_scale(scale as Scalar);
+            }
+
+            /// Restarts the drift noise from `seed`, so a run can be repeated exactly.
+            #[method]
+            fn set_noise_seed(&mut self, seed: i64) {
+                self.interpreter.set_noise_seed(seed as u64);
+            }
+
+            #[method]
+            fn get_noise_seed(&self) -> i64 {
+                self.interpreter.noise_seed() as i64
+            }
+
+            /// `{ agent: template }` for every agent declared with `agent ... from`
+            /// or spawned at runtime.
+            #[method]
+            fn get_agents(&self) -> Dictionary {
+                let dict = Dictionary::new();
+                for (name, agent) in self.interpreter.agents() {
+                    dict.insert(name, agent.template.as_str());
+                }
+                dict.into_shared()
+            }
+
+            /// Freezes `agent` against every write but `execute_code` and scheduled
+            /// sets, e.g. while an animation drives it; `WeaveLangNative` also stops
+            /// copying its `position` to its spawned node. Locks clear on reload
+            /// unless `persistent`.
+            #[method]
+            fn set_agent_locked(&mut self, agent: String, locked: bool, #[opt] persistent: bool) {
+                self.interpreter.set_agent_locked(&agent, locked, persistent);
+            }
+
+            #[method]
+            fn get_locked_agents(&self) -> VariantArray {
+                let agents = VariantArray::new();
+                for agent in self.interpreter.locked_agents() {
+                    agents.push(agent);
+                }
+                agents.into_shared()
+            }
+
+            /// Steers kinematic `agent` to `point`, a Vector3 or array, instead of
+            /// its `target_position`, e.g. along a path a navigation agent found.
+            /// `WeaveLangNative` navigation replaces the waypoints of agents with a
+            /// NavigationAgent every step. False, with nothing changed, for a value
+            /// that isn't a vector.
+            #[method]
+            fn set_waypoint(&mut self, agent: String, point: Variant) -> bool {
+                match vector_from_variant(&point) {
+                    Some(point) => {
+                        self.interpreter.set_waypoint(&agent, point);
+                        true
+                    }
+                    None => false,
+                }
+            }
+
+            #[method]
+            fn clear_waypoint(&mut self, agent: String) -> bool {
+                self.interpreter.clear_waypoint(&agent)
+            }
+
+            #[method]
+            fn set_pid_sensor(&mut self, agent: String, sensor: String) {
+                self.interpreter.set_pid_sensor(&agent, &sensor);
+            }
+
+            #[method]
+            fn set_convergence_epsilon(&mut self, epsilon: Scalar) {
+                self.interpreter.set_convergence_epsilon(epsilon);
+            }
+
+            /// What the last `step` did: tensions, agents updated, fired rules,
+            /// safety trips, total drift, load status and elapsed microseconds.
+            #[method]
+            fn get_frame_metrics(&self) -> Dictionary {
+                metrics_dictionary(self.interpreter.frame_metrics())
+            }
+
+            /// Times parse, tension, drift, resolve and metaweave while on, for
+            /// `get_profile`. Off by default; turning it off keeps the timings.
+            #[method]
+            fn set_profiling(&mut self, enabled: bool) {
+                self.interpreter.profiler_mut().set_enabled(enabled);
+            }
+
+            #[method]
+            fn is_profiling(&self) -> bool {
+                self.interpreter.profiler().enabled()
+            }
+
+            /// Microseconds each phase spent in the last tick, its mean and worst
+            /// tick and its total, as `{ enabled, ticks, phases: { parse, tension,
+            /// drift, resolve, metaweave } }`.
+            #[method]
+            fn get_profile(&self) -> Dictionary {
+                profile_dictionary(self.interpreter.profiler())
+            }
+
+            /// Forgets the timings measured so far, leaving profiling on or off.
+            #[method]
+            fn reset_profile(&mut self) {
+                self.interpreter.profiler_mut().reset();
+            }
+
+            /// Shows messages at `level` and above: 0 error, 1 warn (the default),
+            /// 2 info, 3 debug for per-step summaries, 4 trace for per-step values.
+            /// Filtered messages are never formatted. Every `WeaveLang` program slot
+            /// shares the one log.
+            #[method]
+            fn set_log_level(&mut self, level: i64) {
+                self.interpreter.log().set_level(Level::from_index(level));
+            }
+
+            /// The newest `max_entries` logged messages, oldest first, as
+            /// `{ level, message }` Dictionaries.
+            #[method]
+            fn get_recent_log(&self, max_entries: i64) -> VariantArray {
+                log_array(&self.interpreter.log().recent(max_entries.max(0) as usize))
+            }
+
+            /// Why the last tension is what it is: `{ tension, terms }`, each term a
+            /// `{ sensor, agent, target, sensed, weight, contribution }` Dictionary,
+            /// largest contribution first. Empty terms before the first step.
+            #[method]
+            fn explain_tension(&self) -> Dictionary {
+                tension_explanation_dictionary(self.interpreter.tension_terms())
+            }
+
+            /// Caps the iterations each `repeat` or `while` loop runs per step.
+            #[method]
+            fn set_loop_limit(&mut self, limit: i64) {
+                self.interpreter.set_loop_limit(limit.clamp(1, u32::MAX as i64) as u32);
+            }
+
+            #[method]
+            fn get_loop_limit(&self) -> i64 {
+                self.interpreter.loop_limit() as i64
+            }
+
+            /// Records every field write by drift, resolve, metaweave and `execute_code`
+            /// while on. Turning it on clears the previous trace.
+            #[method]
+            fn set_tracing(&mut self, enabled: bool) {
+                self.interpreter.set_tracing(enabled);
+            }
+
+            /// Emits `tension_high(value)` once a computed tension rises above
+            /// `high`, then `tension_resolved(value)` once one falls to `resolved`
+            /// or below, so the signals don't flap while tension hovers near `high`.
+            #[method]
+            fn set_tension_thresholds(&mut self, high: Scalar, resolved: Scalar) {
+                self.interpreter.set_tension_thresholds(high, resolved);
+            }
+
+            #[method]
+            fn clear_tension_thresholds(&mut self) {
+                self.interpreter.clear_tension_thresholds();
+            }
+
+            /// Whether `tension_high` was emitted without a `tension_resolved` since.
+            #[method]
+            fn is_tension_high(&self) -> bool {
+                self.interpreter.is_tension_high()
+            }
+
+            /// Keeps at most `capacity` trace entries; older ones are dropped.
+            #[method]
+            fn set_trace_capacity(&mut self, capacity: i64) {
+                self.interpreter.set_trace_capacity(capacity.max(1) as usize);
+            }
+
+            /// The newest `max_entries` writes, oldest first, as Dictionaries with
+            /// `tick`, `phase`, `agent`, `param`, `old_value`, `new_value`, `trigger`
+            /// and `trigger_value`.
+            #[method]
+            fn get_trace(&self, max_entries: i64) -> VariantArray {
+                trace_array(self.interpreter.trace(max_entries.max(0) as usize))
+            }
+
+            /// Writes the whole trace to `path` as JSON lines.
+            #[method]
+            fn dump_trace(&mut self, path: String) -> bool {
+                match self.interpreter.dump_trace(std::path::Path::new(&path)) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Failed to dump trace: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            /// Starts (discarding any unsaved capture) or stops recording every
+            /// sensor Dictionary `execute_tension` and `execute_metaweave` receive,
+            /// `step`'s included.
+            #[method]
+            fn set_sensor_recording(&mut self, enabled: bool) {
+                if enabled {
+                    self.interpreter.start_sensor_recording();
+                } else {
+                    self.interpreter.stop_sensor_recording();
+                }
+            }
+
+            #[method]
+            fn save_sensor_recording(&mut self, path: String) -> bool {
+                match self.interpreter.save_sensor_recording(std::path::Path::new(&path)) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Failed to save sensor recording: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            /// Feeds a sensor recording back: each `execute_tension` and
+            /// `execute_metaweave` reads its next recorded frame instead of the
+            /// Dictionary it is given, until the frames run out.
+            #[method]
+            fn start_sensor_replay(&mut self, path: String) -> bool {
+                match self.interpreter.start_sensor_replay(std::path::Path::new(&path)) {
+                    Ok(frames) => {
+                        weave_log!(self.interpreter.log(), Level::Info, "Replaying sensor recording {} ({} frames)", path, frames);
+                        true
+                    }
+                    Err(e) => {
+                        weave_log!(self.interpreter.log(), Level::Error, "Failed to load sensor recording: {}", e);
+                        self.record_error(&e);
+                        false
+                    }
+                }
+            }
+
+            #[method]
+            fn stop_sensor_replay(&mut self) {
+                self.interpreter.stop_sensor_replay();
+            }
+
+            #[method]
+            fn is_replaying_sensors(&self) -> bool {
+                self.interpreter.is_replaying_sensors()
+            }
+
+            #[method]
+            fn get_sensor_replay_remaining(&self) -> i64 {
+                self.interpreter.sensor_replay_remaining() as i64
+            }
+
+            /// `{ mean, variance, min, max, latest, slope }` over the last `window`
+            /// tension samples (0 = all); empty when nothing has been recorded yet.
+            #[method]
+            fn get_history_stats(&self, window: i64) -> Dictionary {
+                stats_dictionary(self.interpreter.history_stats(window.max(0) as usize))
+            }
+
+            /// The retained tension samples, oldest first, for plotting.
+            #[method]
+            fn get_tension_history(&self) -> PoolArray<f32> {
+                vector_array(self.interpreter.tension_history())
+            }
+
+            /// Mean of the retained tension samples; 0 before the first.
+            #[method]
+            fn tension_mean(&self) -> f64 {
+                self.interpreter.history_stats(0).map_or(0.0, |stats| to_f64(stats.mean))
+            }
+
+            /// Population variance of the retained tension samples.
+            #[method]
+            fn tension_variance(&self) -> f64 {
+                self.interpreter.history_stats(0).map_or(0.0, |stats| to_f64(stats.variance))
+            }
+
+            /// Least-squares slope of the retained samples, in tension per tick:
+            /// negative while the swarm settles.
+            #[method]
+            fn tension_trend(&self) -> f64 {
+                self.interpreter.history_stats(0).map_or(0.0, |stats| to_f64(stats.slope))
+            }
+
+            /// The whole tension history as `(tick, tension)` points, min/max bucketed
+            /// down to at most `max_points` so spikes stay visible.
+            #[method]
+            fn get_history_curve(&self, max_points: i64) -> PoolArray<Vector2> {
+                curve_array(&self.interpreter.history_curve(0, usize::MAX, max_points.max(0) as usize))
+            }
+
+            /// Every `(tick, tension)` point for ticks `from_tick..to_tick`.
+            #[method]
+            fn get_history_range(&self, from_tick: i64, to_tick: i64) -> PoolArray<Vector2> {
+                curve_array(&self.interpreter.history_curve(from_tick.max(0) as usize, to_tick.max(0) as usize, usize::MAX))
+            }
+        }
+    };
+}
+
+pub(crate) use shared_methods;
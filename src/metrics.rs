@@ -0,0 +1,153 @@
+use crate::error::WeaveError;
+use crate::scalar::Scalar;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long the server waits on a scraper that connects but sends nothing.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Counters and gauges for watching a long experiment. The counters run
+/// from the interpreter's creation, across loads and resets; the gauges hold
+/// what the last step left.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExperimentMetrics {
+    /// Steps run; a halted step doesn't count.
+    pub ticks: u64,
+    /// Loads, hot reloads and `execute_code` snippets that failed to parse.
+    pub parse_errors: u64,
+    /// Safety rule trips, warn and halt alike.
+    pub safety_violations: u64,
+    pub tension: Scalar,
+    pub smoothed_tension: Scalar,
+    pub coherence: Scalar,
+    pub halted: bool,
+    /// Each agent of the last step with the value of its tracked sensor,
+    /// `coherence` unless `set_pid_sensor` chose another.
+    pub agent_coherence: BTreeMap<String, Scalar>,
+}
+
+impl ExperimentMetrics {
+    /// The metrics in the Prometheus text exposition format, every name
+    /// prefixed `weave_`.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP weave_{} {}\n# TYPE weave_{} {}", name, help, name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "weave_{}{} {}", name, labels, value);
+            }
+        };
+        let single = |value: String| [(String::new(), value)];
+        metric("ticks_total", "counter", "Steps run.", &single(self.ticks.to_string()));
+        metric("parse_errors_total", "counter", "Loads, reloads and snippets that failed to parse.", &single(self.parse_errors.to_string()));
+        metric("safety_violations_total", "counter", "Safety rule trips.", &single(self.safety_violations.to_string()));
+        metric("tension", "gauge", "Tension of the last step.", &single(sample(self.tension)));
+        metric("smoothed_tension", "gauge", "Smoothed tension after the last step.", &single(sample(self.smoothed_tension)));
+        metric("coherence", "gauge", "Coherence after the last step.", &single(sample(self.coherence)));
+        metric("halted", "gauge", "1 while an invariant or an emergency stop halts stepping.", &single(u8::from(self.halted).to_string()));
+        let agents: Vec<(String, String)> = self.agent_coherence.iter().map(|(agent, value)| (format!("{{agent=\"{}\"}}", escape_label(agent)), sample(*value))).collect();
+        metric("agent_coherence", "gauge", "Tracked sensor of each agent in the last step.", &agents);
+        out
+    }
+}
+
+/// A value as Prometheus writes it, which spells out the non-finite ones.
+fn sample(value: Scalar) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// A minimal HTTP server on a thread of its own answering every `GET` with
+/// the page last published, for a Prometheus scraper. Requests are served
+/// one at a time; the server stops when dropped.
+pub struct MetricsServer {
+    address: SocketAddr,
+    page: Arc<Mutex<String>>,
+    stopping: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Listens on `address`, such as `127.0.0.1:9100`, serving `page` until
+    /// the first `publish`. Port 0 takes any free port, which `address()`
+    /// then reports.
+    pub fn start(address: &str, page: String) -> Result<MetricsServer, WeaveError> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let page = Arc::new(Mutex::new(page));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let (served, stop) = (page.clone(), stopping.clone());
+        let thread = std::thread::Builder::new().name("weave-metrics".to_string()).spawn(move || {
+            for stream in listener.incoming() {
+                if stop.load(Ordering::Acquire) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let _ = respond(stream, &served);
+                }
+            }
+        })?;
+        Ok(MetricsServer { address, page, stopping, thread: Some(thread) })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Replaces the page served from now on.
+    pub fn publish(&self, page: String) {
+        if let Ok(mut served) = self.page.lock() {
+            *served = page;
+        }
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.stopping.store(true, Ordering::Release);
+        // The listener blocks in accept until something connects.
+        let _ = TcpStream::connect_timeout(&self.address, REQUEST_TIMEOUT);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answers one request: the page for `GET /` or `GET /metrics`, 404 for
+/// any other path and 405 for any other method.
+fn respond(stream: TcpStream, page: &Mutex<String>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The headers are read through so the client sees a clean close.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let mut parts = request.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next().map(|target| target.split('?').next().unwrap_or(target))) {
+        (Some("GET"), Some("/" | "/metrics")) => ("200 OK", page.lock().map(|page| page.clone()).unwrap_or_default()),
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "only GET is served\n".to_string()),
+    };
+    let mut stream = reader.into_inner();
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)?;
+    stream.flush()
+}
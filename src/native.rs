@@ -0,0 +1,1572 @@
+use gdnative::prelude::*;
+use gdnative::export::hint::{FloatHint, IntHint, RangeHint};
+use gdnative::api::{KinematicBody, NavigationAgent, Node, PackedScene, ResourceLoader, RigidBody, Spatial};
+use indexmap::IndexMap;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use crate::{compiled, constants, noise};
+use crate::alarm::TensionCrossing;
+use crate::config::{DriftStrategy, InterpreterConfig, DEFAULT_DRIFT_RATE, DEFAULT_HISTORY_LIMIT, DEFAULT_RESOLVE_RATE};
+use crate::convert::{agents_from_dictionary, attributes_dictionary, behavior_status_from_variant, behaviors_dictionary, bindings_dictionary, changes_dictionary, code_outcome_dictionary, constants_dictionary, curve_array, diagnostics_array, error_code, error_dictionary, experiment_metrics_dictionary, fields_dictionary, goal_progress_dictionary, invariant_status_dictionary, invariant_violation_dictionary, log_array, message_array, metrics_dictionary, profile_dictionary, params_dictionary, preview_dictionary, rule_dictionary, safety_event_dictionary, safety_log_array, scalar_from_variant, scheduled_array, sensor_spec_from_dictionary, sensor_specs_dictionary, sensors_from_dictionary, state_dictionary, state_from_dictionary, stats_dictionary, tension_explanation_dictionary, trace_array, vector_array, vector_from_variant, vector_model_dictionary, write_agents};
+use crate::datalog::DEFAULT_FLUSH_TICKS;
+use crate::diagnostic::Diagnostic;
+use crate::error::WeaveError;
+use crate::global::Reduction;
+use crate::interpreter::{binding_target, ExperimentState, Fields, Interpreter, ParamValue, Params, ResolveMode, LANGUAGE_VERSION};
+use crate::invariant::InvariantPolicy;
+use crate::kinematics::{self, DriveMode, Limits};
+use crate::layout::FrameLayout;
+use crate::logging::{weave_log, Level};
+use crate::methods::shared_methods;
+use crate::pid::PidGains;
+use crate::reload::FieldChanges;
+use crate::rules::{MetaweaveRule, RuleCondition};
+use crate::safety::SafetySeverity;
+use crate::scalar::{to_f32, to_f64, Scalar};
+use crate::sync::DiffOutcome;
+
+/// Lab nodes a new node registers for lab calls, by name, with their child
+/// path; the `lab_paths` property replaces them.
+const DEFAULT_LAB_PATHS: [(&str, &str); 4] =
+    [("accelerator", "Accelerator"), ("chemistry_lab", "ChemistryLab"), ("observatory", "Observatory"), ("neuroscience_lab", "NeuroscienceLab")];
+
+/// Most ticks one physics frame runs at `tick_rate`; a longer frame drops
+/// the rest rather than falling further behind.
+const MAX_TICKS_PER_FRAME: usize = 4;
+
+/// Whether an agent with a `position` has a Spatial to apply it to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AgentBinding {
+    Bound,
+    Missing,
+    WrongType,
+}
+
+impl AgentBinding {
+    fn as_str(self) -> &'static str {
+        match self {
+            AgentBinding::Bound => "bound",
+            AgentBinding::Missing => "missing",
+            AgentBinding::WrongType => "wrong_type",
+        }
+    }
+}
+
+/// A `WeaveLang` registered as one lab of this program.
+struct ChildLab {
+    lab: Ref<Reference>,
+    /// How much the lab counts towards a weighted-mean combined tension.
+    weight: Scalar,
+}
+
+// Every node owns its interpreter, lab paths and weave script, so several
+// can run side by side in one scene, e.g. one per lab, without sharing state.
+// RwLockData lets the `&self` getters run concurrently from many observer nodes;
+// only `&mut self` methods take the lock exclusively.
+#[derive(NativeClass)]
+#[inherit(Spatial)]
+#[register_with(Self::register)]
+#[user_data(gdnative::export::user_data::RwLockData<WeaveLangNative>)]
+pub struct WeaveLangNative {
+    /// Lab name to node path, relative to this node, for lab calls.
+    lab_paths: IndexMap<String, String>,
+    lab_nodes: HashMap<String, Ref<Node>>,
+    agent_nodes: HashMap<String, Ref<Node>>,
+    spawned_agents: HashSet<String>,
+    agent_bindings: HashMap<String, AgentBinding>,
+    /// How `_physics_process` moves agent nodes to their positions.
+    drive_mode: DriveMode,
+    /// The velocity each KinematicBody agent came out of `move_and_slide`
+    /// with last frame, which its next velocity changes from.
+    kinematic_velocities: HashMap<String, Vec<Scalar>>,
+    /// Whether each step first steers agents along the paths of their
+    /// NavigationAgent children.
+    navigation: bool,
+    /// Agents given a waypoint from a navigation path.
+    navigating: HashSet<String>,
+    #[property]
+    weave_path: String,
+    /// The script to load when `weave_path` is empty.
+    #[property]
+    weave_source: String,
+    #[property(default = false)]
+    auto_reload: bool,
+    #[property(default = 1.0)]
+    reload_interval: f64,
+    /// Node paths of robots whose node name is an agent's, for agents that
+    /// aren't children of this node.
+    #[property]
+    robot_paths: PoolArray<GodotString>,
+    /// Steps per second `_physics_process` runs with every field and the
+    /// bound sensors; 0 leaves stepping to scripts.
+    #[property(default = 0.0)]
+    tick_rate: f64,
+    tick_elapsed: f64,
+    physics_delta: Scalar,
+    broken_bindings: HashSet<String>,
+    children: IndexMap<String, ChildLab>,
+    child_reduction: Reduction,
+    /// The `global` params last sent to the children.
+    published_globals: Params,
+    /// A state imported before `_ready`, applied once the weave file is loaded.
+    pending_state: Option<ExperimentState>,
+    ready: bool,
+    last_error: Dictionary,
+    last_diagnostics: Vec<Diagnostic>,
+    /// Objects and methods called with the event when a `halt` safety rule trips.
+    halt_callbacks: Vec<(Ref<Object>, String)>,
+    /// Behavior tree actions GDScript registered, each an object and method
+    /// called with the agent.
+    behavior_actions: HashMap<String, (Ref<Object>, String)>,
+    /// Agents and sensors registered for `step_buffer`.
+    frame: FrameLayout,
+    /// Every simulated sensor reading draws from this, so a seed replays them.
+    simulation_rng: StdRng,
+    simulation_seed: u64,
+    interpreter: Interpreter,
+}
+
+shared_methods!(WeaveLangNative, Spatial);
+
+#[methods]
+impl WeaveLangNative {
+    fn new(_owner: &Spatial) -> Self {
+        WeaveLangNative {
+            lab_paths: DEFAULT_LAB_PATHS.iter().map(|(name, path)| (name.to_string(), path.to_string())).collect(),
+            lab_nodes: HashMap::new(),
+            agent_nodes: HashMap::new(),
+            spawned_agents: HashSet::new(),
+            agent_bindings: HashMap::new(),
+            drive_mode: DriveMode::Teleport,
+            kinematic_velocities: HashMap::new(),
+            navigation: false,
+            navigating: HashSet::new(),
+            weave_path: String::new(),
+            weave_source: String::new(),
+            auto_reload: false,
+            reload_interval: 1.0,
+            robot_paths: PoolArray::new(),
+            tick_rate: 0.0,
+            tick_elapsed: 0.0,
+            physics_delta: 1.0,
+            broken_bindings: HashSet::new(),
+            children: IndexMap::new(),
+            child_reduction: Reduction::Max,
+            published_globals: Params::new(),
+            pending_state: None,
+            ready: false,
+            last_error: Dictionary::new_shared(),
+            last_diagnostics: Vec::new(),
+            halt_callbacks: Vec::new(),
+            behavior_actions: HashMap::new(),
+            frame: FrameLayout::default(),
+            simulation_rng: StdRng::seed_from_u64(noise::DEFAULT_SEED),
+            simulation_seed: noise::DEFAULT_SEED,
+            interpreter: Interpreter::new(),
+        }
+    }
+
+    fn register(builder: &ClassBuilder<Self>) {
+        Self::register_signals(builder);
+        Self::register_properties(builder);
+        builder.mixin::<SharedMethods>();
+    }
+
+    /// The tuning knobs as inspector properties. They read and write the
+    /// interpreter directly, so scripts and AnimationPlayer see the same
+    /// values, and hot reloads leave them alone.
+    fn register_properties(builder: &ClassBuilder<Self>) {
+        builder
+            .property::<Dictionary>("lab_paths")
+            .with_getter(|this: &Self, _| bindings_dictionary(this.lab_paths.iter().map(|(name, path)| (name.as_str(), path.as_str()))))
+            .with_setter(|this: &mut Self, owner: TRef<Spatial>, paths: Dictionary| this.set_lab_paths(&owner, &paths))
+            .done();
+        builder
+            .property::<String>("tension_reference")
+            .with_default("generalist".to_string())
+            .with_getter(|this: &Self, _| this.interpreter.tension_reference().to_string())
+            .with_setter(|this: &mut Self, _, field: String| this.interpreter.set_tension_reference(&field))
+            .done();
+        builder
+            .property::<f64>("drift_rate")
+            .with_default(to_f64(DEFAULT_DRIFT_RATE))
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 0.1).with_step(0.0001).or_greater()))
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.drift_rate()))
+            .with_setter(|this: &mut Self, _, rate: f64| this.interpreter.set_drift_rate(rate as Scalar))
+            .done();
+        builder
+            .property::<f64>("resolve_rate")
+            .with_default(to_f64(DEFAULT_RESOLVE_RATE))
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 0.1).with_step(0.0001).or_greater()))
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.resolve_rate()))
+            .with_setter(|this: &mut Self, _, rate: f64| this.interpreter.set_resolve_rate(rate as Scalar))
+            .done();
+        builder
+            .property::<f64>("smoothing_alpha")
+            .with_default(1.0)
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 1.0).with_step(0.01)))
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.smoothing_alpha()))
+            .with_setter(|this: &mut Self, _, alpha: f64| this.interpreter.set_smoothing_alpha(alpha as Scalar))
+            .done();
+        builder
+            .property::<i64>("history_limit")
+            .with_default(DEFAULT_HISTORY_LIMIT as i64)
+            .with_hint(IntHint::Range(RangeHint::new(0, 100_000).with_step(100).or_greater()))
+            .with_getter(|this: &Self, _| this.interpreter.history_limit() as i64)
+            .with_setter(|this: &mut Self, _, limit: i64| this.interpreter.set_history_limit(limit.max(0) as usize))
+            .done();
+        builder
+            .property::<bool>("clamp_targets")
+            .with_default(false)
+            .with_getter(|this: &Self, _| this.interpreter.config().clamp_targets)
+            .with_setter(|this: &mut Self, _, clamp: bool| {
+                let config = InterpreterConfig { clamp_targets: clamp, ..*this.interpreter.config() };
+                this.interpreter.set_config(config);
+            })
+            .done();
+        builder
+            .property::<f64>("target_min")
+            .with_default(0.0)
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.config().target_min))
+            .with_setter(|this: &mut Self, _, min: f64| {
+                let config = InterpreterConfig { target_min: min as Scalar, ..*this.interpreter.config() };
+                this.interpreter.set_config(config);
+            })
+            .done();
+        builder
+            .property::<f64>("target_max")
+            .with_default(1.0)
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.config().target_max))
+            .with_setter(|this: &mut Self, _, max: f64| {
+                let config = InterpreterConfig { target_max: max as Scalar, ..*this.interpreter.config() };
+                this.interpreter.set_config(config);
+            })
+            .done();
+        builder
+            .property::<f64>("tension_threshold")
+            .with_default(0.0)
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 1.0).with_step(0.001).or_greater()))
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.tension_threshold()))
+            .with_setter(|this: &mut Self, _, threshold: f64| this.interpreter.set_tension_threshold(threshold as Scalar))
+            .done();
+    }
+
+    fn register_signals(builder: &ClassBuilder<Self>) {
+        builder
+            .signal("weave_reloaded")
+            .with_param("changes", VariantType::Dictionary)
+            .done();
+        builder
+            .signal("weave_reload_failed")
+            .with_param("error", VariantType::Dictionary)
+            .done();
+        builder.signal("converged").done();
+        builder.signal("halted").done();
+        builder
+            .signal("safety_halt")
+            .with_param("rule_index", VariantType::I64)
+            .with_param("sensor_value", VariantType::F64)
+            .done();
+        builder
+            .signal("safety_violation")
+            .with_param("event", VariantType::Dictionary)
+            .done();
+        builder
+            .signal("sensor_missing")
+            .with_param("name", VariantType::GodotString)
+            .done();
+        builder
+            .signal("field_changed")
+            .with_param("agent", VariantType::GodotString)
+            .with_param("param", VariantType::GodotString)
+            .with_param("old_value", VariantType::F64)
+            .with_param("new_value", VariantType::F64)
+            .done();
+        builder
+            .signal("tension_high")
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("tension_resolved")
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("goal_completed")
+            .with_param("name", VariantType::GodotString)
+            .done();
+        builder
+            .signal("invariant_violated")
+            .with_param("name", VariantType::GodotString)
+            .done();
+        builder
+            .signal("param_out_of_bounds")
+            .with_param("field", VariantType::GodotString)
+            .with_param("param", VariantType::GodotString)
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("weave_event")
+            .with_param("condition", VariantType::GodotString)
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("weave_signal")
+            .with_param("signal", VariantType::GodotString)
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("lab_call_failed")
+            .with_param("node", VariantType::GodotString)
+            .with_param("method", VariantType::GodotString)
+            .with_param("reason", VariantType::GodotString)
+            .done();
+    }
+
+    fn load_weave_path(&mut self) -> Result<(), WeaveError> {
+        if self.weave_path.is_empty() {
+            return Ok(());
+        }
+        self.interpreter.set_reload_interval(self.reload_interval);
+        self.broken_bindings.clear();
+        let path = std::path::Path::new(&self.weave_path);
+        let result = if path.extension().is_some_and(|extension| extension == compiled::EXTENSION) {
+            self.interpreter.load_compiled(path)
+        } else {
+            self.interpreter.load(path)
+        };
+        match &result {
+            Ok(()) => {
+                weave_log!(self.interpreter.log(), Level::Info, "Loaded Weave file: {}", self.weave_path);
+                self.clear_error();
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to load Weave file: {}", e);
+                self.record_error(e);
+            }
+        }
+        self.set_auto_reload(self.auto_reload);
+        result
+    }
+
+    /// Replaces the lab paths with `paths`' String entries, resolving them
+    /// right away once the node is in the tree.
+    fn set_lab_paths(&mut self, owner: &Spatial, paths: &Dictionary) {
+        self.lab_paths.clear();
+        for (name, path) in paths.iter() {
+            match (name.try_to::<String>(), path.try_to::<String>()) {
+                (Ok(name), Ok(path)) => {
+                    self.lab_paths.insert(name, path);
+                }
+                _ => weave_log!(self.interpreter.log(), Level::Warn, "lab_paths: skipping {:?}, which is not a name and a node path", name),
+            }
+        }
+        if self.ready {
+            self.resolve_lab_nodes(owner);
+        }
+    }
+
+    /// Registers the node at each lab path; a missing one warns, and calls on
+    /// its lab then fail.
+    fn resolve_lab_nodes(&mut self, owner: &Spatial) {
+        self.lab_nodes.clear();
+        for (name, path) in &self.lab_paths {
+            match owner.get_node(path.as_str()) {
+                Some(node) => {
+                    self.lab_nodes.insert(name.clone(), node);
+                }
+                None => weave_log!(self.interpreter.log(), Level::Warn, "No {} child, so lab calls on {} will fail", path, name),
+            }
+        }
+    }
+
+    /// Registers each robot in `robot_paths` as the node of the agent named
+    /// after it; a missing one warns.
+    fn resolve_robot_nodes(&mut self, owner: &Spatial) {
+        for path in self.robot_paths.read().iter() {
+            match owner.get_node(path.to_string().as_str()) {
+                Some(node) => {
+                    let name = unsafe { node.assume_safe() }.name().to_string();
+                    self.agent_nodes.insert(name, node);
+                }
+                None => weave_log!(self.interpreter.log(), Level::Warn, "No robot node at {}", path),
+            }
+        }
+    }
+
+    /// Runs the steps `tick_rate` owes after `delta` more seconds.
+    fn run_ticks(&mut self, owner: &Spatial, delta: f64) {
+        if self.tick_rate <= 0.0 {
+            self.tick_elapsed = 0.0;
+            return;
+        }
+        let interval = 1.0 / self.tick_rate;
+        self.tick_elapsed += delta;
+        let mut ticks = 0;
+        while self.tick_elapsed >= interval {
+            if ticks == MAX_TICKS_PER_FRAME {
+                self.tick_elapsed = 0.0;
+                break;
+            }
+            self.tick_elapsed -= interval;
+            let sensors = self.read_sensors(owner, &Dictionary::new_shared());
+            let agents: Fields = self.interpreter.fields().keys().map(|name| (name.clone(), Params::new())).collect();
+            self.step_with(owner, &sensors, &agents, interval as Scalar);
+            ticks += 1;
+        }
+    }
+
+    fn record_error(&mut self, error: &WeaveError) {
+        self.last_error = error_dictionary(error);
+        self.last_diagnostics = vec![Diagnostic::from_error(error)];
+    }
+
+    fn clear_error(&mut self) {
+        self.last_error = Dictionary::new_shared();
+        self.last_diagnostics.clear();
+    }
+
+    fn check_reload(&mut self, owner: &Spatial, delta: f64) {
+        if let Some(result) = self.interpreter.poll_reload(delta) {
+            self.finish_reload(owner, &result);
+        }
+    }
+
+    fn finish_reload(&mut self, owner: &Spatial, result: &Result<FieldChanges, WeaveError>) {
+        match result {
+            Ok(changes) => {
+                weave_log!(self.interpreter.log(), Level::Info, "Reloaded Weave file: {}", self.weave_path);
+                self.broken_bindings.clear();
+                self.sync_spawned_agents(owner);
+                owner.emit_signal("weave_reloaded", &[changes_dictionary(changes).to_variant()]);
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to reload Weave file, keeping previous state: {}", e);
+                self.record_error(e);
+                owner.emit_signal("weave_reload_failed", &[error_dictionary(e).to_variant()]);
+            }
+        }
+    }
+
+    /// Instances a child for every field declaring `spawn: true` and a `scene`,
+    /// and frees children spawned earlier whose field no longer asks for one.
+    fn sync_spawned_agents(&mut self, owner: &Spatial) {
+        let requests = self.interpreter.spawn_requests();
+        let stale: Vec<String> = self
+            .spawned_agents
+            .iter()
+            .filter(|name| !requests.iter().any(|(field, _)| field == *name))
+            .cloned()
+            .collect();
+        for name in stale {
+            self.spawned_agents.remove(&name);
+            if let Some(node) = self.agent_nodes.remove(&name) {
+                unsafe { node.assume_safe() }.queue_free();
+            }
+            weave_log!(self.interpreter.log(), Level::Info, "Despawned agent {}", name);
+        }
+        for (name, scene_path) in requests {
+            if self.agent_nodes.contains_key(&name) {
+                continue;
+            }
+            match spawn_scene(owner, &name, &scene_path) {
+                Some(node) => {
+                    self.agent_nodes.insert(name.clone(), node);
+                    self.spawned_agents.insert(name);
+                }
+                None => weave_log!(self.interpreter.log(), Level::Warn, "Could not spawn agent {} from {}", name, scene_path),
+            }
+        }
+    }
+
+    /// Copies each agent's `position` vector to the translation of its node,
+    /// the one spawned for it or else the child named after it, with missing
+    /// components reading 0. Locked agents are left to whatever else moves
+    /// them. An agent whose node is missing or not a Spatial is warned about
+    /// once, until its binding changes.
+    fn apply_agent_positions(&mut self, owner: &Spatial) {
+        self.move_agents(owner, None);
+    }
+
+    /// `apply_agent_positions`, except that with a drive mode other than
+    /// `teleport` a physics frame of `dt` drives RigidBody and KinematicBody
+    /// nodes towards their positions instead. Other nodes are still moved
+    /// with `set_translation`.
+    fn move_agents(&mut self, owner: &Spatial, dt: Option<Scalar>) {
+        let positions: Vec<(String, Vec<Scalar>)> = self
+            .interpreter
+            .attributes()
+            .iter()
+            .filter_map(|(name, params)| match params.get("position") {
+                Some(ParamValue::Vector(components)) => Some((name.clone(), components.clone())),
+                _ => None,
+            })
+            .collect();
+        self.agent_bindings.retain(|name, _| positions.iter().any(|(agent, _)| agent == name));
+        self.kinematic_velocities.retain(|name, _| positions.iter().any(|(agent, _)| agent == name));
+        let drive = dt.filter(|_| self.drive_mode != DriveMode::Teleport);
+        for (name, components) in positions {
+            let node = self.agent_node(owner, &name);
+            let (binding, problem) = match node.map(|node| (node, node.cast::<Spatial>())) {
+                None => (AgentBinding::Missing, "has no node".to_string()),
+                Some((node, None)) => (AgentBinding::WrongType, format!("has a {} node, which is not a Spatial", node.get_class())),
+                Some((node, Some(spatial))) => {
+                    if !self.interpreter.is_agent_locked(&name) {
+                        let driven = drive.is_some_and(|dt| {
+                            let limits = Limits::from_params(self.interpreter.fields().get(&name).unwrap_or(&Params::default()));
+                            let kinematic = self.kinematic_velocities.entry(name.clone()).or_default();
+                            drive_body(node, &components, self.drive_mode, limits, kinematic, dt)
+                        });
+                        if !driven {
+                            spatial.set_translation(godot_vector(&components));
+                        }
+                    }
+                    (AgentBinding::Bound, String::new())
+                }
+            };
+            if self.agent_bindings.insert(name.clone(), binding) != Some(binding) && binding != AgentBinding::Bound {
+                self.interpreter.warn(format!("agent {} {}, so its position is not applied", name, problem));
+            }
+        }
+    }
+
+    /// The node spawned for `agent`, or else the child named after it.
+    fn agent_node<'a>(&self, owner: &'a Spatial, agent: &str) -> Option<TRef<'a, Node>> {
+        match self.agent_nodes.get(agent) {
+            Some(node) => unsafe { node.assume_safe_if_sane() },
+            None => owner.get_node_or_null(agent).map(|node| unsafe { node.assume_safe() }),
+        }
+    }
+
+    /// Hands each agent's `target_position` to the NavigationAgent among its
+    /// node's children as the navigation target, and steers the agent to
+    /// the next point of the path found, up to the target itself once the
+    /// path is done. Agents without one go straight to their target.
+    fn update_waypoints(&mut self, owner: &Spatial) {
+        let mut waypoints: Vec<(String, Option<Vec<Scalar>>)> = Vec::new();
+        for (name, attributes) in self.interpreter.attributes() {
+            let Some(ParamValue::Vector(target)) = attributes.get("target_position") else {
+                continue;
+            };
+            let Some(navigator) = self.agent_node(owner, name).and_then(navigation_agent) else {
+                continue;
+            };
+            let goal = godot_vector(target);
+            if navigator.get_target_location().distance_to(goal) > 1e-4 {
+                navigator.set_target_location(goal);
+            }
+            let next = (!navigator.is_navigation_finished()).then(|| scalar_vector(navigator.get_next_location()));
+            waypoints.push((name.clone(), next.map(|mut next| {
+                next.truncate(target.len());
+                next
+            })));
+        }
+        let routed: HashSet<String> = waypoints.iter().map(|(name, _)| name.clone()).collect();
+        for name in self.navigating.difference(&routed) {
+            self.interpreter.clear_waypoint(name);
+        }
+        for (name, waypoint) in waypoints {
+            match waypoint {
+                Some(point) => self.interpreter.set_waypoint(&name, point),
+                None => {
+                    self.interpreter.clear_waypoint(&name);
+                }
+            }
+        }
+        self.navigating = routed;
+    }
+
+    /// Emits `safety_violation` for every trip; a `halt` rule also runs the
+    /// halt callbacks and emits `safety_halt`.
+    fn emit_safety_events(&mut self, owner: &Spatial) {
+        for event in self.interpreter.take_safety_events() {
+            weave_log!(
+                self.interpreter.log(),
+                Level::Warn,
+                "Safety rule {} ({}) tripped: {} = {}",
+                event.rule_index,
+                event.severity.as_str(),
+                event.sensor,
+                event.sensor_value
+            );
+            let dict = safety_event_dictionary(Some(&event));
+            owner.emit_signal("safety_violation", &[dict.to_variant()]);
+            if event.severity == SafetySeverity::Halt {
+                self.run_halt_callbacks(&dict);
+                owner.emit_signal("safety_halt", &[(event.rule_index as i64).to_variant(), event.sensor_value.to_variant()]);
+            }
+        }
+    }
+
+    /// Calls every halt callback with `event`, dropping those whose object
+    /// has been freed.
+    fn run_halt_callbacks(&mut self, event: &Dictionary) {
+        weave_log!(self.interpreter.log(), Level::Info, "Halting experiment due to safety violation");
+        self.halt_callbacks.retain(|(target, method)| match unsafe { target.assume_safe_if_sane() } {
+            Some(target) => {
+                unsafe { target.call(method.as_str(), &[event.to_variant()]) };
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// `sensor_data` plus a fresh sample of every bound sensor it doesn't
+    /// already carry.
+    fn read_sensors(&mut self, owner: &Spatial, sensor_data: &Dictionary) -> HashMap<String, Scalar> {
+        let mut warnings = Vec::new();
+        let mut sensors = sensors_from_dictionary(sensor_data, &mut warnings);
+        for warning in warnings {
+            self.interpreter.warn(warning);
+        }
+        self.sample_bindings(owner, &mut sensors);
+        sensors
+    }
+
+    /// Reads each bound node property into `sensors`. A binding whose node
+    /// or property can't be read warns once and is left out, so its sensor
+    /// falls back to the missing-sensor policy.
+    fn sample_bindings(&mut self, owner: &Spatial, sensors: &mut HashMap<String, Scalar>) {
+        let mut broken = Vec::new();
+        for (sensor, source) in self.interpreter.sensor_bindings() {
+            if sensors.contains_key(sensor) {
+                continue;
+            }
+            let value = binding_target(source).and_then(|(path, property)| {
+                let node = owner.get_node_or_null(path)?;
+                scalar_from_variant(&unsafe { node.assume_safe() }.get_indexed(property))
+            });
+            match value {
+                Some(value) => {
+                    sensors.insert(sensor.to_string(), value);
+                    self.broken_bindings.remove(sensor);
+                }
+                None => broken.push((sensor.to_string(), source.to_string())),
+            }
+        }
+        for (sensor, source) in broken {
+            if self.broken_bindings.insert(sensor.clone()) {
+                self.interpreter.warn(format!("sensor {} is bound to {}, which can't be read", sensor, source));
+            }
+        }
+    }
+
+    fn read_agents(&mut self, agent_data: &Dictionary) -> Fields {
+        let mut warnings = Vec::new();
+        let agents = agents_from_dictionary(agent_data, &mut warnings);
+        for warning in warnings {
+            self.interpreter.warn(warning);
+        }
+        agents
+    }
+
+    fn emit_missing_sensors(&mut self, owner: &Spatial) {
+        for sensor in self.interpreter.take_missing_sensors() {
+            owner.emit_signal("sensor_missing", &[sensor.to_variant()]);
+        }
+    }
+
+    fn emit_tension_crossings(&mut self, owner: &Spatial) {
+        for crossing in self.interpreter.take_tension_crossings() {
+            match crossing {
+                TensionCrossing::High(value) => owner.emit_signal("tension_high", &[value.to_variant()]),
+                TensionCrossing::Resolved(value) => owner.emit_signal("tension_resolved", &[value.to_variant()]),
+            };
+        }
+    }
+
+    fn emit_completed_goals(&mut self, owner: &Spatial) {
+        for name in self.interpreter.take_completed_goals() {
+            owner.emit_signal("goal_completed", &[name.to_variant()]);
+        }
+    }
+
+    fn emit_invariant_violations(&mut self, owner: &Spatial) {
+        for violation in self.interpreter.take_invariant_violations() {
+            owner.emit_signal("invariant_violated", &[violation.name.to_variant()]);
+        }
+    }
+
+    /// `param_out_of_bounds(field, param, value)` for each param a declared
+    /// range started holding back, with the value it would have taken.
+    fn emit_out_of_bounds(&mut self, owner: &Spatial) {
+        for event in self.interpreter.take_out_of_bounds() {
+            owner.emit_signal("param_out_of_bounds", &[event.field.to_variant(), event.param.to_variant(), event.value.to_variant()]);
+        }
+    }
+
+    /// `weave_event(condition, value)` for each `on` handler that fired; the
+    /// value is 0 for an attribute test.
+    fn emit_fired_events(&mut self, owner: &Spatial) {
+        for event in self.interpreter.take_fired_events() {
+            owner.emit_signal("weave_event", &[event.condition.to_variant(), event.value.unwrap_or(0.0).to_variant()]);
+        }
+    }
+
+    /// Ticks the script's behavior trees, running each registered action as
+    /// `target.method(agent)`. An action whose object was freed counts as
+    /// unregistered.
+    fn tick_behaviors(&mut self, sensors: &HashMap<String, Scalar>, dt: Scalar) {
+        let actions = &self.behavior_actions;
+        self.interpreter.tick_behaviors(sensors, dt, &mut |action, agent| {
+            let (target, method) = actions.get(action)?;
+            let target = unsafe { target.assume_safe_if_sane() }?;
+            Some(behavior_status_from_variant(&unsafe { target.call(method.as_str(), &[agent.to_variant()]) }))
+        });
+    }
+
+    /// Emits each queued `emit` as `weave_signal(signal, value)`, then as a
+    /// signal of its own name, added to the object the first time. A name
+    /// that is already one of the class's signals only goes out as
+    /// `weave_signal`, so a script can't fake `safety_halt` and the like.
+    fn emit_script_signals(&mut self, owner: &Spatial) {
+        for emitted in self.interpreter.take_emitted_signals() {
+            owner.emit_signal("weave_signal", &[emitted.signal.to_variant(), emitted.value.to_variant()]);
+            let signal = emitted.signal.as_str();
+            if !owner.has_signal(signal) {
+                owner.add_user_signal(signal, VariantArray::new_shared());
+            }
+            if owner.has_user_signal(signal) {
+                owner.emit_signal(signal, &[emitted.value.to_variant()]);
+            }
+        }
+    }
+
+    fn emit_field_changes(&mut self, owner: &Spatial) {
+        for change in self.interpreter.take_field_changes() {
+            owner.emit_signal(
+                "field_changed",
+                &[change.agent.to_variant(), change.param.to_variant(), change.old_value.to_variant(), change.new_value.to_variant()],
+            );
+        }
+    }
+
+    /// Runs the lab calls metaweave `call` rules fired since the last dispatch.
+    fn dispatch_lab_calls(&mut self, owner: &Spatial) {
+        for call in self.interpreter.take_lab_calls() {
+            let args: Vec<Variant> = call.args.iter().map(|arg| arg.to_variant()).collect();
+            self.call_lab_node(owner, &call.node, &call.method, &args);
+        }
+    }
+
+    /// Calls `method` on the lab node registered as `name`. A missing or freed
+    /// node, or a missing method, warns, emits `lab_call_failed` and returns null.
+    fn call_lab_node(&mut self, owner: &Spatial, name: &str, method: &str, args: &[Variant]) -> Variant {
+        let node = match self.lab_nodes.get(name) {
+            Some(node) => unsafe { node.assume_safe_if_sane() },
+            None => return self.lab_call_failed(owner, name, method, "no lab node is registered under that name"),
+        };
+        let node = match node {
+            Some(node) => node,
+            None => return self.lab_call_failed(owner, name, method, "the lab node has been freed"),
+        };
+        if !node.has_method(method) {
+            return self.lab_call_failed(owner, name, method, "the lab node has no such method");
+        }
+        unsafe { node.call(method, args) }
+    }
+
+    fn lab_call_failed(&mut self, owner: &Spatial, name: &str, method: &str, reason: &str) -> Variant {
+        self.interpreter.warn(format!("lab call {}.{} failed: {}", name, method, reason));
+        owner.emit_signal("lab_call_failed", &[name.to_variant(), method.to_variant(), reason.to_variant()]);
+        Variant::nil()
+    }
+
+    /// Calls `method` on a lab node registered through `lab_paths` (by default
+    /// `accelerator`, `chemistry_lab`, `observatory` or `neuroscience_lab`)
+    /// the same way metaweave `call` rules do.
+    #[method]
+    fn call_lab(&mut self, #[base] owner: &Spatial, name: String, method: String, args: VariantArray) -> Variant {
+        let args: Vec<Variant> = args.iter().collect();
+        self.call_lab_node(owner, &name, &method, &args)
+    }
+
+    /// Runs a weave snippet against the current state and returns what it
+    /// changed; a snippet that fails to parse or validate changes nothing.
+    #[method]
+    fn execute_code(&mut self, #[base] owner: &Spatial, code: String) -> Dictionary {
+        let outcome = self.interpreter.execute_code(&code);
+        if let Err(e) = &outcome {
+            weave_log!(self.interpreter.log(), Level::Error, "execute_code failed: {}", e);
+            self.record_error(e);
+        }
+        self.emit_field_changes(owner);
+        self.dispatch_lab_calls(owner);
+        code_outcome_dictionary(&outcome)
+    }
+
+    /// `execute_drift` over the last physics frame's delta, limited to the agents tagged `tag`.
+    #[method]
+    fn execute_drift_group(&mut self, #[base] owner: &Spatial, tag: String, agent_data: Dictionary, tension: Scalar) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_drift_group(&tag, &agents, tension, self.physics_delta);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// `execute_resolve` over the last physics frame's delta, limited to the agents tagged `tag`.
+    #[method]
+    fn execute_resolve_group(&mut self, #[base] owner: &Spatial, tag: String, agent_data: Dictionary, tension: Scalar) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_resolve_group(&tag, &agents, tension, self.physics_delta);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// Coherence from the smoothed tension, `1 / (1 + tension)`.
+    #[method]
+    fn sense_coherence(&self) -> Scalar {
+        self.interpreter.coherence()
+    }
+
+    /// Overrides the safety metric; a safety rule tripping sets it back to 0.
+    #[method]
+    fn set_safety_metric(&mut self, value: f64) {
+        self.interpreter.set_safety_metric(value as Scalar);
+    }
+
+    #[method]
+    fn get_safety_metric(&self) -> Scalar {
+        self.interpreter.safety_metric()
+    }
+
+    /// Every number-list param, such as a field's `position`, as
+    /// `{ "field.param": PoolRealArray }`. The arrays are copies.
+    #[method]
+    fn get_vector_model(&self) -> Dictionary {
+        vector_model_dictionary(&self.interpreter.vector_model())
+    }
+
+    /// Sets the vector `key` (`"field.param"`) from a PoolRealArray or an
+    /// Array of numbers, e.g. to override a robot's target position. The
+    /// field must be declared; returns false and logs why otherwise.
+    #[method]
+    fn set_vector(&mut self, key: String, values: Variant) -> bool {
+        let result = match vector_from_variant(&values) {
+            Some(components) => self.interpreter.set_vector(&key, components),
+            None => Err(WeaveError::Validation(format!("vector {} must be set from numbers, got {:?}", key, values.get_type()))),
+        };
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "set_vector failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// Numeric params of `agent`; empty if it isn't declared.
+    #[method]
+    fn get_field(&self, agent: String) -> Dictionary {
+        let field = Dictionary::new();
+        for (param, value) in self.interpreter.fields().get(&agent).into_iter().flatten() {
+            field.insert(param, *value);
+        }
+        field.into_shared()
+    }
+
+    /// Points `sensor` at `source` (`"<node path>:<property>"`), overriding the
+    /// script's binding across reloads. False if `source` is malformed.
+    #[method]
+    fn rebind_sensor(&mut self, sensor: String, source: String) -> bool {
+        if !self.interpreter.rebind_sensor(&sensor, &source) {
+            weave_log!(self.interpreter.log(), Level::Warn, "rebind_sensor({}): \"{}\" is not \"<node path>:<property>\"", sensor, source);
+            return false;
+        }
+        self.broken_bindings.remove(&sensor);
+        true
+    }
+
+    /// Applies a peer's `get_state_diff`. False, with the fields untouched,
+    /// for a stale or duplicate diff, one that skips a revision this side never
+    /// saw, or bytes that aren't a diff.
+    #[method]
+    fn apply_state_diff(&mut self, #[base] owner: &Spatial, bytes: PoolArray<u8>) -> bool {
+        match self.interpreter.apply_state_diff(&bytes.to_vec()) {
+            Ok(DiffOutcome::Applied { .. }) => {
+                self.sync_spawned_agents(owner);
+                true
+            }
+            Ok(_) => false,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "apply_state_diff failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// The value of constant `name`, or null if there is none.
+    #[method]
+    fn get_constant(&self, name: String) -> Variant {
+        match self.interpreter.constants().get(&name) {
+            Some(constant) => constant.value.to_variant(),
+            None => {
+                weave_log!(self.interpreter.log(), Level::Error, "get_constant: unknown constant {}", name);
+                Variant::nil()
+            }
+        }
+    }
+
+    /// Starts the experiment over from the loaded program, as if it had just
+    /// been loaded: fields, tension history, scheduled sets, goals, the trace
+    /// and every lock go back to the script's state. Settings stay. Edits to
+    /// the file since it loaded are not picked up. False, with the error
+    /// logged, if nothing is loaded.
+    #[method]
+    fn reset(&mut self, #[base] owner: &Spatial) -> bool {
+        self.broken_bindings.clear();
+        match self.interpreter.reset() {
+            Ok(()) => {
+                self.sync_spawned_agents(owner);
+                self.apply_agent_positions(owner);
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "reset failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// Injects the sections of a `to_json` String, replacing the current
+    /// ones; sections left out stay. False, changing nothing, if the JSON
+    /// is malformed.
+    #[method]
+    // Named after Interpreter::from_json for GDScript, though it loads into self.
+    #[allow(clippy::wrong_self_convention)]
+    fn from_json(&mut self, #[base] owner: &Spatial, json: String) -> bool {
+        if let Err(e) = self.interpreter.from_json(&json) {
+            weave_log!(self.interpreter.log(), Level::Error, "from_json failed: {}", e);
+            self.record_error(&e);
+            return false;
+        }
+        self.apply_agent_positions(owner);
+        true
+    }
+
+    /// Continues from an exported state. Called before the node enters the
+    /// tree, the state waits until `_ready` has loaded the weave file and
+    /// spawned the agents, which then resume at their saved positions.
+    /// False, with the error logged, if the state doesn't read.
+    #[method]
+    fn import_state(&mut self, #[base] owner: &Spatial, state: Dictionary) -> bool {
+        let mut warnings = Vec::new();
+        let state = state_from_dictionary(&state, &mut warnings);
+        for warning in warnings {
+            self.interpreter.warn(warning);
+        }
+        let state = match state {
+            Ok(state) => state,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "import_state failed: {}", e);
+                self.record_error(&e);
+                return false;
+            }
+        };
+        if self.ready {
+            self.interpreter.import_state(state);
+            self.apply_agent_positions(owner);
+        } else {
+            self.pending_state = Some(state);
+        }
+        true
+    }
+
+    /// Continues from a `snapshot`, moving the agents' nodes to their saved
+    /// positions. False, with nothing changed, if the bytes don't read.
+    #[method]
+    fn restore(&mut self, #[base] owner: &Spatial, snapshot: PoolArray<u8>) -> bool {
+        match self.interpreter.restore(&snapshot.to_vec()) {
+            Ok(()) => {
+                self.sync_spawned_agents(owner);
+                self.apply_agent_positions(owner);
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "restore failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// Computes tension for `sensor_data` and evaluates the script's safety
+    /// rules; a tripped rule halts the experiment.
+    #[method]
+    fn execute_tension(&mut self, #[base] owner: &Spatial, sensor_data: Dictionary) -> Scalar {
+        let sensors = self.read_sensors(owner, &sensor_data);
+        let tension = match self.interpreter.execute_tension(&sensors) {
+            Ok(tension) => tension,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "execute_tension failed: {}", e);
+                self.record_error(&e);
+                0.0
+            }
+        };
+        self.emit_safety_events(owner);
+        self.emit_tension_crossings(owner);
+        self.emit_missing_sensors(owner);
+        tension
+    }
+
+    /// Computes a tension for each `stride`-long row of `samples`, whose
+    /// first columns are `sensor_names`, and returns them in row order. With
+    /// `drift_every > 0`, drift and resolve move `agent_data` once per that
+    /// many rows using the chunk's mean tension. Returns what was computed
+    /// before a failing row, or nothing if the arguments are malformed.
+    #[method]
+    fn execute_tension_batch(
+        &mut self,
+        #[base] owner: &Spatial,
+        sensor_names: PoolArray<GodotString>,
+        samples: PoolArray<f32>,
+        stride: i64,
+        #[opt] drift_every: i64,
+        #[opt] agent_data: Dictionary,
+    ) -> PoolArray<f32> {
+        let names: Vec<String> = sensor_names.read().iter().map(|name| name.to_string()).collect();
+        let samples: Vec<Scalar> = samples.read().iter().map(|&sample| sample as Scalar).collect();
+        let agents = self.read_agents(&agent_data);
+        let tick = self.interpreter.tick();
+        let outcome = self.interpreter.execute_tension_batch(&names, &samples, stride.max(0) as usize, drift_every.max(0) as usize, &agents, self.physics_delta);
+        let tensions = match outcome {
+            Ok(tensions) => tensions,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "execute_tension_batch failed: {}", e);
+                self.record_error(&e);
+                let computed = self.interpreter.tick() - tick;
+                self.interpreter.tension_history()[self.interpreter.tension_history().len().saturating_sub(computed)..].to_vec()
+            }
+        };
+        self.emit_safety_events(owner);
+        self.emit_tension_crossings(owner);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+        vector_array(&tensions)
+    }
+
+    /// Runs tension, drift, resolve and metaweave as one tick lasting `delta`
+    /// seconds (1 if omitted), moves kinematic agents, refreshes
+    /// `get_frame_metrics` and returns the tension.
+    #[method]
+    fn step(&mut self, #[base] owner: &Spatial, sensor_data: Dictionary, agent_data: Dictionary, #[opt] delta: f64) -> Scalar {
+        let sensors = self.read_sensors(owner, &sensor_data);
+        let agents = self.read_agents(&agent_data);
+        self.step_with(owner, &sensors, &agents, if delta > 0.0 { delta as Scalar } else { 1.0 })
+    }
+
+    /// `step` for the registered agents, with one value per registered
+    /// sensor in `sensor_values`; NaN leaves a sensor out. Nothing goes
+    /// through a Dictionary. A buffer of the wrong length steps nothing and
+    /// returns 0.
+    #[method]
+    fn step_buffer(&mut self, #[base] owner: &Spatial, sensor_values: PoolArray<f32>, #[opt] delta: f64) -> Scalar {
+        let mut frame = std::mem::take(&mut self.frame);
+        let values = sensor_values.read();
+        let tension = match frame.read(values.iter().map(|&value| value as Scalar)) {
+            Ok((sensors, agents)) => {
+                self.sample_bindings(owner, sensors);
+                self.step_with(owner, sensors, agents, if delta > 0.0 { delta as Scalar } else { 1.0 })
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "step_buffer failed: {}", e);
+                self.record_error(&e);
+                0.0
+            }
+        };
+        self.frame = frame;
+        tension
+    }
+
+    fn step_with(&mut self, owner: &Spatial, sensors: &HashMap<String, Scalar>, agents: &Fields, dt: Scalar) -> Scalar {
+        if self.navigation {
+            self.update_waypoints(owner);
+        }
+        let tension = match self.interpreter.step_dt(sensors, agents, dt) {
+            Ok(tension) => {
+                self.tick_behaviors(sensors, dt);
+                tension
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "step failed: {}", e);
+                self.record_error(&e);
+                0.0
+            }
+        };
+        self.emit_safety_events(owner);
+        self.emit_tension_crossings(owner);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        self.emit_completed_goals(owner);
+        self.emit_invariant_violations(owner);
+        self.emit_out_of_bounds(owner);
+        self.emit_fired_events(owner);
+        self.emit_script_signals(owner);
+        self.dispatch_lab_calls(owner);
+        tension
+    }
+
+    /// What `step` would do with these inputs, without doing it: the
+    /// tension, param and vector changes and fired rules of a step run on a
+    /// copy. Nothing is changed and no signal is emitted.
+    #[method]
+    fn preview_step(&mut self, #[base] owner: &Spatial, sensor_data: Dictionary, agent_data: Dictionary, #[opt] delta: f64) -> Dictionary {
+        let sensors = self.read_sensors(owner, &sensor_data);
+        let agents = self.read_agents(&agent_data);
+        let preview = self.interpreter.preview_step(&sensors, &agents, if delta > 0.0 { delta as Scalar } else { 1.0 });
+        if let Err(e) = &preview {
+            weave_log!(self.interpreter.log(), Level::Error, "preview_step failed: {}", e);
+            self.record_error(e);
+        }
+        preview_dictionary(&preview)
+    }
+
+    /// Drift over the last physics frame's delta, so the drift rate is per second.
+    #[method]
+    fn execute_drift(&mut self, #[base] owner: &Spatial, agent_data: Dictionary, tension: Scalar) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_drift_dt(&agents, tension, self.physics_delta);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// Resolve over the last physics frame's delta, so the resolve rate is per second.
+    #[method]
+    fn execute_resolve(&mut self, #[base] owner: &Spatial, agent_data: Dictionary, tension: Scalar) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_resolve_dt(&agents, tension, self.physics_delta);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// `execute_drift` for the registered agents.
+    #[method]
+    fn execute_drift_registered(&mut self, #[base] owner: &Spatial, tension: Scalar) {
+        self.interpreter.execute_drift_dt(self.frame.agents(), tension, self.physics_delta);
+        self.emit_field_changes(owner);
+    }
+
+    /// `execute_resolve` for the registered agents.
+    #[method]
+    fn execute_resolve_registered(&mut self, #[base] owner: &Spatial, tension: Scalar) {
+        self.interpreter.execute_resolve_dt(self.frame.agents(), tension, self.physics_delta);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+    }
+
+    /// Re-reads `weave_path` and returns a Godot error code: `OK`,
+    /// `ERR_FILE_NOT_FOUND`, `ERR_FILE_CANT_READ`, `ERR_PARSE_ERROR` or
+    /// `ERR_INVALID_DATA`, matching `WeaveLang.load_weave_ex`.
+    #[method]
+    fn reload_weave(&mut self, #[base] owner: &Spatial) -> i64 {
+        let result = self.load_weave_path();
+        self.sync_spawned_agents(owner);
+        match result {
+            Ok(()) => 0,
+            Err(e) => error_code(&e) as i64,
+        }
+    }
+
+    /// Loads `code`, the text of a whole weave file, e.g. from a resource or
+    /// an editor buffer, and clears `weave_path`, so there is no file to hot
+    /// reload. Returns a Godot error code like `reload_weave`.
+    #[method]
+    fn load_weave_from_string(&mut self, #[base] owner: &Spatial, code: String) -> i64 {
+        self.weave_path.clear();
+        self.broken_bindings.clear();
+        let result = self.interpreter.load_str(&code);
+        match &result {
+            Ok(()) => {
+                weave_log!(self.interpreter.log(), Level::Info, "Loaded Weave source ({} bytes)", code.len());
+                self.clear_error();
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to load Weave source: {}", e);
+                self.record_error(e);
+            }
+        }
+        self.sync_spawned_agents(owner);
+        match result {
+            Ok(()) => 0,
+            Err(e) => error_code(&e) as i64,
+        }
+    }
+
+    /// Sets `weave_path` to the compiled program `path` and loads it.
+    /// Returns a Godot error code like `reload_weave`, with
+    /// `ERR_FILE_UNRECOGNIZED` for a file compiled by a build with another
+    /// format version, which has to be compiled again.
+    #[method]
+    fn load_compiled(&mut self, #[base] owner: &Spatial, path: String) -> i64 {
+        if !path.ends_with(&format!(".{}", compiled::EXTENSION)) {
+            weave_log!(self.interpreter.log(), Level::Error, "load_compiled({}): compiled programs end in .{}", path, compiled::EXTENSION);
+            return GodotError::FileUnrecognized as i64;
+        }
+        self.weave_path = path;
+        self.reload_weave(owner)
+    }
+
+    #[method]
+    fn set_auto_reload(&mut self, enabled: bool) {
+        self.auto_reload = enabled;
+        if !self.interpreter.set_auto_reload(enabled) {
+            weave_log!(self.interpreter.log(), Level::Warn, "Auto reload enabled but weave_path is not loaded");
+        }
+    }
+
+    /// Restarts every random stream of this node from `seed`: the simulated
+    /// sensor readings of `run_accelerator`, `run_chemical_assay` and
+    /// `run_neural_scan`, and the drift noise. Two runs with the same seed
+    /// and inputs draw the same values, however many frames pass between them.
+    #[method]
+    fn set_seed(&mut self, seed: i64) {
+        self.simulation_rng = StdRng::seed_from_u64(seed as u64);
+        self.simulation_seed = seed as u64;
+        self.interpreter.set_noise_seed(seed as u64);
+    }
+
+    /// The seed the simulated sensor readings last started from.
+    #[method]
+    fn get_seed(&self) -> i64 {
+        self.simulation_seed as i64
+    }
+
+    /// Adds the agent `name` as a copy of the field `template`, and instances
+    /// its scene if the template spawns one. Returns false and logs why if
+    /// `name` exists or `template` doesn't.
+    #[method]
+    fn spawn_agent(&mut self, #[base] owner: &Spatial, name: String, template: String) -> bool {
+        match self.interpreter.spawn_agent(&name, &template) {
+            Ok(()) => {
+                self.sync_spawned_agents(owner);
+                self.emit_field_changes(owner);
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "spawn_agent failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// Removes the agent `name`, freeing its spawned node. Returns false and logs why if it
+    /// doesn't exist or is the tension reference.
+    #[method]
+    fn despawn_agent(&mut self, #[base] owner: &Spatial, name: String) -> bool {
+        match self.interpreter.despawn_agent(&name) {
+            Ok(()) => {
+                self.sync_spawned_agents(owner);
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "despawn_agent failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// `"teleport"` (the default), `"velocity"` or `"impulse"`: how each
+    /// physics frame moves agent nodes to their positions, see `DriveMode`.
+    /// The bodies keep to each agent's `max_speed` and `max_accel`. False
+    /// for an unknown name.
+    #[method]
+    fn set_drive_mode(&mut self, mode: String) -> bool {
+        match mode.parse::<DriveMode>() {
+            Ok(mode) => {
+                self.drive_mode = mode;
+                self.kinematic_velocities.clear();
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Warn, "{}", e);
+                false
+            }
+        }
+    }
+
+    #[method]
+    fn get_drive_mode(&self) -> String {
+        self.drive_mode.as_str().to_string()
+    }
+
+    /// Routes agents around lab geometry: while enabled, every step gives the
+    /// NavigationAgent under each agent's node the agent's `target_position`
+    /// and steers the agent along the path it finds, one point at a time.
+    /// Disabling it sends agents straight to their targets again.
+    #[method]
+    fn set_navigation_enabled(&mut self, enabled: bool) {
+        self.navigation = enabled;
+        if !enabled {
+            for name in self.navigating.drain() {
+                self.interpreter.clear_waypoint(&name);
+            }
+        }
+    }
+
+    #[method]
+    fn is_navigation_enabled(&self) -> bool {
+        self.navigation
+    }
+
+    /// `{ agent: status }` for every agent with a `position`, as of the last
+    /// physics frame: `bound` when its node is a Spatial, `missing` when it
+    /// has no node and `wrong_type` when the node is not a Spatial.
+    #[method]
+    fn get_agent_binding_status(&self) -> Dictionary {
+        let dict = Dictionary::new();
+        for (agent, binding) in &self.agent_bindings {
+            dict.insert(agent, binding.as_str());
+        }
+        dict.into_shared()
+    }
+
+    /// Registers a `WeaveLang` as the lab `name`, replacing any lab registered
+    /// under it. The lab reads this program's `global` field as `global.*`
+    /// and its tension counts towards `get_combined_tension` with weight 1.
+    #[method]
+    fn register_child(&mut self, name: String, lab: Ref<Reference>) -> bool {
+        if !unsafe { lab.assume_safe() }.has_method("set_globals") {
+            weave_log!(self.interpreter.log(), Level::Error, "register_child {}: not a WeaveLang", name);
+            return false;
+        }
+        let globals = self.interpreter.global_params();
+        unsafe { lab.assume_safe().call("set_globals", &[params_dictionary(&globals).to_variant()]) };
+        if let Some(previous) = self.children.insert(name, ChildLab { lab, weight: 1.0 }) {
+            unsafe { previous.lab.assume_safe().call("clear_globals", &[]) };
+        }
+        true
+    }
+
+    /// Detaches the lab `name`, which then runs as a standalone program.
+    #[method]
+    fn unregister_child(&mut self, name: String) -> bool {
+        match self.children.shift_remove(&name) {
+            Some(child) => {
+                unsafe { child.lab.assume_safe().call("clear_globals", &[]) };
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[method]
+    fn set_child_weight(&mut self, name: String, weight: f64) -> bool {
+        match self.children.get_mut(&name) {
+            Some(child) => {
+                child.weight = weight.max(0.0) as Scalar;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// How `get_combined_tension` combines the labs: `max` (the default) or
+    /// `mean`, weighted by `set_child_weight`.
+    #[method]
+    fn set_child_reduction(&mut self, reduction: String) -> bool {
+        match Reduction::from_name(&reduction) {
+            Some(reduction) => {
+                self.child_reduction = reduction;
+                true
+            }
+            None => {
+                weave_log!(self.interpreter.log(), Level::Error, "Unknown child reduction {}, expected max or mean", reduction);
+                false
+            }
+        }
+    }
+
+    /// `{ lab: tension }` with each registered lab's latest tension; labs
+    /// that have not computed one yet are left out.
+    #[method]
+    fn get_child_tensions(&self) -> Dictionary {
+        let dict = Dictionary::new();
+        for (name, tension, _) in self.child_tensions() {
+            dict.insert(name, tension);
+        }
+        dict.into_shared()
+    }
+
+    /// The labs' latest tensions combined by `set_child_reduction`; 0 while
+    /// no lab has one.
+    #[method]
+    fn get_combined_tension(&self) -> Scalar {
+        let tensions = self.child_tensions().into_iter().map(|(_, tension, weight)| (tension, weight));
+        self.child_reduction.combine(tensions).unwrap_or(0.0)
+    }
+
+    fn child_tensions(&self) -> Vec<(&str, Scalar, Scalar)> {
+        let mut tensions = Vec::new();
+        for (name, child) in &self.children {
+            let tension = unsafe { child.lab.assume_safe().call("get_latest_tension", &[]) };
+            if let Some(tension) = scalar_from_variant(&tension) {
+                tensions.push((name.as_str(), tension, child.weight));
+            }
+        }
+        tensions
+    }
+
+    /// Sends the `global` field to every lab when it changed since the last send.
+    fn publish_globals(&mut self) {
+        let globals = self.interpreter.global_params();
+        if globals == self.published_globals {
+            return;
+        }
+        let globals_dict = params_dictionary(&globals).to_variant();
+        for child in self.children.values() {
+            unsafe { child.lab.assume_safe().call("set_globals", std::slice::from_ref(&globals_dict)) };
+        }
+        self.published_globals = globals;
+    }
+
+    #[method]
+    fn _ready(&mut self, #[base] owner: &Spatial) {
+        self.resolve_lab_nodes(owner);
+        self.resolve_robot_nodes(owner);
+        if self.weave_path.is_empty() && !self.weave_source.is_empty() {
+            let code = self.weave_source.clone();
+            self.load_weave_from_string(owner, code);
+        } else {
+            let _ = self.load_weave_path();
+        }
+        self.sync_spawned_agents(owner);
+        if let Some(state) = self.pending_state.take() {
+            self.interpreter.import_state(state);
+            self.apply_agent_positions(owner);
+        }
+        self.ready = true;
+    }
+
+    #[method]
+    fn _physics_process(&mut self, #[base] owner: &Spatial, delta: f64) {
+        self.physics_delta = delta as Scalar;
+        self.run_ticks(owner, delta);
+        self.move_agents(owner, Some(delta as Scalar));
+    }
+
+    #[method]
+    fn _process(&mut self, #[base] owner: &Spatial, delta: f64) {
+        self.check_reload(owner, delta);
+        self.publish_globals();
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        // Simulated readings are drawn only when GDScript asks for one, so the
+        // frame rate doesn't shift the seeded stream.
+        let risk = self.check_safety();
+        if risk > 0.1 {
+            weave_log!(self.interpreter.log(), Level::Debug, "Safety violation detected: {}", risk);
+        }
+    }
+
+    #[method]
+    fn design_experiment(&mut self, priority: f32) {
+        weave_log!(self.interpreter.log(), Level::Info, "Designing experiment with priority: {}", priority);
+    }
+
+    #[method]
+    fn optimize_equipment(&mut self, efficiency: f32) {
+        if let Err(e) = self.interpreter.constants_mut().register("equipment_efficiency", efficiency as f64, "1") {
+            weave_log!(self.interpreter.log(), Level::Error, "optimize_equipment failed: {}", e);
+            self.record_error(&e);
+        }
+    }
+
+    #[method]
+    fn run_accelerator(&mut self) -> f32 {
+        let gravity = self.interpreter.constants().get("g").map(|constant| constant.value as f32).unwrap_or(9.81);
+        gravity + self.simulation_rng.gen_range(-0.1..0.1)
+    }
+
+    #[method]
+    fn run_chemical_assay(&mut self) -> f32 {
+        0.8 + self.simulation_rng.gen_range(-0.2..0.2)
+    }
+
+    #[method]
+    fn run_neural_scan(&mut self) -> f32 {
+        let risk = self.simulation_rng.gen_range(0.0..0.2);
+        if risk > 0.1 {
+            weave_log!(self.interpreter.log(), Level::Info, "Safety violation detected: {}", risk);
+        }
+        risk
+    }
+
+    #[method]
+    fn adjust_telescope(&mut self) {
+        weave_log!(self.interpreter.log(), Level::Info, "Adjusting telescope");
+    }
+
+    /// Runs the halt callbacks with an empty event, as if a `halt` rule tripped.
+    #[method]
+    fn halt_experiment(&mut self) {
+        self.run_halt_callbacks(&Dictionary::new_shared());
+    }
+
+    /// The safety risk, 1 minus the safety metric: 1 once a `halt` rule has
+    /// tripped, until `set_safety_metric` restores it.
+    #[method]
+    fn check_safety(&mut self) -> f32 {
+        to_f32(1.0 - self.interpreter.safety_metric())
+    }
+}
+
+fn godot_vector(components: &[Scalar]) -> Vector3 {
+    let component = |index: usize| to_f32(components.get(index).copied().unwrap_or(0.0));
+    Vector3::new(component(0), component(1), component(2))
+}
+
+/// The first NavigationAgent among `node`'s children.
+fn navigation_agent(node: TRef<Node>) -> Option<TRef<NavigationAgent>> {
+    (0..node.get_child_count()).find_map(|index| node.get_child(index).and_then(|child| unsafe { child.assume_safe() }.cast::<NavigationAgent>()))
+}
+
+fn scalar_vector(vector: Vector3) -> Vec<Scalar> {
+    vec![vector.x as Scalar, vector.y as Scalar, vector.z as Scalar]
+}
+
+/// Drives a physics body towards `target` for a frame of `dt`: a RigidBody
+/// by setting its velocity or, under `DriveMode::Impulse`, by the impulse
+/// that changes it, and a KinematicBody with `move_and_slide` at a velocity
+/// changed from `kinematic`, which is updated to what the slide left. False,
+/// with nothing done, for a node that is neither.
+fn drive_body(node: TRef<Node>, target: &[Scalar], mode: DriveMode, limits: Limits, kinematic: &mut Vec<Scalar>, dt: Scalar) -> bool {
+    if let Some(body) = node.cast::<RigidBody>() {
+        let velocity = scalar_vector(body.linear_velocity());
+        let wanted = kinematics::drive_velocity(&scalar_vector(body.translation()), target, &velocity, limits, dt);
+        body.set_sleeping(false);
+        if mode == DriveMode::Impulse {
+            let mass = body.mass() as Scalar;
+            let impulse: Vec<Scalar> = wanted.iter().zip(&velocity).map(|(wanted, velocity)| (wanted - velocity) * mass).collect();
+            body.apply_central_impulse(godot_vector(&impulse));
+        } else {
+            body.set_linear_velocity(godot_vector(&wanted));
+        }
+        return true;
+    }
+    if let Some(body) = node.cast::<KinematicBody>() {
+        let wanted = kinematics::drive_velocity(&scalar_vector(body.translation()), target, kinematic, limits, dt);
+        let slid = body.move_and_slide(godot_vector(&wanted), Vector3::new(0.0, 1.0, 0.0), false, 4, std::f64::consts::FRAC_PI_4, true);
+        *kinematic = scalar_vector(slid);
+        return true;
+    }
+    false
+}
+
+fn spawn_scene(owner: &Spatial, name: &str, scene_path: &str) -> Option<Ref<Node>> {
+    let scene = ResourceLoader::godot_singleton()
+        .load(scene_path, "PackedScene", false)?
+        .cast::<PackedScene>()?;
+    let node = unsafe { scene.assume_safe() }.instance(PackedScene::GEN_EDIT_STATE_DISABLED)?;
+    let node = unsafe { node.assume_safe() };
+    node.set_name(name);
+    owner.add_child(node, false);
+    Some(node.claim())
+}
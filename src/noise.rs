@@ -0,0 +1,60 @@
+use crate::scalar::{to_f64, Scalar};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Seed of a fresh interpreter, so two runs of the same script draw the same noise.
+pub const DEFAULT_SEED: u64 = 0x5EED;
+
+/// The interpreter's seeded random stream and the scale applied to every
+/// declared `noise_stddev`.
+#[derive(Debug, Clone)]
+pub struct Noise {
+    rng: StdRng,
+    seed: u64,
+    scale: Scalar,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise { rng: StdRng::seed_from_u64(DEFAULT_SEED), seed: DEFAULT_SEED, scale: 1.0 }
+    }
+}
+
+impl Noise {
+    /// The seed the stream last started from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the stream from `seed`; the same seed gives the same draws.
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.seed = seed;
+    }
+
+    /// Draws a new seed and restarts the stream from it, so whoever keeps
+    /// the seed can replay every draw from here on.
+    pub fn fork(&mut self) -> u64 {
+        let seed = self.rng.gen();
+        self.reseed(seed);
+        seed
+    }
+
+    pub fn scale(&self) -> Scalar {
+        self.scale
+    }
+
+    /// Multiplies every declared stddev; 0 turns noise off without drawing.
+    pub fn set_scale(&mut self, scale: Scalar) {
+        self.scale = scale.max(0.0);
+    }
+
+    /// A normal draw with standard deviation `stddev` times the scale, by
+    /// the Box-Muller transform.
+    pub fn gaussian(&mut self, stddev: Scalar) -> Scalar {
+        let u1: f64 = 1.0 - self.rng.gen::<f64>();
+        let u2: f64 = self.rng.gen();
+        let normal = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+        (normal * to_f64(stddev * self.scale)) as Scalar
+    }
+}
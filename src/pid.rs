@@ -0,0 +1,63 @@
+use crate::interpreter::Params;
+use crate::scalar::Scalar;
+
+/// Proportional, integral and derivative gains of one field's resolve controller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidGains {
+    pub kp: Scalar,
+    pub ki: Scalar,
+    pub kd: Scalar,
+}
+
+impl Default for PidGains {
+    fn default() -> Self {
+        PidGains { kp: 0.5, ki: 0.05, kd: 0.1 }
+    }
+}
+
+impl PidGains {
+    /// Gains declared as `kp`/`ki`/`kd` params on a field, falling back to the defaults per term.
+    pub fn from_params(params: &Params) -> Option<Self> {
+        if !["kp", "ki", "kd"].iter().any(|gain| params.contains_key(*gain)) {
+            return None;
+        }
+        let defaults = PidGains::default();
+        Some(PidGains {
+            kp: params.get("kp").copied().unwrap_or(defaults.kp),
+            ki: params.get("ki").copied().unwrap_or(defaults.ki),
+            kd: params.get("kd").copied().unwrap_or(defaults.kd),
+        })
+    }
+
+    /// Gains of a field's drift controller, declared as `drift_kp`,
+    /// `drift_ki` and `drift_kd`. Those left out are 1, 0 and 0, which
+    /// drift exactly as the linear strategy does.
+    pub fn drift_from_params(params: &Params) -> Self {
+        PidGains {
+            kp: params.get("drift_kp").copied().unwrap_or(1.0),
+            ki: params.get("drift_ki").copied().unwrap_or(0.0),
+            kd: params.get("drift_kd").copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Integral and previous error carried between ticks.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PidState {
+    integral: Scalar,
+    previous_error: Option<Scalar>,
+}
+
+impl PidState {
+    /// Correction to add to the target for `error` (sensor − target) over `dt`.
+    /// The integral is clamped to ±`integral_limit` so a long saturation can't wind it up.
+    pub fn update(&mut self, gains: PidGains, error: Scalar, dt: Scalar, integral_limit: Scalar) -> Scalar {
+        self.integral = (self.integral + error * dt).clamp(-integral_limit, integral_limit);
+        let derivative = match self.previous_error {
+            Some(previous) if dt > 0.0 => (error - previous) / dt,
+            _ => 0.0,
+        };
+        self.previous_error = Some(error);
+        gains.kp * error + gains.ki * self.integral + gains.kd * derivative
+    }
+}
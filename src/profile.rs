@@ -0,0 +1,114 @@
+use std::time::Instant;
+
+/// The parts of the interpreter's work the profiler times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfilePhase {
+    /// Loads, reloads and `execute_code` snippets.
+    Parse,
+    Tension,
+    Drift,
+    Resolve,
+    Metaweave,
+}
+
+impl ProfilePhase {
+    pub const ALL: [ProfilePhase; 5] = [ProfilePhase::Parse, ProfilePhase::Tension, ProfilePhase::Drift, ProfilePhase::Resolve, ProfilePhase::Metaweave];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProfilePhase::Parse => "parse",
+            ProfilePhase::Tension => "tension",
+            ProfilePhase::Drift => "drift",
+            ProfilePhase::Resolve => "resolve",
+            ProfilePhase::Metaweave => "metaweave",
+        }
+    }
+}
+
+/// Time one phase took, in microseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTiming {
+    /// Spent during the last tick; a phase run several times in a tick,
+    /// as sliced drift is, counts them all.
+    pub last_micros: u64,
+    /// The most spent in any one tick.
+    pub max_micros: u64,
+    pub total_micros: u64,
+    /// How many times the phase ran.
+    pub calls: u64,
+}
+
+/// Per-phase timings, off until enabled so an unprofiled step doesn't read
+/// the clock. Time spent between ticks, such as parsing a snippet, counts
+/// towards the next tick.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    enabled: bool,
+    ticks: u64,
+    pending: [u64; ProfilePhase::ALL.len()],
+    timings: [PhaseTiming; ProfilePhase::ALL.len()],
+}
+
+impl Profiler {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turns timing on or off; what was measured so far is kept.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.pending = Default::default();
+    }
+
+    /// Forgets everything measured.
+    pub fn reset(&mut self) {
+        *self = Profiler { enabled: self.enabled, ..Profiler::default() };
+    }
+
+    /// The start of a timed phase, when enabled.
+    pub fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    /// Charges the time since `started` to `phase`.
+    pub fn stop(&mut self, phase: ProfilePhase, started: Option<Instant>) {
+        let Some(started) = started else {
+            return;
+        };
+        let micros = started.elapsed().as_micros() as u64;
+        let index = phase as usize;
+        self.pending[index] += micros;
+        self.timings[index].total_micros += micros;
+        self.timings[index].calls += 1;
+    }
+
+    /// Closes a tick: what each phase spent since the last one becomes its
+    /// `last_micros`.
+    pub fn end_tick(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        self.ticks += 1;
+        for (timing, pending) in self.timings.iter_mut().zip(&mut self.pending) {
+            timing.last_micros = std::mem::take(pending);
+            timing.max_micros = timing.max_micros.max(timing.last_micros);
+        }
+    }
+
+    /// Ticks closed while enabled.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn timing(&self, phase: ProfilePhase) -> &PhaseTiming {
+        &self.timings[phase as usize]
+    }
+
+    /// Mean time per tick spent in `phase`.
+    pub fn mean_micros(&self, phase: ProfilePhase) -> f64 {
+        match self.ticks {
+            0 => 0.0,
+            ticks => self.timing(phase).total_micros as f64 / ticks as f64,
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::error::WeaveError;
+use crate::interpreter::{Fields, Params};
+use crate::scalar::Scalar;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"WVRC";
+const FORMAT_VERSION: u32 = 3;
+pub(crate) const SCALAR_WIDTH: u32 = std::mem::size_of::<Scalar>() as u32;
+
+/// Everything one `step` saw and did: its inputs and the net change it made
+/// to each param, sorted so two recordings of the same run compare equal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickRecord {
+    pub sensors: Vec<(String, Scalar)>,
+    pub agents: Vec<String>,
+    pub tension: Scalar,
+    pub deltas: Vec<(String, String, Scalar)>,
+}
+
+/// A captured session: the field state and noise seed it started from and
+/// every tick after.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    pub initial_fields: Fields,
+    pub noise_seed: u64,
+    pub ticks: Vec<TickRecord>,
+}
+
+impl Recording {
+    pub fn new(initial_fields: Fields, noise_seed: u64) -> Self {
+        Recording { initial_fields, noise_seed, ticks: Vec::new() }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), WeaveError> {
+        let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+        out.write_all(MAGIC)?;
+        write_u32(&mut out, FORMAT_VERSION)?;
+        write_u32(&mut out, SCALAR_WIDTH)?;
+        out.write_all(&self.noise_seed.to_le_bytes())?;
+        let mut fields: Vec<_> = self.initial_fields.iter().collect();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(&mut out, fields.len() as u32)?;
+        for (name, params) in fields {
+            write_str(&mut out, name)?;
+            let mut params: Vec<_> = params.iter().collect();
+            params.sort_by(|a, b| a.0.cmp(b.0));
+            write_u32(&mut out, params.len() as u32)?;
+            for (param, value) in params {
+                write_str(&mut out, param)?;
+                write_scalar(&mut out, *value)?;
+            }
+        }
+        write_u32(&mut out, self.ticks.len() as u32)?;
+        for tick in &self.ticks {
+            write_u32(&mut out, tick.sensors.len() as u32)?;
+            for (sensor, value) in &tick.sensors {
+                write_str(&mut out, sensor)?;
+                write_scalar(&mut out, *value)?;
+            }
+            write_u32(&mut out, tick.agents.len() as u32)?;
+            for agent in &tick.agents {
+                write_str(&mut out, agent)?;
+            }
+            write_scalar(&mut out, tick.tension)?;
+            write_u32(&mut out, tick.deltas.len() as u32)?;
+            for (agent, param, delta) in &tick.deltas {
+                write_str(&mut out, agent)?;
+                write_str(&mut out, param)?;
+                write_scalar(&mut out, *delta)?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, WeaveError> {
+        let mut input = io::BufReader::new(std::fs::File::open(path)?);
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(WeaveError::Validation(format!("{} is not a weave recording", path.display())));
+        }
+        let version = read_u32(&mut input)?;
+        if version != FORMAT_VERSION {
+            return Err(WeaveError::Validation(format!(
+                "recording format version {} is not supported (expected {})",
+                version, FORMAT_VERSION
+            )));
+        }
+        read_width(&mut input, "recording stores", "fields")?;
+        let mut seed = [0; 8];
+        input.read_exact(&mut seed)?;
+        let mut recording = Recording::new(Fields::new(), u64::from_le_bytes(seed));
+        for _ in 0..read_u32(&mut input)? {
+            let name = read_str(&mut input)?;
+            let mut params = Params::new();
+            for _ in 0..read_u32(&mut input)? {
+                let param = read_str(&mut input)?;
+                params.insert(param, read_scalar(&mut input)?);
+            }
+            recording.initial_fields.insert(name, params);
+        }
+        for _ in 0..read_u32(&mut input)? {
+            let mut sensors = Vec::new();
+            for _ in 0..read_u32(&mut input)? {
+                let sensor = read_str(&mut input)?;
+                sensors.push((sensor, read_scalar(&mut input)?));
+            }
+            let mut agents = Vec::new();
+            for _ in 0..read_u32(&mut input)? {
+                agents.push(read_str(&mut input)?);
+            }
+            let tension = read_scalar(&mut input)?;
+            let mut deltas = Vec::new();
+            for _ in 0..read_u32(&mut input)? {
+                let agent = read_str(&mut input)?;
+                let param = read_str(&mut input)?;
+                deltas.push((agent, param, read_scalar(&mut input)?));
+            }
+            recording.ticks.push(TickRecord { sensors, agents, tension, deltas });
+        }
+        Ok(recording)
+    }
+}
+
+/// Result of replaying one recorded tick.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayOutcome {
+    /// The tick reproduced the recorded tension and deltas bit for bit.
+    Matched { tick: usize },
+    /// The tick produced something else; `first_divergent_tick` is the earliest
+    /// tick of this replay that did not match.
+    Diverged { tick: usize, first_divergent_tick: usize, expected_tension: Scalar, actual_tension: Scalar },
+    /// Every recorded tick has been replayed.
+    Finished,
+}
+
+pub(crate) fn write_u32(out: &mut impl Write, value: u32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_scalar(out: &mut impl Write, value: Scalar) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_str(out: &mut impl Write, value: &str) -> io::Result<()> {
+    write_u32(out, value.len() as u32)?;
+    out.write_all(value.as_bytes())
+}
+
+pub(crate) fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Reads a header's value width and refuses any but this build's, so an
+/// f32 file is never read as f64 or the other way round. `stores` opens the
+/// error, as in "recording stores", and `values` names what this build keeps.
+pub(crate) fn read_width(input: &mut impl Read, stores: &str, values: &str) -> Result<(), WeaveError> {
+    let width = read_u32(input)?;
+    if width != SCALAR_WIDTH {
+        // The width is untrusted, so it is widened before it is scaled.
+        return Err(WeaveError::Validation(format!(
+            "{} {}-bit values but this build uses {}-bit {}",
+            stores,
+            u64::from(width) * 8,
+            SCALAR_WIDTH * 8,
+            values
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn read_scalar(input: &mut impl Read) -> io::Result<Scalar> {
+    let mut bytes = [0; SCALAR_WIDTH as usize];
+    input.read_exact(&mut bytes)?;
+    Ok(Scalar::from_le_bytes(bytes))
+}
+
+/// Reads a length-prefixed string, taking only the bytes that are there so a
+/// corrupt length can't ask for gigabytes up front.
+pub(crate) fn read_str(input: &mut impl Read) -> io::Result<String> {
+    let len = read_u32(input)? as usize;
+    let mut bytes = Vec::new();
+    input.by_ref().take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "string runs past the end of the input"));
+    }
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::SystemTime;
+use crate::constants::Constants;
+use crate::error::WeaveError;
+use crate::interpreter::{parse_weave_with, Fields, ParamValue, WeaveProgram};
+
+/// Polls a weave file's modification time so a running scene can pick up saved edits.
+#[derive(Clone)]
+pub struct ReloadWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    interval: f64,
+    elapsed: f64,
+}
+
+impl ReloadWatch {
+    pub fn new(path: impl Into<PathBuf>, interval: f64) -> Self {
+        let path = path.into();
+        ReloadWatch {
+            modified: modified_time(&path),
+            path,
+            interval,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_interval(&mut self, interval: f64) {
+        self.interval = interval.max(0.0);
+    }
+
+    /// Re-reads the modification time after an explicit load so it isn't reported as a change.
+    pub fn mark_loaded(&mut self) {
+        self.modified = modified_time(&self.path);
+        self.elapsed = 0.0;
+    }
+
+    /// Advances the check timer by `delta` seconds and returns true once per observed save.
+    pub fn poll(&mut self, delta: f64) -> bool {
+        self.elapsed += delta;
+        if self.elapsed < self.interval {
+            return false;
+        }
+        self.elapsed = 0.0;
+        let modified = modified_time(&self.path);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            return true;
+        }
+        false
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// A weave file parsing on a worker thread for the program slot `slot`.
+pub struct BackgroundLoad {
+    slot: String,
+    path: PathBuf,
+    receiver: Receiver<Result<WeaveProgram, WeaveError>>,
+}
+
+impl BackgroundLoad {
+    pub fn start(slot: impl Into<String>, path: impl Into<PathBuf>, constants: Constants) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let path = path.into();
+        let worker_path = path.clone();
+        thread::spawn(move || {
+            let _ = sender.send(parse_weave_with(&worker_path, &constants));
+        });
+        BackgroundLoad { slot: slot.into(), path, receiver }
+    }
+
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The parse result once the worker is done; None while it still runs.
+    pub fn finished(&self) -> Option<Result<WeaveProgram, WeaveError>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(WeaveError::Validation("weave loader thread exited without a result".to_string()))),
+        }
+    }
+}
+
+/// Refuses `what`, e.g. `load_weave(path)`, while `pending` is unfinished,
+/// since its result would replace whatever `what` loaded once it lands.
+pub fn check_idle(pending: Option<&BackgroundLoad>, what: &str) -> Result<(), WeaveError> {
+    match pending {
+        Some(load) => Err(WeaveError::Busy(format!("{} while {} is loading", what, load.path.display()))),
+        None => Ok(()),
+    }
+}
+
+/// `field` or `field.param` keys touched by a hot reload, each list sorted.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FieldChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub updated: Vec<String>,
+}
+
+impl FieldChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
+
+    /// Adds `other`'s keys, keeping each list sorted.
+    pub fn merge(&mut self, other: FieldChanges) {
+        for (list, more) in [(&mut self.added, other.added), (&mut self.removed, other.removed), (&mut self.updated, other.updated)] {
+            list.extend(more);
+            list.sort();
+        }
+    }
+}
+
+/// Merges a re-parsed program into live state without discarding runtime progress.
+///
+/// `declared` is what the previous load parsed. New fields and params are added,
+/// params whose declared value changed in the script take the new value, and
+/// fields/params that were declared before but are gone now are removed. Params
+/// whose declaration is unchanged keep their drifted runtime value, and params
+/// added at runtime (e.g. by metaweave) are left alone.
+pub fn merge_reload(
+    fields: &mut Fields,
+    declared: &Fields,
+    reloaded: &Fields,
+) -> FieldChanges {
+    let mut changes = FieldChanges::default();
+    for (name, params) in reloaded {
+        let previous = declared.get(name);
+        let live = match fields.get_mut(name) {
+            Some(live) => live,
+            None => {
+                fields.insert(name.clone(), params.clone());
+                changes.added.push(name.clone());
+                continue;
+            }
+        };
+        for (param, value) in params {
+            let was_declared = previous.and_then(|p| p.get(param));
+            match live.get_mut(param) {
+                None => {
+                    live.insert(param.clone(), *value);
+                    changes.added.push(format!("{}.{}", name, param));
+                }
+                Some(current) if was_declared != Some(value) => {
+                    *current = *value;
+                    changes.updated.push(format!("{}.{}", name, param));
+                }
+                Some(_) => {}
+            }
+        }
+        if let Some(previous) = previous {
+            for param in previous.keys().filter(|param| !params.contains_key(*param)) {
+                if live.shift_remove(param).is_some() {
+                    changes.removed.push(format!("{}.{}", name, param));
+                }
+            }
+        }
+    }
+    for name in declared.keys().filter(|name| !reloaded.contains_key(*name)) {
+        if fields.shift_remove(name).is_some() {
+            changes.removed.push(name.clone());
+        }
+    }
+    // Declared fields and params follow the script's order; anything added
+    // at runtime keeps its relative order after them.
+    let position = |order: Option<usize>| order.unwrap_or(usize::MAX);
+    fields.sort_by(|a, _, b, _| position(reloaded.get_index_of(a)).cmp(&position(reloaded.get_index_of(b))));
+    for (name, live) in fields.iter_mut() {
+        if let Some(params) = reloaded.get(name) {
+            live.sort_by(|a, _, b, _| position(params.get_index_of(a)).cmp(&position(params.get_index_of(b))));
+        }
+    }
+    changes.added.sort();
+    changes.removed.sort();
+    changes.updated.sort();
+    changes
+}
+
+/// `merge_reload` for non-numeric params. A vector such as `position` keeps
+/// its live value while its declaration is unchanged, so a reload doesn't
+/// send agents back to where the script starts them. A field removed from
+/// the script is already reported by `merge_reload`, so only its params'
+/// removals are listed here.
+pub fn merge_attributes(
+    attributes: &mut HashMap<String, HashMap<String, ParamValue>>,
+    declared: &HashMap<String, HashMap<String, ParamValue>>,
+    reloaded: &HashMap<String, HashMap<String, ParamValue>>,
+) -> FieldChanges {
+    let mut changes = FieldChanges::default();
+    for (name, params) in reloaded {
+        let previous = declared.get(name);
+        let Some(live) = attributes.get_mut(name) else {
+            attributes.insert(name.clone(), params.clone());
+            continue;
+        };
+        for (param, value) in params {
+            let was_declared = previous.and_then(|p| p.get(param));
+            match live.get_mut(param) {
+                None => {
+                    live.insert(param.clone(), value.clone());
+                    changes.added.push(format!("{}.{}", name, param));
+                }
+                Some(current) if was_declared != Some(value) => {
+                    *current = value.clone();
+                    changes.updated.push(format!("{}.{}", name, param));
+                }
+                Some(_) => {}
+            }
+        }
+        if let Some(previous) = previous {
+            for param in previous.keys().filter(|param| !params.contains_key(*param)) {
+                if live.remove(param).is_some() {
+                    changes.removed.push(format!("{}.{}", name, param));
+                }
+            }
+        }
+    }
+    attributes.retain(|name, _| reloaded.contains_key(name) || !declared.contains_key(name));
+    changes.added.sort();
+    changes.removed.sort();
+    changes.updated.sort();
+    changes
+}
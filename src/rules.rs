@@ -0,0 +1,217 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::interpreter::ParamValue;
+use crate::scalar::Scalar;
+
+/// Comparison operator used by rule conditions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparator {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Comparator {
+    pub fn holds(self, lhs: Scalar, rhs: Scalar) -> bool {
+        match self {
+            Comparator::Lt => lhs < rhs,
+            Comparator::Le => lhs <= rhs,
+            Comparator::Gt => lhs > rhs,
+            Comparator::Ge => lhs >= rhs,
+            Comparator::Eq => lhs == rhs,
+            Comparator::Ne => lhs != rhs,
+        }
+    }
+
+    /// The comparator that holds exactly when this one doesn't, NaN aside.
+    pub fn negated(self) -> Comparator {
+        match self {
+            Comparator::Lt => Comparator::Ge,
+            Comparator::Le => Comparator::Gt,
+            Comparator::Gt => Comparator::Le,
+            Comparator::Ge => Comparator::Lt,
+            Comparator::Eq => Comparator::Ne,
+            Comparator::Ne => Comparator::Eq,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Comparator::Lt => "<",
+            Comparator::Le => "<=",
+            Comparator::Gt => ">",
+            Comparator::Ge => ">=",
+            Comparator::Eq => "==",
+            Comparator::Ne => "!=",
+        }
+    }
+}
+
+impl FromStr for Comparator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "<" => Ok(Comparator::Lt),
+            "<=" => Ok(Comparator::Le),
+            ">" => Ok(Comparator::Gt),
+            ">=" => Ok(Comparator::Ge),
+            "==" => Ok(Comparator::Eq),
+            "!=" => Ok(Comparator::Ne),
+            _ => Err(format!("unknown comparator '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What to do when a sensor an execute phase reads is absent from the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorPolicy {
+    /// Report the sensor as missing and skip whatever depended on it.
+    Strict,
+    /// Reuse the last value seen for the sensor; missing-as-strict until one is seen.
+    Hold,
+    /// Use the sensor's declared default, or 0.0 if none was declared.
+    Default,
+}
+
+impl FromStr for SensorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(SensorPolicy::Strict),
+            "hold" => Ok(SensorPolicy::Hold),
+            "default" => Ok(SensorPolicy::Default),
+            _ => Err(format!("unknown sensor policy '{}', expected strict, hold or default", s)),
+        }
+    }
+}
+
+/// What a metaweave rule tests before it sets its param.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleCondition {
+    /// `<sensor> <comparator> <threshold>`
+    Sensor { sensor: String, comparator: Comparator, threshold: Scalar },
+    /// `<field>.<param> == <value>` or `!=`, against a non-numeric param.
+    Attribute { field: String, param: String, equal: bool, value: ParamValue },
+}
+
+impl fmt::Display for RuleCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleCondition::Sensor { sensor, comparator, threshold } => write!(f, "{} {} {}", sensor, comparator, threshold),
+            RuleCondition::Attribute { field, param, equal, value } => {
+                write!(f, "{}.{} {} {}", field, param, if *equal { "==" } else { "!=" }, value)
+            }
+        }
+    }
+}
+
+/// `when <condition> set <field>.<param> = <value>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetaweaveRule {
+    pub condition: RuleCondition,
+    pub field: String,
+    pub param: String,
+    pub value: Scalar,
+}
+
+impl RuleCondition {
+    /// The condition an `else` branch runs under.
+    pub fn negated(&self) -> RuleCondition {
+        match self {
+            RuleCondition::Sensor { sensor, comparator, threshold } => {
+                RuleCondition::Sensor { sensor: sensor.clone(), comparator: comparator.negated(), threshold: *threshold }
+            }
+            RuleCondition::Attribute { field, param, equal, value } => {
+                RuleCondition::Attribute { field: field.clone(), param: param.clone(), equal: !equal, value: value.clone() }
+            }
+        }
+    }
+
+    /// The sensor a sensor condition reads; `None` for attribute conditions.
+    pub fn sensor(&self) -> Option<&str> {
+        match self {
+            RuleCondition::Sensor { sensor, .. } => Some(sensor),
+            RuleCondition::Attribute { .. } => None,
+        }
+    }
+
+    /// Whether a sensor condition holds for `sensed`; always false for attribute conditions.
+    pub fn holds(&self, sensed: Scalar) -> bool {
+        match self {
+            RuleCondition::Sensor { comparator, threshold, .. } => comparator.holds(sensed, *threshold),
+            RuleCondition::Attribute { .. } => false,
+        }
+    }
+
+    /// Whether an attribute condition holds for the param's current value
+    /// (`None` when the field has no such param); always false for sensor conditions.
+    pub fn holds_on(&self, current: Option<&ParamValue>) -> bool {
+        match self {
+            RuleCondition::Attribute { equal, value, .. } => (current == Some(value)) == *equal,
+            RuleCondition::Sensor { .. } => false,
+        }
+    }
+}
+
+impl MetaweaveRule {
+    /// The sensor a sensor condition reads; `None` for attribute conditions.
+    pub fn sensor(&self) -> Option<&str> {
+        self.condition.sensor()
+    }
+
+    /// Whether a sensor condition holds for `sensed`; always false for attribute conditions.
+    pub fn fires(&self, sensed: Scalar) -> bool {
+        self.condition.holds(sensed)
+    }
+
+    /// Whether an attribute condition holds for the param's current value
+    /// (`None` when the field has no such param); always false for sensor conditions.
+    pub fn fires_on(&self, current: Option<&ParamValue>) -> bool {
+        self.condition.holds_on(current)
+    }
+}
+
+impl fmt::Display for MetaweaveRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "when {} set {}.{} = {}", self.condition, self.field, self.param, self.value)
+    }
+}
+
+/// A method call on a registered lab node, e.g. `accelerator.reduce_power(0.5)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabCall {
+    pub node: String,
+    pub method: String,
+    pub args: Vec<Scalar>,
+}
+
+impl fmt::Display for LabCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|arg| arg.to_string()).collect();
+        write!(f, "{}.{}({})", self.node, self.method, args.join(", "))
+    }
+}
+
+/// `when <condition> call <node>.<method>(<args>)` from a `metaweave` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallRule {
+    pub condition: RuleCondition,
+    pub call: LabCall,
+}
+
+impl fmt::Display for CallRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "when {} call {}", self.condition, self.call)
+    }
+}
@@ -0,0 +1,132 @@
+use crate::rules::Comparator;
+use crate::scalar::Scalar;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+/// Violations `SafetyLog` keeps before the oldest are dropped.
+pub const SAFETY_LOG_CAPACITY: usize = 256;
+
+/// What a tripped safety rule does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafetySeverity {
+    /// Logged and signalled; the experiment carries on.
+    Warn,
+    /// Also drops the safety metric to 0 and runs the halt callbacks.
+    Halt,
+}
+
+impl SafetySeverity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SafetySeverity::Warn => "warn",
+            SafetySeverity::Halt => "halt",
+        }
+    }
+}
+
+impl FromStr for SafetySeverity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(SafetySeverity::Warn),
+            "halt" => Ok(SafetySeverity::Halt),
+            _ => Err(format!("unknown safety severity '{}', expected warn or halt", s)),
+        }
+    }
+}
+
+/// `<sensor> <comparator> <threshold>`, one clause of a safety rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafetyCheck {
+    pub sensor: String,
+    pub comparator: Comparator,
+    pub threshold: Scalar,
+}
+
+impl SafetyCheck {
+    pub fn violated(&self, sensed: Scalar) -> bool {
+        self.comparator.holds(sensed, self.threshold)
+    }
+}
+
+impl fmt::Display for SafetyCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.sensor, self.comparator, self.threshold)
+    }
+}
+
+/// `rule: <check> [and <check>...] <severity> [grace_ticks: n]` from a
+/// `safety` block. It is violated on a tick when every check is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafetyRule {
+    pub checks: Vec<SafetyCheck>,
+    pub severity: SafetySeverity,
+    /// Consecutive violating ticks tolerated before the rule trips.
+    pub grace_ticks: u32,
+}
+
+impl fmt::Display for SafetyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("rule: ")?;
+        for (i, check) in self.checks.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" and ")?;
+            }
+            write!(f, "{}", check)?;
+        }
+        write!(f, " {}", self.severity.as_str())?;
+        if self.grace_ticks > 0 {
+            write!(f, " grace_ticks: {}", self.grace_ticks)?;
+        }
+        Ok(())
+    }
+}
+
+/// A safety rule that tripped, as reported by `get_last_safety_event` and
+/// kept in the safety log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SafetyEvent {
+    pub rule_index: usize,
+    /// The rule as source text.
+    pub rule: String,
+    pub severity: SafetySeverity,
+    /// The first check's sensor and the value it read.
+    pub sensor: String,
+    pub sensor_value: Scalar,
+    /// Every check's sensor with the value it read, in rule order.
+    pub values: Vec<(String, Scalar)>,
+    pub tick: u64,
+}
+
+/// The newest violations, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct SafetyLog {
+    events: VecDeque<SafetyEvent>,
+}
+
+impl SafetyLog {
+    pub fn push(&mut self, event: SafetyEvent) {
+        if self.events.len() == SAFETY_LOG_CAPACITY {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &SafetyEvent> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
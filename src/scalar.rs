@@ -0,0 +1,20 @@
+/// Numeric type of field params, sensors and tension. `f32` by default; the
+/// `f64` feature widens it for experiments that accumulate many tiny increments.
+#[cfg(not(feature = "f64"))]
+pub type Scalar = f32;
+
+#[cfg(feature = "f64")]
+pub type Scalar = f64;
+
+/// `value` as the `f64` expressions evaluate in; exact in both builds.
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f64(value: Scalar) -> f64 {
+    value as f64
+}
+
+/// `value` as the `f32` Godot vectors and pool arrays hold, rounded in the
+/// `f64` build.
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f32(value: Scalar) -> f32 {
+    value as f32
+}
@@ -0,0 +1,99 @@
+use crate::scalar::Scalar;
+use std::fmt;
+
+/// `at tick <n> set <field>.<param> = <value>;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledSet {
+    pub tick: usize,
+    pub field: String,
+    pub param: String,
+    pub value: Scalar,
+}
+
+impl fmt::Display for ScheduledSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at tick {} set {}.{} = {};", self.tick, self.field, self.param, self.value)
+    }
+}
+
+/// A pending [`ScheduledSet`] with the id `cancel` takes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledAction {
+    pub id: u64,
+    pub set: ScheduledSet,
+    /// Declared in the weave file rather than scheduled at runtime; a reload
+    /// replaces these and keeps the others.
+    pub from_script: bool,
+}
+
+/// Pending scheduled sets, kept in firing order: by tick, then by when they
+/// were scheduled. Each fires once, on the first check at or after its tick.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    pending: Vec<ScheduledAction>,
+    /// Script sets that already fired, so a reload doesn't fire them again.
+    fired_script_sets: Vec<ScheduledSet>,
+    next_id: u64,
+}
+
+impl Schedule {
+    /// Rebuilds a schedule saved through `pending`, `fired_script_sets` and `next_id`.
+    pub fn from_parts(mut pending: Vec<ScheduledAction>, fired_script_sets: Vec<ScheduledSet>, next_id: u64) -> Self {
+        pending.sort_by_key(|action| action.set.tick);
+        let next_id = pending.iter().map(|action| action.id).fold(next_id, u64::max);
+        Schedule { pending, fired_script_sets, next_id }
+    }
+
+    /// Queues `set` and returns its id.
+    pub fn add(&mut self, set: ScheduledSet, from_script: bool) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        let at = self.pending.partition_point(|action| action.set.tick <= set.tick);
+        self.pending.insert(at, ScheduledAction { id, set, from_script });
+        id
+    }
+
+    /// Swaps in the sets of a newly loaded script. On reload (`keep_fired`),
+    /// a set identical to one that already fired stays fired.
+    pub fn replace_script_sets(&mut self, sets: Vec<ScheduledSet>, keep_fired: bool) {
+        self.pending.retain(|action| !action.from_script);
+        if keep_fired {
+            self.fired_script_sets.retain(|fired| sets.contains(fired));
+        } else {
+            self.fired_script_sets.clear();
+        }
+        for set in sets {
+            if !self.fired_script_sets.contains(&set) {
+                self.add(set, true);
+            }
+        }
+    }
+
+    /// Removes the pending action `id`; false if it fired or never existed.
+    pub fn cancel(&mut self, id: u64) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|action| action.id != id);
+        self.pending.len() != before
+    }
+
+    /// Removes and returns every action due by `tick`, in firing order.
+    pub fn take_due(&mut self, tick: usize) -> Vec<ScheduledAction> {
+        let due = self.pending.partition_point(|action| action.set.tick <= tick);
+        let due: Vec<ScheduledAction> = self.pending.drain(..due).collect();
+        self.fired_script_sets.extend(due.iter().filter(|action| action.from_script).map(|action| action.set.clone()));
+        due
+    }
+
+    pub fn pending(&self) -> &[ScheduledAction] {
+        &self.pending
+    }
+
+    pub fn fired_script_sets(&self) -> &[ScheduledSet] {
+        &self.fired_script_sets
+    }
+
+    /// The id the last scheduled action got; the next one gets a higher id.
+    pub fn next_id(&self) -> u64 {
+        self.next_id
+    }
+}
@@ -0,0 +1,50 @@
+use crate::scalar::Scalar;
+
+/// What is known of a sensor before any reading arrives: the value it takes
+/// when a frame lacks it, and the range its readings should stay in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SensorSpec {
+    pub default: Option<Scalar>,
+    pub min: Option<Scalar>,
+    pub max: Option<Scalar>,
+}
+
+impl SensorSpec {
+    pub fn in_range(&self, value: Scalar) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+
+    /// `self`, with whatever it leaves unset taken from `other`.
+    pub fn or(self, other: SensorSpec) -> SensorSpec {
+        SensorSpec { default: self.default.or(other.default), min: self.min.or(other.min), max: self.max.or(other.max) }
+    }
+
+    /// Why the spec can't hold: a range whose ends are swapped, or a
+    /// default outside it.
+    pub fn problem(&self) -> Option<String> {
+        if let (Some(min), Some(max)) = (self.min, self.max) {
+            if min > max {
+                return Some(format!("range {}..{} is empty", min, max));
+            }
+        }
+        self.default.filter(|default| !self.in_range(*default)).map(|default| format!("default {} is outside its range {}", default, self.range()))
+    }
+
+    /// The range as `min..max`, with an open end left blank.
+    pub fn range(&self) -> String {
+        let end = |bound: Option<Scalar>| bound.map(|bound| bound.to_string()).unwrap_or_default();
+        format!("{}..{}", end(self.min), end(self.max))
+    }
+}
+
+/// Why a sensor was warned about. Each is warned about once per sensor,
+/// and again only after it has cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SensorIssue {
+    /// Supplied, but neither declared, added nor bound.
+    Unknown,
+    /// Read outside its range.
+    OutOfRange,
+    /// Read with no value and no default anywhere, so it counted as 0.
+    Zeroed,
+}
@@ -0,0 +1,172 @@
+use crate::error::WeaveError;
+use crate::goals::GoalProgress;
+use crate::interpreter::{parse_param_value, ExperimentState, Params};
+use crate::recording::{read_scalar, read_str, read_u32, read_width, write_scalar, write_str, write_u32, SCALAR_WIDTH};
+use crate::schedule::{Schedule, ScheduledAction, ScheduledSet};
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"WVSS";
+const FORMAT_VERSION: u32 = 1;
+
+/// `state` as bytes for `decode`: the binary form of what `export_state`
+/// returns, for saves and rewind buffers. Attributes are kept as weave
+/// source text, as in the `export_state` Dictionary.
+pub fn encode(state: &ExperimentState) -> Vec<u8> {
+    let mut out = Vec::new();
+    // Writing to a Vec can't fail.
+    let _ = write_state(&mut out, state);
+    out
+}
+
+/// Reads back `encode`'s bytes. Bytes that aren't a snapshot from this
+/// format version and field width are an error.
+pub fn decode(mut bytes: &[u8]) -> Result<ExperimentState, WeaveError> {
+    let input = &mut bytes;
+    let mut magic = [0; 4];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(WeaveError::Validation("not a weave snapshot".to_string()));
+    }
+    let version = read_u32(input)?;
+    if version != FORMAT_VERSION {
+        return Err(WeaveError::Validation(format!("snapshot format version {} is not supported (expected {})", version, FORMAT_VERSION)));
+    }
+    read_width(input, "snapshot stores", "fields")?;
+    let mut state = ExperimentState::default();
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let mut params = Params::new();
+        for _ in 0..read_u32(input)? {
+            let param = read_str(input)?;
+            params.insert(param, read_scalar(input)?);
+        }
+        state.fields.insert(name, params);
+    }
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let field = state.attributes.entry(name.clone()).or_default();
+        for _ in 0..read_u32(input)? {
+            let (param, text) = (read_str(input)?, read_str(input)?);
+            let value = parse_param_value(&text).ok_or_else(|| WeaveError::Validation(format!("snapshot has an unreadable value {} for {}.{}", text, name, param)))?;
+            field.insert(param, value);
+        }
+    }
+    state.tension_history = (0..read_u32(input)?).map(|_| read_scalar(input)).collect::<io::Result<_>>()?;
+    state.dropped_history = read_u64(input)? as usize;
+    state.smoothed_tension = if read_u8(input)? != 0 { Some(read_scalar(input)?) } else { None };
+    let mut pending = Vec::new();
+    for _ in 0..read_u32(input)? {
+        let id = read_u64(input)?;
+        let from_script = read_u8(input)? != 0;
+        pending.push(ScheduledAction { id, set: read_set(input)?, from_script });
+    }
+    let fired = (0..read_u32(input)?).map(|_| read_set(input)).collect::<io::Result<_>>()?;
+    state.schedule = Schedule::from_parts(pending, fired, read_u64(input)?);
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let satisfied_ticks = read_u32(input)?;
+        state.goal_progress.insert(name, GoalProgress { satisfied_ticks, completed: read_u8(input)? != 0 });
+    }
+    for _ in 0..read_u32(input)? {
+        let agent = read_str(input)?;
+        state.locked_agents.insert(agent, read_u8(input)? != 0);
+    }
+    state.noise_seed = read_u64(input)?;
+    Ok(state)
+}
+
+fn write_state(out: &mut impl Write, state: &ExperimentState) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    write_u32(out, FORMAT_VERSION)?;
+    write_u32(out, SCALAR_WIDTH)?;
+    write_u32(out, state.fields.len() as u32)?;
+    for (name, params) in &state.fields {
+        write_str(out, name)?;
+        write_u32(out, params.len() as u32)?;
+        for (param, value) in params {
+            write_str(out, param)?;
+            write_scalar(out, *value)?;
+        }
+    }
+    let mut attributes: Vec<_> = state.attributes.iter().collect();
+    attributes.sort_by(|a, b| a.0.cmp(b.0));
+    write_u32(out, attributes.len() as u32)?;
+    for (name, values) in attributes {
+        write_str(out, name)?;
+        let mut values: Vec<_> = values.iter().collect();
+        values.sort_by(|a, b| a.0.cmp(b.0));
+        write_u32(out, values.len() as u32)?;
+        for (param, value) in values {
+            write_str(out, param)?;
+            write_str(out, &value.to_string())?;
+        }
+    }
+    write_u32(out, state.tension_history.len() as u32)?;
+    for tension in &state.tension_history {
+        write_scalar(out, *tension)?;
+    }
+    write_u64(out, state.dropped_history as u64)?;
+    match state.smoothed_tension {
+        Some(tension) => {
+            out.write_all(&[1])?;
+            write_scalar(out, tension)?;
+        }
+        None => out.write_all(&[0])?,
+    }
+    write_u32(out, state.schedule.pending().len() as u32)?;
+    for action in state.schedule.pending() {
+        write_u64(out, action.id)?;
+        out.write_all(&[action.from_script as u8])?;
+        write_set(out, &action.set)?;
+    }
+    write_u32(out, state.schedule.fired_script_sets().len() as u32)?;
+    for set in state.schedule.fired_script_sets() {
+        write_set(out, set)?;
+    }
+    write_u64(out, state.schedule.next_id())?;
+    let mut goals: Vec<_> = state.goal_progress.iter().collect();
+    goals.sort_by(|a, b| a.0.cmp(b.0));
+    write_u32(out, goals.len() as u32)?;
+    for (name, progress) in goals {
+        write_str(out, name)?;
+        write_u32(out, progress.satisfied_ticks)?;
+        out.write_all(&[progress.completed as u8])?;
+    }
+    let mut locks: Vec<_> = state.locked_agents.iter().collect();
+    locks.sort();
+    write_u32(out, locks.len() as u32)?;
+    for (agent, persistent) in locks {
+        write_str(out, agent)?;
+        out.write_all(&[*persistent as u8])?;
+    }
+    write_u64(out, state.noise_seed)
+}
+
+fn write_set(out: &mut impl Write, set: &ScheduledSet) -> io::Result<()> {
+    write_u64(out, set.tick as u64)?;
+    write_str(out, &set.field)?;
+    write_str(out, &set.param)?;
+    write_scalar(out, set.value)
+}
+
+fn read_set(input: &mut impl Read) -> io::Result<ScheduledSet> {
+    let tick = read_u64(input)? as usize;
+    let (field, param) = (read_str(input)?, read_str(input)?);
+    Ok(ScheduledSet { tick, field, param, value: read_scalar(input)? })
+}
+
+fn write_u64(out: &mut impl Write, value: u64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
@@ -0,0 +1,164 @@
+use crate::error::WeaveError;
+use crate::recording::{read_scalar, read_str, read_u32, read_width, write_scalar, write_str, write_u32, SCALAR_WIDTH};
+use crate::scalar::Scalar;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"WVST";
+const FORMAT_VERSION: u32 = 1;
+
+/// The execute phase a sensor map was passed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamPhase {
+    Tension,
+    Metaweave,
+}
+
+impl StreamPhase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StreamPhase::Tension => "tension",
+            StreamPhase::Metaweave => "metaweave",
+        }
+    }
+}
+
+/// One sensor map as `execute_tension` or `execute_metaweave` received it,
+/// sorted by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorFrame {
+    pub tick: usize,
+    pub phase: StreamPhase,
+    pub sensors: Vec<(String, Scalar)>,
+}
+
+impl SensorFrame {
+    pub fn to_map(&self) -> HashMap<String, Scalar> {
+        self.sensors.iter().cloned().collect()
+    }
+}
+
+/// Every sensor map an interpreter was given while recording, in call
+/// order. Unlike a `Recording` it holds inputs only, so it can be fed to a
+/// program that has changed since.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SensorStream {
+    pub frames: Vec<SensorFrame>,
+}
+
+impl SensorStream {
+    pub fn push(&mut self, tick: usize, phase: StreamPhase, sensors: &HashMap<String, Scalar>) {
+        let mut sensors: Vec<(String, Scalar)> = sensors.iter().map(|(name, value)| (name.clone(), *value)).collect();
+        sensors.sort_by(|a, b| a.0.cmp(&b.0));
+        self.frames.push(SensorFrame { tick, phase, sensors });
+    }
+
+    /// Writes each sensor name once, and each frame as indices into those
+    /// names with their values.
+    pub fn save(&self, path: &Path) -> Result<(), WeaveError> {
+        let mut names: Vec<&str> = self.frames.iter().flat_map(|frame| frame.sensors.iter().map(|(name, _)| name.as_str())).collect();
+        names.sort_unstable();
+        names.dedup();
+        let mut out = io::BufWriter::new(std::fs::File::create(path)?);
+        out.write_all(MAGIC)?;
+        write_u32(&mut out, FORMAT_VERSION)?;
+        write_u32(&mut out, SCALAR_WIDTH)?;
+        write_u32(&mut out, names.len() as u32)?;
+        for name in &names {
+            write_str(&mut out, name)?;
+        }
+        write_u32(&mut out, self.frames.len() as u32)?;
+        for frame in &self.frames {
+            write_u32(&mut out, frame.tick as u32)?;
+            out.write_all(&[frame.phase as u8])?;
+            write_u32(&mut out, frame.sensors.len() as u32)?;
+            for (name, value) in &frame.sensors {
+                write_u32(&mut out, names.binary_search(&name.as_str()).unwrap_or_default() as u32)?;
+                write_scalar(&mut out, *value)?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, WeaveError> {
+        let mut input = io::BufReader::new(std::fs::File::open(path)?);
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(WeaveError::Validation(format!("{} is not a sensor recording", path.display())));
+        }
+        let version = read_u32(&mut input)?;
+        if version != FORMAT_VERSION {
+            return Err(WeaveError::Validation(format!("sensor recording format version {} is not supported (expected {})", version, FORMAT_VERSION)));
+        }
+        read_width(&mut input, "sensor recording stores", "sensors")?;
+        let names = (0..read_u32(&mut input)?).map(|_| read_str(&mut input)).collect::<io::Result<Vec<_>>>()?;
+        let mut stream = SensorStream::default();
+        for _ in 0..read_u32(&mut input)? {
+            let tick = read_u32(&mut input)? as usize;
+            let mut phase = [0; 1];
+            input.read_exact(&mut phase)?;
+            let phase = match phase[0] {
+                0 => StreamPhase::Tension,
+                1 => StreamPhase::Metaweave,
+                tag => return Err(WeaveError::Validation(format!("sensor recording has an unknown phase {}", tag))),
+            };
+            let mut sensors = Vec::new();
+            for _ in 0..read_u32(&mut input)? {
+                let index = read_u32(&mut input)? as usize;
+                let name = names.get(index).ok_or_else(|| WeaveError::Validation(format!("sensor recording names sensor {} of {}", index, names.len())))?;
+                sensors.push((name.clone(), read_scalar(&mut input)?));
+            }
+            stream.frames.push(SensorFrame { tick, phase, sensors });
+        }
+        Ok(stream)
+    }
+}
+
+/// A stream being fed back: each phase reads its own frames in order, so
+/// a replay keeps up however the calls of the two phases interleave.
+#[derive(Debug, Clone)]
+pub struct SensorReplay {
+    stream: SensorStream,
+    cursors: [usize; 2],
+    /// A frame has been fed to another tick than it was recorded on.
+    out_of_step: bool,
+}
+
+impl SensorReplay {
+    pub fn new(stream: SensorStream) -> Self {
+        SensorReplay { stream, cursors: [0; 2], out_of_step: false }
+    }
+
+    fn position(&self, phase: StreamPhase) -> Option<usize> {
+        let start = self.cursors[phase as usize];
+        self.stream.frames[start.min(self.stream.frames.len())..].iter().position(|frame| frame.phase == phase).map(|offset| start + offset)
+    }
+
+    /// The frame the next call of `phase` reads.
+    pub fn peek(&self, phase: StreamPhase) -> Option<&SensorFrame> {
+        self.position(phase).map(|index| &self.stream.frames[index])
+    }
+
+    /// Reads the next frame of `phase` as a sensor map, noting whether it
+    /// was recorded on another tick than `tick`.
+    pub fn next(&mut self, phase: StreamPhase, tick: usize) -> Option<HashMap<String, Scalar>> {
+        let index = self.position(phase)?;
+        self.cursors[phase as usize] = index + 1;
+        let frame = &self.stream.frames[index];
+        self.out_of_step |= frame.tick != tick;
+        Some(frame.to_map())
+    }
+
+    pub fn out_of_step(&self) -> bool {
+        self.out_of_step
+    }
+
+    /// Frames neither phase has read yet.
+    pub fn remaining(&self) -> usize {
+        let read = |phase: StreamPhase| self.stream.frames[..self.cursors[phase as usize].min(self.stream.frames.len())].iter().filter(|frame| frame.phase == phase).count();
+        self.stream.frames.len() - read(StreamPhase::Tension) - read(StreamPhase::Metaweave)
+    }
+}
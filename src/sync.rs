@@ -0,0 +1,254 @@
+use crate::error::WeaveError;
+use crate::interpreter::{Fields, Params};
+use crate::recording::{read_scalar, read_str, read_u32, read_width, write_scalar, write_str, write_u32, SCALAR_WIDTH};
+use crate::scalar::Scalar;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"WVSD";
+const FORMAT_VERSION: u32 = 1;
+
+const FIELD_UPDATE: u8 = 0;
+const FIELD_REPLACE: u8 = 1;
+const FIELD_REMOVE: u8 = 2;
+const PARAM_SET: u8 = 0;
+const PARAM_REMOVE: u8 = 1;
+
+/// Remembers the revision at which each field and param last changed, so a
+/// diff can be cut from any earlier revision for any peer.
+///
+/// Revisions only advance in `commit`, which compares the live fields with a
+/// shadow copy taken at the previous commit. That catches every way fields
+/// change (phases, reloads, snippets) without hooking each of them.
+#[derive(Debug, Clone, Default)]
+pub struct SyncState {
+    revision: u64,
+    shadow: Fields,
+    /// When each field last appeared or disappeared.
+    field_revisions: HashMap<String, u64>,
+    /// When each param last changed; a param missing from `shadow` was removed then.
+    param_revisions: HashMap<String, HashMap<String, u64>>,
+    /// Revision of the newest diff applied on the receiving side.
+    applied: u64,
+}
+
+/// What `SyncState::apply` did with a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOutcome {
+    Applied { revision: u64 },
+    /// The diff is not newer than what was already applied.
+    Stale { revision: u64, applied: u64 },
+    /// The diff starts after the applied revision, so changes in between are missing.
+    Gap { since: u64, applied: u64 },
+}
+
+impl SyncState {
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn applied_revision(&self) -> u64 {
+        self.applied
+    }
+
+    /// Records every difference between `fields` and the last commit under a
+    /// new revision. Nothing changes, and the revision stays, if they match.
+    pub fn commit(&mut self, fields: &Fields) {
+        let next = self.revision + 1;
+        let mut changed = false;
+        for (name, params) in fields {
+            if !self.shadow.contains_key(name) {
+                self.shadow.insert(name.clone(), Params::new());
+                self.field_revisions.insert(name.clone(), next);
+                self.param_revisions.remove(name);
+                changed = true;
+            }
+            let shadow = &mut self.shadow[name.as_str()];
+            let revisions = self.param_revisions.entry(name.clone()).or_default();
+            for (param, value) in params {
+                if shadow.get(param).map(|old| old.to_bits()) != Some(value.to_bits()) {
+                    shadow.insert(param.clone(), *value);
+                    revisions.insert(param.clone(), next);
+                    changed = true;
+                }
+            }
+            let before = shadow.len();
+            shadow.retain(|param, _| {
+                let kept = params.contains_key(param);
+                if !kept {
+                    revisions.insert(param.clone(), next);
+                }
+                kept
+            });
+            changed |= shadow.len() != before;
+        }
+        let before = self.shadow.len();
+        self.shadow.retain(|name, _| {
+            let kept = fields.contains_key(name);
+            if !kept {
+                self.field_revisions.insert(name.clone(), next);
+                self.param_revisions.remove(name);
+            }
+            kept
+        });
+        changed |= self.shadow.len() != before;
+        if changed {
+            self.revision = next;
+        }
+    }
+
+    /// Every change committed after `since`, as absolute values, so applying
+    /// a diff twice or on top of a later one's starting point is harmless.
+    pub fn encode(&self, since: u64) -> Vec<u8> {
+        let mut entries = Vec::new();
+        for (name, params) in &self.shadow {
+            let replace = self.field_revisions.get(name).is_some_and(|revision| *revision > since);
+            let mut changes: Vec<(&String, Option<Scalar>)> = self
+                .param_revisions
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter(|(_, revision)| **revision > since)
+                .map(|(param, _)| (param, params.get(param).copied()))
+                .collect();
+            if replace || !changes.is_empty() {
+                changes.sort_by(|a, b| a.0.cmp(b.0));
+                entries.push((name, if replace { FIELD_REPLACE } else { FIELD_UPDATE }, changes));
+            }
+        }
+        for (name, revision) in &self.field_revisions {
+            if *revision > since && !self.shadow.contains_key(name) {
+                entries.push((name, FIELD_REMOVE, Vec::new()));
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut out = Vec::new();
+        // Writing to a Vec cannot fail.
+        let _ = write_diff(&mut out, since, self.revision, &entries);
+        out
+    }
+
+    /// Applies a diff from `encode` to `fields` if it follows on from the last
+    /// applied revision; stale and out-of-order diffs leave `fields` untouched.
+    /// A diff from revision 0 is a full snapshot, so it also drops the fields
+    /// the sender doesn't have. A truncated diff is refused as malformed.
+    pub fn apply(&mut self, bytes: &[u8], fields: &mut Fields) -> Result<DiffOutcome, WeaveError> {
+        let (since, revision, entries) = read_diff(&mut &bytes[..]).map_err(|error| match error {
+            WeaveError::Io(_) => WeaveError::Validation("malformed state diff".to_string()),
+            other => other,
+        })?;
+        if revision <= self.applied {
+            return Ok(DiffOutcome::Stale { revision, applied: self.applied });
+        }
+        if since > self.applied {
+            return Ok(DiffOutcome::Gap { since, applied: self.applied });
+        }
+        if since == 0 {
+            fields.retain(|name, _| entries.iter().any(|(entry, kind, _)| entry == name && *kind != FIELD_REMOVE));
+        }
+        for (name, kind, changes) in entries {
+            if kind == FIELD_REMOVE {
+                fields.shift_remove(&name);
+                continue;
+            }
+            let params = fields.entry(name).or_default();
+            if kind == FIELD_REPLACE {
+                params.clear();
+            }
+            for (param, value) in changes {
+                match value {
+                    Some(value) => {
+                        params.insert(param, value);
+                    }
+                    None => {
+                        params.shift_remove(&param);
+                    }
+                }
+            }
+        }
+        self.applied = revision;
+        Ok(DiffOutcome::Applied { revision })
+    }
+}
+
+type Entry<S> = (S, u8, Vec<(S, Option<Scalar>)>);
+
+fn write_diff(out: &mut impl Write, since: u64, revision: u64, entries: &[Entry<&String>]) -> io::Result<()> {
+    out.write_all(MAGIC)?;
+    write_u32(out, FORMAT_VERSION)?;
+    write_u32(out, SCALAR_WIDTH)?;
+    write_u64(out, since)?;
+    write_u64(out, revision)?;
+    write_u32(out, entries.len() as u32)?;
+    for (name, kind, changes) in entries {
+        write_str(out, name)?;
+        out.write_all(&[*kind])?;
+        write_u32(out, changes.len() as u32)?;
+        for (param, value) in changes {
+            write_str(out, param)?;
+            match value {
+                Some(value) => {
+                    out.write_all(&[PARAM_SET])?;
+                    write_scalar(out, *value)?;
+                }
+                None => out.write_all(&[PARAM_REMOVE])?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_diff(input: &mut impl Read) -> Result<(u64, u64, Vec<Entry<String>>), WeaveError> {
+    let mut magic = [0; 4];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(WeaveError::Validation("not a weave state diff".to_string()));
+    }
+    let version = read_u32(input)?;
+    if version != FORMAT_VERSION {
+        return Err(WeaveError::Validation(format!(
+            "state diff format version {} is not supported (expected {})",
+            version, FORMAT_VERSION
+        )));
+    }
+    read_width(input, "state diff carries", "fields")?;
+    let since = read_u64(input)?;
+    let revision = read_u64(input)?;
+    let mut entries = Vec::new();
+    for _ in 0..read_u32(input)? {
+        let name = read_str(input)?;
+        let kind = read_u8(input)?;
+        if kind > FIELD_REMOVE {
+            return Err(WeaveError::Validation(format!("state diff has unknown entry kind {} for {}", kind, name)));
+        }
+        let mut changes = Vec::new();
+        for _ in 0..read_u32(input)? {
+            let param = read_str(input)?;
+            let value = match read_u8(input)? {
+                PARAM_SET => Some(read_scalar(input)?),
+                PARAM_REMOVE => None,
+                other => return Err(WeaveError::Validation(format!("state diff has unknown change kind {} for {}.{}", other, name, param))),
+            };
+            changes.push((param, value));
+        }
+        entries.push((name, kind, changes));
+    }
+    Ok((since, revision, entries))
+}
+
+fn write_u64(out: &mut impl Write, value: u64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0; 1];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
@@ -0,0 +1,154 @@
+use crate::error::WeaveError;
+use crate::interpreter::Fields;
+use crate::safety::SafetyEvent;
+use crate::scalar::Scalar;
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::thread::JoinHandle;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, oneshot};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Frames a client may fall behind by before it skips to the newest.
+pub const FRAME_BACKLOG: usize = 64;
+
+/// What one tick looks like to a dashboard, sent as one JSON text message.
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryFrame<'a> {
+    pub tick: usize,
+    pub tension: Scalar,
+    pub smoothed_tension: Scalar,
+    /// Whether an invariant or an emergency stop has halted stepping.
+    pub halted: bool,
+    /// Every field's numeric params.
+    pub fields: &'a Fields,
+    /// The safety rules that tripped during the tick.
+    pub safety_events: Vec<SafetyFrame<'a>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SafetyFrame<'a> {
+    pub rule_index: usize,
+    pub rule: &'a str,
+    pub severity: &'static str,
+    pub sensor: &'a str,
+    pub sensor_value: Scalar,
+    pub tick: u64,
+}
+
+impl<'a> From<&'a SafetyEvent> for SafetyFrame<'a> {
+    fn from(event: &'a SafetyEvent) -> Self {
+        SafetyFrame {
+            rule_index: event.rule_index,
+            rule: &event.rule,
+            severity: event.severity.as_str(),
+            sensor: &event.sensor,
+            sensor_value: event.sensor_value,
+            tick: event.tick,
+        }
+    }
+}
+
+impl TelemetryFrame<'_> {
+    pub fn to_json(&self) -> String {
+        // Maps with String keys and plain numbers always serialize.
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// A WebSocket server on a thread of its own that sends each published
+/// frame to every connected client. Clients only listen; whatever they
+/// send is ignored, and one that falls more than `FRAME_BACKLOG` frames
+/// behind skips to the newest. The server stops when dropped.
+pub struct TelemetryServer {
+    address: SocketAddr,
+    frames: broadcast::Sender<String>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TelemetryServer {
+    /// Listens on `address`, such as `127.0.0.1:9001`. Port 0 takes any free
+    /// port, which `address()` then reports.
+    pub fn start(address: &str) -> Result<TelemetryServer, WeaveError> {
+        let listener = std::net::TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let (frames, _) = broadcast::channel(FRAME_BACKLOG);
+        let (shutdown, stop) = oneshot::channel();
+        let sender = frames.clone();
+        let thread = std::thread::Builder::new().name("weave-telemetry".to_string()).spawn(move || runtime.block_on(serve(listener, sender, stop)))?;
+        Ok(TelemetryServer { address, frames, shutdown: Some(shutdown), thread: Some(thread) })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// Clients connected now.
+    pub fn clients(&self) -> usize {
+        self.frames.receiver_count()
+    }
+
+    /// Sends `frame` to every client; without any, it isn't even encoded.
+    pub fn publish(&self, frame: &TelemetryFrame) {
+        if self.clients() > 0 {
+            let _ = self.frames.send(frame.to_json());
+        }
+    }
+}
+
+impl Drop for TelemetryServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Accepts clients until `stop` fires; dropping the runtime afterwards
+/// disconnects them.
+async fn serve(listener: std::net::TcpListener, frames: broadcast::Sender<String>, mut stop: oneshot::Receiver<()>) {
+    let Ok(listener) = TcpListener::from_std(listener) else {
+        return;
+    };
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            accepted = listener.accept() => {
+                if let Ok((stream, _)) = accepted {
+                    tokio::spawn(stream_frames(stream, frames.subscribe()));
+                }
+            }
+        }
+    }
+}
+
+async fn stream_frames(stream: TcpStream, mut frames: broadcast::Receiver<String>) {
+    let Ok(socket) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut outgoing, mut incoming) = socket.split();
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    if outgoing.send(Message::Text(frame)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = incoming.next() => match message {
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::error::WeaveError;
+use crate::interpreter::{Fields, TensionTerm};
+use crate::scalar::Scalar;
+use std::fmt;
+
+/// What a `TensionModel` reads: the fields and the sensor values, after
+/// declared defaults and the missing-sensor policy.
+pub struct TensionInput<'a> {
+    pub fields: &'a Fields,
+    /// The `tension_reference` field, which the built-in model compares against.
+    pub reference: &'a str,
+    sensor: &'a dyn Fn(&str) -> Option<Scalar>,
+}
+
+impl<'a> TensionInput<'a> {
+    pub fn new(fields: &'a Fields, reference: &'a str, sensor: &'a dyn Fn(&str) -> Option<Scalar>) -> Self {
+        TensionInput { fields, reference, sensor }
+    }
+
+    /// `name`'s value; `MissingSensor` when it has none under its policy.
+    pub fn sensor(&self, name: &str) -> Result<Scalar, WeaveError> {
+        (self.sensor)(name).ok_or_else(|| WeaveError::MissingSensor(name.to_string()))
+    }
+
+    /// `field.param`, or `default` when the field lacks the param;
+    /// `MissingField` when there is no such field.
+    pub fn param(&self, field: &str, param: &str, default: Scalar) -> Result<Scalar, WeaveError> {
+        let params = self.fields.get(field).ok_or_else(|| WeaveError::MissingField(field.to_string()))?;
+        Ok(params.get(param).copied().unwrap_or(default))
+    }
+}
+
+/// Turns sensors and fields into tension terms, which the interpreter sums
+/// into the tension, records and explains. Install one from Rust with
+/// `Interpreter::set_tension_model`; it then wins over the script's
+/// `tension` block. A `MissingSensor` error is reported as a missing sensor.
+pub trait TensionModel: fmt::Debug + Send + Sync {
+    fn terms(&self, input: &TensionInput) -> Result<Vec<TensionTerm>, WeaveError>;
+}
+
+/// The model without a `tension` block: the `coherence` sensor against the
+/// reference field's `coherence_target` (0.5 when undeclared), weight 1.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CoherenceTension;
+
+impl TensionModel for CoherenceTension {
+    fn terms(&self, input: &TensionInput) -> Result<Vec<TensionTerm>, WeaveError> {
+        let sensed = input.sensor("coherence")?;
+        let target = input.param(input.reference, "coherence_target", 0.5)?;
+        Ok(vec![TensionTerm {
+            sensor: "coherence".to_string(),
+            agent: input.reference.to_string(),
+            target,
+            sensed,
+            weight: 1.0,
+            contribution: (sensed - target).abs(),
+        }])
+    }
+}
+
+/// `sensor: field.param * weight` in a `tension` block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedTerm {
+    pub sensor: String,
+    pub field: String,
+    pub param: String,
+    pub weight: Scalar,
+}
+
+impl fmt::Display for WeightedTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}.{} * {}", self.sensor, self.field, self.param, self.weight)
+    }
+}
+
+/// A script's `tension` block: one term per sensor and target param, each
+/// contributing `weight * |sensed - target|`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeightedTension {
+    pub terms: Vec<WeightedTerm>,
+}
+
+impl TensionModel for WeightedTension {
+    fn terms(&self, input: &TensionInput) -> Result<Vec<TensionTerm>, WeaveError> {
+        self.terms
+            .iter()
+            .map(|term| {
+                let sensed = input.sensor(&term.sensor)?;
+                let target = input.param(&term.field, &term.param, 0.0)?;
+                Ok(TensionTerm {
+                    sensor: term.sensor.clone(),
+                    agent: term.field.clone(),
+                    target,
+                    sensed,
+                    weight: term.weight,
+                    contribution: term.weight * (sensed - target).abs(),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,209 @@
+use crate::error::WeaveError;
+use crate::scalar::Scalar;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Entries kept by default before the oldest are dropped.
+pub const DEFAULT_TRACE_CAPACITY: usize = 10_000;
+
+/// The execute phase that performed a traced write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Drift,
+    Resolve,
+    Metaweave,
+    /// Field blocks and assignments from `execute_code`.
+    Code,
+    /// Scheduled sets, `at tick` in the script or `schedule_set`.
+    Schedule,
+    /// Derived params computed from their expression.
+    Derive,
+    /// `set` statements in `repeat` and `while` loops.
+    Loop,
+    /// `set` actions of `on` handlers.
+    Event,
+    /// `set` nodes of behavior trees.
+    Behavior,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Drift => "drift",
+            Phase::Resolve => "resolve",
+            Phase::Metaweave => "metaweave",
+            Phase::Code => "code",
+            Phase::Schedule => "schedule",
+            Phase::Derive => "derive",
+            Phase::Loop => "loop",
+            Phase::Event => "event",
+            Phase::Behavior => "behavior",
+        }
+    }
+}
+
+/// Why a write happened: its phase and the tension or sensor that drove it.
+/// Borrowed, so building one costs nothing while tracing is off.
+#[derive(Debug, Clone, Copy)]
+pub struct Cause<'a> {
+    pub phase: Phase,
+    /// `tension`, a sensor name, the field of an attribute rule, or `code`.
+    pub trigger: &'a str,
+    /// The attribute an attribute rule compared, traced as `trigger.param`.
+    pub trigger_param: Option<&'a str>,
+    /// The trigger's value, when it has a numeric one.
+    pub value: Option<Scalar>,
+}
+
+impl<'a> Cause<'a> {
+    pub fn new(phase: Phase, trigger: &'a str, value: Option<Scalar>) -> Self {
+        Cause { phase, trigger, trigger_param: None, value }
+    }
+
+    fn trigger_name(&self) -> String {
+        match self.trigger_param {
+            Some(param) => format!("{}.{}", self.trigger, param),
+            None => self.trigger.to_string(),
+        }
+    }
+}
+
+/// One field param write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    pub tick: usize,
+    pub phase: Phase,
+    pub agent: String,
+    pub param: String,
+    pub old_value: Scalar,
+    pub new_value: Scalar,
+    pub trigger: String,
+    pub trigger_value: Option<Scalar>,
+}
+
+impl TraceEntry {
+    pub fn new(tick: usize, agent: &str, param: &str, old_value: Scalar, new_value: Scalar, cause: Cause) -> Self {
+        TraceEntry {
+            tick,
+            phase: cause.phase,
+            agent: agent.to_string(),
+            param: param.to_string(),
+            old_value,
+            new_value,
+            trigger: cause.trigger_name(),
+            trigger_value: cause.value,
+        }
+    }
+
+    /// The entry as one JSON object, without a trailing newline.
+    pub fn to_json(&self) -> String {
+        let mut line = String::new();
+        let _ = write!(
+            line,
+            "{{\"tick\":{},\"phase\":\"{}\",\"agent\":{},\"param\":{},\"old_value\":{},\"new_value\":{},\"trigger\":{},\"trigger_value\":{}}}",
+            self.tick,
+            self.phase.as_str(),
+            json_string(&self.agent),
+            json_string(&self.param),
+            json_number(Some(self.old_value)),
+            json_number(Some(self.new_value)),
+            json_string(&self.trigger),
+            json_number(self.trigger_value),
+        );
+        line
+    }
+}
+
+/// Ring buffer of the most recent writes, filled only while enabled.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    enabled: bool,
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Trace { enabled: false, capacity: DEFAULT_TRACE_CAPACITY, entries: VecDeque::new() }
+    }
+}
+
+impl Trace {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turning tracing on starts an empty buffer; turning it off keeps the
+    /// entries so they can still be read.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            self.entries.clear();
+        }
+        self.enabled = enabled;
+    }
+
+    /// Keeps at most `capacity` entries (at least one), dropping the oldest.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn push(&mut self, entry: TraceEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// The newest `max_entries` entries, oldest first.
+    pub fn latest(&self, max_entries: usize) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter().skip(self.entries.len().saturating_sub(max_entries))
+    }
+
+    /// Writes every entry to `path` as JSON lines, oldest first.
+    pub fn dump(&self, path: &Path) -> Result<(), WeaveError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        for entry in &self.entries {
+            writeln!(out, "{}", entry.to_json())?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// JSON has no NaN or infinity, so those, like a missing value, become `null`.
+fn json_number(value: Option<Scalar>) -> String {
+    match value {
+        Some(value) if value.is_finite() => value.to_string(),
+        _ => "null".to_string(),
+    }
+}
@@ -1,127 +1,1081 @@
 use gdnative::prelude::*;
+use gdnative::export::hint::{FloatHint, IntHint, RangeHint};
 use std::collections::HashMap;
+use std::mem;
 use std::path::Path;
-use crate::interpreter::{parse_weave, execute_tension, execute_drift, execute_resolve, execute_metaweave};
+use crate::alarm::TensionCrossing;
+use crate::config::{DriftStrategy, InterpreterConfig, DEFAULT_DRIFT_RATE, DEFAULT_HISTORY_LIMIT, DEFAULT_RESOLVE_RATE};
+use crate::convert::{agents_from_dictionary, attributes_dictionary, behavior_status_from_variant, behaviors_dictionary, bindings_dictionary, changes_dictionary, code_outcome_dictionary, constants_dictionary, curve_array, diagnostics_array, error_code, error_dictionary, experiment_metrics_dictionary, fields_dictionary, globals_from_dictionary, goal_progress_dictionary, invariant_status_dictionary, invariant_violation_dictionary, lab_call_dictionary, log_array, message_array, metrics_dictionary, profile_dictionary, preview_dictionary, replay_dictionary, rule_dictionary, safety_event_dictionary, safety_log_array, scheduled_array, sensor_spec_from_dictionary, sensor_specs_dictionary, sensors_from_dictionary, state_dictionary, state_from_dictionary, stats_dictionary, tension_explanation_dictionary, trace_array, vector_array, vector_from_variant, write_agents};
+use crate::compiled;
+use crate::constants;
+use crate::datalog::DEFAULT_FLUSH_TICKS;
+use crate::diagnostic::Diagnostic;
+use crate::error::WeaveError;
+use crate::interpreter::{Fields, Interpreter, Params, ResolveMode, LANGUAGE_VERSION};
+use crate::invariant::InvariantPolicy;
+use crate::layout::FrameLayout;
+use crate::logging::{weave_log, Level, Log};
+use crate::methods::shared_methods;
+use crate::rules::{MetaweaveRule, RuleCondition};
+use crate::pid::PidGains;
+use crate::recording::ReplayOutcome;
+use crate::reload::{check_idle, BackgroundLoad, FieldChanges};
+use crate::safety::SafetySeverity;
+use crate::scalar::{to_f64, Scalar};
+use crate::sync::DiffOutcome;
+
+const DEFAULT_PROGRAM: &str = "default";
 
 #[derive(NativeClass)]
-#[inherit(RefCounted)]
+#[inherit(Reference)]
+#[register_with(Self::register)]
 #[user_data(gdnative::export::user_data::MutexData<WeaveLang>)]
 pub struct WeaveLang {
-    fields: HashMap<String, HashMap<String, f32>>,
-    tension_history: Vec<f32>,
+    /// The active program slot; the execute methods run against it.
+    interpreter: Interpreter,
+    active_program: String,
+    /// Every other loaded slot, parked until `set_active_program` swaps it in.
+    programs: HashMap<String, Interpreter>,
+    last_error: Dictionary,
+    last_diagnostics: Vec<Diagnostic>,
+    /// Objects and methods called with the event when a `halt` safety rule trips.
+    halt_callbacks: Vec<(Ref<Object>, String)>,
+    /// Behavior tree actions GDScript registered, each an object and method
+    /// called with the agent.
+    behavior_actions: HashMap<String, (Ref<Object>, String)>,
+    /// Agents and sensors registered for `step_buffer`.
+    frame: FrameLayout,
+    pending_load: Option<BackgroundLoad>,
 }
 
+shared_methods!(WeaveLang, Reference);
+
 #[methods]
 impl WeaveLang {
-    fn new(_owner: &RefCounted) -> Self {
+    fn new(_owner: &Reference) -> Self {
         WeaveLang {
-            fields: HashMap::new(),
-            tension_history: Vec::new(),
+            interpreter: Interpreter::new(),
+            active_program: DEFAULT_PROGRAM.to_string(),
+            programs: HashMap::new(),
+            last_error: Dictionary::new_shared(),
+            last_diagnostics: Vec::new(),
+            halt_callbacks: Vec::new(),
+            behavior_actions: HashMap::new(),
+            frame: FrameLayout::default(),
+            pending_load: None,
+        }
+    }
+
+    fn register(builder: &ClassBuilder<Self>) {
+        Self::register_signals(builder);
+        Self::register_properties(builder);
+        builder.mixin::<SharedMethods>();
+    }
+
+    /// The tuning knobs as inspector properties. They read and write the
+    /// active program's interpreter directly, so scripts and AnimationPlayer
+    /// see the same values, and hot reloads leave them alone.
+    fn register_properties(builder: &ClassBuilder<Self>) {
+        builder
+            .property::<String>("tension_reference")
+            .with_default("generalist".to_string())
+            .with_getter(|this: &Self, _| this.interpreter.tension_reference().to_string())
+            .with_setter(|this: &mut Self, _, field: String| this.interpreter.set_tension_reference(&field))
+            .done();
+        builder
+            .property::<f64>("drift_rate")
+            .with_default(to_f64(DEFAULT_DRIFT_RATE))
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 0.1).with_step(0.0001).or_greater()))
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.drift_rate()))
+            .with_setter(|this: &mut Self, _, rate: f64| this.interpreter.set_drift_rate(rate as Scalar))
+            .done();
+        builder
+            .property::<f64>("resolve_rate")
+            .with_default(to_f64(DEFAULT_RESOLVE_RATE))
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 0.1).with_step(0.0001).or_greater()))
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.resolve_rate()))
+            .with_setter(|this: &mut Self, _, rate: f64| this.interpreter.set_resolve_rate(rate as Scalar))
+            .done();
+        builder
+            .property::<f64>("smoothing_alpha")
+            .with_default(1.0)
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 1.0).with_step(0.01)))
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.smoothing_alpha()))
+            .with_setter(|this: &mut Self, _, alpha: f64| this.interpreter.set_smoothing_alpha(alpha as Scalar))
+            .done();
+        builder
+            .property::<i64>("history_limit")
+            .with_default(DEFAULT_HISTORY_LIMIT as i64)
+            .with_hint(IntHint::Range(RangeHint::new(0, 100_000).with_step(100).or_greater()))
+            .with_getter(|this: &Self, _| this.interpreter.history_limit() as i64)
+            .with_setter(|this: &mut Self, _, limit: i64| this.interpreter.set_history_limit(limit.max(0) as usize))
+            .done();
+        builder
+            .property::<bool>("clamp_targets")
+            .with_default(false)
+            .with_getter(|this: &Self, _| this.interpreter.config().clamp_targets)
+            .with_setter(|this: &mut Self, _, clamp: bool| {
+                let config = InterpreterConfig { clamp_targets: clamp, ..*this.interpreter.config() };
+                this.interpreter.set_config(config);
+            })
+            .done();
+        builder
+            .property::<f64>("target_min")
+            .with_default(0.0)
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.config().target_min))
+            .with_setter(|this: &mut Self, _, min: f64| {
+                let config = InterpreterConfig { target_min: min as Scalar, ..*this.interpreter.config() };
+                this.interpreter.set_config(config);
+            })
+            .done();
+        builder
+            .property::<f64>("target_max")
+            .with_default(1.0)
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.config().target_max))
+            .with_setter(|this: &mut Self, _, max: f64| {
+                let config = InterpreterConfig { target_max: max as Scalar, ..*this.interpreter.config() };
+                this.interpreter.set_config(config);
+            })
+            .done();
+        builder
+            .property::<f64>("tension_threshold")
+            .with_default(0.0)
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 1.0).with_step(0.001).or_greater()))
+            .with_getter(|this: &Self, _| to_f64(this.interpreter.tension_threshold()))
+            .with_setter(|this: &mut Self, _, threshold: f64| this.interpreter.set_tension_threshold(threshold as Scalar))
+            .done();
+    }
+
+    fn register_signals(builder: &ClassBuilder<Self>) {
+        builder
+            .signal("weave_loaded")
+            .with_param("success", VariantType::Bool)
+            .with_param("error", VariantType::Dictionary)
+            .done();
+        builder
+            .signal("weave_reloaded")
+            .with_param("changes", VariantType::Dictionary)
+            .done();
+        builder
+            .signal("weave_reload_failed")
+            .with_param("error", VariantType::Dictionary)
+            .done();
+        builder.signal("converged").done();
+        builder.signal("halted").done();
+        builder
+            .signal("safety_halt")
+            .with_param("rule_index", VariantType::I64)
+            .with_param("sensor_value", VariantType::F64)
+            .done();
+        builder
+            .signal("safety_violation")
+            .with_param("event", VariantType::Dictionary)
+            .done();
+        builder
+            .signal("sensor_missing")
+            .with_param("name", VariantType::GodotString)
+            .done();
+        builder
+            .signal("field_changed")
+            .with_param("agent", VariantType::GodotString)
+            .with_param("param", VariantType::GodotString)
+            .with_param("old_value", VariantType::F64)
+            .with_param("new_value", VariantType::F64)
+            .done();
+        builder
+            .signal("tension_high")
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("tension_resolved")
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("goal_completed")
+            .with_param("name", VariantType::GodotString)
+            .done();
+        builder
+            .signal("invariant_violated")
+            .with_param("name", VariantType::GodotString)
+            .done();
+        builder
+            .signal("param_out_of_bounds")
+            .with_param("field", VariantType::GodotString)
+            .with_param("param", VariantType::GodotString)
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("weave_event")
+            .with_param("condition", VariantType::GodotString)
+            .with_param("value", VariantType::F64)
+            .done();
+        builder
+            .signal("weave_signal")
+            .with_param("signal", VariantType::GodotString)
+            .with_param("value", VariantType::F64)
+            .done();
+    }
+
+    fn record_error(&mut self, error: &WeaveError) {
+        self.last_error = error_dictionary(error);
+        self.last_diagnostics = vec![Diagnostic::from_error(error)];
+    }
+
+    fn clear_error(&mut self) {
+        self.last_error = Dictionary::new_shared();
+        self.last_diagnostics.clear();
+    }
+
+    fn program_mut(&mut self, slot: &str) -> Option<&mut Interpreter> {
+        if slot == self.active_program {
+            Some(&mut self.interpreter)
+        } else {
+            self.programs.get_mut(slot)
+        }
+    }
+
+    fn check_reload(&mut self, owner: &Reference, delta: f64) {
+        if let Some(result) = self.interpreter.poll_reload(delta) {
+            self.finish_reload(owner, &result);
+        }
+    }
+
+    fn finish_reload(&mut self, owner: &Reference, result: &Result<FieldChanges, WeaveError>) {
+        match result {
+            Ok(changes) => {
+                weave_log!(self.interpreter.log(), Level::Info, "Reloaded Weave file: {}", self.interpreter.source_path().unwrap().display());
+                owner.emit_signal("weave_reloaded", &[changes_dictionary(changes).to_variant()]);
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to reload Weave file, keeping previous state: {}", e);
+                self.record_error(e);
+                owner.emit_signal("weave_reload_failed", &[self.last_error.to_variant()]);
+            }
+        }
+    }
+
+    /// Emits `safety_violation` for every trip; a `halt` rule also runs the
+    /// halt callbacks and emits `safety_halt`.
+    fn emit_safety_events(&mut self, owner: &Reference) {
+        for event in self.interpreter.take_safety_events() {
+            weave_log!(
+                self.interpreter.log(),
+                Level::Warn,
+                "Safety rule {} ({}) tripped: {} = {}",
+                event.rule_index,
+                event.severity.as_str(),
+                event.sensor,
+                event.sensor_value
+            );
+            let dict = safety_event_dictionary(Some(&event));
+            owner.emit_signal("safety_violation", &[dict.to_variant()]);
+            if event.severity == SafetySeverity::Halt {
+                self.run_halt_callbacks(&dict);
+                owner.emit_signal("safety_halt", &[(event.rule_index as i64).to_variant(), event.sensor_value.to_variant()]);
+            }
+        }
+    }
+
+    /// Calls every halt callback with `event`, dropping those whose object
+    /// has been freed.
+    fn run_halt_callbacks(&mut self, event: &Dictionary) {
+        weave_log!(self.interpreter.log(), Level::Info, "Halting experiment due to safety violation");
+        self.halt_callbacks.retain(|(target, method)| match unsafe { target.assume_safe_if_sane() } {
+            Some(target) => {
+                unsafe { target.call(method.as_str(), &[event.to_variant()]) };
+                true
+            }
+            None => false,
+        });
+    }
+
+    fn read_sensors(&mut self, sensor_data: &Dictionary) -> HashMap<String, Scalar> {
+        let mut warnings = Vec::new();
+        let sensors = sensors_from_dictionary(sensor_data, &mut warnings);
+        for warning in warnings {
+            self.interpreter.warn(warning);
         }
+        sensors
     }
 
+    fn read_agents(&mut self, agent_data: &Dictionary) -> Fields {
+        let mut warnings = Vec::new();
+        let agents = agents_from_dictionary(agent_data, &mut warnings);
+        for warning in warnings {
+            self.interpreter.warn(warning);
+        }
+        agents
+    }
+
+    fn emit_missing_sensors(&mut self, owner: &Reference) {
+        for sensor in self.interpreter.take_missing_sensors() {
+            owner.emit_signal("sensor_missing", &[sensor.to_variant()]);
+        }
+    }
+
+    fn emit_tension_crossings(&mut self, owner: &Reference) {
+        for crossing in self.interpreter.take_tension_crossings() {
+            match crossing {
+                TensionCrossing::High(value) => owner.emit_signal("tension_high", &[value.to_variant()]),
+                TensionCrossing::Resolved(value) => owner.emit_signal("tension_resolved", &[value.to_variant()]),
+            };
+        }
+    }
+
+    fn emit_completed_goals(&mut self, owner: &Reference) {
+        for name in self.interpreter.take_completed_goals() {
+            owner.emit_signal("goal_completed", &[name.to_variant()]);
+        }
+    }
+
+    fn emit_invariant_violations(&mut self, owner: &Reference) {
+        for violation in self.interpreter.take_invariant_violations() {
+            owner.emit_signal("invariant_violated", &[violation.name.to_variant()]);
+        }
+    }
+
+    /// `param_out_of_bounds(field, param, value)` for each param a declared
+    /// range started holding back, with the value it would have taken.
+    fn emit_out_of_bounds(&mut self, owner: &Reference) {
+        for event in self.interpreter.take_out_of_bounds() {
+            owner.emit_signal("param_out_of_bounds", &[event.field.to_variant(), event.param.to_variant(), event.value.to_variant()]);
+        }
+    }
+
+    /// `weave_event(condition, value)` for each `on` handler that fired; the
+    /// value is 0 for an attribute test.
+    fn emit_fired_events(&mut self, owner: &Reference) {
+        for event in self.interpreter.take_fired_events() {
+            owner.emit_signal("weave_event", &[event.condition.to_variant(), event.value.unwrap_or(0.0).to_variant()]);
+        }
+    }
+
+    /// Ticks the script's behavior trees, running each registered action as
+    /// `target.method(agent)`. An action whose object was freed counts as
+    /// unregistered.
+    fn tick_behaviors(&mut self, sensors: &HashMap<String, Scalar>, dt: Scalar) {
+        let actions = &self.behavior_actions;
+        self.interpreter.tick_behaviors(sensors, dt, &mut |action, agent| {
+            let (target, method) = actions.get(action)?;
+            let target = unsafe { target.assume_safe_if_sane() }?;
+            Some(behavior_status_from_variant(&unsafe { target.call(method.as_str(), &[agent.to_variant()]) }))
+        });
+    }
+
+    /// Emits each queued `emit` as `weave_signal(signal, value)`, then as a
+    /// signal of its own name, added to the object the first time. A name
+    /// that is already one of the class's signals only goes out as
+    /// `weave_signal`, so a script can't fake `safety_halt` and the like.
+    fn emit_script_signals(&mut self, owner: &Reference) {
+        for emitted in self.interpreter.take_emitted_signals() {
+            owner.emit_signal("weave_signal", &[emitted.signal.to_variant(), emitted.value.to_variant()]);
+            let signal = emitted.signal.as_str();
+            if !owner.has_signal(signal) {
+                owner.add_user_signal(signal, VariantArray::new_shared());
+            }
+            if owner.has_user_signal(signal) {
+                owner.emit_signal(signal, &[emitted.value.to_variant()]);
+            }
+        }
+    }
+
+    fn emit_field_changes(&mut self, owner: &Reference) {
+        for change in self.interpreter.take_field_changes() {
+            owner.emit_signal(
+                "field_changed",
+                &[change.agent.to_variant(), change.param.to_variant(), change.old_value.to_variant(), change.new_value.to_variant()],
+            );
+        }
+    }
+
+    /// Loads `path` into the active program slot. Refused, returning false,
+    /// while a `load_weave_async` is in flight.
     #[method]
     fn load_weave(&mut self, path: String) -> bool {
-        match parse_weave(Path::new(&path)) {
-            Ok(parsed_fields) => {
-                self.fields = parsed_fields;
-                godot_print!("Loaded Weave file: {}", path);
-                true
+        let slot = self.active_program.clone();
+        self.load_weave_named(slot, path)
+    }
+
+    /// Loads `path` into the program slot `slot`, creating it if needed. A slot
+    /// that fails its first load is not created. Refused like `load_weave`.
+    #[method]
+    fn load_weave_named(&mut self, slot: String, path: String) -> bool {
+        self.load_into(slot, &path).is_ok()
+    }
+
+    /// Like `load_weave`, but returns a Godot error code that GDScript can
+    /// compare against the global constants: `OK`, `ERR_FILE_NOT_FOUND`,
+    /// `ERR_FILE_CANT_READ`, `ERR_PARSE_ERROR`, `ERR_INVALID_DATA` for a script
+    /// that parsed but failed validation, or `ERR_BUSY` while an async load is
+    /// in flight. `get_last_error` carries the details.
+    #[method]
+    fn load_weave_ex(&mut self, path: String) -> i64 {
+        let slot = self.active_program.clone();
+        match self.load_into(slot, &path) {
+            Ok(()) => 0,
+            Err(e) => error_code(&e) as i64,
+        }
+    }
+
+    /// Loads `code`, the text of a whole weave file, into the active slot,
+    /// e.g. from a resource or an editor buffer. With no file behind it, the
+    /// slot has nothing to hot reload. Returns a Godot error code like
+    /// `load_weave_ex`.
+    #[method]
+    fn load_weave_from_string(&mut self, code: String) -> i64 {
+        if let Err(e) = check_idle(self.pending_load.as_ref(), "load_weave_from_string") {
+            weave_log!(self.interpreter.log(), Level::Warn, "{}", e);
+            return error_code(&e) as i64;
+        }
+        match self.interpreter.load_str(&code) {
+            Ok(()) => {
+                self.clear_error();
+                weave_log!(self.interpreter.log(), Level::Info, "Loaded Weave source ({} bytes) into program {}", code.len(), self.active_program);
+                0
             }
             Err(e) => {
-                godot_error!("Failed to load Weave file: {:?}", e);
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to load Weave source: {}", e);
+                self.record_error(&e);
+                error_code(&e) as i64
+            }
+        }
+    }
+
+    /// Loads the compiled program `path` into the active slot. Returns a
+    /// Godot error code like `load_weave_ex`, with `ERR_FILE_UNRECOGNIZED`
+    /// for a file compiled by a build with another format version.
+    #[method]
+    fn load_compiled(&mut self, path: String) -> i64 {
+        if !path.ends_with(&format!(".{}", compiled::EXTENSION)) {
+            weave_log!(self.interpreter.log(), Level::Error, "load_compiled({}): compiled programs end in .{}", path, compiled::EXTENSION);
+            return GodotError::FileUnrecognized as i64;
+        }
+        self.load_weave_ex(path)
+    }
+
+    fn load_into(&mut self, slot: String, path: &str) -> Result<(), WeaveError> {
+        // The pending result would replace this load when `poll` applies it.
+        if let Err(e) = check_idle(self.pending_load.as_ref(), &format!("loading {}", path)) {
+            weave_log!(self.interpreter.log(), Level::Warn, "{}", e);
+            self.record_error(&e);
+            return Err(e);
+        }
+        let existed = slot == self.active_program || self.programs.contains_key(&slot);
+        let program = if existed {
+            self.program_mut(&slot).unwrap()
+        } else {
+            let (log, globals) = (self.interpreter.log().clone(), self.interpreter.globals().cloned());
+            self.programs.entry(slot.clone()).or_insert_with(|| new_program(log, globals))
+        };
+        let file = Path::new(path);
+        let result = if file.extension().is_some_and(|extension| extension == compiled::EXTENSION) {
+            program.load_compiled(file)
+        } else {
+            program.load(file)
+        };
+        match result {
+            Ok(()) => {
+                self.clear_error();
+                weave_log!(self.interpreter.log(), Level::Info, "Loaded Weave file: {} into program {}", path, slot);
+                Ok(())
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to load Weave file: {}", e);
+                if !existed {
+                    self.programs.remove(&slot);
+                }
+                self.record_error(&e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Makes `slot` the program the execute methods run against. Parked slots
+    /// keep their fields, history and settings, so switching never re-parses.
+    #[method]
+    fn set_active_program(&mut self, slot: String) -> bool {
+        if slot == self.active_program {
+            return true;
+        }
+        match self.programs.remove(&slot) {
+            Some(next) => {
+                let previous = mem::replace(&mut self.interpreter, next);
+                let previous_slot = mem::replace(&mut self.active_program, slot);
+                self.programs.insert(previous_slot, previous);
+                true
+            }
+            None => {
+                weave_log!(self.interpreter.log(), Level::Warn, "No weave program named {}", slot);
                 false
             }
         }
     }
 
     #[method]
-    fn execute_tension(&mut self, sensor_data: Dictionary) -> f32 {
-        let mut sensors: HashMap<String, f32> = HashMap::new();
-        for (key, value) in sensor_data.iter_shared() {
-            if let Some(key_str) = key.to_string() {
-                if let Some(val_f32) = value.to_f32() {
-                    sensors.insert(key_str, val_f32);
+    fn get_active_program(&self) -> String {
+        self.active_program.clone()
+    }
+
+    /// Drops a parked slot. Unloading the active slot leaves it active but empty.
+    #[method]
+    fn unload_program(&mut self, slot: String) -> bool {
+        if slot == self.active_program {
+            self.interpreter = new_program(self.interpreter.log().clone(), self.interpreter.globals().cloned());
+            true
+        } else {
+            self.programs.remove(&slot).is_some()
+        }
+    }
+
+    /// Every slot name, sorted, including the active one.
+    #[method]
+    fn get_program_names(&self) -> VariantArray {
+        let mut names: Vec<&String> = self.programs.keys().chain(std::iter::once(&self.active_program)).collect();
+        names.sort();
+        let array = VariantArray::new();
+        for name in names {
+            array.push(name);
+        }
+        array.into_shared()
+    }
+
+    /// Parses `path` on a worker thread for the active program slot. The current
+    /// fields stay live until `poll` picks up the result and emits `weave_loaded`.
+    /// Returns false without starting anything if another load is still in flight.
+    #[method]
+    fn load_weave_async(&mut self, path: String) -> bool {
+        if self.pending_load.is_some() {
+            weave_log!(self.interpreter.log(), Level::Warn, "Ignoring load of {}: a weave file is already loading", path);
+            return false;
+        }
+        let constants = self.interpreter.constants().clone();
+        self.pending_load = Some(BackgroundLoad::start(self.active_program.clone(), path, constants));
+        true
+    }
+
+    #[method]
+    fn is_loading(&self) -> bool {
+        self.pending_load.is_some()
+    }
+
+    /// Watches the loaded file and hot-reloads it when it is saved, checking every
+    /// `set_reload_interval` seconds from `poll`.
+    #[method]
+    fn set_auto_reload(&mut self, enabled: bool) {
+        if !self.interpreter.set_auto_reload(enabled) {
+            weave_log!(self.interpreter.log(), Level::Warn, "Auto reload enabled before any weave file was loaded");
+        }
+    }
+
+    #[method]
+    fn set_reload_interval(&mut self, seconds: f64) {
+        self.interpreter.set_reload_interval(seconds);
+    }
+
+    /// Completes a pending `load_weave_async` and runs the auto-reload check;
+    /// call once per frame from the main thread.
+    #[method]
+    fn poll(&mut self, #[base] owner: &Reference, delta: f64) {
+        self.check_reload(owner, delta);
+        let Some(result) = self.pending_load.as_ref().and_then(BackgroundLoad::finished) else {
+            return;
+        };
+        let load = self.pending_load.take().unwrap();
+        let (slot, path) = (load.slot().to_owned(), load.path());
+        let success = match result {
+            Ok(program) => {
+                if slot != self.active_program {
+                    let (log, globals) = (self.interpreter.log().clone(), self.interpreter.globals().cloned());
+                    self.programs.entry(slot.clone()).or_insert_with(|| new_program(log, globals));
                 }
+                self.program_mut(&slot).unwrap().apply_parsed(path, program);
+                self.clear_error();
+                weave_log!(self.interpreter.log(), Level::Info, "Loaded Weave file: {}", path.display());
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to load Weave file: {}", e);
+                self.record_error(&e);
+                false
+            }
+        };
+        owner.emit_signal("weave_loaded", &[success.to_variant(), self.last_error.to_variant()]);
+    }
+
+    /// Adds the agent `name` as a copy of the field `template`.
+    /// Returns false and logs why if `name` exists or `template` doesn't.
+    #[method]
+    fn spawn_agent(&mut self, #[base] owner: &Reference, name: String, template: String) -> bool {
+        match self.interpreter.spawn_agent(&name, &template) {
+            Ok(()) => {
+                self.emit_field_changes(owner);
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "spawn_agent failed: {}", e);
+                self.record_error(&e);
+                false
             }
         }
-        let tension = execute_tension(&mut self.fields, &sensors);
-        self.tension_history.push(tension);
+    }
+
+    /// Removes the agent `name`. Returns false and logs why if it
+    /// doesn't exist or is the tension reference.
+    #[method]
+    fn despawn_agent(&mut self, name: String) -> bool {
+        match self.interpreter.despawn_agent(&name) {
+            Ok(()) => true,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "despawn_agent failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// Runs every program slot as a lab of a parent `WeaveLangNative`, which
+    /// calls this whenever its `global` field changes. Expressions and rules
+    /// read `global.*` from `globals`, and writes to them are rejected.
+    #[method]
+    fn set_globals(&mut self, globals: Dictionary) {
+        let mut warnings = Vec::new();
+        let globals = globals_from_dictionary(&globals, &mut warnings);
+        for warning in warnings {
+            self.interpreter.warn(warning);
+        }
+        for program in std::iter::once(&mut self.interpreter).chain(self.programs.values_mut()) {
+            program.set_globals(Some(globals.clone()));
+        }
+    }
+
+    /// Detaches every program slot from its parent's `global` field.
+    #[method]
+    fn clear_globals(&mut self) {
+        for program in std::iter::once(&mut self.interpreter).chain(self.programs.values_mut()) {
+            program.set_globals(None);
+        }
+    }
+
+    /// The active program's latest tension, or null before the first one.
+    #[method]
+    fn get_latest_tension(&self) -> Variant {
+        match self.interpreter.tension_history().last() {
+            Some(tension) => tension.to_variant(),
+            None => Variant::nil(),
+        }
+    }
+
+    /// Computes a tension for each `stride`-long row of `samples`, whose
+    /// first columns are `sensor_names`, and returns them in row order. With
+    /// `drift_every > 0`, drift and resolve move `agent_data` once per that
+    /// many rows using the chunk's mean tension. Returns what was computed
+    /// before a failing row, or nothing if the arguments are malformed.
+    #[method]
+    fn execute_tension_batch(
+        &mut self,
+        #[base] owner: &Reference,
+        sensor_names: PoolArray<GodotString>,
+        samples: PoolArray<f32>,
+        stride: i64,
+        #[opt] drift_every: i64,
+        #[opt] agent_data: Dictionary,
+    ) -> PoolArray<f32> {
+        let names: Vec<String> = sensor_names.read().iter().map(|name| name.to_string()).collect();
+        let samples: Vec<Scalar> = samples.read().iter().map(|&sample| sample as Scalar).collect();
+        let agents = self.read_agents(&agent_data);
+        let tick = self.interpreter.tick();
+        let outcome = self.interpreter.execute_tension_batch(&names, &samples, stride.max(0) as usize, drift_every.max(0) as usize, &agents, 1.0);
+        let tensions = match outcome {
+            Ok(tensions) => tensions,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "execute_tension_batch failed: {}", e);
+                self.record_error(&e);
+                let computed = self.interpreter.tick() - tick;
+                self.interpreter.tension_history()[self.interpreter.tension_history().len().saturating_sub(computed)..].to_vec()
+            }
+        };
+        self.emit_safety_events(owner);
+        self.emit_tension_crossings(owner);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+        vector_array(&tensions)
+    }
+
+    /// Runs tension, drift, resolve and metaweave as one tick lasting `delta`
+    /// seconds (1 if omitted), moves kinematic agents and returns the tension.
+    #[method]
+    fn step(&mut self, #[base] owner: &Reference, sensor_data: Dictionary, agent_data: Dictionary, #[opt] delta: f64) -> Scalar {
+        let sensors = self.read_sensors(&sensor_data);
+        let agents = self.read_agents(&agent_data);
+        self.step_with(owner, &sensors, &agents, if delta > 0.0 { delta as Scalar } else { 1.0 })
+    }
+
+    /// `step` for the registered agents, with one value per registered
+    /// sensor in `sensor_values`; NaN leaves a sensor out. Nothing goes
+    /// through a Dictionary. A buffer of the wrong length steps nothing and
+    /// returns 0.
+    #[method]
+    fn step_buffer(&mut self, #[base] owner: &Reference, sensor_values: PoolArray<f32>, #[opt] delta: f64) -> Scalar {
+        let mut frame = std::mem::take(&mut self.frame);
+        let values = sensor_values.read();
+        let tension = match frame.read(values.iter().map(|&value| value as Scalar)) {
+            Ok((sensors, agents)) => self.step_with(owner, sensors, agents, if delta > 0.0 { delta as Scalar } else { 1.0 }),
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "step_buffer failed: {}", e);
+                self.record_error(&e);
+                0.0
+            }
+        };
+        self.frame = frame;
+        tension
+    }
+
+    fn step_with(&mut self, owner: &Reference, sensors: &HashMap<String, Scalar>, agents: &Fields, dt: Scalar) -> Scalar {
+        let tension = match self.interpreter.step_dt(sensors, agents, dt) {
+            Ok(tension) => {
+                self.tick_behaviors(sensors, dt);
+                tension
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "step failed: {}", e);
+                self.record_error(&e);
+                0.0
+            }
+        };
+        self.emit_safety_events(owner);
+        self.emit_tension_crossings(owner);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        self.emit_completed_goals(owner);
+        self.emit_invariant_violations(owner);
+        self.emit_out_of_bounds(owner);
+        self.emit_fired_events(owner);
+        self.emit_script_signals(owner);
         tension
     }
 
+    /// What `step` would do with these inputs, without doing it: the
+    /// tension, param and vector changes and fired rules of a step run on a
+    /// copy. Nothing is changed and no signal is emitted.
     #[method]
-    fn execute_drift(&mut self, agent_data: Dictionary, tension: f32) {
-        let mut agents: HashMap<String, HashMap<String, f32>> = HashMap::new();
-        for (agent_name, props) in agent_data.iter_shared() {
-            if let Some(name) = agent_name.to_string() {
-                if let Some(props_dict) = props.cast::<Dictionary>() {
-                    let mut props_map: HashMap<String, f32> = HashMap::new();
-                    for (prop, val) in props_dict.iter_shared() {
-                        if let Some(prop_str) = prop.to_string() {
-                            if let Some(val_f32) = val.to_f32() {
-                                props_map.insert(prop_str, val_f32);
-                            }
-                        }
-                    }
-                    agents.insert(name, props_map);
-                }
+    fn preview_step(&mut self, sensor_data: Dictionary, agent_data: Dictionary, #[opt] delta: f64) -> Dictionary {
+        let sensors = self.read_sensors(&sensor_data);
+        let agents = self.read_agents(&agent_data);
+        let preview = self.interpreter.preview_step(&sensors, &agents, if delta > 0.0 { delta as Scalar } else { 1.0 });
+        if let Err(e) = &preview {
+            weave_log!(self.interpreter.log(), Level::Error, "preview_step failed: {}", e);
+            self.record_error(e);
+        }
+        preview_dictionary(&preview)
+    }
+
+    /// Starts (discarding any unsaved capture) or stops recording every `step`.
+    #[method]
+    fn set_recording(&mut self, enabled: bool) {
+        if enabled {
+            self.interpreter.start_recording();
+        } else {
+            self.interpreter.stop_recording();
+        }
+    }
+
+    #[method]
+    fn save_recording(&mut self, path: String) -> bool {
+        match self.interpreter.save_recording(Path::new(&path)) {
+            Ok(()) => true,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to save recording: {}", e);
+                self.record_error(&e);
+                false
             }
         }
-        execute_drift(&mut self.fields, &agents, &self.tension_history, tension);
-        for (name, props) in agents.iter() {
-            let mut dict = Dictionary::new();
-            for (prop, val) in props {
-                dict.insert(prop, *val);
+    }
+
+    /// Loads a recording and rewinds the fields to where it started.
+    #[method]
+    fn load_recording(&mut self, path: String) -> bool {
+        match self.interpreter.load_recording(Path::new(&path)) {
+            Ok(ticks) => {
+                weave_log!(self.interpreter.log(), Level::Info, "Loaded recording {} ({} ticks)", path, ticks);
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "Failed to load recording: {}", e);
+                self.record_error(&e);
+                false
             }
-            agent_data.insert(name, dict);
         }
     }
 
+    /// Replays the next recorded tick; the result's `status` is `matched`,
+    /// `diverged` (with the first divergent tick) or `finished`.
+    #[method]
+    fn replay_step(&mut self, #[base] owner: &Reference) -> Dictionary {
+        let outcome = match self.interpreter.replay_step() {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "replay_step failed: {}", e);
+                self.record_error(&e);
+                return error_dictionary(&e);
+            }
+        };
+        if let ReplayOutcome::Diverged { tick, first_divergent_tick, .. } = outcome {
+            weave_log!(self.interpreter.log(), Level::Warn, "Replay diverged at tick {} (first divergence at tick {})", tick, first_divergent_tick);
+        }
+        self.emit_field_changes(owner);
+        replay_dictionary(&outcome)
+    }
+
+    #[method]
+    fn execute_tension(&mut self, #[base] owner: &Reference, sensor_data: Dictionary) -> Scalar {
+        let sensors = self.read_sensors(&sensor_data);
+        let tension = match self.interpreter.execute_tension(&sensors) {
+            Ok(tension) => tension,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "execute_tension failed: {}", e);
+                self.record_error(&e);
+                0.0
+            }
+        };
+        self.emit_safety_events(owner);
+        self.emit_tension_crossings(owner);
+        self.emit_missing_sensors(owner);
+        tension
+    }
+
+    /// Drift for one tick, whatever the frame rate; see `execute_drift_dt`.
+    #[method]
+    fn execute_drift(&mut self, #[base] owner: &Reference, agent_data: Dictionary, tension: Scalar) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_drift(&agents, tension);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// Resolve for one tick, whatever the frame rate; see `execute_resolve_dt`.
+    #[method]
+    fn execute_resolve(&mut self, #[base] owner: &Reference, agent_data: Dictionary, tension: Scalar) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_resolve(&agents, tension);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// `execute_drift` for the registered agents.
     #[method]
-    fn execute_resolve(&mut self, agent_data: Dictionary, tension: f32) {
-        let mut agents: HashMap<String, HashMap<String, f32>> = HashMap::new();
-        for (agent_name, props) in agent_data.iter_shared() {
-            if let Some(name) = agent_name.to_string() {
-                if let Some(props_dict) = props.cast::<Dictionary>() {
-                    let mut props_map: HashMap<String, f32> = HashMap::new();
-                    for (prop, val) in props_dict.iter_shared() {
-                        if let Some(prop_str) = prop.to_string() {
-                            if let Some(val_f32) = val.to_f32() {
-                                props_map.insert(prop_str, val_f32);
-                            }
-                        }
-                    }
-                    agents.insert(name, props_map);
+    fn execute_drift_registered(&mut self, #[base] owner: &Reference, tension: Scalar) {
+        self.interpreter.execute_drift(self.frame.agents(), tension);
+        self.emit_field_changes(owner);
+    }
+
+    /// `execute_resolve` for the registered agents.
+    #[method]
+    fn execute_resolve_registered(&mut self, #[base] owner: &Reference, tension: Scalar) {
+        self.interpreter.execute_resolve(self.frame.agents(), tension);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+    }
+
+    /// Like `execute_drift`, but the drift rate is per second and scaled by `delta`.
+    #[method]
+    fn execute_drift_dt(&mut self, #[base] owner: &Reference, agent_data: Dictionary, tension: Scalar, delta: f64) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_drift_dt(&agents, tension, delta as Scalar);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// Like `execute_resolve`, but the resolve rate is per second and scaled by `delta`.
+    #[method]
+    fn execute_resolve_dt(&mut self, #[base] owner: &Reference, agent_data: Dictionary, tension: Scalar, delta: f64) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_resolve_dt(&agents, tension, delta as Scalar);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    #[method]
+    /// Returns the rules that fired, each as a Dictionary like `get_metaweave_rules`.
+    #[method]
+    fn execute_metaweave(&mut self, #[base] owner: &Reference, sensor_data: Dictionary) -> VariantArray {
+        let sensors = self.read_sensors(&sensor_data);
+        let fired = VariantArray::new();
+        match self.interpreter.execute_metaweave(&sensors) {
+            Ok(rules) => {
+                for rule in &rules {
+                    fired.push(rule_dictionary(rule));
                 }
             }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "execute_metaweave failed: {}", e);
+                self.record_error(&e);
+            }
         }
-        execute_resolve(&mut self.fields, &agents, tension);
-        for (name, props) in agents.iter() {
-            let mut dict = Dictionary::new();
-            for (prop, val) in props {
-                dict.insert(prop, *val);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        fired.into_shared()
+    }
+
+    /// Runs a weave snippet against the current state and returns what it
+    /// changed; a snippet that fails to parse or validate changes nothing.
+    #[method]
+    fn execute_code(&mut self, #[base] owner: &Reference, code: String) -> Dictionary {
+        let outcome = self.interpreter.execute_code(&code);
+        if let Err(e) = &outcome {
+            weave_log!(self.interpreter.log(), Level::Error, "execute_code failed: {}", e);
+            self.record_error(e);
+        }
+        self.emit_field_changes(owner);
+        code_outcome_dictionary(&outcome)
+    }
+
+    /// `execute_drift` for one tick, limited to the agents tagged `tag`.
+    #[method]
+    fn execute_drift_group(&mut self, #[base] owner: &Reference, tag: String, agent_data: Dictionary, tension: Scalar) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_drift_group(&tag, &agents, tension, 1.0);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// `execute_resolve` for one tick, limited to the agents tagged `tag`.
+    #[method]
+    fn execute_resolve_group(&mut self, #[base] owner: &Reference, tag: String, agent_data: Dictionary, tension: Scalar) {
+        let agents = self.read_agents(&agent_data);
+        self.interpreter.execute_resolve_group(&tag, &agents, tension, 1.0);
+        self.emit_missing_sensors(owner);
+        self.emit_field_changes(owner);
+        write_agents(&agent_data, &agents);
+    }
+
+    /// Points `sensor` at `source` (`"<node path>:<property>"`), overriding the
+    /// script's binding across reloads. False if `source` is malformed.
+    #[method]
+    fn rebind_sensor(&mut self, sensor: String, source: String) -> bool {
+        if !self.interpreter.rebind_sensor(&sensor, &source) {
+            weave_log!(self.interpreter.log(), Level::Warn, "rebind_sensor({}): \"{}\" is not \"<node path>:<property>\"", sensor, source);
+            return false;
+        }
+        true
+    }
+
+    /// Applies a peer's `get_state_diff`. False, with the fields untouched,
+    /// for a stale or duplicate diff, one that skips a revision this side never
+    /// saw, or bytes that aren't a diff.
+    #[method]
+    fn apply_state_diff(&mut self, bytes: PoolArray<u8>) -> bool {
+        match self.interpreter.apply_state_diff(&bytes.to_vec()) {
+            Ok(DiffOutcome::Applied { .. }) => true,
+            Ok(_) => false,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "apply_state_diff failed: {}", e);
+                self.record_error(&e);
+                false
             }
-            agent_data.insert(name, dict);
         }
     }
 
+    /// The value of constant `name`, or null if there is none.
     #[method]
-    fn execute_metaweave(&mut self, sensor_data: Dictionary) {
-        let mut sensors: HashMap<String, f32> = HashMap::new();
-        for (key, value) in sensor_data.iter_shared() {
-            if let Some(key_str) = key.to_string() {
-                if let Some(val_f32) = value.to_f32() {
-                    sensors.insert(key_str, val_f32);
-                }
+    fn get_constant(&mut self, name: String) -> Variant {
+        match self.interpreter.constants().get(&name) {
+            Some(constant) => constant.value.to_variant(),
+            None => {
+                self.record_error(&WeaveError::Validation(format!("unknown constant {}", name)));
+                Variant::nil()
+            }
+        }
+    }
+
+    /// Starts the active program over as it was loaded, as if it had just
+    /// been loaded: fields, tension history, scheduled sets, goals, the trace
+    /// and every lock go back to the script's state. Settings stay. Edits to
+    /// the file since it loaded are not picked up. False if nothing is
+    /// loaded; see `get_last_error`.
+    #[method]
+    fn reset(&mut self) -> bool {
+        match self.interpreter.reset() {
+            Ok(()) => true,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "reset failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// Injects the sections of a `to_json` String, replacing the current
+    /// ones; sections left out stay. False, changing nothing, if the JSON
+    /// is malformed.
+    #[method]
+    // Named after Interpreter::from_json for GDScript, though it loads into self.
+    #[allow(clippy::wrong_self_convention)]
+    fn from_json(&mut self, json: String) -> bool {
+        if let Err(e) = self.interpreter.from_json(&json) {
+            weave_log!(self.interpreter.log(), Level::Error, "from_json failed: {}", e);
+            self.record_error(&e);
+            return false;
+        }
+        true
+    }
+
+    /// Continues the active program from an exported state, usually after
+    /// loading the same weave file. False if the state doesn't read.
+    #[method]
+    fn import_state(&mut self, state: Dictionary) -> bool {
+        let mut warnings = Vec::new();
+        let state = state_from_dictionary(&state, &mut warnings);
+        for warning in warnings {
+            self.interpreter.warn(warning);
+        }
+        match state {
+            Ok(state) => {
+                self.interpreter.import_state(state);
+                true
+            }
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "import_state failed: {}", e);
+                self.record_error(&e);
+                false
             }
         }
-        execute_metaweave(&mut self.fields, &sensors);
     }
-}
 
-fn init(handle: InitHandle) {
-    handle.add_class::<WeaveLang>();
+    /// Continues the active program from a `snapshot`. False, with nothing
+    /// changed, if the bytes don't read.
+    #[method]
+    fn restore(&mut self, snapshot: PoolArray<u8>) -> bool {
+        match self.interpreter.restore(&snapshot.to_vec()) {
+            Ok(()) => true,
+            Err(e) => {
+                weave_log!(self.interpreter.log(), Level::Error, "restore failed: {}", e);
+                self.record_error(&e);
+                false
+            }
+        }
+    }
+
+    /// Lab calls fired by metaweave `call` rules since the last take, as
+    /// `{ node, method, args }`. `WeaveLang` has no scene, so GDScript
+    /// dispatches these itself; `WeaveLangNative` calls its lab nodes directly.
+    #[method]
+    fn take_lab_calls(&mut self) -> VariantArray {
+        let array = VariantArray::new();
+        for call in self.interpreter.take_lab_calls() {
+            array.push(lab_call_dictionary(&call));
+        }
+        array.into_shared()
+    }
+
 }
 
-godot_init!(init);
\ No newline at end of file
+/// A fresh program slot writing to `log` and reading a parent's `globals`.
+fn new_program(log: Log, globals: Option<Params>) -> Interpreter {
+    let mut program = Interpreter::new();
+    program.set_log(log);
+    program.set_globals(globals);
+    program
+}
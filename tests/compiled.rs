@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use weavelang::compiled;
+use weavelang::constants::Constants;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SOURCE: &str = "weave 2\nfn half(x) { x / 2 }\nfield generalist { coherence_target: clamp(0.4 * 1.5, 0, 1), noise_stddev: 0.01 }\nfield acc { physics_constant: 1.0, heat_scaled: heat * 2 + generalist.coherence_target, mode: cautious, tags: [mobile], position: [0.0, 1.0], velocity: [0.5, 0.0], label: \"beam\" }\nsensors { heat: 1.5 }\nmetaweave { when heat > 2 set acc.physics_constant = max(g, 2); when acc.mode == cautious set generalist.noise_stddev = 0.02; when heat > 3 call accelerator.reduce_power(0.5); }\nsafety { rule: heat > 9 halt grace_ticks: 2 }\ngoal calm { when tension < 0.2 and -heat <= sqrt(4) hold_ticks: 3 }\nassert sane: acc.physics_constant < 100\nat tick 5 set acc.physics_constant = 4.0;";
+
+fn compiled_pair(name: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+    let dir = std::env::temp_dir();
+    let (source, out) = (dir.join(format!("{}.weave", name)), dir.join(format!("{}.{}", name, compiled::EXTENSION)));
+    std::fs::write(&source, SOURCE).unwrap();
+    (source, out)
+}
+
+#[test]
+fn a_compiled_program_runs_like_its_source() {
+    let (source, out) = compiled_pair("weavelang_compiled_round_trip");
+    let program = compiled::compile(&source, &out, &Constants::default()).unwrap();
+    assert_eq!(compiled::load(&out).unwrap(), program);
+    let (mut from_source, mut from_compiled) = (Interpreter::new(), Interpreter::new());
+    from_source.load(&source).unwrap();
+    from_compiled.load_compiled(&out).unwrap();
+    let agents: Fields = ["generalist", "acc"].into_iter().map(|name| (name.to_string(), Default::default())).collect();
+    for tick in 0..40u8 {
+        let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.3 + Scalar::from(tick % 7) * 0.1), ("heat".to_string(), Scalar::from(tick % 5))].into();
+        assert_eq!(from_source.step(&sensors, &agents).unwrap(), from_compiled.step(&sensors, &agents).unwrap());
+        assert_eq!(from_source.take_lab_calls(), from_compiled.take_lab_calls());
+    }
+    assert_eq!(from_source.fields(), from_compiled.fields());
+    assert_eq!(from_source.attributes(), from_compiled.attributes());
+    assert_eq!(from_source.goal_progress().collect::<Vec<_>>(), from_compiled.goal_progress().collect::<Vec<_>>());
+    from_compiled.reset().unwrap();
+    assert_eq!(from_compiled.tick(), 0);
+}
+
+#[test]
+fn damaged_compiled_programs_are_refused() {
+    let (source, out) = compiled_pair("weavelang_compiled_damaged");
+    compiled::compile(&source, &out, &Constants::default()).unwrap();
+    let bytes = std::fs::read(&out).unwrap();
+    let damaged = std::env::temp_dir().join("weavelang_compiled_damaged_copy.wvc");
+
+    let mut version = bytes.clone();
+    version[4] = 99;
+    std::fs::write(&damaged, &version).unwrap();
+    let error = Interpreter::new().load_compiled(&damaged).unwrap_err();
+    assert!(matches!(error, WeaveError::UnsupportedFormat { found: 99, expected: compiled::FORMAT_VERSION }), "{}", error);
+    assert_eq!(error.kind(), "unsupported_format");
+
+    let mut width = bytes.clone();
+    width[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    std::fs::write(&damaged, &width).unwrap();
+    assert!(matches!(compiled::load(&damaged), Err(WeaveError::Validation(message)) if message.contains("34359738360-bit values")));
+
+    // The first field name's length follows the 16-byte header and the field count.
+    let mut length = bytes.clone();
+    length[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+    std::fs::write(&damaged, &length).unwrap();
+    assert!(matches!(compiled::load(&damaged), Err(WeaveError::Io(_))));
+
+    std::fs::write(&damaged, &bytes[..bytes.len() / 2]).unwrap();
+    assert!(compiled::load(&damaged).is_err());
+    assert!(matches!(Interpreter::new().load_compiled(&source), Err(WeaveError::Validation(_))));
+}
@@ -0,0 +1,52 @@
+use weavelang::constants::{convert, parse_unit, Constants};
+use weavelang::interpreter::Interpreter;
+use weavelang::scalar;
+
+fn close(actual: f64, expected: f64) -> bool {
+    ((actual - expected) / expected).abs() < 1e-9
+}
+
+#[test]
+fn units_convert_between_compatible_dimensions() {
+    for (value, from, to, expected) in [
+        (36.0, "km/h", "m/s", 10.0),
+        (1.0, "mi", "km", 1.609344),
+        (90.0, "min", "h", 1.5),
+        (1.0, "kWh", "J", 3.6e6),
+        (1.0, "atm", "kPa", 101.325),
+        (1.0, "J", "kg*m^2/s^2", 1.0),
+        (1.0, "J/(mol*K)", "J/mol/K", 1.0),
+    ] {
+        assert!(close(convert(value, from, to).unwrap(), expected), "{} {} in {}", value, from, to);
+    }
+    assert!(convert(1.0, "m", "s").is_err());
+    assert!(convert(1.0, "furlong", "m").is_err());
+    assert!(parse_unit("m/").is_err());
+    assert!(parse_unit("(m").is_err());
+    assert!(parse_unit("1/mol").is_ok());
+}
+
+#[test]
+fn the_table_starts_standard_and_takes_registrations() {
+    let mut constants = Constants::default();
+    assert_eq!(constants.get("g").map(|constant| constant.value), Some(9.80665));
+    assert_eq!(constants.get("C").map(|constant| constant.unit.as_str()), Some("m/s"));
+    constants.register("mass_unit", 2.0, "kg").unwrap();
+    assert_eq!(constants.get("mass_unit").map(|constant| constant.value), Some(2.0));
+    assert!(constants.register("bad", 2.0, "parsec").is_err());
+    assert!(constants.register("1x", 2.0, "kg").is_err());
+    assert!(constants.get("bad").is_none());
+}
+
+#[test]
+fn expressions_read_constants_by_name() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.1, mass: 2, energy: mass * C * C, weight: mass * g }").unwrap();
+    let generalist = &interpreter.fields()["generalist"];
+    assert!((scalar::to_f64(generalist["energy"]) / (2.0 * 299792458.0 * 299792458.0) - 1.0).abs() < 1e-6);
+    assert!((scalar::to_f64(generalist["weight"]) - 2.0 * 9.80665).abs() < 1e-5);
+    assert!(interpreter.load_str("field x { e: MASS * 2 }").is_err());
+    interpreter.constants_mut().register("MASS", 3.0, "kg").unwrap();
+    interpreter.load_str("field generalist { e: MASS * 2 }").unwrap();
+    assert_eq!(interpreter.fields()["generalist"]["e"], 6.0);
+}
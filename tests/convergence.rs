@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+fn run(interpreter: &mut Interpreter, coherence: Scalar) {
+    let agents: Fields = [("generalist".to_string(), Default::default())].into_iter().collect();
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), coherence)].into();
+    let tension = interpreter.execute_tension(&sensors).unwrap();
+    interpreter.execute_drift(&agents, tension);
+    interpreter.execute_resolve(&agents, tension);
+}
+
+fn loaded() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }").unwrap();
+    interpreter
+}
+
+#[test]
+fn a_decaying_error_converges_once() {
+    let mut interpreter = loaded();
+    let mut flips = 0;
+    assert!(!interpreter.is_converged(0.01, 10), "nothing has run yet");
+    for tick in 0..200 {
+        let target = interpreter.fields()["generalist"]["coherence_target"];
+        let error: Scalar = 0.9;
+        run(&mut interpreter, target + error.powi(tick));
+        if interpreter.update_convergence(0.01, 10) {
+            flips += 1;
+        }
+    }
+    assert!(interpreter.is_converged(0.01, 10));
+    assert_eq!(flips, 1);
+    assert!(!interpreter.is_converged(0.01, 500), "the window is longer than the history");
+}
+
+#[test]
+fn an_oscillating_sensor_never_converges() {
+    let mut interpreter = loaded();
+    for tick in 0..500 {
+        run(&mut interpreter, if tick % 2 == 0 { 1.0 } else { 0.0 });
+        assert!(!interpreter.is_converged(0.01, 10));
+        assert!(!interpreter.update_convergence(0.01, 10));
+    }
+}
+
+#[test]
+fn moving_params_hold_convergence_off() {
+    let mut interpreter = loaded();
+    for _ in 0..20 {
+        run(&mut interpreter, 0.5);
+    }
+    assert!(interpreter.is_converged(0.01, 10));
+    interpreter.set_convergence_epsilon(0.0);
+    assert!(!interpreter.is_converged(0.01, 10), "no change is below an epsilon of 0");
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field generalist { coherence_target: 0.5 }\nfield none { mass: 2.0, label: \"x\" }\nfield one { physics_constant: 0.2 }\nfield both { coherence_target: 0.1, physics_constant: 0.3, energy_target: 0.4 }\nfield own { coherence_target: 0.1, energy_target: 0.4, drives: [energy_target] }";
+
+fn setup() -> (Interpreter, Fields, HashMap<String, Scalar>) {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(SCRIPT).unwrap();
+    interpreter.set_drift_rate(0.5);
+    let agents = ["none", "one", "both", "own"].into_iter().map(|name| (name.to_string(), Default::default())).collect();
+    (interpreter, agents, [("coherence".to_string(), 0.9)].into())
+}
+
+#[test]
+fn every_default_target_a_field_has_is_driven() {
+    let (mut interpreter, agents, sensors) = setup();
+    let before = interpreter.fields().clone();
+    interpreter.step(&sensors, &agents).unwrap();
+    let fields = interpreter.fields();
+    assert_eq!(fields["none"], before["none"]);
+    assert!(fields["one"]["physics_constant"] > 0.2);
+    assert!(fields["both"]["coherence_target"] > 0.1 && fields["both"]["physics_constant"] > 0.3);
+    assert_eq!(fields["both"]["energy_target"], 0.4);
+    assert_eq!(fields["own"]["coherence_target"], 0.1, "drives overrides the defaults");
+    assert!(fields["own"]["energy_target"] > 0.4);
+    assert_eq!(interpreter.driven_params("own"), vec![("energy_target".to_string(), interpreter.fields()["own"]["energy_target"])]);
+}
+
+#[test]
+fn a_skipped_agent_is_reported_once() {
+    let (mut interpreter, agents, sensors) = setup();
+    interpreter.step(&sensors, &agents).unwrap();
+    interpreter.step(&sensors, &agents).unwrap();
+    let warnings = interpreter.take_warnings();
+    let skipped: Vec<_> = warnings.iter().filter(|warning| warning.contains("skip none")).collect();
+    assert_eq!(skipped.len(), 1, "{:?}", warnings);
+    assert!(skipped[0].contains("coherence_target, physics_constant") && skipped[0].contains("mass"), "{}", skipped[0]);
+}
+
+#[test]
+fn drift_targets_are_configurable() {
+    let (mut interpreter, agents, sensors) = setup();
+    interpreter.set_drift_targets(vec!["energy_target".to_string(), "mass".to_string()]);
+    interpreter.step(&sensors, &agents).unwrap();
+    assert!(interpreter.fields()["none"]["mass"] < 2.0);
+    assert!(interpreter.fields()["both"]["energy_target"] > 0.4);
+    let warnings = interpreter.take_warnings();
+    assert!(warnings.iter().any(|warning| warning.contains("skip one") && warning.contains("physics_constant")), "{:?}", warnings);
+}
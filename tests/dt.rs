@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+fn loaded() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }\nfield probe { physics_constant: 1.0 }").unwrap();
+    interpreter
+}
+
+fn agents() -> Fields {
+    ["generalist", "probe"].into_iter().map(|name| (name.to_string(), Default::default())).collect()
+}
+
+fn target(interpreter: &Interpreter, field: &str, param: &str) -> Scalar {
+    interpreter.fields()[field][param]
+}
+
+#[test]
+fn two_half_steps_move_as_far_as_one_whole_step() {
+    let (mut halves, mut whole) = (loaded(), loaded());
+    halves.execute_drift_dt(&agents(), 0.4, 0.5);
+    halves.execute_drift_dt(&agents(), 0.4, 0.5);
+    halves.execute_resolve_dt(&agents(), 0.4, 0.5);
+    halves.execute_resolve_dt(&agents(), 0.4, 0.5);
+    whole.execute_drift_dt(&agents(), 0.4, 1.0);
+    whole.execute_resolve_dt(&agents(), 0.4, 1.0);
+    for (field, param) in [("generalist", "coherence_target"), ("probe", "physics_constant")] {
+        assert!((target(&halves, field, param) - target(&whole, field, param)).abs() < 1e-6, "{}.{}", field, param);
+    }
+    let (mut plain, mut timed) = (loaded(), loaded());
+    plain.execute_drift(&agents(), 0.4);
+    timed.execute_drift_dt(&agents(), 0.4, 1.0);
+    assert_eq!(plain.fields(), timed.fields(), "the plain phases last one second");
+}
+
+#[test]
+fn a_second_at_30_fps_ends_where_a_second_at_60_fps_does() {
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.9)].into();
+    let mut end = Vec::new();
+    for fps in [30u8, 60] {
+        let mut interpreter = loaded();
+        for _ in 0..fps {
+            interpreter.step_dt(&sensors, &agents(), 1.0 / Scalar::from(fps)).unwrap();
+        }
+        end.push(target(&interpreter, "generalist", "coherence_target"));
+    }
+    assert!(end[0] != 0.5, "the target moved");
+    assert!((end[0] - end[1]).abs() < 1e-3, "30 fps ended at {}, 60 fps at {}", end[0], end[1]);
+}
+
+#[test]
+fn a_zero_length_step_moves_nothing() {
+    let mut interpreter = loaded();
+    let fields = interpreter.fields().clone();
+    interpreter.step_dt(&[("coherence".to_string(), 0.9)].into(), &agents(), 0.0).unwrap();
+    assert_eq!(interpreter.fields(), &fields);
+}
@@ -0,0 +1,43 @@
+use weavelang::interpreter::parse_weave_str;
+use weavelang::scalar::Scalar;
+
+fn value(expression: &str) -> Result<Scalar, String> {
+    let program = parse_weave_str(&format!("field generalist {{ base: 0.9, low: 2, high: 10, urgency: 0.25, v: {} }}", expression)).map_err(|error| error.to_string())?;
+    Ok(program.fields["generalist"]["v"])
+}
+
+#[test]
+fn builtins_evaluate() {
+    for (expression, expected) in [
+        ("clamp(base * 1.2, 0.0, 1.0)", 1.0),
+        ("clamp(0.5, 1.0, 0.0)", 0.5),
+        ("clamp(5, 1.0, 0.0)", 1.0),
+        ("lerp(low, high, urgency)", 4.0),
+        ("lerp(low, high, 2)", 18.0),
+        ("floor(-1.5)", -2.0),
+        ("ceil(1.2)", 2.0),
+        ("sqrt(16)", 4.0),
+        ("sqrt(0)", 0.0),
+        ("min(3, low, 7)", 2.0),
+        ("max(3, low, 7)", 7.0),
+        ("abs(-3)", 3.0),
+    ] {
+        assert_eq!(value(expression), Ok(expected), "{}", expression);
+    }
+}
+
+#[test]
+fn bad_arities_name_the_function_and_its_count() {
+    assert!(value("clamp(1, 2)").unwrap_err().contains("clamp takes 3 arguments, got 2"));
+    assert!(value("abs(1, 2)").unwrap_err().contains("abs takes 1 argument, got 2"));
+    assert!(value("min(1)").unwrap_err().contains("at least 2"));
+    assert!(value("sqrt(-4)").unwrap_err().contains("square root"));
+    assert!(value("foo(1)").is_err());
+}
+
+#[test]
+fn rules_and_goals_call_builtins_too() {
+    let program = parse_weave_str("field generalist { coherence_target: 0.5 }\nfield acc { e: 1 }\nmetaweave { when power > 0.5 set acc.e = clamp(generalist.coherence_target * 4, 0, 1.5); }\ngoal g { when sqrt(tension) < 1 and lerp(0, 2, tension) > 0, hold_ticks: 1 }").unwrap();
+    assert_eq!(program.metaweave_rules[0].value, 1.5);
+    assert!(parse_weave_str("field acc { e: 1 }\nmetaweave { when power > 0.5 set acc.e = nope; }").is_err());
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{parse_weave_str, Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field generalist { coherence_target: 0.5 }\ngoal stable { when heat < 2 and generalist.coherence_target * 2 >= 1, hold_ticks: 3 }\ngoal hot { when heat > 100 hold_ticks: 1 }";
+
+fn heat(value: Scalar) -> HashMap<String, Scalar> {
+    [("heat".to_string(), value)].into()
+}
+
+fn loaded() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(SCRIPT).unwrap();
+    interpreter
+}
+
+#[test]
+fn a_goal_completes_after_its_hold_ticks_in_a_row() {
+    let mut interpreter = loaded();
+    for value in [1.0, 1.0, 5.0, 1.0, 1.0] {
+        interpreter.step(&heat(value), &Fields::new()).unwrap();
+    }
+    assert!(interpreter.take_completed_goals().is_empty(), "the broken streak restarted");
+    let (goal, progress) = interpreter.goal_progress().next().unwrap();
+    assert_eq!((goal.name.as_str(), progress.satisfied_ticks), ("stable", 2));
+    interpreter.step(&heat(1.0), &Fields::new()).unwrap();
+    assert_eq!(interpreter.take_completed_goals(), vec!["stable".to_string()]);
+    interpreter.step(&heat(5.0), &Fields::new()).unwrap();
+    assert!(interpreter.take_completed_goals().is_empty(), "completion is reported once");
+    assert!(interpreter.goal_progress().next().unwrap().1.completed);
+}
+
+#[test]
+fn reloads_keep_unchanged_goals_and_reset_restarts_one() {
+    let mut interpreter = loaded();
+    for _ in 0..3 {
+        interpreter.step(&heat(1.0), &Fields::new()).unwrap();
+    }
+    interpreter.apply_reload(parse_weave_str(&SCRIPT.replace("heat > 100", "heat > 50")).unwrap());
+    let completed: Vec<_> = interpreter.goal_progress().map(|(goal, progress)| (goal.name.clone(), progress.completed)).collect();
+    assert_eq!(completed, vec![("stable".to_string(), true), ("hot".to_string(), false)]);
+    assert!(interpreter.reset_goal("stable"));
+    assert!(!interpreter.reset_goal("nope"));
+    interpreter.take_completed_goals();
+    for _ in 0..3 {
+        interpreter.step(&heat(1.0), &Fields::new()).unwrap();
+    }
+    assert_eq!(interpreter.take_completed_goals(), vec!["stable".to_string()]);
+}
+
+#[test]
+fn goals_print_and_must_be_unique() {
+    let interpreter = loaded();
+    assert_eq!(interpreter.goals()[0].to_string(), "goal stable { when heat < 2 and (generalist.coherence_target * 2) >= 1 hold_ticks: 3 }");
+    assert!(parse_weave_str("goal a { when x > 1 hold_ticks: 1 }\ngoal a { when x > 1 hold_ticks: 1 }").is_err());
+}
+
+#[test]
+fn hold_ticks_must_be_a_whole_count() {
+    for ticks in ["-3.5", "-1", "0.5"] {
+        match parse_weave_str(&format!("goal a {{ when x > 1 hold_ticks: {} }}", ticks)) {
+            Err(WeaveError::Validation(message)) => assert_eq!(message, format!("hold_ticks {} must be a whole, non-negative count", ticks)),
+            other => panic!("hold_ticks: {} gave {:?}", ticks, other),
+        }
+    }
+    assert_eq!(parse_weave_str("goal a { when x > 1 hold_ticks: 0 }").unwrap().goals[0].hold_ticks, 0);
+}
@@ -0,0 +1,45 @@
+use weavelang::interpreter::Interpreter;
+use weavelang::scalar::Scalar;
+
+fn with_history(ticks: usize, spike: usize) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }").unwrap();
+    interpreter.set_history_limit(10_000);
+    for tick in 0..ticks {
+        let coherence: Scalar = if tick == spike { 5.0 } else { 0.6 };
+        interpreter.execute_tension(&[("coherence".to_string(), coherence)].into()).unwrap();
+    }
+    interpreter
+}
+
+#[test]
+fn the_curve_is_downsampled_without_losing_spikes() {
+    let interpreter = with_history(1000, 437);
+    let curve = interpreter.history_curve(0, usize::MAX, 50);
+    assert!(!curve.is_empty() && curve.len() <= 50, "{} points", curve.len());
+    assert!(curve.windows(2).all(|pair| pair[0].0 < pair[1].0), "ticks ascend");
+    let peak = curve.iter().cloned().fold((0, Scalar::MIN), |best, point| if point.1 > best.1 { point } else { best });
+    assert_eq!(peak.0, 437);
+    assert_eq!(interpreter.history_curve(0, usize::MAX, usize::MAX).len(), 1000);
+}
+
+#[test]
+fn a_range_zooms_in_on_ticks() {
+    let interpreter = with_history(1000, 437);
+    let zoom = interpreter.history_curve(430, 440, usize::MAX);
+    assert_eq!(zoom.iter().map(|point| point.0).collect::<Vec<_>>(), (430..440).collect::<Vec<_>>());
+    assert!(interpreter.history_curve(2000, 3000, 10).is_empty());
+}
+
+#[test]
+fn ticks_past_the_history_limit_are_skipped() {
+    let mut interpreter = with_history(0, 0);
+    interpreter.set_history_limit(5);
+    for _ in 0..20 {
+        interpreter.execute_tension(&[("coherence".to_string(), 0.9)].into()).unwrap();
+    }
+    assert_eq!(interpreter.tension_history().len(), 5);
+    let curve = interpreter.history_curve(0, usize::MAX, usize::MAX);
+    assert_eq!(curve.first().map(|point| point.0), Some(15));
+    assert_eq!(interpreter.history_curve(17, 19, usize::MAX).len(), 2);
+}
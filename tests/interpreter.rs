@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field generalist { coherence_target: 0.5 }\nfield probe { physics_constant: 1.0 }\nmetaweave { when heat > 2 set probe.gain = 3; }";
+
+fn loaded() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(SCRIPT).unwrap();
+    interpreter
+}
+
+#[test]
+fn a_step_runs_the_phases_one_by_one_would() {
+    let (mut stepped, mut phased) = (loaded(), loaded());
+    let agents: Fields = ["generalist", "probe"].into_iter().map(|name| (name.to_string(), Default::default())).collect();
+    for tick in 0..20u8 {
+        let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.9), ("heat".to_string(), Scalar::from(tick % 4))].into();
+        let tension = stepped.step(&sensors, &agents).unwrap();
+        assert_eq!(phased.execute_tension(&sensors).unwrap(), tension);
+        phased.execute_drift(&agents, tension);
+        phased.execute_resolve(&agents, tension);
+        phased.execute_metaweave(&sensors).unwrap();
+        assert_eq!(stepped.fields(), phased.fields());
+    }
+    assert_eq!(stepped.fields()["probe"]["gain"], 3.0);
+    assert_eq!(stepped.tension_history().len(), 20);
+    assert_eq!(phased.tension_history().len(), 20);
+}
+
+#[test]
+fn interpreters_keep_their_own_state() {
+    let (mut first, second) = (loaded(), loaded());
+    first.execute_code("generalist.coherence_target = 0.9").unwrap();
+    first.execute_tension(&[("coherence".to_string(), 0.1)].into()).unwrap();
+    assert_eq!(second.fields()["generalist"]["coherence_target"], 0.5);
+    assert!(second.tension_history().is_empty());
+}
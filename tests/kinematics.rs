@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter, ParamValue};
+use weavelang::kinematics::{drive_velocity, Limits};
+use weavelang::scalar::Scalar;
+
+fn vector(interpreter: &Interpreter, agent: &str, param: &str) -> Vec<Scalar> {
+    match interpreter.attribute(agent, param) {
+        Some(ParamValue::Vector(components)) => components.clone(),
+        other => panic!("{}.{} is {:?}", agent, param, other),
+    }
+}
+
+fn loaded(script: &str) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(script).unwrap();
+    interpreter
+}
+
+fn coherence(value: Scalar) -> HashMap<String, Scalar> {
+    [("coherence".to_string(), value)].into()
+}
+
+#[test]
+fn positions_advance_by_clamped_velocity_times_dt() {
+    let script = "field generalist { coherence_target: 0.5 }\nfield r { position: [0.0, 0.0], velocity: [3.0, 4.0], max_speed: 2.5 }\nfield s { position: [1.0, 1.0] }";
+    let (mut quarters, mut wholes) = (loaded(script), loaded(script));
+    for _ in 0..8 {
+        quarters.step_dt(&coherence(0.5), &Fields::new(), 0.25).unwrap();
+    }
+    for _ in 0..2 {
+        wholes.step_dt(&coherence(0.5), &Fields::new(), 1.0).unwrap();
+    }
+    let velocity = vector(&quarters, "r", "velocity");
+    assert!((velocity[0].hypot(velocity[1]) - 2.5).abs() < 1e-5, "{:?}", velocity);
+    let (moved, jumped) = (vector(&quarters, "r", "position"), vector(&wholes, "r", "position"));
+    assert!((moved[0] - 3.0).abs() < 1e-5 && (moved[1] - 4.0).abs() < 1e-5, "{:?}", moved);
+    assert!((moved[0] - jumped[0]).abs() < 1e-5 && (moved[1] - jumped[1]).abs() < 1e-5, "{:?} {:?}", moved, jumped);
+    assert_eq!(vector(&quarters, "s", "position"), vec![1.0, 1.0], "no velocity, no motion");
+}
+
+#[test]
+fn steering_respects_max_accel_and_max_speed() {
+    let mut interpreter = loaded("field generalist { coherence_target: 0.5 }\nfield r { position: [0.0], velocity: [0.0], target_position: [10.0], max_accel: 1.0, max_speed: 3.0 }");
+    interpreter.set_resolve_rate(1.0);
+    let agents: Fields = [("r".to_string(), Default::default())].into_iter().collect();
+    let mut last: Scalar = 0.0;
+    for _ in 0..600 {
+        interpreter.step_dt(&coherence(1.5), &agents, 0.1).unwrap();
+        let speed = vector(&interpreter, "r", "velocity")[0];
+        assert!((-1e-4..=3.0 + 1e-5).contains(&speed), "{}", speed);
+        assert!((speed - last).abs() <= 0.1 + 1e-5, "accelerated from {} to {}", last, speed);
+        last = speed;
+        assert!(vector(&interpreter, "r", "position")[0] <= 10.0 + 1e-3);
+    }
+    assert!((vector(&interpreter, "r", "position")[0] - 10.0).abs() < 0.05);
+}
+
+#[test]
+fn drive_velocity_limits_speed_and_acceleration() {
+    let free = Limits { max_speed: Scalar::INFINITY, max_accel: Scalar::INFINITY };
+    assert_eq!(drive_velocity(&[0.0, 0.0, 0.0], &[1.0, 2.0], &[], free, 0.5), vec![2.0, 4.0, 0.0]);
+    let capped = Limits { max_speed: 1.0, max_accel: 2.0 };
+    let velocity = drive_velocity(&[0.0, 0.0], &[10.0, 0.0], &[0.0, 0.0], capped, 0.25);
+    assert!((velocity[0] - 0.5).abs() < 1e-6 && velocity[1] == 0.0, "{:?}", velocity);
+    let velocity = drive_velocity(&[0.0, 0.0], &[10.0, 0.0], &[0.9, 0.0], capped, 0.25);
+    assert!((velocity[0] - 1.0).abs() < 1e-6, "{:?}", velocity);
+    let braking = drive_velocity(&[0.0], &[0.0], &[1.0], capped, 0.25);
+    assert!((braking[0] - 0.5).abs() < 1e-6, "{:?}", braking);
+    assert_eq!(drive_velocity(&[0.0], &[5.0], &[1.0], capped, 0.0), vec![1.0]);
+}
@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field a { coherence_target: 0.5 }\nfield generalist { coherence_target: 0.5 }\nmetaweave { when coherence > 0.1 set a.coherence_target = 3.0; }";
+
+fn agents() -> Fields {
+    ["a", "generalist"].into_iter().map(|name| (name.to_string(), Default::default())).collect()
+}
+
+#[test]
+fn a_locked_agent_holds_for_a_hundred_steps() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(SCRIPT).unwrap();
+    interpreter.set_agent_locked("a", true, false);
+    let before = interpreter.fields()["a"].clone();
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.9)].into();
+    for _ in 0..100 {
+        interpreter.step(&sensors, &agents()).unwrap();
+    }
+    assert_eq!(interpreter.fields()["a"], before);
+    assert_ne!(interpreter.fields()["generalist"]["coherence_target"], 0.5);
+    assert_eq!(interpreter.locked_agents(), vec!["a"]);
+    interpreter.execute_code("a.coherence_target = 0.25").unwrap();
+    assert_eq!(interpreter.fields()["a"]["coherence_target"], 0.25, "execute_code still writes");
+    interpreter.set_agent_locked("a", false, false);
+    interpreter.step(&sensors, &agents()).unwrap();
+    assert_eq!(interpreter.fields()["a"]["coherence_target"], 3.0);
+}
+
+#[test]
+fn only_persistent_locks_survive_a_load() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(SCRIPT).unwrap();
+    interpreter.set_agent_locked("a", true, false);
+    interpreter.set_agent_locked("generalist", true, true);
+    interpreter.load_str(SCRIPT).unwrap();
+    assert_eq!(interpreter.locked_agents(), vec!["generalist"]);
+}
@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+fn sensors(coherence: Scalar) -> HashMap<String, Scalar> {
+    [("coherence".to_string(), coherence)].into()
+}
+
+#[test]
+fn while_tension_sees_the_tension_its_body_leaves() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .load_str("field generalist { coherence_target: 0.5, passes: 0 }\nwhile tension > 0.25 { set generalist.coherence_target = generalist.coherence_target + 0.1; set generalist.passes = generalist.passes + 1; }")
+        .unwrap();
+    interpreter.step(&sensors(0.9), &Fields::new()).unwrap();
+    let generalist = &interpreter.fields()["generalist"];
+    assert_eq!(generalist["passes"], 2.0);
+    assert!((generalist["coherence_target"] - 0.7).abs() < 1e-4);
+    assert!(interpreter.take_warnings().is_empty());
+}
+
+#[test]
+fn repeat_runs_its_count_and_while_stops_at_the_limit() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .load_str("field generalist { coherence_target: 0.5, count: 0 }\nrepeat 3 { set generalist.count = generalist.count + 1; }\nwhile heat > 1 { set generalist.count = generalist.count + 1; }")
+        .unwrap();
+    interpreter.set_loop_limit(10);
+    let mut readings = sensors(0.5);
+    readings.insert("heat".to_string(), 2.0);
+    interpreter.step(&readings, &Fields::new()).unwrap();
+    assert_eq!(interpreter.fields()["generalist"]["count"], 13.0);
+    assert_eq!(interpreter.take_warnings().len(), 1);
+    interpreter.step(&readings, &Fields::new()).unwrap();
+    assert!(interpreter.take_warnings().is_empty(), "the limit warning is given once");
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::recording::ReplayOutcome;
+use weavelang::scalar::{self, Scalar};
+
+const SCRIPT: &str = "field acc { coherence_target: 0.5, noise_stddev: 0.002 }\nfield quiet { coherence_target: 0.5 }";
+
+fn loaded(script: &str, seed: u64) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(script).unwrap();
+    interpreter.set_noise_seed(seed);
+    interpreter
+}
+
+/// How far each of `ticks` zero-tension drifts moves `acc`'s target.
+fn wander(interpreter: &mut Interpreter, ticks: usize) -> Vec<Scalar> {
+    let agents: Fields = ["acc", "quiet"].into_iter().map(|name| (name.to_string(), Default::default())).collect();
+    (0..ticks)
+        .map(|_| {
+            let before = interpreter.fields()["acc"]["coherence_target"];
+            interpreter.execute_drift(&agents, 0.0);
+            interpreter.fields()["acc"]["coherence_target"] - before
+        })
+        .collect()
+}
+
+#[test]
+fn declared_noise_has_the_declared_stddev() {
+    let steps = wander(&mut loaded(SCRIPT, 42), 20_000);
+    let count = steps.len() as f64;
+    let mean = steps.iter().map(|step| scalar::to_f64(*step)).sum::<f64>() / count;
+    let variance = steps.iter().map(|step| (scalar::to_f64(*step) - mean).powi(2)).sum::<f64>() / count;
+    assert!((variance.sqrt() - 0.002).abs() < 0.0001, "stddev {}", variance.sqrt());
+}
+
+#[test]
+fn a_seed_repeats_its_noise_and_quiet_fields_stay_still() {
+    let mut interpreter = loaded(SCRIPT, 42);
+    assert_eq!(wander(&mut interpreter, 1000), wander(&mut loaded(SCRIPT, 42), 1000));
+    assert_ne!(wander(&mut loaded(SCRIPT, 7), 10), wander(&mut loaded(SCRIPT, 42), 10));
+    assert_eq!(interpreter.fields()["quiet"]["coherence_target"], 0.5);
+    interpreter.set_noise_scale(0.0);
+    assert!(wander(&mut interpreter, 10).iter().all(|step| *step == 0.0));
+}
+
+#[test]
+fn noise_stays_inside_the_target_bounds() {
+    let mut interpreter = loaded("field acc { coherence_target: 0.5, noise_stddev: 1, target_min: 0.4, target_max: 0.6 }", 1);
+    for _ in 0..100 {
+        wander(&mut interpreter, 1);
+        assert!((0.4..=0.6).contains(&interpreter.fields()["acc"]["coherence_target"]));
+    }
+}
+
+#[test]
+fn a_replay_reproduces_the_noise() {
+    let mut interpreter = loaded("field generalist { coherence_target: 0.5, noise_stddev: 0.01 }", 3);
+    interpreter.start_recording();
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.7)].into();
+    let agents: Fields = [("generalist".to_string(), Default::default())].into_iter().collect();
+    for _ in 0..20 {
+        interpreter.step(&sensors, &agents).unwrap();
+    }
+    let path = std::env::temp_dir().join("weavelang_noise.rec");
+    interpreter.save_recording(&path).unwrap();
+    interpreter.load_recording(&path).unwrap();
+    for _ in 0..20 {
+        let outcome = interpreter.replay_step().unwrap();
+        assert!(matches!(outcome, ReplayOutcome::Matched { .. }), "{:?}", outcome);
+    }
+}
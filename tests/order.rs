@@ -0,0 +1,31 @@
+use weavelang::interpreter::{parse_weave_str, Interpreter};
+
+fn script(names: &[String]) -> String {
+    names.iter().map(|name| format!("field {} {{ b: 1, a: 2 }}\n", name)).collect()
+}
+
+fn shuffled_names() -> Vec<String> {
+    (0..20).map(|i| format!("f{}", (i * 7919) % 101)).collect()
+}
+
+#[test]
+fn fields_and_params_keep_declaration_order() {
+    let names = shuffled_names();
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(&script(&names)).unwrap();
+    assert_eq!(interpreter.fields().keys().cloned().collect::<Vec<_>>(), names);
+    assert_eq!(interpreter.fields()["f0"].keys().collect::<Vec<_>>(), vec!["b", "a"]);
+    let mut again = Interpreter::new();
+    again.load_str(&script(&names)).unwrap();
+    assert_eq!(format!("{:?}", again.fields()), format!("{:?}", interpreter.fields()));
+}
+
+#[test]
+fn a_reload_takes_the_new_declaration_order() {
+    let mut names = shuffled_names();
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(&script(&names)).unwrap();
+    names.insert(5, "mid".to_string());
+    interpreter.apply_reload(parse_weave_str(&script(&names)).unwrap());
+    assert_eq!(interpreter.fields().keys().cloned().collect::<Vec<_>>(), names);
+}
@@ -0,0 +1,50 @@
+use weavelang::constants::Constants;
+use weavelang::interpreter::{parse_snippet, parse_weave_str};
+
+#[test]
+fn crlf_and_a_bom_parse_like_lf() {
+    let lf = parse_weave_str("weave 2\nfield generalist { // target\n  coherence_target: 0.5, label: \"a // b\",\n}\n").unwrap();
+    let crlf = parse_weave_str("\u{feff}weave 2\r\nfield generalist { // target\r\n  coherence_target: 0.5, label: \"a // b\",\r\n}\r\n").unwrap();
+    assert_eq!(lf, crlf);
+    assert_eq!(crlf.version, 2);
+    assert_eq!(parse_weave_str("field a {\r x: 1 }\r").unwrap().fields["a"]["x"], 1.0);
+    assert!(parse_snippet("\u{feff}a.x = 1;\r\n// done", &Constants::default(), &Default::default(), &Default::default()).is_ok());
+}
+
+#[test]
+fn empty_scripts_parse_to_nothing() {
+    assert!(parse_weave_str("").unwrap().fields.is_empty());
+    assert!(parse_weave_str("// nothing here\n// at all").unwrap().fields.is_empty());
+}
+
+#[test]
+fn trailing_and_truncated_input_is_an_error_at_its_position() {
+    assert_eq!(parse_weave_str("field a { x: 1").unwrap_err().line_col(), Some((1, 15)));
+    assert_eq!(parse_weave_str("field a { x: 1 }\nthis is junk").unwrap_err().line_col(), Some((2, 1)));
+}
+
+#[test]
+fn identifiers_are_ascii_but_strings_and_comments_are_not() {
+    let error = parse_weave_str("field a { x: 1 }\nfield cohérence { x: 1 }").unwrap_err();
+    assert_eq!(error.line_col(), Some((2, 10)));
+    assert!(error.to_string().contains("'é' is not allowed"), "{}", error);
+    assert!(parse_weave_str("// café\nfield a { label: \"cohérence\" }").is_ok());
+}
+
+#[test]
+fn plain_fields_parse_as_the_grammar_does() {
+    // An empty tension block changes nothing but sends the file through pest.
+    let scripts = [
+        "field a { x: 1, y: -0.5, }\nfield b {}",
+        "\u{feff} weave 2 fieldx{x:1}",
+        "field a { x: 1, x: 2 }\nfield b { y: 3 }\nfield a { z: 4 }",
+        "/* header */ field a { x: 1 /* one */, // two\r\n y: 2.25\r}",
+    ];
+    for script in scripts {
+        assert_eq!(parse_weave_str(script).unwrap(), parse_weave_str(&format!("{}\ntension {{}}", script)).unwrap(), "{:?}", script);
+    }
+    assert_eq!(parse_weave_str("field a { x: 1 + 1, y: 2 * 3 }").unwrap().fields["a"]["y"], 6.0);
+    for broken in ["field a { x: 1. }", "field a { x: - }", "field a { , }", "field a { x: 1 } /*", "weave field a {}"] {
+        assert!(parse_weave_str(broken).is_err(), "{:?}", broken);
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter, ResolveMode};
+use weavelang::scalar::Scalar;
+
+/// The coherence target after each of `ticks` ticks of `mode` tracking a
+/// coherence of 1 from 0.5.
+fn trajectory(mode: ResolveMode, ticks: usize) -> Vec<Scalar> {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }").unwrap();
+    interpreter.set_resolve_mode(mode);
+    let agents: Fields = [("generalist".to_string(), Default::default())].into_iter().collect();
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 1.0)].into();
+    (0..ticks)
+        .map(|_| {
+            let tension = interpreter.execute_tension(&sensors).unwrap();
+            interpreter.execute_drift(&agents, tension);
+            interpreter.execute_resolve(&agents, tension);
+            interpreter.fields()["generalist"]["coherence_target"]
+        })
+        .collect()
+}
+
+/// Ticks `mode` takes to bring the target within 0.01 of a coherence of 1.
+fn ticks_to_settle(mode: ResolveMode) -> Option<usize> {
+    trajectory(mode, 2000).iter().position(|target| (target - 1.0).abs() < 0.01)
+}
+
+/// How far the target ever goes past 1, and how often it crosses 1.
+fn overshoot_and_crossings(targets: &[Scalar]) -> (Scalar, usize) {
+    let overshoot = targets.iter().fold(0.0, |most: Scalar, target| most.max(target - 1.0));
+    let crossings = targets.windows(2).filter(|pair| (pair[0] - 1.0).signum() != (pair[1] - 1.0).signum()).count();
+    (overshoot, crossings)
+}
+
+#[test]
+fn pid_settles_well_before_the_fixed_gain() {
+    let fixed = ticks_to_settle(ResolveMode::Fixed).expect("the fixed gain settles");
+    let pid = ticks_to_settle(ResolveMode::Pid).expect("the PID controller settles");
+    assert!(pid * 5 < fixed, "PID took {} ticks, the fixed gain {}", pid, fixed);
+}
+
+#[test]
+fn pid_damps_its_overshoot_and_stays_settled() {
+    let fixed = trajectory(ResolveMode::Fixed, 2000);
+    let pid = trajectory(ResolveMode::Pid, 2000);
+    let (fixed_overshoot, fixed_crossings) = overshoot_and_crossings(&fixed);
+    let (pid_overshoot, pid_crossings) = overshoot_and_crossings(&pid);
+    // A damped response may pass the target once, by less than a fifth of
+    // the 0.5 step, where the fixed gain creeps up without passing it.
+    assert!(pid_overshoot < fixed_overshoot + 0.1, "PID overshot by {}, the fixed gain by {}", pid_overshoot, fixed_overshoot);
+    assert!(pid_crossings <= fixed_crossings + 1, "PID crossed the target {} times, the fixed gain {}", pid_crossings, fixed_crossings);
+    for (name, targets) in [("fixed", &fixed), ("PID", &pid)] {
+        let settled = targets.iter().position(|target| (target - 1.0).abs() < 0.01).unwrap();
+        assert!(targets[settled..].iter().all(|target| (target - 1.0).abs() < 0.01), "the {} target left the band after settling", name);
+    }
+}
+
+#[test]
+fn non_finite_integral_limits_are_refused() {
+    let mut interpreter = Interpreter::new();
+    for limit in [Scalar::NAN, Scalar::INFINITY, Scalar::NEG_INFINITY] {
+        assert!(matches!(interpreter.set_pid_integral_limit(limit), Err(WeaveError::Validation(_))));
+    }
+    interpreter.set_pid_integral_limit(-2.0).unwrap();
+}
@@ -0,0 +1,26 @@
+use weavelang::scalar::{self, Scalar};
+
+#[cfg(feature = "f64")]
+#[test]
+fn the_f64_build_keeps_what_f32_rounds_away() {
+    assert_eq!(std::mem::size_of::<Scalar>(), 8);
+    let mut interpreter = weavelang::interpreter::Interpreter::new();
+    interpreter.set_loop_limit(1000);
+    interpreter.load_str("field generalist { coherence_target: 0.5 }\nfield star { mass: 1.0, fine: 0.1000000001 }\nrepeat 1000 { set star.mass = star.mass + 0.000000001; }").unwrap();
+    assert_eq!(interpreter.fields()["star"]["fine"], 0.1000000001);
+    interpreter.step(&[("coherence".to_string(), 0.5)].into(), &Default::default()).unwrap();
+    assert!((interpreter.fields()["star"]["mass"] - 1.000001).abs() < 1e-12, "{}", interpreter.fields()["star"]["mass"]);
+}
+
+#[cfg(not(feature = "f64"))]
+#[test]
+fn the_default_build_runs_in_f32() {
+    assert_eq!(std::mem::size_of::<Scalar>(), 4);
+}
+
+#[test]
+fn scalars_convert_both_ways() {
+    let value: Scalar = 0.25;
+    assert_eq!(scalar::to_f64(value), 0.25);
+    assert_eq!(scalar::to_f32(value), 0.25);
+}
@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+fn state(interpreter: &Interpreter) -> String {
+    format!(
+        "{:?}|{:?}|{:?}|{}|{:?}|{:?}",
+        interpreter.fields(),
+        interpreter.tension_history(),
+        interpreter.attributes(),
+        interpreter.tick(),
+        interpreter.smoothed_tension(),
+        interpreter.vector_model()
+    )
+}
+
+#[test]
+fn a_preview_changes_nothing_and_predicts_the_step() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }\nfield acc { physics_constant: 1.0, position: [0.0], velocity: [1.0] }\nmetaweave { when power > 0.5 set acc.energy = 150.0; }").unwrap();
+    interpreter.set_smoothing_alpha(0.3);
+    let agents: Fields = ["generalist", "acc"].into_iter().map(|name| (name.to_string(), Default::default())).collect();
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.9), ("power".to_string(), 1.0)].into();
+    interpreter.step(&sensors, &agents).unwrap();
+    let before = state(&interpreter);
+    let preview = interpreter.preview_step(&sensors, &agents, 0.5).unwrap();
+    assert_eq!(state(&interpreter), before);
+    assert_eq!(preview.fired.len(), 1);
+
+    let old = interpreter.fields().clone();
+    assert_eq!(interpreter.step_dt(&sensors, &agents, 0.5).unwrap(), preview.tension);
+    let mut changed = Vec::new();
+    for (agent, params) in interpreter.fields() {
+        for (param, value) in params {
+            let was = old.get(agent).and_then(|params| params.get(param)).copied();
+            if was != Some(*value) {
+                changed.push((agent.clone(), param.clone(), was.unwrap_or(0.0), *value));
+            }
+        }
+    }
+    let previewed: Vec<_> = preview.changes.iter().map(|change| (change.agent.clone(), change.param.clone(), change.old_value, change.new_value)).collect();
+    assert!(!previewed.is_empty());
+    assert_eq!(changed, previewed);
+    let moved: Vec<_> = interpreter.vector_model().into_iter().filter(|vector| vector.0 == "acc.position").collect();
+    assert_eq!(moved, preview.vectors);
+}
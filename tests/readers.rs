@@ -0,0 +1,79 @@
+use std::sync::{Arc, RwLock};
+use weavelang::interpreter::{Fields, Interpreter};
+
+fn shareable<T: Send + Sync>() {}
+
+#[test]
+fn readers_share_the_interpreter_across_threads() {
+    shareable::<Interpreter>();
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }").unwrap();
+    interpreter.step(&[("coherence".to_string(), 0.7)].into(), &Fields::new()).unwrap();
+    let shared = Arc::new(RwLock::new(interpreter));
+    let expected = shared.read().unwrap().fields()["generalist"].clone();
+    // Every reader holds the read lock at once, which only works if reads
+    // never need the write side.
+    let held = Arc::new(std::sync::Barrier::new(4));
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let (shared, held) = (shared.clone(), held.clone());
+            std::thread::spawn(move || {
+                let interpreter = shared.read().unwrap();
+                held.wait();
+                (interpreter.coherence(), interpreter.fields()["generalist"].clone())
+            })
+        })
+        .collect();
+    let coherence = shared.read().unwrap().coherence();
+    for reader in readers {
+        assert_eq!(reader.join().unwrap(), (coherence, expected.clone()));
+    }
+}
+
+#[test]
+fn readers_never_see_a_half_written_step() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5, noise_stddev: 0.01 }\nfield acc { physics_constant: 1.0 }").unwrap();
+    let sensors = [("coherence".to_string(), 0.9)].into();
+    let agents: Fields = interpreter.fields().clone();
+    // The fields after every tick, from a copy stepped on its own.
+    let mut expected = vec![interpreter.fields().clone()];
+    let mut copy = interpreter.clone();
+    for _ in 0..200 {
+        copy.step(&sensors, &agents).unwrap();
+        expected.push(copy.fields().clone());
+    }
+    let shared = Arc::new(RwLock::new(interpreter));
+    let expected = Arc::new(expected);
+    let writer = {
+        let shared = shared.clone();
+        std::thread::spawn(move || {
+            for _ in 0..200 {
+                shared.write().unwrap().step(&sensors, &agents).unwrap();
+                std::thread::yield_now();
+            }
+        })
+    };
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let (shared, expected) = (shared.clone(), expected.clone());
+            std::thread::spawn(move || {
+                let mut last = 0;
+                while last < 200 {
+                    let interpreter = shared.read().unwrap();
+                    let tick = interpreter.tick();
+                    assert!(tick >= last, "the tick went back from {} to {}", last, tick);
+                    assert_eq!(interpreter.fields(), &expected[tick], "fields at tick {} don't match that tick", tick);
+                    assert_eq!(interpreter.tension_history().len(), tick);
+                    last = tick;
+                    drop(interpreter);
+                    std::thread::yield_now();
+                }
+            })
+        })
+        .collect();
+    writer.join().unwrap();
+    for reader in readers {
+        reader.join().unwrap();
+    }
+}
@@ -0,0 +1,28 @@
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter};
+
+fn saved_recording(name: &str) -> (Interpreter, std::path::PathBuf) {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }").unwrap();
+    interpreter.start_recording();
+    for _ in 0..3 {
+        interpreter.step(&[("coherence".to_string(), 0.7)].into(), &Fields::new()).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("weavelang_recording_{}.rec", name));
+    interpreter.save_recording(&path).unwrap();
+    (interpreter, path)
+}
+
+#[test]
+fn a_recording_of_another_width_is_refused() {
+    let (mut interpreter, path) = saved_recording("width");
+    let mut bytes = std::fs::read(&path).unwrap();
+    for width in [2, u32::MAX] {
+        bytes[8..12].copy_from_slice(&width.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        match interpreter.load_recording(&path) {
+            Err(WeaveError::Validation(message)) => assert!(message.contains(&format!("{}-bit values", u64::from(width) * 8)), "{}", message),
+            other => panic!("a width of {} gave {:?}", width, other),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use weavelang::constants::Constants;
+use weavelang::error::WeaveError;
+use weavelang::reload::{check_idle, BackgroundLoad};
+
+#[test]
+fn loads_are_refused_until_a_background_load_is_applied() {
+    let path = std::env::temp_dir().join("weavelang_reload_background.weave");
+    std::fs::write(&path, "field generalist { coherence_target: 0.5 }").unwrap();
+    let load = BackgroundLoad::start("main", &path, Constants::default());
+    assert_eq!((load.slot(), load.path()), ("main", path.as_path()));
+    match check_idle(Some(&load), "loading other.weave") {
+        Err(error @ WeaveError::Busy(_)) => {
+            assert_eq!(error.kind(), "busy");
+            assert_eq!(error.to_string(), format!("refused loading other.weave while {} is loading", path.display()));
+        }
+        other => panic!("a load during a background load gave {:?}", other),
+    }
+    let program = loop {
+        if let Some(result) = load.finished() {
+            break result.unwrap();
+        }
+        std::thread::yield_now();
+    };
+    assert!(program.fields.contains_key("generalist"));
+    assert!(check_idle(None, "loading other.weave").is_ok());
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::safety::SafetySeverity;
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field generalist { coherence_target: 0.5 }\nsafety { rule: equipment_status < 0.3 halt, rule: safety_risk > 0.15 warn }";
+
+fn sensors(pairs: &[(&str, Scalar)]) -> HashMap<String, Scalar> {
+    pairs.iter().map(|(sensor, value)| (sensor.to_string(), *value)).collect()
+}
+
+fn loaded() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(SCRIPT).unwrap();
+    interpreter
+}
+
+#[test]
+fn a_halt_rule_zeroes_the_safety_metric() {
+    let mut interpreter = loaded();
+    assert_eq!(interpreter.safety_rules().len(), 2);
+    assert_eq!(interpreter.safety_rules()[0].to_string(), "rule: equipment_status < 0.3 halt");
+    interpreter.step(&sensors(&[("coherence", 0.5), ("equipment_status", 0.9), ("safety_risk", 0.0)]), &Fields::new()).unwrap();
+    assert!(interpreter.take_safety_events().is_empty());
+    assert!(interpreter.safety_metric() > 0.0);
+    interpreter.step(&sensors(&[("coherence", 0.5), ("equipment_status", 0.1), ("safety_risk", 0.0)]), &Fields::new()).unwrap();
+    let events = interpreter.take_safety_events();
+    assert_eq!(events.len(), 1);
+    assert_eq!((events[0].rule_index, events[0].severity), (0, SafetySeverity::Halt));
+    assert_eq!((events[0].sensor.as_str(), events[0].sensor_value), ("equipment_status", 0.1));
+    assert_eq!(interpreter.safety_metric(), 0.0);
+}
+
+#[test]
+fn a_warn_rule_reports_without_halting() {
+    let mut interpreter = loaded();
+    interpreter.step(&sensors(&[("coherence", 0.5), ("equipment_status", 0.9), ("safety_risk", 0.5)]), &Fields::new()).unwrap();
+    let events = interpreter.take_safety_events();
+    assert_eq!(events.len(), 1);
+    assert_eq!((events[0].rule_index, events[0].severity), (1, SafetySeverity::Warn));
+    assert!(interpreter.safety_metric() > 0.0);
+}
+
+#[test]
+fn malformed_safety_blocks_are_refused() {
+    let mut interpreter = Interpreter::new();
+    assert!(interpreter.load_str("safety { rule: heat > 1 explode }").is_err());
+    assert!(interpreter.load_str("safety { heat > 1 halt }").is_err());
+}
+
+#[test]
+fn grace_ticks_must_be_a_whole_count() {
+    let mut interpreter = Interpreter::new();
+    for ticks in ["-3.5", "-1", "2.5"] {
+        let script = format!("safety {{ rule: heat > 1 halt grace_ticks: {} }}", ticks);
+        match interpreter.load_str(&script) {
+            Err(WeaveError::Validation(message)) => assert_eq!(message, format!("grace_ticks {} must be a whole, non-negative count", ticks)),
+            other => panic!("grace_ticks: {} gave {:?}", ticks, other),
+        }
+    }
+    interpreter.load_str("safety { rule: heat > 1 halt grace_ticks: 2 }").unwrap();
+    assert_eq!(interpreter.safety_rules()[0].grace_ticks, 2);
+}
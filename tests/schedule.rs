@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::invariant::InvariantPolicy;
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field generalist { coherence_target: 0.5 }\nfield accelerator { collision_energy: 1.0 }\nat tick 3 set accelerator.collision_energy = 150.0;";
+
+fn sensors() -> HashMap<String, Scalar> {
+    [("coherence".to_string(), 0.5)].into()
+}
+
+fn loaded(script: &str) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(script).unwrap();
+    interpreter
+}
+
+fn energy(interpreter: &Interpreter) -> Scalar {
+    interpreter.fields()["accelerator"]["collision_energy"]
+}
+
+#[test]
+fn a_set_fires_once_on_the_step_that_reaches_its_tick() {
+    let mut interpreter = loaded(SCRIPT);
+    for _ in 0..2 {
+        interpreter.step(&sensors(), &Fields::new()).unwrap();
+    }
+    assert_eq!(interpreter.tick(), 2);
+    assert_eq!(energy(&interpreter), 1.0);
+    interpreter.step(&sensors(), &Fields::new()).unwrap();
+    assert_eq!(energy(&interpreter), 150.0);
+    interpreter.execute_code("accelerator.collision_energy = 9.0").unwrap();
+    interpreter.step(&sensors(), &Fields::new()).unwrap();
+    assert_eq!(energy(&interpreter), 9.0);
+    assert!(interpreter.scheduled().is_empty());
+}
+
+#[test]
+fn only_steps_advance_the_tick() {
+    let mut interpreter = loaded(SCRIPT);
+    for _ in 0..5 {
+        interpreter.execute_tension(&sensors()).unwrap();
+    }
+    assert_eq!(interpreter.tick(), 0);
+    assert_eq!(interpreter.tension_history().len(), 5);
+    interpreter.step(&sensors(), &Fields::new()).unwrap();
+    assert_eq!(interpreter.tick(), 1);
+    assert_eq!(energy(&interpreter), 1.0);
+}
+
+#[test]
+fn load_and_reset_start_the_tick_over() {
+    let path = std::env::temp_dir().join("weavelang_schedule_reset.weave");
+    std::fs::write(&path, SCRIPT).unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.load(&path).unwrap();
+    for _ in 0..3 {
+        interpreter.step(&sensors(), &Fields::new()).unwrap();
+    }
+    assert_eq!(energy(&interpreter), 150.0);
+    interpreter.reset().unwrap();
+    assert_eq!(interpreter.tick(), 0);
+    assert_eq!(energy(&interpreter), 1.0);
+    interpreter.step(&sensors(), &Fields::new()).unwrap();
+    interpreter.load(&path).unwrap();
+    assert_eq!(interpreter.tick(), 0);
+    assert_eq!(interpreter.scheduled().len(), 1);
+}
+
+#[test]
+fn past_ticks_fire_immediately_and_duplicate_ticks_fire_in_order() {
+    let mut interpreter = loaded(SCRIPT);
+    interpreter.step(&sensors(), &Fields::new()).unwrap();
+    interpreter.schedule_set(0, "accelerator", "past", 7.0).unwrap();
+    assert_eq!(interpreter.fields()["accelerator"]["past"], 7.0);
+    interpreter.schedule_set(2, "accelerator", "twice", 1.0).unwrap();
+    interpreter.schedule_set(2, "accelerator", "twice", 2.0).unwrap();
+    let cancelled = interpreter.schedule_set(2, "accelerator", "cancelled", 1.0).unwrap();
+    assert!(interpreter.cancel_scheduled(cancelled));
+    assert!(!interpreter.cancel_scheduled(cancelled));
+    interpreter.step(&sensors(), &Fields::new()).unwrap();
+    assert_eq!(interpreter.fields()["accelerator"]["twice"], 2.0);
+    assert!(!interpreter.fields()["accelerator"].contains_key("cancelled"));
+}
+
+#[test]
+fn a_rollback_rewinds_the_tick() {
+    let mut interpreter = loaded("field generalist { coherence_target: 0.5 }\nfield probe { physics_constant: 0.1 }\nassert bounded: probe.physics_constant <= 0.3");
+    interpreter.set_drift_rate(0.5);
+    interpreter.set_invariant_policy(InvariantPolicy::Rollback);
+    let agents: Fields = [("probe".to_string(), Default::default())].into_iter().collect();
+    let readings: HashMap<String, Scalar> = [("coherence".to_string(), 0.9)].into();
+    for _ in 0..10 {
+        interpreter.step(&readings, &agents).unwrap();
+    }
+    let violations = interpreter.take_invariant_violations();
+    assert!(!violations.is_empty());
+    assert_eq!(interpreter.tick(), violations[0].tick - 1);
+    assert_eq!(interpreter.tension_history().len(), interpreter.tick());
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::Interpreter;
+use weavelang::rules::SensorPolicy;
+use weavelang::scalar::Scalar;
+
+fn loaded(policy: SensorPolicy) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }").unwrap();
+    interpreter.set_sensor_policy(policy);
+    interpreter
+}
+
+fn coherence(value: Scalar) -> HashMap<String, Scalar> {
+    [("coherence".to_string(), value)].into()
+}
+
+#[test]
+fn strict_reports_the_missing_sensor_and_skips_the_update() {
+    let mut interpreter = loaded(SensorPolicy::Strict);
+    let error = interpreter.execute_tension(&HashMap::new()).unwrap_err();
+    assert!(matches!(&error, WeaveError::MissingSensor(sensor) if sensor == "coherence"), "{}", error);
+    assert_eq!(error.kind(), "missing_sensor");
+    assert_eq!(interpreter.take_missing_sensors(), vec!["coherence".to_string()]);
+    assert!(interpreter.tension_history().is_empty());
+}
+
+#[test]
+fn hold_reuses_the_last_reading() {
+    let mut interpreter = loaded(SensorPolicy::Hold);
+    assert!(matches!(interpreter.execute_tension(&HashMap::new()), Err(WeaveError::MissingSensor(_))), "nothing has been seen yet");
+    let seen = interpreter.execute_tension(&coherence(0.9)).unwrap();
+    assert_eq!(interpreter.execute_tension(&HashMap::new()).unwrap(), seen);
+    assert_eq!(interpreter.tension_history().len(), 2);
+}
+
+#[test]
+fn default_uses_the_declared_default() {
+    let mut interpreter = loaded(SensorPolicy::Default);
+    interpreter.set_sensor_default("coherence", 0.8);
+    let defaulted = interpreter.execute_tension(&HashMap::new()).unwrap();
+    assert_eq!(defaulted, loaded(SensorPolicy::Default).execute_tension(&coherence(0.8)).unwrap());
+}
+
+#[test]
+fn a_per_sensor_policy_overrides_the_global_one() {
+    let mut interpreter = loaded(SensorPolicy::Default);
+    interpreter.set_sensor_policy_for("coherence", SensorPolicy::Strict);
+    assert!(interpreter.execute_tension(&HashMap::new()).is_err());
+    assert_eq!("hold".parse::<SensorPolicy>(), Ok(SensorPolicy::Hold));
+    assert!("lenient".parse::<SensorPolicy>().is_err());
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+/// The generalist's target after each of 2000 steps against a fixed reading.
+fn targets(sensed: Scalar, signed: bool) -> Vec<Scalar> {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }").unwrap();
+    interpreter.set_signed_correction(signed);
+    let agents: Fields = [("generalist".to_string(), Default::default())].into_iter().collect();
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), sensed)].into();
+    (0..2000)
+        .map(|_| {
+            interpreter.step(&sensors, &agents).unwrap();
+            interpreter.fields()["generalist"]["coherence_target"]
+        })
+        .collect()
+}
+
+#[test]
+fn the_target_closes_on_the_sensor_from_either_side() {
+    for sensed in [0.9, 0.1] {
+        let path = targets(sensed, true);
+        let mut error = (0.5 - sensed).abs();
+        for target in &path {
+            let next = (target - sensed).abs();
+            assert!(next <= error + 1e-7, "the error grew from {} to {} sensing {}", error, next, sensed);
+            error = next;
+        }
+        assert!(error < 1e-3, "ended {} from {}", error, sensed);
+    }
+}
+
+#[test]
+fn a_target_on_its_sensor_stays_put() {
+    assert!(targets(0.5, true).iter().all(|target| *target == 0.5));
+}
+
+#[test]
+fn unsigned_correction_still_drifts_away_from_a_lower_sensor() {
+    assert!(*targets(0.1, false).last().unwrap() > 0.5);
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+fn swarm() -> (Interpreter, Fields) {
+    let mut script = String::from("field generalist { coherence_target: 0.5 }\n");
+    for n in 0..100 {
+        script.push_str(&format!("field f{} {{ coherence_target: {}.0 }}\n", n, n));
+    }
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(&script).unwrap();
+    interpreter.set_resolve_rate(0.02);
+    let agents = interpreter.fields().clone();
+    (interpreter, agents)
+}
+
+#[test]
+fn a_sliced_swarm_tracks_the_whole_one() {
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.3)].into();
+    let ((mut whole, agents), (mut sliced, _)) = (swarm(), swarm());
+    sliced.set_agents_per_tick(7);
+    assert_eq!(whole.step_progress(), 0.0);
+    for tick in 0..2020 {
+        whole.step(&sensors, &agents).unwrap();
+        sliced.step(&sensors, &agents).unwrap();
+        if tick == 5 {
+            assert!(sliced.step_progress() > 0.0 && sliced.step_progress() < 1.0);
+        }
+    }
+    assert_eq!(whole.step_progress(), 0.0, "slicing is off");
+    for (name, params) in whole.fields() {
+        let (expected, actual) = (params["coherence_target"], sliced.fields()[name]["coherence_target"]);
+        assert!((expected - actual).abs() < 0.05 * (1.0 + expected.abs()), "{}: {} sliced, {} whole", name, actual, expected);
+    }
+}
+
+#[test]
+fn a_tick_moves_at_most_its_batch() {
+    let (mut interpreter, agents) = swarm();
+    interpreter.set_agents_per_tick(7);
+    let before = interpreter.fields().clone();
+    interpreter.step(&[("coherence".to_string(), 0.3)].into(), &agents).unwrap();
+    let moved = before.iter().filter(|(name, params)| interpreter.fields()[name.as_str()]["coherence_target"] != params["coherence_target"]).count();
+    assert!(moved > 0 && moved <= 7, "{} agents moved", moved);
+}
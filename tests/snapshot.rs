@@ -0,0 +1,34 @@
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter};
+
+fn stepped() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5, noise_stddev: 0.05 }\nat tick 50 set generalist.coherence_target = 0.1;").unwrap();
+    for _ in 0..3 {
+        interpreter.step(&[("coherence".to_string(), 0.8)].into(), &Fields::new()).unwrap();
+    }
+    interpreter
+}
+
+#[test]
+fn a_snapshot_restores_and_replays() {
+    let mut interpreter = stepped();
+    let bytes = interpreter.snapshot();
+    let saved = interpreter.fields().clone();
+    let ahead: Vec<_> = (0..4).map(|_| interpreter.step(&[("coherence".to_string(), 0.8)].into(), &Fields::new()).unwrap()).collect();
+    interpreter.restore(&bytes).unwrap();
+    assert_eq!(interpreter.fields(), &saved);
+    let again: Vec<_> = (0..4).map(|_| interpreter.step(&[("coherence".to_string(), 0.8)].into(), &Fields::new()).unwrap()).collect();
+    assert_eq!(ahead, again);
+}
+
+#[test]
+fn damaged_snapshots_are_refused() {
+    let mut interpreter = stepped();
+    let bytes = interpreter.snapshot();
+    let mut width = bytes.clone();
+    width[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(interpreter.restore(&width), Err(WeaveError::Validation(message)) if message.contains("34359738360-bit values")));
+    assert!(interpreter.restore(b"nope").is_err());
+    assert!(interpreter.restore(&bytes[..bytes.len() - 3]).is_err());
+}
@@ -0,0 +1,103 @@
+use std::collections::{BTreeMap, HashMap};
+use weavelang::interpreter::{parse_param_value, Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field generalist { coherence_target: 0.5, noise_stddev: 0.1 }\nfield acc { physics_constant: 1.0, mode: cautious, label: \"a b\", tags: [x, y], position: [0.5, -1.25] }\nat tick 3 set acc.physics_constant = 4.0;\ngoal g { when tension < 0.01 hold_ticks: 2 }";
+
+fn state(interpreter: &Interpreter) -> String {
+    let attributes: BTreeMap<_, BTreeMap<_, _>> = interpreter.attributes().iter().map(|(field, values)| (field.clone(), values.iter().collect())).collect();
+    format!(
+        "{:?}|{:?}|{:?}|{}|{:?}|{:?}|{:?}|{:?}",
+        interpreter.fields(),
+        interpreter.tension_history(),
+        attributes,
+        interpreter.tick(),
+        interpreter.smoothed_tension(),
+        interpreter.scheduled(),
+        interpreter.locked_agents(),
+        interpreter.goal_progress().collect::<Vec<_>>()
+    )
+}
+
+/// Loaded from a file, as a scene's `weave_path` would be.
+fn loaded(name: &str) -> Interpreter {
+    let path = std::env::temp_dir().join(format!("weavelang_state_{}.weave", name));
+    std::fs::write(&path, SCRIPT).unwrap();
+    let mut interpreter = Interpreter::new();
+    interpreter.load(&path).unwrap();
+    interpreter
+}
+
+fn agents() -> Fields {
+    ["generalist", "acc"].into_iter().map(|name| (name.to_string(), Default::default())).collect()
+}
+
+fn sensors() -> HashMap<String, Scalar> {
+    [("coherence".to_string(), 0.9)].into()
+}
+
+#[test]
+fn reset_matches_a_fresh_load() {
+    let fresh = loaded("reset");
+    let mut interpreter = loaded("reset");
+    interpreter.set_drift_rate(0.5);
+    for _ in 0..50 {
+        interpreter.step(&sensors(), &agents()).unwrap();
+    }
+    interpreter.schedule_set(80, "acc", "physics_constant", 2.0).unwrap();
+    interpreter.set_agent_locked("acc", true, true);
+    interpreter.set_tracing(true);
+    interpreter.step(&sensors(), &agents()).unwrap();
+    assert_ne!(state(&interpreter), state(&fresh));
+    interpreter.reset().unwrap();
+    assert_eq!(state(&interpreter), state(&fresh));
+    assert_eq!(interpreter.trace(100).count(), 0);
+    assert!(Interpreter::new().reset().is_err(), "nothing is loaded to reset");
+}
+
+#[test]
+fn reset_restores_a_script_loaded_from_a_string() {
+    let mut fresh = Interpreter::new();
+    fresh.load_str(SCRIPT).unwrap();
+    let mut interpreter = fresh.clone();
+    for _ in 0..10 {
+        interpreter.step(&sensors(), &agents()).unwrap();
+    }
+    assert_ne!(state(&interpreter), state(&fresh));
+    interpreter.reset().unwrap();
+    assert_eq!(state(&interpreter), state(&fresh));
+}
+
+#[test]
+fn reset_ignores_edits_made_to_the_file_since_it_loaded() {
+    let fresh = loaded("edited");
+    let mut interpreter = loaded("edited");
+    interpreter.step(&sensors(), &agents()).unwrap();
+    std::fs::write(interpreter.source_path().unwrap(), "field other { k: 1 }").unwrap();
+    interpreter.reset().unwrap();
+    assert_eq!(state(&interpreter), state(&fresh));
+}
+
+#[test]
+fn an_imported_state_carries_on_like_the_original() {
+    let mut interpreter = loaded("import");
+    for _ in 0..20 {
+        interpreter.step(&sensors(), &agents()).unwrap();
+    }
+    interpreter.schedule_set(30, "acc", "physics_constant", 2.0).unwrap();
+    interpreter.set_agent_locked("acc", true, false);
+    let exported = interpreter.export_state();
+    let mut other = loaded("import");
+    other.import_state(exported.clone());
+    assert_eq!(state(&interpreter), state(&other));
+    for _ in 0..15 {
+        assert_eq!(interpreter.step(&sensors(), &agents()).unwrap(), other.step(&sensors(), &agents()).unwrap());
+    }
+    assert_eq!(state(&interpreter), state(&other));
+    for values in exported.attributes.values() {
+        for value in values.values() {
+            assert_eq!(parse_param_value(&value.to_string()).as_ref(), Some(value));
+        }
+    }
+    assert!(parse_param_value("1.5").is_none());
+}
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter};
+
+fn recorded(name: &str) -> (Interpreter, PathBuf) {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("field generalist { coherence_target: 0.5 }").unwrap();
+    interpreter.start_sensor_recording();
+    for _ in 0..3 {
+        interpreter.step(&[("coherence".to_string(), 0.7)].into(), &Fields::new()).unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("weavelang_stream_{}.wvst", name));
+    interpreter.save_sensor_recording(&path).unwrap();
+    (interpreter, path)
+}
+
+#[test]
+fn snapshots_and_sensor_recordings_are_told_apart() {
+    let (mut interpreter, path) = recorded("apart");
+    assert!(interpreter.start_sensor_replay(&path).unwrap() > 0);
+    interpreter.stop_sensor_replay();
+    let stream = std::fs::read(&path).unwrap();
+    assert!(matches!(interpreter.restore(&stream), Err(WeaveError::Validation(message)) if message == "not a weave snapshot"));
+    let snapshot = std::env::temp_dir().join("weavelang_stream_snapshot.wvst");
+    std::fs::write(&snapshot, interpreter.snapshot()).unwrap();
+    assert!(matches!(interpreter.start_sensor_replay(&snapshot), Err(WeaveError::Validation(message)) if message.contains("is not a sensor recording")));
+}
+
+#[test]
+fn a_sensor_recording_of_another_width_is_refused() {
+    let (mut interpreter, path) = recorded("width");
+    let mut bytes = std::fs::read(&path).unwrap();
+    bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(interpreter.start_sensor_replay(&path), Err(WeaveError::Validation(message)) if message.contains("34359738360-bit values")));
+}
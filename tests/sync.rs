@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+use weavelang::sync::DiffOutcome;
+
+fn loaded(script: &str) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(script).unwrap();
+    interpreter
+}
+
+#[test]
+fn dropped_duplicated_and_reordered_diffs_converge() {
+    let mut sender = loaded("field generalist { coherence_target: 0.5 }\nfield x { coherence_target: 1.0, k: 2 }\nfield y { physics_constant: 3 }");
+    let mut receiver = loaded("field generalist { coherence_target: 0.1 }\nfield z { q: 1 }");
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.3)].into();
+    // A fixed xorshift, so the losses are the same on every run.
+    let mut state = 12345u64;
+    let mut roll = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state % 100
+    };
+    let snapshot = sender.state_diff(0);
+    assert!(matches!(receiver.apply_state_diff(&snapshot).unwrap(), DiffOutcome::Applied { .. }));
+    assert!(!receiver.fields().contains_key("z"));
+    let mut queue: Vec<Vec<u8>> = Vec::new();
+    for step in 0..500 {
+        let agents: Fields = sender.fields().clone();
+        sender.step(&sensors, &agents).unwrap();
+        if step == 100 {
+            sender.execute_code("field w { v: 1 }").unwrap();
+        }
+        if step == 200 {
+            sender.execute_code("x.k = 5").unwrap();
+        }
+        let diff = sender.state_diff(receiver.applied_revision());
+        let chance = roll();
+        if chance < 30 {
+            continue;
+        }
+        queue.push(diff.clone());
+        if chance < 50 {
+            queue.push(diff);
+        }
+        if chance < 60 {
+            queue.reverse();
+        }
+        for diff in queue.drain(..) {
+            receiver.apply_state_diff(&diff).unwrap();
+        }
+    }
+    let last = sender.state_diff(receiver.applied_revision());
+    receiver.apply_state_diff(&last).unwrap();
+    assert_eq!(sender.fields(), receiver.fields());
+    let revision = sender.state_revision();
+    assert_eq!(receiver.apply_state_diff(&last).unwrap(), DiffOutcome::Stale { revision, applied: revision });
+}
+
+#[test]
+fn truncated_diffs_are_malformed() {
+    let mut sender = loaded("field generalist { coherence_target: 0.5 }\nfield x { k: 2 }");
+    let mut receiver = Interpreter::new();
+    let diff = sender.state_diff(0);
+    for len in [2, 12, diff.len() - 1] {
+        match receiver.apply_state_diff(&diff[..len]) {
+            Err(WeaveError::Validation(message)) => assert_eq!(message, "malformed state diff"),
+            other => panic!("a diff cut to {} bytes gave {:?}", len, other),
+        }
+    }
+    assert!(matches!(receiver.apply_state_diff(b"junk"), Err(WeaveError::Validation(_))));
+    assert!(receiver.fields().is_empty());
+}
+
+#[test]
+fn a_diff_of_another_width_is_refused() {
+    let mut sender = loaded("field generalist { coherence_target: 0.5 }");
+    let mut diff = sender.state_diff(0);
+    diff[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    match Interpreter::new().apply_state_diff(&diff) {
+        Err(WeaveError::Validation(message)) => assert!(message.contains("34359738360-bit values"), "{}", message),
+        other => panic!("a corrupt width gave {:?}", other),
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field generalist { coherence_target: 0.5, tags: [expert, mobile] }\nfield lab { physics_constant: 9.81, tags: [frozen, infrastructure,] }";
+
+fn loaded() -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(SCRIPT).unwrap();
+    interpreter
+}
+
+fn agents() -> Fields {
+    ["generalist", "lab"].into_iter().map(|name| (name.to_string(), Default::default())).collect()
+}
+
+#[test]
+fn frozen_fields_stay_bit_identical() {
+    let mut interpreter = loaded();
+    let before = interpreter.fields()["lab"].clone();
+    interpreter.execute_drift(&agents(), 1e6);
+    interpreter.execute_resolve(&agents(), 1e6);
+    let sensors: HashMap<String, Scalar> = [("coherence".to_string(), 0.9)].into();
+    for _ in 0..50 {
+        interpreter.step(&sensors, &agents()).unwrap();
+    }
+    for (param, value) in &before {
+        assert_eq!(interpreter.fields()["lab"][param.as_str()].to_bits(), value.to_bits(), "lab.{}", param);
+    }
+    assert!(interpreter.fields()["generalist"]["coherence_target"] != 0.5);
+}
+
+#[test]
+fn group_phases_only_move_their_tag() {
+    let mut interpreter = loaded();
+    interpreter.execute_drift_group("infrastructure", &agents(), 10.0, 1.0);
+    interpreter.execute_resolve_group("infrastructure", &agents(), 10.0, 1.0);
+    assert_eq!(interpreter.fields()["generalist"]["coherence_target"], 0.5);
+    interpreter.execute_drift_group("expert", &agents(), 10.0, 1.0);
+    assert!(interpreter.fields()["generalist"]["coherence_target"] != 0.5);
+    assert_eq!(interpreter.agents_with_tag("frozen"), vec!["lab".to_string()]);
+    assert_eq!(interpreter.agents_with_tag("mobile"), vec!["generalist".to_string()]);
+    assert!(interpreter.agents_with_tag("nobody").is_empty());
+}
@@ -0,0 +1,56 @@
+use weavelang::compiled;
+use weavelang::error::WeaveError;
+use weavelang::interpreter::{parse_weave_str, Interpreter, ParamValue};
+
+const EXPERT: &str = "template expert { coherence_target: 0.5, energy: 2.0, live: generalist.coherence_target * 2, role: \"lab\" } field generalist { coherence_target: 0.5 }";
+
+#[test]
+fn fields_extend_templates_and_fields() {
+    let program = parse_weave_str(&format!("{} field quantum_expert extends expert {{ energy: 3.0 }} field twin extends quantum_expert {{ k: 2 * 3 }}", EXPERT)).unwrap();
+    assert!(!program.fields.contains_key("expert"));
+    assert_eq!(program.fields["quantum_expert"]["coherence_target"], 0.5);
+    assert_eq!(program.fields["quantum_expert"]["energy"], 3.0);
+    assert_eq!(program.fields["twin"]["energy"], 3.0);
+    assert_eq!(program.fields["twin"]["k"], 6.0);
+    assert!(program.attributes["twin"].contains_key("role"));
+    assert!(parse_weave_str("template a { x: 1 } template a { x: 2 }").is_err());
+    assert!(parse_weave_str("field b extends nope { x: 1 }").is_err());
+}
+
+#[test]
+fn agents_are_declared_from_templates() {
+    let program = parse_weave_str(&format!("{} agent probe from expert;", EXPERT)).unwrap();
+    assert_eq!(program.fields["probe"]["energy"], 2.0);
+    assert_eq!(program.attributes["probe"]["role"], ParamValue::Text("lab".to_string()));
+    assert!(program.derived_params.iter().any(|derived| derived.field == "probe" && derived.param == "live"));
+    assert_eq!(program.agents["probe"], "expert");
+    assert!(matches!(parse_weave_str("agent probe from nope;"), Err(WeaveError::MissingField(name)) if name == "nope"));
+}
+
+#[test]
+fn agents_spawn_from_templates() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(EXPERT).unwrap();
+    interpreter.spawn_agent("rover", "expert").unwrap();
+    assert_eq!(interpreter.fields()["rover"]["energy"], 2.0);
+    assert_eq!(interpreter.fields()["rover"]["live"], 1.0);
+    interpreter.execute_code("generalist.coherence_target = 0.25").unwrap();
+    interpreter.step(&[("coherence".to_string(), 0.5)].into(), &Default::default()).unwrap();
+    assert_eq!(interpreter.fields()["rover"]["live"], 0.5);
+    assert_eq!(interpreter.attribute("rover", "role"), Some(&ParamValue::Text("lab".to_string())));
+    assert_eq!(interpreter.agents()["rover"].template, "expert");
+    assert!(matches!(interpreter.spawn_agent("ghost", "nope"), Err(WeaveError::MissingField(name)) if name == "nope"));
+}
+
+#[test]
+fn templates_survive_for_code_and_compiles() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str("template expert { coherence_target: 0.5 } field a extends expert { }").unwrap();
+    interpreter.execute_code("field b extends expert { x: 1 }").unwrap();
+    assert_eq!(interpreter.fields()["b"]["coherence_target"], 0.5);
+    let program = parse_weave_str("template expert { coherence_target: 0.5, live: sin(a.x) } field a extends expert { x: 1 }").unwrap();
+    assert_eq!(program.derived_params[0].field, "a");
+    let path = std::env::temp_dir().join("weavelang_templates.wvc");
+    compiled::save(&program, &path).unwrap();
+    assert_eq!(compiled::load(&path).unwrap().templates, program.templates);
+}
@@ -0,0 +1,65 @@
+use std::collections::{HashMap, HashSet};
+use weavelang::interpreter::{Fields, Interpreter};
+use weavelang::scalar::Scalar;
+
+const SCRIPT: &str = "field generalist { coherence_target: 0.5 }\nfield probe { coherence_target: 0.2, mode: \"hot\" }\nmetaweave { when heat > 2 set generalist.coherence_target = 0.7; when probe.mode == \"hot\" set probe.physics_constant = 1; }";
+
+fn sensors(coherence: Scalar, heat: Scalar) -> HashMap<String, Scalar> {
+    [("coherence".to_string(), coherence), ("heat".to_string(), heat)].into()
+}
+
+fn traced() -> (Interpreter, Scalar) {
+    let mut interpreter = Interpreter::new();
+    interpreter.load_str(SCRIPT).unwrap();
+    let agents: Fields = ["generalist", "probe"].into_iter().map(|name| (name.to_string(), Default::default())).collect();
+    interpreter.step(&sensors(0.9, 1.0), &agents).unwrap();
+    assert_eq!(interpreter.trace(usize::MAX).count(), 0, "tracing starts off");
+    let start = interpreter.fields()["generalist"]["coherence_target"];
+    interpreter.set_tracing(true);
+    for tick in 0..40u8 {
+        let heat = if tick % 7 == 0 { 3.0 } else { 1.0 };
+        interpreter.step(&sensors(0.9 - Scalar::from(tick) * 0.01, heat), &agents).unwrap();
+    }
+    interpreter.execute_code("generalist.coherence_target = 0.6;").unwrap();
+    interpreter.step(&sensors(0.1, 1.0), &agents).unwrap();
+    (interpreter, start)
+}
+
+#[test]
+fn the_trace_accounts_for_every_change() {
+    let (interpreter, start) = traced();
+    let moved: Scalar = interpreter
+        .trace(usize::MAX)
+        .filter(|entry| entry.agent == "generalist" && entry.param == "coherence_target")
+        .map(|entry| entry.new_value - entry.old_value)
+        .sum();
+    let end = interpreter.fields()["generalist"]["coherence_target"];
+    assert!((start + moved - end).abs() < 1e-5, "started at {}, traced {}, ended at {}", start, moved, end);
+    let phases: HashSet<&str> = interpreter.trace(usize::MAX).map(|entry| entry.phase.as_str()).collect();
+    assert_eq!(phases.len(), 4, "{:?}", phases);
+}
+
+#[test]
+fn entries_name_what_triggered_them() {
+    let (interpreter, _) = traced();
+    let attribute = interpreter.trace(usize::MAX).find(|entry| entry.param == "physics_constant" && entry.phase.as_str() == "metaweave").unwrap();
+    assert_eq!(attribute.trigger, "probe.mode");
+    let hot = interpreter.trace(usize::MAX).find(|entry| entry.phase.as_str() == "metaweave" && entry.agent == "generalist").unwrap();
+    assert_eq!((hot.trigger.as_str(), hot.trigger_value), ("heat", Some(3.0)));
+}
+
+#[test]
+fn the_trace_dumps_caps_and_stops() {
+    let (mut interpreter, _) = traced();
+    assert_eq!(interpreter.trace(3).count(), 3);
+    let out = std::env::temp_dir().join("weavelang_trace.jsonl");
+    interpreter.dump_trace(&out).unwrap();
+    let text = std::fs::read_to_string(&out).unwrap();
+    assert_eq!(text.lines().count(), interpreter.trace(usize::MAX).count());
+    assert!(text.starts_with("{\"tick\":2,\"phase\":\"drift\",\"agent\":\"generalist\""), "{}", text.lines().next().unwrap_or_default());
+    interpreter.set_trace_capacity(5);
+    assert_eq!(interpreter.trace(usize::MAX).count(), 5);
+    interpreter.set_tracing(false);
+    interpreter.step(&sensors(0.1, 1.0), &Fields::new()).unwrap();
+    assert_eq!(interpreter.trace(usize::MAX).count(), 5);
+}